@@ -0,0 +1,152 @@
+use crate::git_history::GitHistoryEntry;
+use crate::locale;
+use chrono::{DateTime, Datelike, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Number of commits in a calendar month.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct MonthlyActivity {
+    /// Year of the month.
+    pub year: i32,
+    /// Month of the year, starting from 1.
+    pub month: u32,
+    /// Commits in the month.
+    pub commits: usize,
+}
+
+impl MonthlyActivity {
+    /// Name of the month in the language of `locale`, like "Mar 2024".
+    pub fn label(&self, locale: Option<&str>) -> String {
+        format!("{} {}", locale::short_month(self.month, locale), self.year)
+    }
+}
+
+/// Counts the commits of a history by month, oldest first, from the month of its oldest commit
+/// to the month of `until`, but no more than the last `max_months` months.
+///
+/// Months without commits are included, commits after `until` are ignored.
+pub fn monthly<'a>(
+    history: impl Iterator<Item = &'a GitHistoryEntry> + Clone,
+    until: DateTime<Utc>,
+    max_months: usize,
+) -> Vec<MonthlyActivity> {
+    // Months are numbered from year 0, so that consecutive months have consecutive numbers
+    let index = |date: DateTime<Utc>| i64::from(date.year()) * 12 + i64::from(date.month0());
+    let last = index(until);
+    let oldest = match history.clone().map(|entry| index(entry.timestamp)).min() {
+        Some(oldest) if oldest <= last => oldest,
+        _ => return Vec::new(),
+    };
+    let first = oldest.max(last - max_months as i64 + 1);
+
+    let mut months = (first..=last)
+        .map(|i| MonthlyActivity {
+            year: i.div_euclid(12) as i32,
+            month: i.rem_euclid(12) as u32 + 1,
+            commits: 0,
+        })
+        .collect::<Vec<_>>();
+    for entry in history {
+        let i = index(entry.timestamp);
+        if (first..=last).contains(&i) {
+            months[(i - first) as usize].commits += 1;
+        }
+    }
+    months
+}
+
+/// Scales the commit counts to intensity levels from 0, for months without commits,
+/// to `levels - 1`, for the busiest months.
+pub fn levels(activity: &[MonthlyActivity], levels: usize) -> Vec<usize> {
+    let max = activity
+        .iter()
+        .map(|month| month.commits)
+        .max()
+        .unwrap_or(0);
+    activity
+        .iter()
+        .map(|month| match month.commits {
+            0 => 0,
+            // Rounded up, so that any commit shows
+            commits => (commits * (levels - 1)).div_ceil(max),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn month(year: i32, month: u32, commits: usize) -> MonthlyActivity {
+        MonthlyActivity {
+            year,
+            month,
+            commits,
+        }
+    }
+
+    fn history(dates: &[&str]) -> Vec<GitHistoryEntry> {
+        dates
+            .iter()
+            .enumerate()
+            .map(|(i, date)| GitHistoryEntry::test(&i.to_string(), "Alice", date))
+            .collect()
+    }
+
+    #[test]
+    fn months_without_commits_are_counted_across_years() {
+        let history = history(&[
+            "2024-02-29T23:59:59Z",
+            "2024-02-01T00:00:00Z",
+            "2023-11-15T12:00:00Z",
+        ]);
+        let until = "2024-03-10T12:00:00Z".parse().unwrap();
+
+        assert_eq!(
+            monthly(history.iter(), until, 12),
+            [
+                month(2023, 11, 1),
+                month(2023, 12, 0),
+                month(2024, 1, 0),
+                month(2024, 2, 2),
+                month(2024, 3, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_the_last_months_up_to_until_are_counted() {
+        let history = history(&[
+            "2024-04-01T12:00:00Z",
+            "2024-03-01T12:00:00Z",
+            "2023-01-01T12:00:00Z",
+        ]);
+        let until = "2024-03-31T23:59:59Z".parse().unwrap();
+
+        assert_eq!(
+            monthly(history.iter(), until, 2),
+            [month(2024, 2, 0), month(2024, 3, 1)]
+        );
+
+        // Histories entirely after `until`
+        let until = "2022-12-31T12:00:00Z".parse().unwrap();
+        assert!(monthly(history.iter(), until, 12).is_empty());
+        assert!(monthly([].iter(), until, 12).is_empty());
+    }
+
+    #[test]
+    fn levels_are_scaled_to_the_busiest_month() {
+        let activity = [
+            month(2024, 1, 0),
+            month(2024, 2, 1),
+            month(2024, 3, 5),
+            month(2024, 4, 10),
+        ];
+
+        assert_eq!(levels(&activity, 5), [0, 1, 2, 4]);
+        assert_eq!(levels(&activity, 2), [0, 1, 1, 1]);
+        assert_eq!(levels(&activity[..1], 5), [0]);
+        assert!(levels(&[], 5).is_empty());
+    }
+}
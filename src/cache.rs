@@ -0,0 +1,255 @@
+use crate::config::GitInfoOptions;
+use crate::git_history::GitHistoryEntry;
+use crate::metadata;
+use crate::provider::HistoryProvider;
+use anyhow::{Context, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Version of the on-disk format, bumped on incompatible changes.
+const CACHE_VERSION: u32 = 3;
+
+/// Histories persisted between runs, valid as long as `HEAD` does not move.
+pub struct HistoryCache {
+    path: PathBuf,
+    contents: CacheContents,
+    /// Files with uncommitted changes, which never use cached data.
+    dirty: HashSet<PathBuf>,
+    modified: bool,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+struct CacheContents {
+    version: u32,
+    /// `HEAD` commit and extraction options the histories were computed with.
+    key: String,
+    /// Histories by canonical path of the file.
+    histories: HashMap<PathBuf, Vec<GitHistoryEntry>>,
+    /// Lines per author according to `git blame`, by canonical path of the file.
+    #[serde(default)]
+    blames: HashMap<PathBuf, Vec<(String, usize)>>,
+}
+
+impl HistoryCache {
+    /// Opens the cache stored at `path` for the repository containing `dir`.
+    ///
+    /// A missing, corrupt or stale cache file results in an empty cache.
+    pub fn open(
+        path: PathBuf,
+        dir: &Path,
+        options: &GitInfoOptions,
+        provider: &dyn HistoryProvider,
+    ) -> Result<HistoryCache> {
+        let head = provider
+            .head(dir, options)
+            .context("Cannot determine the HEAD commit")?;
+        let key = format!("{} {}", head, options.history_fingerprint());
+        let dirty = if options.cache_dirty_check {
+            provider
+                .dirty_files(dir, options)
+                .context("Cannot determine the modified files")?
+        } else {
+            HashSet::new()
+        };
+
+        let contents = match fs::read(&path) {
+            Ok(bytes) => match serde_json::from_slice::<CacheContents>(&bytes) {
+                Ok(contents) if contents.version == CACHE_VERSION && contents.key == key => {
+                    contents
+                }
+                Ok(_) => CacheContents::default(),
+                Err(e) => {
+                    warn!("Ignoring corrupt cache file {}: {}", path.display(), e);
+                    CacheContents::default()
+                }
+            },
+            Err(_) => CacheContents::default(),
+        };
+
+        Ok(HistoryCache {
+            path,
+            contents: CacheContents {
+                version: CACHE_VERSION,
+                key,
+                ..contents
+            },
+            dirty,
+            modified: false,
+        })
+    }
+
+    /// Returns the cached history of a file, given its canonical path.
+    pub fn get(&self, path: &Path) -> Option<&Vec<GitHistoryEntry>> {
+        if self.dirty.contains(path) {
+            return None;
+        }
+        self.contents.histories.get(path)
+    }
+
+    /// Stores the history of a file, given its canonical path.
+    pub fn insert(&mut self, path: PathBuf, history: Vec<GitHistoryEntry>) {
+        if !self.dirty.contains(&path) {
+            self.contents.histories.insert(path, history);
+            self.modified = true;
+        }
+    }
+
+    /// Returns the cached lines per author of a file, given its canonical path.
+    pub fn get_blame(&self, path: &Path) -> Option<&Vec<(String, usize)>> {
+        if self.dirty.contains(path) {
+            return None;
+        }
+        self.contents.blames.get(path)
+    }
+
+    /// Stores the lines per author of a file, given its canonical path.
+    pub fn insert_blame(&mut self, path: PathBuf, blame: Vec<(String, usize)>) {
+        if !self.dirty.contains(&path) {
+            self.contents.blames.insert(path, blame);
+            self.modified = true;
+        }
+    }
+
+    /// Writes the cache back to disk, if anything changed.
+    pub fn save(&self) -> Result<()> {
+        if !self.modified {
+            return Ok(());
+        }
+
+        metadata::write_atomically(&self.path, &serde_json::to_vec(&self.contents)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::CliProvider;
+    use crate::test_support::FixtureRepo;
+
+    /// A repository with a committed intro and guide, the canonical paths of the two files
+    /// and the path of the cache file, outside of the source directory.
+    fn fixture() -> Result<(FixtureRepo, PathBuf, PathBuf, PathBuf)> {
+        let mut repo = FixtureRepo::new()?;
+        repo.file("src/intro.md", "# Intro\n")?
+            .file("src/guide.md", "# Guide\n")?;
+        repo.commit(
+            "Add the chapters",
+            "Alice",
+            "alice@example.com",
+            "2024-01-10T12:00:00Z".parse()?,
+        )?;
+        let intro = repo.path().join("src/intro.md");
+        let guide = repo.path().join("src/guide.md");
+        let cache = repo.path().join("cache.json");
+        Ok((repo, intro, guide, cache))
+    }
+
+    fn open(repo: &FixtureRepo, cache: &Path, options: &GitInfoOptions) -> Result<HistoryCache> {
+        HistoryCache::open(
+            cache.to_path_buf(),
+            &repo.path().join("src"),
+            options,
+            &CliProvider,
+        )
+    }
+
+    fn history() -> Vec<GitHistoryEntry> {
+        vec![GitHistoryEntry::test("a", "Alice", "2024-01-10T12:00:00Z")]
+    }
+
+    #[test]
+    fn saved_histories_are_hits_until_head_moves() -> Result<()> {
+        let (mut repo, intro, guide, path) = fixture()?;
+        let options = GitInfoOptions::default();
+
+        let mut cache = open(&repo, &path, &options)?;
+        assert!(cache.get(&intro).is_none());
+        cache.insert(intro.clone(), history());
+        cache.insert_blame(intro.clone(), vec![("Alice".to_string(), 1)]);
+        cache.save()?;
+
+        let cache = open(&repo, &path, &options)?;
+        assert_eq!(cache.get(&intro).map(Vec::len), Some(1));
+        assert_eq!(
+            cache.get_blame(&intro).cloned(),
+            Some(vec![("Alice".to_string(), 1)])
+        );
+        assert!(cache.get(&guide).is_none());
+
+        repo.file("src/guide.md", "# Guide\n\nMore.\n")?;
+        repo.commit(
+            "Expand the guide",
+            "Bob",
+            "bob@example.com",
+            "2024-02-01T12:00:00Z".parse()?,
+        )?;
+        let cache = open(&repo, &path, &options)?;
+        assert!(cache.get(&intro).is_none());
+        assert!(cache.get_blame(&intro).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn histories_are_invalidated_by_extraction_options() -> Result<()> {
+        let (repo, intro, _, path) = fixture()?;
+
+        let mut cache = open(&repo, &path, &GitInfoOptions::default())?;
+        cache.insert(intro.clone(), history());
+        cache.save()?;
+
+        let options = GitInfoOptions::builder().max_history(5).build();
+        assert!(open(&repo, &path, &options)?.get(&intro).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn dirty_files_are_never_cached() -> Result<()> {
+        let (mut repo, intro, guide, path) = fixture()?;
+        let options = GitInfoOptions::default();
+        let mut cache = open(&repo, &path, &options)?;
+        cache.insert(intro.clone(), history());
+        cache.insert(guide.clone(), history());
+        cache.save()?;
+
+        repo.file("src/intro.md", "# Intro\n\nUncommitted.\n")?;
+        let mut cache = open(&repo, &path, &options)?;
+        assert!(cache.get(&intro).is_none());
+        assert!(cache.get(&guide).is_some());
+        cache.insert(intro.clone(), history());
+        assert!(cache.get(&intro).is_none());
+
+        // Unless the check is disabled
+        let options = GitInfoOptions::builder().cache_dirty_check(false).build();
+        assert!(open(&repo, &path, &options)?.get(&intro).is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn corrupt_caches_are_empty() -> Result<()> {
+        let (repo, intro, _, path) = fixture()?;
+        fs::write(&path, "{ not json")?;
+
+        let mut cache = open(&repo, &path, &GitInfoOptions::default())?;
+        assert!(cache.get(&intro).is_none());
+        cache.insert(intro.clone(), history());
+        cache.save()?;
+
+        assert!(open(&repo, &path, &GitInfoOptions::default())?
+            .get(&intro)
+            .is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn unmodified_caches_are_not_written() -> Result<()> {
+        let (repo, _, _, path) = fixture()?;
+
+        open(&repo, &path, &GitInfoOptions::default())?.save()?;
+
+        assert!(!path.exists());
+        Ok(())
+    }
+}
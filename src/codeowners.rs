@@ -0,0 +1,228 @@
+use anyhow::{Context, Result};
+use glob::{MatchOptions, Pattern};
+use std::fs;
+use std::path::Path;
+
+/// Locations of the CODEOWNERS file relative to the root of the repository,
+/// in the order GitHub looks them up.
+const LOCATIONS: [&str; 3] = [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// Owners of the files of a repository, as declared in a GitHub CODEOWNERS file.
+#[derive(Debug, Clone, Default)]
+pub struct CodeOwners {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    pattern: Pattern,
+    /// Whether the pattern only matches directories, because it ends with `/`.
+    directory_only: bool,
+    owners: Vec<String>,
+}
+
+impl CodeOwners {
+    /// Reads the CODEOWNERS file of the repository rooted at `root`, if there is one.
+    pub fn find(root: &Path) -> Result<Option<CodeOwners>> {
+        for location in LOCATIONS {
+            let path = root.join(location);
+            if path.is_file() {
+                let contents = fs::read_to_string(&path)
+                    .with_context(|| format!("Cannot read {}", path.display()))?;
+                return CodeOwners::parse(&contents)
+                    .with_context(|| format!("Invalid CODEOWNERS file {}", path.display()))
+                    .map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Parses the contents of a CODEOWNERS file.
+    ///
+    /// Each line holds a gitignore-like pattern followed by the owners (`@user`, `@org/team`
+    /// or email addresses). `#` starts a comment and `\` escapes spaces and `#` in patterns.
+    pub fn parse(contents: &str) -> Result<CodeOwners> {
+        let mut rules = Vec::new();
+        for (number, line) in contents.lines().enumerate() {
+            let mut tokens = tokenize(line).into_iter();
+            let pattern = match tokens.next() {
+                Some(pattern) => pattern,
+                None => continue,
+            };
+            rules.push(
+                Rule::new(&pattern, tokens.collect()).with_context(|| {
+                    format!("Line {}: invalid pattern {:?}", number + 1, pattern)
+                })?,
+            );
+        }
+        Ok(CodeOwners { rules })
+    }
+
+    /// Returns the owners of a file, given its path relative to the root of the repository.
+    ///
+    /// The last matching line wins, and it may well declare no owners.
+    pub fn owners(&self, path: &Path) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(path))
+            .map_or(&[], |rule| &rule.owners)
+    }
+}
+
+impl Rule {
+    fn new(pattern: &str, owners: Vec<String>) -> Result<Rule> {
+        let directory_only = pattern.ends_with('/');
+        let trimmed = pattern.trim_end_matches('/');
+
+        // Like in gitignore, patterns without an inner `/` match at any depth,
+        // while the others are relative to the root
+        let glob = match trimmed.strip_prefix('/') {
+            Some(anchored) => anchored.to_string(),
+            None if trimmed.contains('/') => trimmed.to_string(),
+            None => format!("**/{}", trimmed),
+        };
+        Ok(Rule {
+            pattern: Pattern::new(&glob)?,
+            directory_only,
+            owners,
+        })
+    }
+
+    /// Whether the rule matches the file, or any of the directories containing it.
+    fn matches(&self, path: &Path) -> bool {
+        let options = MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        let file = std::iter::once(path).filter(|_| !self.directory_only);
+        let directories = path
+            .ancestors()
+            .skip(1)
+            .filter(|dir| !dir.as_os_str().is_empty());
+        file.chain(directories)
+            .any(|candidate| self.pattern.matches_path_with(candidate, options))
+    }
+}
+
+/// Splits a line into whitespace-separated tokens, dropping comments and handling escapes.
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped @ (' ' | '#' | '\t')) => token.push(escaped),
+                // Other backslashes belong to the glob pattern
+                Some(other) => {
+                    token.push('\\');
+                    token.push(other);
+                }
+                None => token.push('\\'),
+            },
+            '#' if token.is_empty() => break,
+            c if c.is_whitespace() => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            c => token.push(c),
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn owners<'a>(codeowners: &'a CodeOwners, path: &str) -> &'a [String] {
+        codeowners.owners(Path::new(path))
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() -> Result<()> {
+        let codeowners = CodeOwners::parse(
+            "# Owners of the book\n\
+             \n\
+             \t\n\
+             *.md @writers # the prose\n\
+             #src/ @nobody\n",
+        )?;
+        assert_eq!(owners(&codeowners, "src/intro.md"), ["@writers"]);
+        assert!(owners(&codeowners, "src/main.rs").is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn escaped_spaces_belong_to_the_pattern() -> Result<()> {
+        let codeowners = CodeOwners::parse("my\\ notes/ @alice\nissue\\#1.md @bob\n")?;
+        assert_eq!(owners(&codeowners, "my notes/intro.md"), ["@alice"]);
+        assert!(owners(&codeowners, "my/intro.md").is_empty());
+        assert_eq!(owners(&codeowners, "docs/issue#1.md"), ["@bob"]);
+        Ok(())
+    }
+
+    #[test]
+    fn owners_are_users_teams_and_emails() -> Result<()> {
+        let codeowners =
+            CodeOwners::parse("/docs/   @alice  @example/docs-team\tbob@example.com\n")?;
+        assert_eq!(
+            owners(&codeowners, "docs/guide/intro.md"),
+            ["@alice", "@example/docs-team", "bob@example.com"]
+        );
+        // Anchored to the root
+        assert!(owners(&codeowners, "book/docs/intro.md").is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn the_last_matching_line_wins() -> Result<()> {
+        let codeowners = CodeOwners::parse(
+            "* @everyone\n\
+             src/ @writers\n\
+             intro.md @alice\n\
+             src/drafts/\n",
+        )?;
+        assert_eq!(owners(&codeowners, "README.md"), ["@everyone"]);
+        assert_eq!(owners(&codeowners, "src/guide.md"), ["@writers"]);
+        // Unanchored names match at any depth
+        assert_eq!(owners(&codeowners, "src/intro.md"), ["@alice"]);
+        // Later lines may remove the owners
+        assert!(owners(&codeowners, "src/drafts/intro.md").is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn directory_patterns_only_match_directories() -> Result<()> {
+        let codeowners = CodeOwners::parse("guide/ @writers\n")?;
+        assert_eq!(owners(&codeowners, "src/guide/intro.md"), ["@writers"]);
+        assert!(owners(&codeowners, "src/guide").is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_patterns_name_their_line() {
+        let error = CodeOwners::parse("* @a\n[ @b\n").unwrap_err();
+        assert!(format!("{:#}", error).starts_with("Line 2: invalid pattern \"[\""));
+    }
+
+    #[test]
+    fn the_github_directory_is_looked_up_first() -> Result<()> {
+        let dir = TempDir::new()?;
+        assert!(CodeOwners::find(dir.path())?.is_none());
+
+        fs::write(dir.path().join("CODEOWNERS"), "* @root\n")?;
+        fs::create_dir(dir.path().join(".github"))?;
+        fs::write(dir.path().join(".github/CODEOWNERS"), "* @github\n")?;
+        let codeowners = CodeOwners::find(dir.path())?.unwrap();
+        assert_eq!(owners(&codeowners, "intro.md"), ["@github"]);
+        Ok(())
+    }
+}
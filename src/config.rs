@@ -0,0 +1,2282 @@
+use crate::front_matter::{self, FrontMatter};
+use crate::git_history::{self, GitHistoryEntry};
+use crate::history_file;
+use crate::info::GitInfo;
+use crate::locale;
+use crate::provider::{CliProvider, HistoryProvider};
+use anyhow::{Context, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Utc};
+use mdbook::Config;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+/// Options of the preprocessor, read from the `[preprocessor.git-info]` table of `book.toml`.
+///
+/// Use [`GitInfoOptions::builder`] to create them programmatically.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct GitInfoOptions {
+    /// Whether the preprocessor does anything at all.
+    /// Overridden by the [`ENABLED_ENV_VAR`] environment variable.
+    pub enabled: bool,
+    /// Fail instead of replacing invalid UTF-8 sequences in the git output.
+    pub strict_utf8: bool,
+    /// What to do with lines of the `git log` output that cannot be parsed.
+    pub on_malformed_line: MalformedLineAction,
+    /// `strftime`-like format of the rendered dates.
+    pub date_format: String,
+    /// Glob patterns of chapter source paths (relative to the book's source directory)
+    /// which are left untouched.
+    pub exclude: Vec<String>,
+    /// How histories are extracted from git.
+    pub extraction: ExtractionMode,
+    /// Whether the history of a file continues across renames.
+    pub follow_renames: bool,
+    /// Maximum number of concurrent `git` processes in per-file extraction mode.
+    /// Defaults to the number of CPUs, up to [`MAX_DEFAULT_JOBS`].
+    pub jobs: Option<usize>,
+    /// File, relative to the book root, where histories are cached between runs.
+    /// The cache is invalidated whenever `HEAD` moves.
+    ///
+    /// Do not place it inside the build directory, which mdbook cleans on every build.
+    pub cache_file: Option<PathBuf>,
+    /// Never use cached histories for files with uncommitted changes.
+    pub cache_dirty_check: bool,
+    /// Backend used to read the git repository.
+    pub backend: Backend,
+    /// Path of the `git` binary.
+    pub git_binary: PathBuf,
+    /// Extra arguments passed to every `git` invocation, before the subcommand
+    /// (e.g. `["-c", "safe.directory=*"]`).
+    pub git_args: Vec<String>,
+    /// Maximum duration of any `git` invocation, after which it is killed.
+    pub git_timeout_secs: Option<u64>,
+    /// Version of the `git` binary, probed by the preprocessor rather than configured.
+    /// Versions older than [`git_history::MIN_GIT_VERSION`] are asked for the whole commit
+    /// messages, whose trailers are then parsed like by the libgit2 backend.
+    #[serde(skip)]
+    pub git_version: Option<git_history::GitVersion>,
+    /// Fail the build when `git` cannot be run. When disabled, a warning is logged instead
+    /// and the book is left untouched, so that it builds on machines without git.
+    pub require_git: bool,
+    /// Maximum number of commits fetched for each file.
+    ///
+    /// The creation of the file is always looked up, so "Created on/by" stay correct,
+    /// but contributors only come from the fetched commits and are marked with
+    /// [`GitInfoOptions::incomplete_suffix`]. Commit counts then only include the fetched
+    /// commits plus the creating one.
+    pub max_history: Option<usize>,
+    /// Text appended to the contributors of truncated histories.
+    pub incomplete_suffix: String,
+    /// Renderers for which the chapters are enriched. The book is passed through
+    /// unchanged for any other renderer.
+    pub renderers: Vec<String>,
+    /// Hide the footer in the printable version of the book (`print.html`).
+    pub hide_in_print: bool,
+    /// Markup of the footer for HTML-capable renderers.
+    pub output: OutputFormat,
+    /// Path, relative to the book root, where the bundled stylesheet is written
+    /// (e.g. `theme/git-info.css`), to be listed under `output.html.additional-css`.
+    pub write_css: Option<PathBuf>,
+    /// Link each chapter to the bundled stylesheet, written to
+    /// [`LINKED_FILE`](crate::stylesheet::LINKED_FILE) in the source directory, for books
+    /// whose configuration cannot list it as additional CSS.
+    pub inline_css: bool,
+    /// Add schema.org structured data (JSON-LD) with the dates and authors of each chapter.
+    pub json_ld: bool,
+    /// Base URL where the book is published, used to build absolute links to its pages.
+    pub site_url: Option<String>,
+    /// File, relative to the book root, where the git metadata of every chapter is written
+    /// as JSON, keyed by source path.
+    pub metadata_output: Option<PathBuf>,
+    /// Generated chapter listing the contributors of the whole book, added when the table is present.
+    pub contributors_chapter: Option<ContributorsChapter>,
+    /// Generated chapter listing the most recently edited chapters, added when the table is present.
+    pub recently_updated: Option<RecentlyUpdated>,
+    /// Generated chapter listing the recent commits touching the book, added when the table is present.
+    pub changelog: Option<Changelog>,
+    /// Atom feed of the chapter updates, written when the table is present.
+    pub feed: Option<Feed>,
+    /// Sitemap of the chapters, dated by their last commit, written when the table is present.
+    pub sitemap: Option<Sitemap>,
+    /// Stamp with the revision the book is built from, added when the table is present.
+    pub provenance: Option<Provenance>,
+    /// History file written by `mdbook-git-info export-history`, relative to the book root.
+    /// When set, histories are read from it and git is never run.
+    pub history_file: Option<PathBuf>,
+    /// Do not log the summary line at the end of each run, shown at the `info` level.
+    ///
+    /// Also hides the progress, unless [`GitInfoOptions::progress`] is `always`.
+    pub quiet: bool,
+    /// Progress of the history extraction, printed to stderr while it runs.
+    pub progress: ProgressMode,
+    /// Show the owners of each chapter according to the CODEOWNERS file of the repository.
+    pub codeowners: bool,
+    /// Look up the GitHub accounts of the contributors with the GitHub API, to link their names
+    /// to their profiles and show their avatars. Needs a token in `GITHUB_TOKEN`, and is
+    /// silently skipped without one or when the API cannot be reached.
+    pub github_api: bool,
+    /// Repository hosting the commits on GitHub, as `owner/repo`. Detected from the `origin`
+    /// remote when unset.
+    pub github_repository: Option<String>,
+    /// File, relative to the book root, where the accounts found with the GitHub API are cached
+    /// between runs.
+    pub github_cache: PathBuf,
+    /// Days after which the cached accounts are looked up again.
+    pub github_cache_ttl_days: u32,
+    /// Maximum number of requests sent to the GitHub API in a single run.
+    pub github_max_requests: usize,
+    /// Remove the front matter block (YAML between `---` lines or TOML between `+++` lines)
+    /// from the beginning of the chapters, which mdbook would render literally.
+    ///
+    /// Its `authors`, `created` and `last_edit` keys always override the git data,
+    /// and `git-info: skip` leaves the chapter untouched.
+    pub strip_front_matter: bool,
+    /// Add the keys of [`GitInfoOptions::front_matter_keys`] to the front matter of each chapter
+    /// with a history, for renderers which read it like pandoc-based ones. A YAML block is created
+    /// for chapters without front matter, and keys which are already there are kept.
+    ///
+    /// mdbook itself renders front matter literally: see [`GitInfoOptions::renderers`].
+    pub emit_front_matter: bool,
+    /// Keys added by [`GitInfoOptions::emit_front_matter`].
+    pub front_matter_keys: Vec<FrontMatterKey>,
+    /// Creation dates and authors replacing the git-derived ones, by chapter source path
+    /// or glob pattern (relative to the book's source directory).
+    ///
+    /// Exact paths take precedence over patterns. Front matter takes precedence over both.
+    pub created_overrides: BTreeMap<String, CreatedOverride>,
+    /// Merge into the history of each chapter the histories of the files it pulls in
+    /// with `{{#include}}` or `{{#rustdoc_include}}`, recursively.
+    pub include_includes: bool,
+    /// Whether included files older than a chapter make it look older.
+    /// Otherwise, only their commits made after the creation of the chapter count.
+    pub includes_affect_creation: bool,
+    /// Rules showing the history of another file for some chapters, such as the original
+    /// of translated chapters.
+    pub path_map: PathMap,
+    /// Locations of the chapter files before the book was moved, whose histories are merged
+    /// into the ones of the chapters, for moves which `follow_renames` cannot reconstruct.
+    ///
+    /// Keys are globs matching source paths, values are paths relative to the root of the
+    /// repository, where `{path}`, `{dir}`, `{name}`, `{stem}` and `{ext}` are replaced with
+    /// the parts of the source path (e.g. `"**/*.md" = ["documentation/{stem}.md"]`).
+    pub historical_paths: BTreeMap<String, Vec<String>>,
+    /// Keys of the commit trailers naming the reviewers of a commit.
+    pub reviewers_from: Vec<String>,
+    /// Show the reviewers of each chapter, taken from [`GitInfoOptions::reviewers_from`].
+    pub show_reviewers: bool,
+    /// Show the number of distinct contributors of each chapter, like "5 contributors".
+    pub show_contributor_count: bool,
+    /// Wrap the footer in a collapsed `<details>` element, for the HTML renderer only,
+    /// since e-readers do not all support it.
+    pub collapsible: bool,
+    /// Label of the `<details>` element of [`GitInfoOptions::collapsible`].
+    pub collapsible_summary: String,
+    /// Expand the `<details>` element of [`GitInfoOptions::collapsible`] by default.
+    pub open_by_default: bool,
+    /// What separates the chapter from the markdown table of the footer.
+    pub separator: Separator,
+    /// Markdown or HTML separating the chapter from the footer with `separator = "custom"`.
+    pub separator_text: String,
+    /// Options merged over the other ones for the chapters whose source path (relative to
+    /// the book's source directory) matches a glob pattern, e.g. `[preprocessor.git-info.overrides."notes/**"]`.
+    ///
+    /// When several patterns match, the most specific one wins: the one with the most characters
+    /// other than `*` and `?`, then the last one in alphabetical order. Only the options affecting
+    /// the footer of a chapter apply, extraction and generated chapters use the global options.
+    pub overrides: BTreeMap<String, toml::value::Table>,
+    /// Text of the heading (e.g. `About this page`) whose section the footer is inserted at
+    /// the end of, instead of the end of the chapter.
+    pub insert_under_heading: Option<String>,
+    /// Whether [`GitInfoOptions::insert_under_heading`] is compared case-sensitively.
+    pub heading_case_sensitive: bool,
+    /// What to do with chapters without the heading of [`GitInfoOptions::insert_under_heading`].
+    pub missing_heading: MissingHeading,
+    /// Git notes ref (e.g. `reviews` for `refs/notes/reviews`) recording the editorial reviews
+    /// of the commits, whose most recent one is shown when set.
+    pub notes_ref: Option<String>,
+    /// Key of the note lines recording a review, like `Reviewed: 2024-06-01 by Alice`.
+    pub notes_key: String,
+    /// Count the lines added and removed by each commit, shown as a total for each chapter.
+    ///
+    /// This roughly doubles the size of the git output to parse.
+    pub churn: bool,
+    /// Show the author of most of the current lines of each chapter, according to `git blame`.
+    pub primary_author: bool,
+    /// Show the share of the lines written by the primary author, like "Jane Doe (72%)".
+    pub primary_author_percentage: bool,
+    /// Files larger than this are not blamed, which is expensive.
+    pub blame_max_bytes: u64,
+    /// What to do with chapters whose file has no git history, e.g. because it is not committed yet.
+    pub missing_history: MissingHistory,
+    /// Markdown shown instead of the footer by [`MissingHistory::Placeholder`].
+    pub missing_history_text: String,
+    /// Fail the build with a report of every chapter whose file has no git history or does not
+    /// exist, excluded chapters and drafts aside.
+    ///
+    /// Takes precedence over [`GitInfoOptions::missing_history`] and
+    /// [`GitInfoOptions::require_git`].
+    pub strict: bool,
+    /// Embed the info of each chapter as JSON in a hidden
+    /// `<script type="application/json" class="git-info-data">` element, for themes to display it their own way.
+    pub data_attributes: bool,
+    /// Leave out the visible footer, e.g. when the theme displays [`GitInfoOptions::data_attributes`] instead.
+    pub hide_footer: bool,
+    /// Show the "Other contributors" column.
+    ///
+    /// Without it, and without any other feature needing the whole history of the chapters
+    /// (see [`GitInfoOptions::needs_full_history`]), per-file extraction only queries
+    /// the newest commit and the creating commit of each file.
+    pub show_other_contributors: bool,
+    /// Leave out of the footer of each chapter the columns without content, like the other
+    /// contributors of a chapter with a single author.
+    pub hide_empty_columns: bool,
+    /// Show the creation and the last edit as a single "Written on … by …" when the chapter
+    /// was only ever touched by the commit which created it.
+    pub collapse_single_edit: bool,
+    /// Graph of the commits of each chapter by month, shown by HTML renderers.
+    /// Sparklines fall back to unicode block characters for other renderers.
+    pub activity_graph: ActivityGraph,
+    /// Width of [`GitInfoOptions::activity_graph`], in pixels.
+    pub activity_graph_width: u32,
+    /// Height of [`GitInfoOptions::activity_graph`], in pixels.
+    pub activity_graph_height: u32,
+    /// Number of months, up to the current one, covered by [`GitInfoOptions::activity_graph`].
+    pub activity_graph_months: usize,
+    /// Language of the month and weekday names in [`GitInfoOptions::date_format`] (e.g. `de` or `fr`),
+    /// English by default.
+    pub locale: Option<String>,
+    /// Conventional commit types (e.g. `chore` for `chore: fix links`) of the commits which
+    /// are not substantive changes. Subjects without a type are never ignored.
+    pub ignore_commit_types: Vec<String>,
+    /// What [`GitInfoOptions::ignore_commit_types`] ignores the commits for.
+    pub ignore_commit_types_affects: Vec<IgnoredCommitEffect>,
+    /// Ref the book is published from (e.g. `origin/gh-pages`), to show how many commits of
+    /// each chapter are not published yet.
+    pub published_ref: Option<String>,
+    /// Who is credited for a commit, in every author and contributor shown.
+    pub identity: Identity,
+    /// Show both the author and the committer of the creating and of the last commit,
+    /// like "authored by Alice, committed by Bob", when they differ.
+    pub show_both: bool,
+    /// Copyright line of each chapter, with the years of its first and last commits,
+    /// added when the table is present.
+    pub copyright: Option<Copyright>,
+    /// Show the date of the most recent edit of any chapter in the footer of every chapter.
+    pub show_book_last_update: bool,
+    /// Show the first release (tag) containing the last edit of each chapter.
+    pub show_version: bool,
+    /// Glob matching the tags of releases, e.g. `v*`.
+    pub version_pattern: String,
+    /// Text shown instead of the release for chapters changed since the latest release.
+    pub unreleased_text: String,
+    /// Show whether the last commit of each chapter is signed by a trusted key. Slow, since
+    /// every signature is checked with gpg or ssh.
+    pub show_signature_status: bool,
+    /// Texts shown for the signature statuses.
+    pub signature_labels: SignatureLabels,
+    /// Rules checked by `mdbook-git-info lint`, which have no effect on the build.
+    pub lint: Lint,
+}
+
+/// Environment variable which forces the preprocessor `on` or `off`, whatever the configuration says.
+pub const ENABLED_ENV_VAR: &str = "MDBOOK_GIT_INFO";
+
+/// Upper bound of the default value of [`GitInfoOptions::jobs`].
+pub const MAX_DEFAULT_JOBS: usize = 8;
+
+impl Default for GitInfoOptions {
+    fn default() -> Self {
+        GitInfoOptions {
+            enabled: true,
+            strict_utf8: false,
+            on_malformed_line: MalformedLineAction::default(),
+            date_format: "%d %b %Y".to_string(),
+            exclude: Vec::new(),
+            extraction: ExtractionMode::default(),
+            follow_renames: true,
+            jobs: None,
+            cache_file: None,
+            cache_dirty_check: true,
+            backend: Backend::default(),
+            git_binary: PathBuf::from("git"),
+            git_args: Vec::new(),
+            git_timeout_secs: None,
+            git_version: None,
+            require_git: true,
+            max_history: None,
+            incomplete_suffix: "and others".to_string(),
+            hide_in_print: false,
+            output: OutputFormat::default(),
+            write_css: None,
+            inline_css: false,
+            json_ld: false,
+            site_url: None,
+            metadata_output: None,
+            contributors_chapter: None,
+            recently_updated: None,
+            changelog: None,
+            feed: None,
+            sitemap: None,
+            provenance: None,
+            history_file: None,
+            quiet: false,
+            progress: ProgressMode::default(),
+            codeowners: false,
+            github_api: false,
+            github_repository: None,
+            github_cache: PathBuf::from(".git-info-github.json"),
+            github_cache_ttl_days: 7,
+            github_max_requests: 100,
+            strip_front_matter: false,
+            emit_front_matter: false,
+            front_matter_keys: vec![
+                FrontMatterKey::Date,
+                FrontMatterKey::Author,
+                FrontMatterKey::Contributors,
+                FrontMatterKey::GitCommit,
+            ],
+            created_overrides: BTreeMap::new(),
+            include_includes: false,
+            includes_affect_creation: true,
+            path_map: PathMap::default(),
+            historical_paths: BTreeMap::new(),
+            reviewers_from: vec!["Reviewed-by".to_string()],
+            show_reviewers: false,
+            show_contributor_count: false,
+            collapsible: false,
+            collapsible_summary: "Page history".to_string(),
+            open_by_default: false,
+            separator: Separator::default(),
+            separator_text: String::new(),
+            overrides: BTreeMap::new(),
+            insert_under_heading: None,
+            heading_case_sensitive: false,
+            missing_heading: MissingHeading::default(),
+            notes_ref: None,
+            notes_key: "Reviewed".to_string(),
+            churn: false,
+            primary_author: false,
+            primary_author_percentage: true,
+            blame_max_bytes: 1_000_000,
+            missing_history: MissingHistory::default(),
+            missing_history_text: "_No git history available for this page._".to_string(),
+            strict: false,
+            data_attributes: false,
+            hide_footer: false,
+            show_other_contributors: true,
+            hide_empty_columns: false,
+            collapse_single_edit: false,
+            activity_graph: ActivityGraph::default(),
+            activity_graph_width: 120,
+            activity_graph_height: 24,
+            activity_graph_months: 36,
+            locale: None,
+            ignore_commit_types: Vec::new(),
+            ignore_commit_types_affects: vec![IgnoredCommitEffect::LastEdit],
+            published_ref: None,
+            identity: Identity::default(),
+            show_both: false,
+            copyright: None,
+            show_book_last_update: false,
+            show_version: false,
+            version_pattern: "*".to_string(),
+            unreleased_text: "unreleased".to_string(),
+            show_signature_status: false,
+            signature_labels: SignatureLabels::default(),
+            lint: Lint::default(),
+            renderers: vec![
+                "html".to_string(),
+                "markdown".to_string(),
+                "epub".to_string(),
+            ],
+        }
+    }
+}
+
+/// Settings of the generated contributors chapter.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct ContributorsChapter {
+    /// Title of the chapter.
+    pub title: String,
+    /// Path of the chapter, relative to the book's source directory.
+    ///
+    /// If `SUMMARY.md` already lists a chapter with this path, its content is replaced,
+    /// which allows placing the chapter anywhere. Otherwise it is appended to the book.
+    pub path: PathBuf,
+}
+
+impl Default for ContributorsChapter {
+    fn default() -> Self {
+        ContributorsChapter {
+            title: "Contributors".to_string(),
+            path: PathBuf::from("contributors.md"),
+        }
+    }
+}
+
+/// Settings of the generated "Recently updated" chapter.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct RecentlyUpdated {
+    /// Title of the chapter.
+    pub title: String,
+    /// Path of the chapter, relative to the book's source directory.
+    /// See [`ContributorsChapter::path`].
+    pub path: PathBuf,
+    /// Where the chapter is added, unless `SUMMARY.md` already lists it.
+    pub position: ChapterPosition,
+    /// Maximum number of listed chapters.
+    pub max_entries: usize,
+    /// Only list chapters edited in this many days before the build.
+    pub window_days: Option<u64>,
+    /// Show the subject of the last commit of each chapter.
+    pub show_subject: bool,
+}
+
+impl Default for RecentlyUpdated {
+    fn default() -> Self {
+        RecentlyUpdated {
+            title: "Recently updated".to_string(),
+            path: PathBuf::from("recently-updated.md"),
+            position: ChapterPosition::default(),
+            max_entries: 10,
+            window_days: None,
+            show_subject: false,
+        }
+    }
+}
+
+/// Settings of the generated changelog chapter.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct Changelog {
+    /// Title of the chapter.
+    pub title: String,
+    /// Path of the chapter, relative to the book's source directory.
+    /// See [`ContributorsChapter::path`].
+    pub path: PathBuf,
+    /// Where the chapter is added, unless `SUMMARY.md` already lists it.
+    pub position: ChapterPosition,
+    /// Maximum number of listed commits.
+    pub max_commits: usize,
+    /// Maximum number of listed months, starting from the most recent commit.
+    pub max_months: Option<usize>,
+    /// List merge commits as well.
+    pub include_merges: bool,
+    /// URL of the page of a commit, where `{hash}` is replaced with the full hash
+    /// (e.g. `https://github.com/owner/repo/commit/{hash}`).
+    pub commit_url: Option<String>,
+}
+
+impl Default for Changelog {
+    fn default() -> Self {
+        Changelog {
+            title: "Changelog".to_string(),
+            path: PathBuf::from("changelog.md"),
+            position: ChapterPosition::default(),
+            max_commits: 50,
+            max_months: None,
+            include_merges: false,
+            commit_url: None,
+        }
+    }
+}
+
+/// Settings of the Atom feed of the chapter updates.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct Feed {
+    /// Path of the feed, relative to the book's source directory, so that it is copied
+    /// to the built book.
+    pub path: PathBuf,
+    /// Base URL of the published book. Defaults to [`GitInfoOptions::site_url`].
+    pub site_url: Option<String>,
+    /// Title of the feed. Defaults to the title of the book.
+    pub title: Option<String>,
+    /// Maximum number of entries, one for each of the most recently edited chapters.
+    pub max_entries: usize,
+}
+
+impl Default for Feed {
+    fn default() -> Self {
+        Feed {
+            path: PathBuf::from("feed.xml"),
+            site_url: None,
+            title: None,
+            max_entries: 20,
+        }
+    }
+}
+
+/// Settings of the copyright line of the chapters.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct Copyright {
+    /// Holder of the copyright, e.g. `Example Corp`.
+    pub holder: String,
+    /// Text before the years.
+    pub prefix: String,
+    /// Text after the holder.
+    pub suffix: String,
+    /// Year shown for chapters without history. The line is omitted for them when unset.
+    pub fallback_year: Option<i32>,
+    /// Show the line in the footer. Otherwise, it only replaces `{{#git_copyright}}` in the chapters.
+    pub footer: bool,
+}
+
+impl Default for Copyright {
+    fn default() -> Self {
+        Copyright {
+            holder: String::new(),
+            prefix: "\u{a9} ".to_string(),
+            suffix: String::new(),
+            fallback_year: None,
+            footer: true,
+        }
+    }
+}
+
+/// Texts shown for the signature of the last commit of a chapter. Unsigned commits show nothing.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct SignatureLabels {
+    /// Good signature by a trusted key.
+    pub verified: String,
+    /// Bad signature, or signature by an expired or revoked key.
+    pub unverified: String,
+    /// Signature which cannot be trusted or checked, usually because the key is missing
+    /// or not trusted where the book is built.
+    pub unknown: String,
+}
+
+impl Default for SignatureLabels {
+    fn default() -> Self {
+        SignatureLabels {
+            verified: "verified".to_string(),
+            unverified: "unverified".to_string(),
+            unknown: "unknown key".to_string(),
+        }
+    }
+}
+
+/// Rules checked by `mdbook-git-info lint`, from the `[preprocessor.git-info.lint]` table.
+///
+/// Every rule is off by default. Excluded chapters and drafts are never checked.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct Lint {
+    /// Fail when the last edit of a chapter is older than this many days.
+    pub max_age_days: Option<u32>,
+    /// Fail when a chapter has no git history.
+    pub require_history: bool,
+    /// Report the chapters edited by fewer distinct people.
+    pub min_contributors: Option<usize>,
+    /// Whether too few contributors only warn or fail.
+    pub min_contributors_level: LintLevel,
+}
+
+/// Settings of the sitemap of the book.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct Sitemap {
+    /// Write the sitemap. Allows turning it off without removing the table.
+    pub enabled: bool,
+    /// Path of the sitemap, relative to the book's source directory, so that it is copied
+    /// to the built book. When run as a renderer, relative to its destination instead.
+    pub path: PathBuf,
+    /// Base URL of the published book. Defaults to [`GitInfoOptions::site_url`].
+    pub site_url: Option<String>,
+}
+
+impl Default for Sitemap {
+    fn default() -> Self {
+        Sitemap {
+            enabled: true,
+            path: PathBuf::from("sitemap.xml"),
+            site_url: None,
+        }
+    }
+}
+
+/// Settings of the stamp with the revision the book is built from.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct Provenance {
+    /// Where the stamp is shown.
+    pub position: ProvenancePosition,
+    /// Title of the colophon chapter.
+    pub title: String,
+    /// Path of the colophon chapter, relative to the book's source directory.
+    pub path: PathBuf,
+    /// URL of the page of a commit, where `{hash}` is replaced with the full hash.
+    /// Defaults to the `commit_url` of the changelog.
+    pub commit_url: Option<String>,
+}
+
+impl Default for Provenance {
+    fn default() -> Self {
+        Provenance {
+            position: ProvenancePosition::default(),
+            title: "Colophon".to_string(),
+            path: PathBuf::from("colophon.md"),
+            commit_url: None,
+        }
+    }
+}
+
+/// Creation date and author of the chapters matching an entry of
+/// [`GitInfoOptions::created_overrides`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct CreatedOverride {
+    /// Creation date, as `YYYY-MM-DD` or RFC 3339.
+    pub date: String,
+    /// Creator shown instead of the author of the first commit.
+    pub author: Option<String>,
+}
+
+impl CreatedOverride {
+    /// Overrides the creation date only, keeping the git-derived creator.
+    pub fn new(date: impl Into<String>) -> Self {
+        CreatedOverride {
+            date: date.into(),
+            author: None,
+        }
+    }
+
+    /// Replaces the creation date and author of `info`, leaving the rest untouched.
+    pub fn apply(&self, info: &mut GitInfo) {
+        let front_matter = FrontMatter {
+            authors: self.author.clone().map(|author| vec![author]),
+            created: front_matter::parse_date(&self.date).ok(),
+            ..FrontMatter::default()
+        };
+        front_matter.apply(info);
+    }
+}
+
+/// Rules mapping the source paths of chapters to the files whose history they show,
+/// e.g. `"fr/(.*)" = "en/$1"`.
+///
+/// Patterns are regular expressions matching whole source paths (relative to the book's
+/// source directory, with `/` separators), and replacements can refer to their capture
+/// groups as `$1` or `${name}`. When several patterns match, the first one in alphabetical
+/// order wins.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct PathMap {
+    /// Whether the commits to the chapter itself are kept.
+    pub merge: PathMapMerge,
+    /// Replacement paths by pattern.
+    #[serde(flatten)]
+    pub rules: BTreeMap<String, String>,
+}
+
+impl PathMap {
+    /// Returns the path of the file whose history is shown for the chapter with the given
+    /// source path, if any rule matches.
+    pub fn map(&self, source_path: &Path) -> Option<PathBuf> {
+        let source_path = history_file::normalize(source_path);
+        self.rules.iter().find_map(|(pattern, replacement)| {
+            let regex = Regex::new(&format!("^(?:{})$", pattern)).ok()?;
+            regex
+                .is_match(&source_path)
+                .then(|| PathBuf::from(regex.replace(&source_path, replacement.as_str()).as_ref()))
+        })
+    }
+}
+
+/// How the history of a mapped chapter (see [`PathMap`]) is combined with its own one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum PathMapMerge {
+    /// Only the history of the mapped file is shown.
+    #[default]
+    Replace,
+    /// The histories of both files are merged.
+    Union,
+}
+
+/// Separator between the chapter and the markdown table of the footer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Separator {
+    /// A horizontal rule, surrounded by line breaks for HTML-capable renderers.
+    #[default]
+    Hr,
+    /// Nothing.
+    None,
+    /// [`GitInfoOptions::separator_text`].
+    Custom,
+}
+
+/// What to do with chapters without the heading of [`GitInfoOptions::insert_under_heading`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum MissingHeading {
+    /// Append the footer at the end of the chapter.
+    #[default]
+    Append,
+    /// Leave the chapter without footer.
+    Skip,
+    /// Fail the chapter.
+    Fail,
+}
+
+/// What the commits of [`GitInfoOptions::ignore_commit_types`] are ignored for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum IgnoredCommitEffect {
+    /// The last edit, also used to find the recently updated chapters. The newest commit is
+    /// still the last edit when every commit is ignored.
+    LastEdit,
+    /// The other contributors.
+    Contributors,
+    /// The commit and line counts, and the activity graph.
+    Totals,
+}
+
+/// Graph of the commits of a chapter by month.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum ActivityGraph {
+    /// No graph.
+    #[default]
+    None,
+    /// A line of the commits by month.
+    Sparkline,
+    /// A grid of months by year, with cells of class `git-info-heat-0` to `git-info-heat-4`
+    /// by number of commits.
+    Heatmap,
+}
+
+/// What to do with chapters whose file has no git history.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum MissingHistory {
+    /// Show [`GitInfoOptions::missing_history_text`] instead of the footer.
+    Placeholder,
+    /// Leave the chapter without footer.
+    #[default]
+    Omit,
+    /// Fail the build, listing every chapter without history.
+    Error,
+}
+
+/// Identity credited for a commit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Identity {
+    /// The person who wrote the change.
+    #[default]
+    Author,
+    /// The person who committed the change, e.g. when applying a patch.
+    Committer,
+}
+
+/// When the progress of the history extraction is shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum ProgressMode {
+    /// A progress bar when stderr is a terminal, a line every few seconds otherwise,
+    /// unless [`GitInfoOptions::quiet`] is set.
+    #[default]
+    Auto,
+    /// A progress bar, even when stderr is not a terminal or the output is quiet.
+    Always,
+    /// No progress.
+    Never,
+}
+
+/// Outcome of a broken lint rule.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum LintLevel {
+    /// Report the chapter without failing.
+    Warn,
+    /// Report the chapter and fail.
+    #[default]
+    Error,
+}
+
+/// Key added to the front matter of the chapters by [`GitInfoOptions::emit_front_matter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum FrontMatterKey {
+    /// Date of the last edit, as `YYYY-MM-DD`.
+    Date,
+    /// Creator of the chapter.
+    Author,
+    /// Everyone who edited the chapter, creator and last editor first.
+    Contributors,
+    /// Hash of the last edit.
+    GitCommit,
+}
+
+impl FrontMatterKey {
+    /// Name of the key in the front matter.
+    pub fn name(self) -> &'static str {
+        match self {
+            FrontMatterKey::Date => "date",
+            FrontMatterKey::Author => "author",
+            FrontMatterKey::Contributors => "contributors",
+            FrontMatterKey::GitCommit => "git_commit",
+        }
+    }
+}
+
+/// Where the stamp with the revision the book is built from is shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ProvenancePosition {
+    /// At the end of the first chapter.
+    #[default]
+    FirstChapter,
+    /// In the footer of every chapter.
+    EveryChapter,
+    /// In a generated chapter at the back of the book.
+    Colophon,
+}
+
+/// Where a generated chapter is added to the book.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum ChapterPosition {
+    /// Before every other chapter.
+    Front,
+    /// After every other chapter, separated from the last part.
+    #[default]
+    Back,
+}
+
+/// Action taken when a line of the `git log` output cannot be parsed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum MalformedLineAction {
+    /// Fail the chapter.
+    #[default]
+    Fail,
+    /// Skip the line with a warning.
+    Skip,
+}
+
+/// Strategy used to extract the histories of the chapters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum ExtractionMode {
+    /// A single `git log` over the whole source directory of the book.
+    #[default]
+    Batch,
+    /// One `git log` for each chapter.
+    PerFile,
+}
+
+/// Backend used to read the git repository.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Backend {
+    /// Run the `git` command line tool.
+    #[default]
+    Cli,
+    /// Use libgit2 in-process. Requires the `libgit2` cargo feature.
+    Libgit2,
+}
+
+/// Markup of the footer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum OutputFormat {
+    /// A markdown table.
+    #[default]
+    Markdown,
+    /// A `<footer class="git-info">` element with CSS classes for theming.
+    /// Renderers which do not accept raw HTML still get a markdown table.
+    Html,
+}
+
+impl GitInfoOptions {
+    /// Starts building options programmatically, starting from the defaults.
+    pub fn builder() -> GitInfoOptionsBuilder {
+        GitInfoOptionsBuilder::default()
+    }
+
+    /// Reads the options for the preprocessor `name` from the book configuration.
+    pub fn from_config(config: &Config, name: &str) -> Result<GitInfoOptions> {
+        GitInfoOptions::default().merge_config(config, name)
+    }
+
+    /// Overrides these options with the ones set for the preprocessor `name` in the book configuration.
+    ///
+    /// Keys present in `book.toml` always win; nested tables are merged key by key.
+    pub fn merge_config(&self, config: &Config, name: &str) -> Result<GitInfoOptions> {
+        match config.get_preprocessor(name) {
+            Some(table) => self
+                .merge_table(table)
+                .with_context(|| format!("Invalid configuration for [preprocessor.{}]", name)),
+            None => Ok(self.clone()),
+        }
+    }
+
+    /// Overrides these options with the keys of a TOML table and validates the result.
+    pub fn merge_table(&self, table: &toml::value::Table) -> Result<GitInfoOptions> {
+        let mut merged = toml::Value::try_from(self).context("Cannot serialize options")?;
+        merge_toml(&mut merged, toml::Value::Table(table.clone()));
+        let mut options: GitInfoOptions = merged.try_into()?;
+        // Probed rather than configured, so never serialized
+        options.git_version = self.git_version;
+        options.validate()?;
+        Ok(options)
+    }
+
+    /// Returns the options of the chapter with the given source path, with the matching
+    /// [`GitInfoOptions::overrides`] merged in.
+    pub fn for_chapter(&self, source_path: &Path) -> Result<Cow<'_, GitInfoOptions>> {
+        let mut matching = self
+            .overrides
+            .iter()
+            .filter(|(pattern, _)| {
+                glob::Pattern::new(pattern).is_ok_and(|p| p.matches_path(source_path))
+            })
+            .collect::<Vec<_>>();
+        if matching.is_empty() {
+            return Ok(Cow::Borrowed(self));
+        }
+
+        // Merge from the least to the most specific, so that the latter wins
+        matching.sort_by_key(|(pattern, _)| {
+            (
+                pattern.chars().filter(|c| !matches!(c, '*' | '?')).count(),
+                pattern.as_str(),
+            )
+        });
+        let mut overlay = toml::Value::Table(toml::value::Table::new());
+        for (_, table) in matching {
+            merge_toml(&mut overlay, toml::Value::Table(table.clone()));
+        }
+        match overlay {
+            toml::Value::Table(overlay) => Ok(Cow::Owned(
+                self.without_overrides()
+                    .merge_table(&overlay)
+                    .with_context(|| format!("Invalid overrides for {}", source_path.display()))?,
+            )),
+            _ => unreachable!("merging tables results in a table"),
+        }
+    }
+
+    fn without_overrides(&self) -> GitInfoOptions {
+        GitInfoOptions {
+            overrides: BTreeMap::new(),
+            ..self.clone()
+        }
+    }
+
+    /// Checks the options for values which would fail later on.
+    pub fn validate(&self) -> Result<()> {
+        if StrftimeItems::new(&self.date_format).any(|item| matches!(item, Item::Error)) {
+            return Err(anyhow::anyhow!(
+                "Invalid date_format {:?}",
+                self.date_format
+            ));
+        }
+        if let Some(locale) = self.locale.as_deref().filter(|l| !locale::is_supported(l)) {
+            return Err(anyhow::anyhow!(
+                "Unknown locale {:?}, supported: {}",
+                locale,
+                locale::supported().join(", ")
+            ));
+        }
+        if self.ignore_commit_types.iter().any(|t| t.trim().is_empty()) {
+            return Err(anyhow::anyhow!(
+                "ignore_commit_types cannot contain empty types"
+            ));
+        }
+        if self
+            .published_ref
+            .as_deref()
+            .is_some_and(|r| r.trim().is_empty() || r.starts_with('-'))
+        {
+            return Err(anyhow::anyhow!("published_ref must name a git ref"));
+        }
+        if self
+            .copyright
+            .as_ref()
+            .is_some_and(|copyright| copyright.holder.trim().is_empty())
+        {
+            return Err(anyhow::anyhow!("copyright.holder cannot be empty"));
+        }
+        if self.version_pattern.trim().is_empty() {
+            return Err(anyhow::anyhow!("version_pattern cannot be empty"));
+        }
+        if self.jobs == Some(0) {
+            return Err(anyhow::anyhow!("jobs must be at least 1"));
+        }
+        if self.git_binary.as_os_str().is_empty() {
+            return Err(anyhow::anyhow!("git_binary cannot be empty"));
+        }
+        if self.git_args.iter().any(String::is_empty) {
+            return Err(anyhow::anyhow!("git_args cannot contain empty arguments"));
+        }
+        if self.git_timeout_secs == Some(0) {
+            return Err(anyhow::anyhow!("git_timeout_secs must be at least 1"));
+        }
+        if self.max_history == Some(0) {
+            return Err(anyhow::anyhow!("max_history must be at least 1"));
+        }
+        if self.lint.min_contributors == Some(0) {
+            return Err(anyhow::anyhow!("lint.min_contributors must be at least 1"));
+        }
+        if self.activity_graph_width == 0 || self.activity_graph_height == 0 {
+            return Err(anyhow::anyhow!(
+                "activity_graph_width and activity_graph_height must be at least 1"
+            ));
+        }
+        if self.activity_graph_months == 0 {
+            return Err(anyhow::anyhow!("activity_graph_months must be at least 1"));
+        }
+        if self.backend == Backend::Libgit2 {
+            if !cfg!(feature = "libgit2") {
+                return Err(libgit2_unavailable());
+            }
+            if self.follow_renames {
+                return Err(anyhow::anyhow!(
+                    "The libgit2 backend cannot follow renames: set follow_renames = false"
+                ));
+            }
+            if self.show_signature_status {
+                return Err(anyhow::anyhow!(
+                    "The libgit2 backend cannot verify signatures: set show_signature_status = false"
+                ));
+            }
+        }
+        let generated = [
+            (
+                "contributors_chapter",
+                self.contributors_chapter.as_ref().map(|c| &c.path),
+            ),
+            (
+                "recently_updated",
+                self.recently_updated.as_ref().map(|c| &c.path),
+            ),
+            (
+                "provenance",
+                self.provenance
+                    .as_ref()
+                    .filter(|p| p.position == ProvenancePosition::Colophon)
+                    .map(|p| &p.path),
+            ),
+        ];
+        for (name, path) in generated {
+            if path.is_some_and(|path| path.as_os_str().is_empty() || path.is_absolute()) {
+                return Err(anyhow::anyhow!(
+                    "{}.path must be a path relative to the source directory",
+                    name
+                ));
+            }
+        }
+        if self.github_repository.as_ref().is_some_and(|repository| {
+            !matches!(
+                repository.split('/').collect::<Vec<_>>().as_slice(),
+                [owner, repo] if !owner.is_empty() && !repo.is_empty()
+            )
+        }) {
+            return Err(anyhow::anyhow!(
+                "github_repository must be of the form owner/repo"
+            ));
+        }
+        if self.github_cache.as_os_str().is_empty() {
+            return Err(anyhow::anyhow!("github_cache must not be empty"));
+        }
+        if self
+            .sitemap
+            .as_ref()
+            .is_some_and(|s| s.path.as_os_str().is_empty() || s.path.is_absolute())
+        {
+            return Err(anyhow::anyhow!(
+                "sitemap.path must be a path relative to the source directory"
+            ));
+        }
+        if self
+            .recently_updated
+            .as_ref()
+            .is_some_and(|c| c.max_entries == 0)
+        {
+            return Err(anyhow::anyhow!(
+                "recently_updated.max_entries must be at least 1"
+            ));
+        }
+        for (pattern, created) in &self.created_overrides {
+            glob::Pattern::new(pattern)
+                .with_context(|| format!("Invalid created_overrides pattern {:?}", pattern))?;
+            front_matter::parse_date(&created.date).with_context(|| {
+                format!(
+                    "Invalid date {:?} in created_overrides for {:?}",
+                    created.date, pattern
+                )
+            })?;
+        }
+        if self
+            .reviewers_from
+            .iter()
+            .any(|key| key.is_empty() || key.contains(|c: char| c.is_whitespace() || c == ':'))
+        {
+            return Err(anyhow::anyhow!(
+                "reviewers_from must only contain trailer keys, like \"Reviewed-by\""
+            ));
+        }
+        if self.notes_ref.as_ref().is_some_and(|r| r.trim().is_empty()) {
+            return Err(anyhow::anyhow!("notes_ref cannot be empty"));
+        }
+        if self.notes_key.trim().is_empty() {
+            return Err(anyhow::anyhow!("notes_key cannot be empty"));
+        }
+        if self.separator == Separator::Custom && self.separator_text.trim().is_empty() {
+            return Err(anyhow::anyhow!(
+                "separator = \"custom\" requires separator_text to be set"
+            ));
+        }
+        for (pattern, table) in &self.overrides {
+            glob::Pattern::new(pattern)
+                .with_context(|| format!("Invalid overrides pattern {:?}", pattern))?;
+            if table.contains_key("overrides") {
+                return Err(anyhow::anyhow!("overrides cannot be nested"));
+            }
+            self.without_overrides()
+                .merge_table(table)
+                .with_context(|| format!("Invalid overrides for {:?}", pattern))?;
+        }
+        for pattern in self.path_map.rules.keys() {
+            Regex::new(&format!("^(?:{})$", pattern))
+                .with_context(|| format!("Invalid path_map pattern {:?}", pattern))?;
+        }
+        for pattern in &self.exclude {
+            glob::Pattern::new(pattern)
+                .with_context(|| format!("Invalid exclude pattern {:?}", pattern))?;
+        }
+        for (pattern, templates) in &self.historical_paths {
+            glob::Pattern::new(pattern)
+                .with_context(|| format!("Invalid historical_paths pattern {:?}", pattern))?;
+            if templates
+                .iter()
+                .any(|t| t.trim().is_empty() || Path::new(t).is_absolute())
+            {
+                return Err(anyhow::anyhow!(
+                    "historical_paths for {:?} must be paths relative to the root of the repository",
+                    pattern
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the preprocessor runs, given the value of the [`ENABLED_ENV_VAR`] environment
+    /// variable, which takes precedence over [`GitInfoOptions::enabled`].
+    pub fn is_enabled(&self, env: Option<&str>) -> Result<bool> {
+        match env.map(str::trim) {
+            None | Some("") => Ok(self.enabled),
+            Some("on") => Ok(true),
+            Some("off") => Ok(false),
+            Some(value) => Err(anyhow::anyhow!(
+                "Invalid value {:?} of {}: expected \"on\" or \"off\"",
+                value,
+                ENABLED_ENV_VAR
+            )),
+        }
+    }
+
+    /// The effective number of concurrent extraction jobs.
+    pub fn jobs(&self) -> usize {
+        self.jobs.unwrap_or_else(|| {
+            thread::available_parallelism()
+                .map_or(1, |n| n.get())
+                .min(MAX_DEFAULT_JOBS)
+        })
+    }
+
+    /// Instantiates the configured backend, failing if it was not compiled in.
+    pub fn provider(&self) -> Result<Box<dyn HistoryProvider>> {
+        match self.backend {
+            Backend::Cli => Ok(Box::new(CliProvider)),
+            #[cfg(feature = "libgit2")]
+            Backend::Libgit2 => Ok(Box::new(crate::provider::Libgit2Provider)),
+            #[cfg(not(feature = "libgit2"))]
+            Backend::Libgit2 => Err(libgit2_unavailable()),
+        }
+    }
+
+    /// Describes the options which affect the extracted histories, so that cached
+    /// histories are not reused after they change.
+    pub fn history_fingerprint(&self) -> String {
+        format!(
+            "backend={:?} follow_renames={} git_args={:?} max_history={:?} churn={} full_history={} identity={:?}",
+            self.backend,
+            self.follow_renames,
+            self.git_args,
+            self.max_history,
+            self.churn,
+            self.needs_full_history(),
+            self.identity
+        )
+    }
+
+    /// Whether the enabled features need the whole history of each file, rather than only its
+    /// newest commit and the commit which created it, which are much cheaper to query.
+    ///
+    /// Every option is listed, so that new features have to decide whether they need the whole history.
+    pub fn needs_full_history(&self) -> bool {
+        let GitInfoOptions {
+            enabled: _,
+            strict_utf8: _,
+            on_malformed_line: _,
+            date_format: _,
+            exclude: _,
+            extraction: _,
+            follow_renames: _,
+            jobs: _,
+            cache_file: _,
+            cache_dirty_check: _,
+            backend: _,
+            git_binary: _,
+            git_args: _,
+            git_timeout_secs: _,
+            git_version: _,
+            require_git: _,
+            max_history: _,
+            incomplete_suffix: _,
+            renderers: _,
+            hide_in_print: _,
+            output: _,
+            write_css: _,
+            inline_css: _,
+            json_ld,
+            site_url: _,
+            metadata_output,
+            contributors_chapter,
+            recently_updated: _,
+            changelog: _,
+            feed: _,
+            sitemap: _,
+            provenance: _,
+            history_file: _,
+            quiet: _,
+            progress: _,
+            codeowners: _,
+            github_api: _,
+            github_repository: _,
+            github_cache: _,
+            github_cache_ttl_days: _,
+            github_max_requests: _,
+            strip_front_matter: _,
+            emit_front_matter,
+            front_matter_keys,
+            created_overrides: _,
+            include_includes: _,
+            includes_affect_creation: _,
+            path_map: _,
+            historical_paths: _,
+            reviewers_from: _,
+            show_reviewers,
+            show_contributor_count,
+            collapsible: _,
+            collapsible_summary: _,
+            open_by_default: _,
+            separator: _,
+            separator_text: _,
+            overrides,
+            insert_under_heading: _,
+            heading_case_sensitive: _,
+            missing_heading: _,
+            notes_ref,
+            notes_key: _,
+            churn,
+            primary_author: _,
+            primary_author_percentage: _,
+            blame_max_bytes: _,
+            missing_history: _,
+            missing_history_text: _,
+            strict: _,
+            data_attributes,
+            hide_footer: _,
+            show_other_contributors,
+            hide_empty_columns: _,
+            collapse_single_edit: _,
+            activity_graph,
+            activity_graph_width: _,
+            activity_graph_height: _,
+            activity_graph_months: _,
+            locale: _,
+            ignore_commit_types,
+            ignore_commit_types_affects: _,
+            published_ref,
+            identity: _,
+            show_both: _,
+            copyright: _,
+            show_book_last_update: _,
+            show_version: _,
+            version_pattern: _,
+            unreleased_text: _,
+            show_signature_status: _,
+            signature_labels: _,
+            lint: _,
+        } = self;
+        *json_ld
+            || metadata_output.is_some()
+            || contributors_chapter.is_some()
+            || *show_reviewers
+            || *show_contributor_count
+            // Overrides may enable anything for some chapters
+            || !overrides.is_empty()
+            || notes_ref.is_some()
+            || *churn
+            || *data_attributes
+            || *show_other_contributors
+            || *activity_graph != ActivityGraph::None
+            || !ignore_commit_types.is_empty()
+            || published_ref.is_some()
+            || (*emit_front_matter && front_matter_keys.contains(&FrontMatterKey::Contributors))
+    }
+
+    /// Formats a date with [`GitInfoOptions::date_format`] in the language of [`GitInfoOptions::locale`].
+    pub fn format_date(&self, date: &DateTime<Utc>) -> String {
+        locale::format(date, &self.date_format, self.locale.as_deref())
+    }
+
+    /// Whether a commit is ignored for `effect`, according to [`GitInfoOptions::ignore_commit_types`].
+    pub fn is_ignored_commit(&self, entry: &GitHistoryEntry, effect: IgnoredCommitEffect) -> bool {
+        self.ignore_commit_types_affects.contains(&effect)
+            && git_history::commit_type(&entry.subject).is_some_and(|kind| {
+                self.ignore_commit_types
+                    .iter()
+                    .any(|ignored| ignored.trim().eq_ignore_ascii_case(kind))
+            })
+    }
+
+    /// Returns the entry of [`GitInfoOptions::created_overrides`] for the chapter with
+    /// the given source path: the one listing its exact path, or else the first matching pattern.
+    pub fn created_override(&self, source_path: &Path) -> Option<&CreatedOverride> {
+        self.created_overrides
+            .iter()
+            .find(|(path, _)| Path::new(path) == source_path)
+            .or_else(|| {
+                self.created_overrides.iter().find(|(pattern, _)| {
+                    glob::Pattern::new(pattern).is_ok_and(|p| p.matches_path(source_path))
+                })
+            })
+            .map(|(_, created)| created)
+    }
+
+    /// Returns the old locations of the chapter with the given source path, relative to the root
+    /// of the repository, from every matching entry of [`GitInfoOptions::historical_paths`].
+    pub fn historical_paths(&self, source_path: &Path) -> Vec<PathBuf> {
+        let path = history_file::normalize(source_path);
+        let (dir, name) = path.rsplit_once('/').unwrap_or(("", &path));
+        let (stem, ext) = name
+            .rsplit_once('.')
+            .filter(|(stem, _)| !stem.is_empty())
+            .unwrap_or((name, ""));
+
+        let mut paths = Vec::new();
+        for (pattern, templates) in &self.historical_paths {
+            if !glob::Pattern::new(pattern).is_ok_and(|p| p.matches_path(source_path)) {
+                continue;
+            }
+            for template in templates {
+                let old = template
+                    .replace("{path}", &path)
+                    .replace("{dir}", dir)
+                    .replace("{name}", name)
+                    .replace("{stem}", stem)
+                    .replace("{ext}", ext);
+                // Empty parts, like `{dir}` at the top level, leave empty components behind
+                let old = Path::new(&old).components().collect::<PathBuf>();
+                if !paths.contains(&old) {
+                    paths.push(old);
+                }
+            }
+        }
+        paths
+    }
+
+    /// Whether the chapter with the given source path has to be left untouched.
+    pub fn is_excluded(&self, source_path: &Path) -> bool {
+        self.exclude
+            .iter()
+            .any(|pattern| glob::Pattern::new(pattern).is_ok_and(|p| p.matches_path(source_path)))
+    }
+}
+
+/// Recursively merges `overlay` into `base`: tables are merged key by key,
+/// any other value is replaced.
+fn merge_toml(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_toml(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Error for [`Backend::Libgit2`] in builds without the `libgit2` feature.
+fn libgit2_unavailable() -> anyhow::Error {
+    anyhow::anyhow!(
+        "backend = \"libgit2\" requires mdbook-git-info to be built with the `libgit2` feature"
+    )
+}
+
+/// Fluent builder for [`GitInfoOptions`].
+#[derive(Debug, Clone, Default)]
+pub struct GitInfoOptionsBuilder {
+    options: GitInfoOptions,
+}
+
+impl GitInfoOptionsBuilder {
+    /// See [`GitInfoOptions::enabled`].
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.options.enabled = enabled;
+        self
+    }
+
+    /// See [`GitInfoOptions::strict_utf8`].
+    pub fn strict_utf8(mut self, strict_utf8: bool) -> Self {
+        self.options.strict_utf8 = strict_utf8;
+        self
+    }
+
+    /// See [`GitInfoOptions::on_malformed_line`].
+    pub fn on_malformed_line(mut self, action: MalformedLineAction) -> Self {
+        self.options.on_malformed_line = action;
+        self
+    }
+
+    /// See [`GitInfoOptions::date_format`].
+    pub fn date_format(mut self, format: impl Into<String>) -> Self {
+        self.options.date_format = format.into();
+        self
+    }
+
+    /// See [`GitInfoOptions::exclude`].
+    pub fn exclude<I, S>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.exclude = patterns.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// See [`GitInfoOptions::extraction`].
+    pub fn extraction(mut self, mode: ExtractionMode) -> Self {
+        self.options.extraction = mode;
+        self
+    }
+
+    /// See [`GitInfoOptions::follow_renames`].
+    pub fn follow_renames(mut self, follow_renames: bool) -> Self {
+        self.options.follow_renames = follow_renames;
+        self
+    }
+
+    /// See [`GitInfoOptions::jobs`].
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.options.jobs = Some(jobs);
+        self
+    }
+
+    /// See [`GitInfoOptions::cache_file`].
+    pub fn cache_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.cache_file = Some(path.into());
+        self
+    }
+
+    /// See [`GitInfoOptions::cache_dirty_check`].
+    pub fn cache_dirty_check(mut self, cache_dirty_check: bool) -> Self {
+        self.options.cache_dirty_check = cache_dirty_check;
+        self
+    }
+
+    /// See [`GitInfoOptions::backend`].
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.options.backend = backend;
+        self
+    }
+
+    /// See [`GitInfoOptions::git_binary`].
+    pub fn git_binary(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.git_binary = path.into();
+        self
+    }
+
+    /// See [`GitInfoOptions::git_args`].
+    pub fn git_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.git_args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// See [`GitInfoOptions::git_timeout_secs`].
+    pub fn git_timeout_secs(mut self, secs: u64) -> Self {
+        self.options.git_timeout_secs = Some(secs);
+        self
+    }
+
+    /// See [`GitInfoOptions::max_history`].
+    pub fn max_history(mut self, max_history: usize) -> Self {
+        self.options.max_history = Some(max_history);
+        self
+    }
+
+    /// See [`GitInfoOptions::incomplete_suffix`].
+    pub fn incomplete_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.options.incomplete_suffix = suffix.into();
+        self
+    }
+
+    /// See [`GitInfoOptions::renderers`].
+    pub fn renderers<I, S>(mut self, renderers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.renderers = renderers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// See [`GitInfoOptions::hide_in_print`].
+    pub fn hide_in_print(mut self, hide_in_print: bool) -> Self {
+        self.options.hide_in_print = hide_in_print;
+        self
+    }
+
+    /// See [`GitInfoOptions::output`].
+    pub fn output(mut self, output: OutputFormat) -> Self {
+        self.options.output = output;
+        self
+    }
+
+    /// See [`GitInfoOptions::write_css`].
+    pub fn write_css(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.write_css = Some(path.into());
+        self
+    }
+
+    /// See [`GitInfoOptions::inline_css`].
+    pub fn inline_css(mut self, inline_css: bool) -> Self {
+        self.options.inline_css = inline_css;
+        self
+    }
+
+    /// See [`GitInfoOptions::json_ld`].
+    pub fn json_ld(mut self, json_ld: bool) -> Self {
+        self.options.json_ld = json_ld;
+        self
+    }
+
+    /// See [`GitInfoOptions::site_url`].
+    pub fn site_url(mut self, url: impl Into<String>) -> Self {
+        self.options.site_url = Some(url.into());
+        self
+    }
+
+    /// See [`GitInfoOptions::metadata_output`].
+    pub fn metadata_output(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.metadata_output = Some(path.into());
+        self
+    }
+
+    /// See [`GitInfoOptions::contributors_chapter`].
+    pub fn contributors_chapter(mut self, chapter: ContributorsChapter) -> Self {
+        self.options.contributors_chapter = Some(chapter);
+        self
+    }
+
+    /// See [`GitInfoOptions::recently_updated`].
+    pub fn recently_updated(mut self, chapter: RecentlyUpdated) -> Self {
+        self.options.recently_updated = Some(chapter);
+        self
+    }
+
+    /// See [`GitInfoOptions::changelog`].
+    pub fn changelog(mut self, chapter: Changelog) -> Self {
+        self.options.changelog = Some(chapter);
+        self
+    }
+
+    /// See [`GitInfoOptions::feed`].
+    pub fn feed(mut self, feed: Feed) -> Self {
+        self.options.feed = Some(feed);
+        self
+    }
+
+    /// See [`GitInfoOptions::sitemap`].
+    pub fn sitemap(mut self, sitemap: Sitemap) -> Self {
+        self.options.sitemap = Some(sitemap);
+        self
+    }
+
+    /// See [`GitInfoOptions::provenance`].
+    pub fn provenance(mut self, provenance: Provenance) -> Self {
+        self.options.provenance = Some(provenance);
+        self
+    }
+
+    /// See [`GitInfoOptions::history_file`].
+    pub fn history_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.history_file = Some(path.into());
+        self
+    }
+
+    /// See [`GitInfoOptions::quiet`].
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.options.quiet = quiet;
+        self
+    }
+
+    /// See [`GitInfoOptions::codeowners`].
+    pub fn codeowners(mut self, codeowners: bool) -> Self {
+        self.options.codeowners = codeowners;
+        self
+    }
+
+    /// See [`GitInfoOptions::created_overrides`].
+    pub fn created_override(mut self, path: impl Into<String>, created: CreatedOverride) -> Self {
+        self.options.created_overrides.insert(path.into(), created);
+        self
+    }
+
+    /// See [`GitInfoOptions::include_includes`].
+    pub fn include_includes(mut self, include_includes: bool) -> Self {
+        self.options.include_includes = include_includes;
+        self
+    }
+
+    /// See [`GitInfoOptions::includes_affect_creation`].
+    pub fn includes_affect_creation(mut self, includes_affect_creation: bool) -> Self {
+        self.options.includes_affect_creation = includes_affect_creation;
+        self
+    }
+
+    /// See [`GitInfoOptions::path_map`].
+    pub fn path_map(mut self, path_map: PathMap) -> Self {
+        self.options.path_map = path_map;
+        self
+    }
+
+    /// Adds old locations of the chapters matching `pattern`, see
+    /// [`GitInfoOptions::historical_paths`].
+    pub fn historical_paths<I, S>(mut self, pattern: impl Into<String>, templates: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options
+            .historical_paths
+            .entry(pattern.into())
+            .or_default()
+            .extend(templates.into_iter().map(Into::into));
+        self
+    }
+
+    /// See [`GitInfoOptions::reviewers_from`].
+    pub fn reviewers_from<I, S>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.reviewers_from = keys.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// See [`GitInfoOptions::show_reviewers`].
+    pub fn show_reviewers(mut self, show_reviewers: bool) -> Self {
+        self.options.show_reviewers = show_reviewers;
+        self
+    }
+
+    /// See [`GitInfoOptions::show_contributor_count`].
+    pub fn show_contributor_count(mut self, show_contributor_count: bool) -> Self {
+        self.options.show_contributor_count = show_contributor_count;
+        self
+    }
+
+    /// See [`GitInfoOptions::collapsible`].
+    pub fn collapsible(mut self, collapsible: bool) -> Self {
+        self.options.collapsible = collapsible;
+        self
+    }
+
+    /// See [`GitInfoOptions::collapsible_summary`].
+    pub fn collapsible_summary(mut self, summary: impl Into<String>) -> Self {
+        self.options.collapsible_summary = summary.into();
+        self
+    }
+
+    /// See [`GitInfoOptions::open_by_default`].
+    pub fn open_by_default(mut self, open_by_default: bool) -> Self {
+        self.options.open_by_default = open_by_default;
+        self
+    }
+
+    /// See [`GitInfoOptions::separator`].
+    pub fn separator(mut self, separator: Separator) -> Self {
+        self.options.separator = separator;
+        self
+    }
+
+    /// See [`GitInfoOptions::separator_text`].
+    pub fn separator_text(mut self, text: impl Into<String>) -> Self {
+        self.options.separator_text = text.into();
+        self
+    }
+
+    /// See [`GitInfoOptions::overrides`].
+    pub fn override_for(mut self, pattern: impl Into<String>, table: toml::value::Table) -> Self {
+        self.options.overrides.insert(pattern.into(), table);
+        self
+    }
+
+    /// See [`GitInfoOptions::insert_under_heading`].
+    pub fn insert_under_heading(mut self, heading: impl Into<String>) -> Self {
+        self.options.insert_under_heading = Some(heading.into());
+        self
+    }
+
+    /// See [`GitInfoOptions::heading_case_sensitive`].
+    pub fn heading_case_sensitive(mut self, heading_case_sensitive: bool) -> Self {
+        self.options.heading_case_sensitive = heading_case_sensitive;
+        self
+    }
+
+    /// See [`GitInfoOptions::missing_heading`].
+    pub fn missing_heading(mut self, action: MissingHeading) -> Self {
+        self.options.missing_heading = action;
+        self
+    }
+
+    /// See [`GitInfoOptions::notes_ref`].
+    pub fn notes_ref(mut self, notes_ref: impl Into<String>) -> Self {
+        self.options.notes_ref = Some(notes_ref.into());
+        self
+    }
+
+    /// See [`GitInfoOptions::notes_key`].
+    pub fn notes_key(mut self, key: impl Into<String>) -> Self {
+        self.options.notes_key = key.into();
+        self
+    }
+
+    /// See [`GitInfoOptions::churn`].
+    pub fn churn(mut self, churn: bool) -> Self {
+        self.options.churn = churn;
+        self
+    }
+
+    /// See [`GitInfoOptions::primary_author`].
+    pub fn primary_author(mut self, primary_author: bool) -> Self {
+        self.options.primary_author = primary_author;
+        self
+    }
+
+    /// See [`GitInfoOptions::primary_author_percentage`].
+    pub fn primary_author_percentage(mut self, percentage: bool) -> Self {
+        self.options.primary_author_percentage = percentage;
+        self
+    }
+
+    /// See [`GitInfoOptions::blame_max_bytes`].
+    pub fn blame_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.options.blame_max_bytes = max_bytes;
+        self
+    }
+
+    /// See [`GitInfoOptions::missing_history`].
+    pub fn missing_history(mut self, action: MissingHistory) -> Self {
+        self.options.missing_history = action;
+        self
+    }
+
+    /// See [`GitInfoOptions::missing_history_text`].
+    pub fn missing_history_text(mut self, text: impl Into<String>) -> Self {
+        self.options.missing_history_text = text.into();
+        self
+    }
+
+    /// See [`GitInfoOptions::data_attributes`].
+    pub fn data_attributes(mut self, data_attributes: bool) -> Self {
+        self.options.data_attributes = data_attributes;
+        self
+    }
+
+    /// See [`GitInfoOptions::hide_footer`].
+    pub fn hide_footer(mut self, hide_footer: bool) -> Self {
+        self.options.hide_footer = hide_footer;
+        self
+    }
+
+    /// See [`GitInfoOptions::show_other_contributors`].
+    pub fn show_other_contributors(mut self, show: bool) -> Self {
+        self.options.show_other_contributors = show;
+        self
+    }
+
+    /// See [`GitInfoOptions::activity_graph`].
+    pub fn activity_graph(mut self, graph: ActivityGraph) -> Self {
+        self.options.activity_graph = graph;
+        self
+    }
+
+    /// See [`GitInfoOptions::activity_graph_width`] and [`GitInfoOptions::activity_graph_height`].
+    pub fn activity_graph_size(mut self, width: u32, height: u32) -> Self {
+        self.options.activity_graph_width = width;
+        self.options.activity_graph_height = height;
+        self
+    }
+
+    /// See [`GitInfoOptions::activity_graph_months`].
+    pub fn activity_graph_months(mut self, months: usize) -> Self {
+        self.options.activity_graph_months = months;
+        self
+    }
+
+    /// See [`GitInfoOptions::locale`].
+    pub fn locale(mut self, locale: impl Into<String>) -> Self {
+        self.options.locale = Some(locale.into());
+        self
+    }
+
+    /// See [`GitInfoOptions::ignore_commit_types`].
+    pub fn ignore_commit_types<I, S>(mut self, types: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.ignore_commit_types = types.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// See [`GitInfoOptions::ignore_commit_types_affects`].
+    pub fn ignore_commit_types_affects(
+        mut self,
+        effects: impl IntoIterator<Item = IgnoredCommitEffect>,
+    ) -> Self {
+        self.options.ignore_commit_types_affects = effects.into_iter().collect();
+        self
+    }
+
+    /// See [`GitInfoOptions::published_ref`].
+    pub fn published_ref(mut self, published_ref: impl Into<String>) -> Self {
+        self.options.published_ref = Some(published_ref.into());
+        self
+    }
+
+    /// See [`GitInfoOptions::require_git`].
+    pub fn require_git(mut self, require_git: bool) -> Self {
+        self.options.require_git = require_git;
+        self
+    }
+
+    /// See [`GitInfoOptions::identity`].
+    pub fn identity(mut self, identity: Identity) -> Self {
+        self.options.identity = identity;
+        self
+    }
+
+    /// See [`GitInfoOptions::show_both`].
+    pub fn show_both(mut self, show_both: bool) -> Self {
+        self.options.show_both = show_both;
+        self
+    }
+
+    /// See [`GitInfoOptions::copyright`].
+    pub fn copyright(mut self, copyright: Copyright) -> Self {
+        self.options.copyright = Some(copyright);
+        self
+    }
+
+    /// See [`GitInfoOptions::show_book_last_update`].
+    pub fn show_book_last_update(mut self, show_book_last_update: bool) -> Self {
+        self.options.show_book_last_update = show_book_last_update;
+        self
+    }
+
+    /// See [`GitInfoOptions::show_version`].
+    pub fn show_version(mut self, show_version: bool) -> Self {
+        self.options.show_version = show_version;
+        self
+    }
+
+    /// See [`GitInfoOptions::version_pattern`].
+    pub fn version_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.options.version_pattern = pattern.into();
+        self
+    }
+
+    /// See [`GitInfoOptions::unreleased_text`].
+    pub fn unreleased_text(mut self, text: impl Into<String>) -> Self {
+        self.options.unreleased_text = text.into();
+        self
+    }
+
+    /// See [`GitInfoOptions::show_signature_status`].
+    pub fn show_signature_status(mut self, show_signature_status: bool) -> Self {
+        self.options.show_signature_status = show_signature_status;
+        self
+    }
+
+    /// See [`GitInfoOptions::signature_labels`].
+    pub fn signature_labels(mut self, labels: SignatureLabels) -> Self {
+        self.options.signature_labels = labels;
+        self
+    }
+
+    /// See [`GitInfoOptions::progress`].
+    pub fn progress(mut self, progress: ProgressMode) -> Self {
+        self.options.progress = progress;
+        self
+    }
+
+    /// See [`GitInfoOptions::lint`].
+    pub fn lint(mut self, lint: Lint) -> Self {
+        self.options.lint = lint;
+        self
+    }
+
+    /// See [`GitInfoOptions::hide_empty_columns`].
+    pub fn hide_empty_columns(mut self, hide: bool) -> Self {
+        self.options.hide_empty_columns = hide;
+        self
+    }
+
+    /// See [`GitInfoOptions::collapse_single_edit`].
+    pub fn collapse_single_edit(mut self, collapse: bool) -> Self {
+        self.options.collapse_single_edit = collapse;
+        self
+    }
+
+    /// See [`GitInfoOptions::emit_front_matter`].
+    pub fn emit_front_matter(mut self, emit: bool) -> Self {
+        self.options.emit_front_matter = emit;
+        self
+    }
+
+    /// See [`GitInfoOptions::front_matter_keys`].
+    pub fn front_matter_keys(mut self, keys: Vec<FrontMatterKey>) -> Self {
+        self.options.front_matter_keys = keys;
+        self
+    }
+
+    /// See [`GitInfoOptions::github_api`].
+    pub fn github_api(mut self, github_api: bool) -> Self {
+        self.options.github_api = github_api;
+        self
+    }
+
+    /// See [`GitInfoOptions::github_repository`].
+    pub fn github_repository(mut self, repository: impl Into<String>) -> Self {
+        self.options.github_repository = Some(repository.into());
+        self
+    }
+
+    /// See [`GitInfoOptions::github_cache`].
+    pub fn github_cache(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.github_cache = path.into();
+        self
+    }
+
+    /// See [`GitInfoOptions::github_cache_ttl_days`].
+    pub fn github_cache_ttl_days(mut self, days: u32) -> Self {
+        self.options.github_cache_ttl_days = days;
+        self
+    }
+
+    /// See [`GitInfoOptions::github_max_requests`].
+    pub fn github_max_requests(mut self, max_requests: usize) -> Self {
+        self.options.github_max_requests = max_requests;
+        self
+    }
+
+    /// See [`GitInfoOptions::strict`].
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.options.strict = strict;
+        self
+    }
+
+    /// See [`GitInfoOptions::strip_front_matter`].
+    pub fn strip_front_matter(mut self, strip_front_matter: bool) -> Self {
+        self.options.strip_front_matter = strip_front_matter;
+        self
+    }
+
+    /// Returns the built options.
+    pub fn build(self) -> GitInfoOptions {
+        self.options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(text: &str) -> toml::value::Table {
+        toml::from_str(text).unwrap()
+    }
+
+    fn date_format(options: &GitInfoOptions, source_path: &str) -> String {
+        options
+            .for_chapter(Path::new(source_path))
+            .unwrap()
+            .date_format
+            .clone()
+    }
+
+    #[test]
+    fn more_specific_overrides_win() {
+        let options = GitInfoOptions::builder()
+            .override_for("guide/intro.md", table("date_format = \"%m\""))
+            .override_for("**", table("date_format = \"%Y\""))
+            .override_for("guide/**", table("date_format = \"%d/%m\""))
+            .build();
+
+        assert_eq!(date_format(&options, "guide/intro.md"), "%m");
+        assert_eq!(date_format(&options, "guide/setup.md"), "%d/%m");
+        assert_eq!(date_format(&options, "notes.md"), "%Y");
+    }
+
+    #[test]
+    fn chapters_without_overrides_borrow_the_options() {
+        let options = GitInfoOptions::builder()
+            .override_for("guide/*", table("date_format = \"%Y\""))
+            .build();
+
+        assert!(matches!(
+            options.for_chapter(Path::new("intro.md")).unwrap(),
+            Cow::Borrowed(_)
+        ));
+        let guide = options.for_chapter(Path::new("guide/intro.md")).unwrap();
+        assert!(guide.overrides.is_empty());
+    }
+
+    #[test]
+    fn ties_are_broken_by_pattern() {
+        // As specific as each other, so the last pattern in lexicographic order wins
+        let options = GitInfoOptions::builder()
+            .override_for("g*/intro.md", table("date_format = \"%Y\""))
+            .override_for("*e/intro.md", table("date_format = \"%m\""))
+            .build();
+
+        assert_eq!(date_format(&options, "guide/intro.md"), "%Y");
+    }
+
+    #[test]
+    fn nested_tables_are_merged_key_by_key() {
+        let mut options = GitInfoOptions::builder()
+            .override_for("**", table("[lint]\nmax_age_days = 30"))
+            .override_for(
+                "guide/**",
+                table("[lint]\nrequire_history = true\n[signature_labels]\nunknown = \"?\""),
+            )
+            .build();
+        options.signature_labels.verified = "ok".to_string();
+        options.lint.min_contributors = Some(2);
+
+        let guide = options.for_chapter(Path::new("guide/intro.md")).unwrap();
+        assert_eq!(guide.lint.max_age_days, Some(30));
+        assert!(guide.lint.require_history);
+        assert_eq!(guide.lint.min_contributors, Some(2));
+        assert_eq!(guide.signature_labels.verified, "ok");
+        assert_eq!(guide.signature_labels.unverified, "unverified");
+        assert_eq!(guide.signature_labels.unknown, "?");
+
+        let intro = options.for_chapter(Path::new("intro.md")).unwrap();
+        assert_eq!(intro.lint.max_age_days, Some(30));
+        assert!(!intro.lint.require_history);
+        assert_eq!(intro.signature_labels.unknown, "unknown key");
+    }
+
+    #[test]
+    fn invalid_overrides_are_rejected() {
+        let options = GitInfoOptions::builder()
+            .override_for("**", table("date_format = \"%Q\""))
+            .build();
+        assert!(options.for_chapter(Path::new("intro.md")).is_err());
+        assert!(options.validate().is_err());
+
+        let nested = GitInfoOptions::builder()
+            .override_for("**", table("[overrides.\"*\"]\nquiet = true"))
+            .build();
+        assert!(nested.validate().is_err());
+    }
+
+    #[test]
+    fn the_environment_variable_wins_over_enabled() {
+        let enabled = GitInfoOptions::default();
+        let disabled = GitInfoOptions::builder().enabled(false).build();
+
+        assert!(enabled.is_enabled(None).unwrap());
+        assert!(!disabled.is_enabled(None).unwrap());
+        // Empty values are unset
+        assert!(!disabled.is_enabled(Some(" ")).unwrap());
+        assert!(disabled.is_enabled(Some("on")).unwrap());
+        assert!(disabled.is_enabled(Some(" on\n")).unwrap());
+        assert!(!enabled.is_enabled(Some("off")).unwrap());
+
+        let error = enabled.is_enabled(Some("yes")).unwrap_err().to_string();
+        assert!(error.contains("\"yes\""), "{}", error);
+        assert!(error.contains(ENABLED_ENV_VAR), "{}", error);
+    }
+
+    #[test]
+    fn enabled_is_read_from_book_toml() {
+        let mut config = mdbook::Config::default();
+        config.set("preprocessor.git-info.enabled", false).unwrap();
+        let options = GitInfoOptions::from_config(&config, "git-info").unwrap();
+        assert!(!options.is_enabled(None).unwrap());
+        assert!(options.is_enabled(Some("on")).unwrap());
+    }
+
+    #[test]
+    fn exact_created_overrides_win_over_patterns() {
+        let options = GitInfoOptions::builder()
+            .created_override("*.md", CreatedOverride::new("2019-01-01"))
+            .created_override("guide/*", CreatedOverride::new("2020-01-01"))
+            .created_override(
+                "guide/intro.md",
+                CreatedOverride {
+                    date: "2021-01-01".to_string(),
+                    author: Some("Zoë".to_string()),
+                },
+            )
+            .build();
+        let date = |path: &str| {
+            options
+                .created_override(Path::new(path))
+                .map(|created| created.date.as_str())
+        };
+
+        // Even though the patterns come first in alphabetical order
+        assert_eq!(date("guide/intro.md"), Some("2021-01-01"));
+        assert_eq!(date("guide/setup.md"), Some("2019-01-01"));
+        assert_eq!(date("guide/setup.txt"), Some("2020-01-01"));
+        assert_eq!(date("notes.txt"), None);
+    }
+
+    #[test]
+    fn created_overrides_replace_the_creation_only() {
+        let history = [
+            GitHistoryEntry::test("b", "Bob", "2024-03-01T12:00:00Z"),
+            GitHistoryEntry::test("a", "Alice", "2024-01-01T12:00:00Z"),
+        ];
+        let mut info = GitInfo::from_history(&history, &GitInfoOptions::default());
+
+        CreatedOverride::new("2020-05-01").apply(&mut info);
+        let created = info.created.as_ref().unwrap();
+        assert_eq!(
+            created.timestamp,
+            "2020-05-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(created.author, "Alice");
+
+        CreatedOverride {
+            date: "2020-05-01T12:00:00+02:00".to_string(),
+            author: Some("Zoë".to_string()),
+        }
+        .apply(&mut info);
+        let created = info.created.as_ref().unwrap();
+        assert_eq!(
+            created.timestamp,
+            "2020-05-01T10:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(created.author, "Zoë");
+        assert_eq!(info.last_edit.as_ref().unwrap().author, "Bob");
+    }
+
+    fn path_map(rules: &[(&str, &str)]) -> PathMap {
+        PathMap {
+            rules: rules
+                .iter()
+                .map(|(pattern, replacement)| (pattern.to_string(), replacement.to_string()))
+                .collect(),
+            ..PathMap::default()
+        }
+    }
+
+    #[test]
+    fn path_maps_expand_capture_groups() {
+        let map = path_map(&[
+            ("fr/(.*)", "en/$1"),
+            ("(?P<lang>de|it)/(?P<rest>.*)\\.md", "en/${rest}.${lang}.md"),
+        ]);
+        let mapped = |path: &str| map.map(Path::new(path));
+
+        assert_eq!(
+            mapped("fr/guide/intro.md"),
+            Some(PathBuf::from("en/guide/intro.md"))
+        );
+        assert_eq!(mapped("it/intro.md"), Some(PathBuf::from("en/intro.it.md")));
+        // Patterns match whole paths
+        assert_eq!(mapped("old/fr/intro.md"), None);
+        assert_eq!(mapped("de/intro.txt"), None);
+    }
+
+    #[test]
+    fn the_first_matching_path_map_wins() {
+        let map = path_map(&[("fr/(.*)", "en/$1"), ("(.*)", "all/$1")]);
+
+        // In alphabetical order, `(` comes before `f`
+        assert_eq!(
+            map.map(Path::new("fr/intro.md")),
+            Some(PathBuf::from("all/fr/intro.md"))
+        );
+
+        // Invalid patterns, rejected by validation, never match
+        let map = path_map(&[("[", "invalid"), ("fr/(.*)", "en/$1")]);
+        assert_eq!(
+            map.map(Path::new("fr/intro.md")),
+            Some(PathBuf::from("en/intro.md"))
+        );
+        let options = GitInfoOptions::builder().path_map(map).build();
+        assert!(options.validate().is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use crate::config::Copyright;
+use crate::info::GitInfo;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use std::env;
+
+/// Variable replaced with the copyright line of the chapter.
+pub const VARIABLE: &str = "{{#git_copyright}}";
+
+/// Environment variable fixing the time of reproducible builds, in seconds since the epoch.
+const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+
+/// Time of `SOURCE_DATE_EPOCH`, if set.
+pub fn source_date() -> Result<Option<DateTime<Utc>>> {
+    let epoch = match env::var(SOURCE_DATE_EPOCH) {
+        Ok(epoch) if !epoch.trim().is_empty() => epoch,
+        _ => return Ok(None),
+    };
+    let seconds = epoch
+        .trim()
+        .parse::<i64>()
+        .with_context(|| format!("Invalid {} {:?}", SOURCE_DATE_EPOCH, epoch))?;
+    let date = Utc
+        .timestamp_opt(seconds, 0)
+        .single()
+        .with_context(|| format!("{} {} is out of range", SOURCE_DATE_EPOCH, seconds))?;
+    Ok(Some(date))
+}
+
+/// Year of `SOURCE_DATE_EPOCH`, if set.
+pub fn source_date_year() -> Result<Option<i32>> {
+    Ok(source_date()?.map(|date| date.year()))
+}
+
+/// Years of the first and of the last commit of a chapter, bounded by `max_year`, or the fallback
+/// year of the settings for chapters without history.
+pub fn years(info: &GitInfo, settings: &Copyright, max_year: Option<i32>) -> Option<(i32, i32)> {
+    let years = info
+        .created
+        .iter()
+        .chain(&info.last_edit)
+        .map(|entry| entry.timestamp.year());
+    let (first, last) = match (years.clone().min(), years.max()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => (settings.fallback_year?, settings.fallback_year?),
+    };
+    let last = max_year.map_or(last, |max_year| last.min(max_year));
+    Some((first.min(last), last))
+}
+
+/// Formats the copyright line of a chapter, like "© 2019–2025 Example Corp".
+pub fn line(info: &GitInfo, settings: &Copyright, max_year: Option<i32>) -> Option<String> {
+    let (first, last) = years(info, settings, max_year)?;
+    let years = if first == last {
+        first.to_string()
+    } else {
+        format!("{}\u{2013}{}", first, last)
+    };
+    Some(format!(
+        "{}{} {}{}",
+        settings.prefix, years, settings.holder, settings.suffix
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GitInfoOptions;
+    use crate::git_history::GitHistoryEntry;
+
+    fn info(dates: &[&str]) -> GitInfo {
+        let history = dates
+            .iter()
+            .enumerate()
+            .map(|(i, date)| GitHistoryEntry::test(&i.to_string(), "Alice", date))
+            .collect::<Vec<_>>();
+        GitInfo::from_history(&history, &GitInfoOptions::default())
+    }
+
+    fn settings() -> Copyright {
+        Copyright {
+            holder: "Example Corp".to_string(),
+            ..Copyright::default()
+        }
+    }
+
+    #[test]
+    fn years_span_the_history() {
+        let info = info(&["2025-06-01T12:00:00Z", "2019-01-01T12:00:00Z"]);
+
+        assert_eq!(years(&info, &settings(), None), Some((2019, 2025)));
+        assert_eq!(
+            line(&info, &settings(), None).as_deref(),
+            Some("\u{a9} 2019\u{2013}2025 Example Corp")
+        );
+    }
+
+    #[test]
+    fn single_years_are_not_ranges() {
+        let info = info(&["2024-12-31T12:00:00Z", "2024-01-01T12:00:00Z"]);
+        let settings = Copyright {
+            prefix: "Copyright ".to_string(),
+            suffix: ". All rights reserved.".to_string(),
+            ..settings()
+        };
+
+        assert_eq!(
+            line(&info, &settings, None).as_deref(),
+            Some("Copyright 2024 Example Corp. All rights reserved.")
+        );
+    }
+
+    #[test]
+    fn years_are_bounded_by_the_build_date() {
+        let info = info(&["2025-06-01T12:00:00Z", "2019-01-01T12:00:00Z"]);
+
+        assert_eq!(years(&info, &settings(), Some(2030)), Some((2019, 2025)));
+        assert_eq!(years(&info, &settings(), Some(2022)), Some((2019, 2022)));
+        assert_eq!(years(&info, &settings(), Some(2010)), Some((2010, 2010)));
+    }
+
+    #[test]
+    fn chapters_without_history_use_the_fallback_year() {
+        let info = GitInfo::default();
+
+        assert_eq!(line(&info, &settings(), None), None);
+        let settings = Copyright {
+            fallback_year: Some(2020),
+            ..settings()
+        };
+        assert_eq!(
+            line(&info, &settings, Some(2024)).as_deref(),
+            Some("\u{a9} 2020 Example Corp")
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use crate::config::Identity;
+use crate::info::GitInfo;
+use crate::render;
+use chrono::{TimeZone, Utc};
+use std::path::Path;
+
+const ATOM_NAMESPACE: &str = "http://www.w3.org/2005/Atom";
+
+/// Renders an Atom feed with an entry for each given chapter, as `(name, path, info)`,
+/// crediting the last commit of each chapter to `identity`.
+///
+/// The ids of the entries combine the page of the chapter with the hash of its last commit,
+/// so they are stable across builds and change with every edit.
+pub fn render(
+    title: &str,
+    site_url: &str,
+    feed_path: &Path,
+    chapters: &[(&str, &Path, &GitInfo)],
+    identity: Identity,
+) -> String {
+    let home = render::file_url(site_url, Path::new(""));
+    let updated = chapters
+        .iter()
+        .filter_map(|(_, _, info)| info.last_edit.as_ref().map(|e| e.timestamp))
+        .max()
+        .unwrap_or_else(|| Utc.timestamp_opt(0, 0).unwrap())
+        .to_rfc3339();
+
+    let mut xml = XmlWriter::new();
+    xml.open("feed", &[("xmlns", ATOM_NAMESPACE)]);
+    xml.leaf("id", &[], Some(&home));
+    xml.leaf("title", &[], Some(title));
+    xml.leaf("updated", &[], Some(&updated));
+    xml.leaf("link", &[("href", &home)], None);
+    xml.leaf(
+        "link",
+        &[
+            ("rel", "self"),
+            ("href", &render::file_url(site_url, feed_path)),
+        ],
+        None,
+    );
+
+    for (name, path, info) in chapters {
+        let last_edit = match &info.last_edit {
+            Some(last_edit) => last_edit,
+            None => continue,
+        };
+        let url = render::page_url(site_url, path);
+        xml.open("entry", &[]);
+        xml.leaf("id", &[], Some(&format!("{}#{}", url, last_edit.hash)));
+        xml.leaf("title", &[], Some(name));
+        xml.leaf("link", &[("href", &url)], None);
+        xml.leaf("updated", &[], Some(&last_edit.timestamp.to_rfc3339()));
+        xml.open("author", &[]);
+        xml.leaf("name", &[], Some(last_edit.name(identity)));
+        xml.close("author");
+        xml.leaf("summary", &[], Some(&last_edit.subject));
+        xml.close("entry");
+    }
+
+    xml.close("feed");
+    xml.finish()
+}
+
+/// Minimal XML writer, which escapes every text and attribute value.
+pub(crate) struct XmlWriter {
+    out: String,
+    depth: usize,
+}
+
+impl XmlWriter {
+    pub(crate) fn new() -> XmlWriter {
+        XmlWriter {
+            out: "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n".to_string(),
+            depth: 0,
+        }
+    }
+
+    /// Starts an element, to be ended with [`XmlWriter::close`].
+    pub(crate) fn open(&mut self, name: &str, attributes: &[(&str, &str)]) {
+        self.start_tag(name, attributes);
+        self.out.push_str(">\n");
+        self.depth += 1;
+    }
+
+    pub(crate) fn close(&mut self, name: &str) {
+        self.depth -= 1;
+        self.indent();
+        self.out.push_str("</");
+        self.out.push_str(name);
+        self.out.push_str(">\n");
+    }
+
+    /// Writes a whole element, which is empty when `text` is `None`.
+    pub(crate) fn leaf(&mut self, name: &str, attributes: &[(&str, &str)], text: Option<&str>) {
+        self.start_tag(name, attributes);
+        match text {
+            Some(text) => {
+                self.out.push('>');
+                self.out.push_str(&render::escape_html(text));
+                self.out.push_str("</");
+                self.out.push_str(name);
+                self.out.push_str(">\n");
+            }
+            None => self.out.push_str(" />\n"),
+        }
+    }
+
+    fn start_tag(&mut self, name: &str, attributes: &[(&str, &str)]) {
+        self.indent();
+        self.out.push('<');
+        self.out.push_str(name);
+        for (attribute, value) in attributes {
+            self.out.push(' ');
+            self.out.push_str(attribute);
+            self.out.push_str("=\"");
+            self.out.push_str(&render::escape_html(value));
+            self.out.push('"');
+        }
+    }
+
+    fn indent(&mut self) {
+        for _ in 0..self.depth {
+            self.out.push_str("  ");
+        }
+    }
+
+    pub(crate) fn finish(self) -> String {
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GitInfoOptions;
+    use crate::git_history::GitHistoryEntry;
+
+    fn info(history: &[GitHistoryEntry]) -> GitInfo {
+        GitInfo::from_history(history, &GitInfoOptions::default())
+    }
+
+    /// Text of the child of `node` named `name`.
+    fn child<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> &'a str {
+        node.children()
+            .find(|c| c.has_tag_name((ATOM_NAMESPACE, name)))
+            .and_then(|c| c.text())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn feeds_are_well_formed_atom() {
+        let mut edited = GitHistoryEntry::test("c0ffee", "Bob & Co", "2024-03-05T12:00:00Z");
+        edited.subject = "Fix <b>bold</b> \"quotes\"".to_string();
+        let intro = info(&[
+            edited,
+            GitHistoryEntry::test("a", "Alice", "2024-01-10T12:00:00Z"),
+        ]);
+        let guide = info(&[GitHistoryEntry::test("b", "Alice", "2024-02-01T12:00:00Z")]);
+        let draft = info(&[]);
+
+        let xml = render(
+            "Q&A <book>",
+            "https://example.com/book/",
+            Path::new("feed.xml"),
+            &[
+                ("Intro & more", Path::new("intro.md"), &intro),
+                ("Draft", Path::new("draft.md"), &draft),
+                ("Guide", Path::new("guide/index.md"), &guide),
+            ],
+            Identity::Author,
+        );
+
+        let document = roxmltree::Document::parse(&xml).unwrap();
+        let feed = document.root_element();
+        assert!(feed.has_tag_name((ATOM_NAMESPACE, "feed")));
+        assert_eq!(child(feed, "title"), "Q&A <book>");
+        assert_eq!(child(feed, "id"), "https://example.com/book/");
+        // The most recent edit of any chapter
+        assert_eq!(child(feed, "updated"), "2024-03-05T12:00:00+00:00");
+        let links = feed
+            .children()
+            .filter(|c| c.has_tag_name((ATOM_NAMESPACE, "link")))
+            .map(|c| (c.attribute("rel"), c.attribute("href").unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            links,
+            [
+                (None, "https://example.com/book/"),
+                (Some("self"), "https://example.com/book/feed.xml"),
+            ]
+        );
+
+        // In the given order, without the chapters never edited
+        let entries = feed
+            .children()
+            .filter(|c| c.has_tag_name((ATOM_NAMESPACE, "entry")))
+            .map(|entry| {
+                let author = entry
+                    .children()
+                    .find(|c| c.has_tag_name((ATOM_NAMESPACE, "author")))
+                    .unwrap();
+                (
+                    child(entry, "title"),
+                    child(entry, "id"),
+                    child(entry, "updated"),
+                    child(author, "name"),
+                    child(entry, "summary"),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            [
+                (
+                    "Intro & more",
+                    "https://example.com/book/intro.html#c0ffee",
+                    "2024-03-05T12:00:00+00:00",
+                    "Bob & Co",
+                    "Fix <b>bold</b> \"quotes\"",
+                ),
+                (
+                    "Guide",
+                    "https://example.com/book/guide/index.html#b",
+                    "2024-02-01T12:00:00+00:00",
+                    "Alice",
+                    "Commit b",
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_feeds_are_well_formed() {
+        let xml = render(
+            "Updates",
+            "https://example.com",
+            Path::new("feed.xml"),
+            &[],
+            Identity::Author,
+        );
+        let document = roxmltree::Document::parse(&xml).unwrap();
+        let feed = document.root_element();
+        assert_eq!(child(feed, "updated"), "1970-01-01T00:00:00+00:00");
+        assert_eq!(feed.children().filter(|c| c.is_element()).count(), 5);
+    }
+}
@@ -0,0 +1,435 @@
+use crate::config::{FrontMatterKey, GitInfoOptions};
+use crate::git_history::GitHistoryEntry;
+use crate::info::GitInfo;
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use toml::value::{Table, Value};
+
+/// Metadata declared at the top of a chapter, which takes precedence over git.
+#[derive(Debug, Clone, Default)]
+pub struct FrontMatter {
+    /// True authors of the chapter, shown as its creators.
+    pub authors: Option<Vec<String>>,
+    /// True creation date of the chapter.
+    pub created: Option<DateTime<Utc>>,
+    /// Date of the last edit of the chapter.
+    pub last_edit: Option<DateTime<Utc>>,
+    /// Whether the chapter asks to be left untouched (`git-info: skip`).
+    pub skip: bool,
+}
+
+impl FrontMatter {
+    /// Replaces the git-derived values of `info` with the ones specified here.
+    ///
+    /// Chapters without history get entries for the dates specified here, without hash.
+    pub fn apply(&self, info: &mut GitInfo) {
+        if let Some(created) = self.created {
+            entry(&mut info.created, created).timestamp = created;
+        }
+        if let Some(last_edit) = self.last_edit {
+            entry(&mut info.last_edit, last_edit).timestamp = last_edit;
+        }
+        if let (Some(authors), Some(created)) = (&self.authors, &mut info.created) {
+            // The authors are credited whatever the configured identity
+            created.author = authors.join(", ");
+            created.committer = created.author.clone();
+        }
+    }
+}
+
+/// Returns the entry, creating an empty one at `timestamp` if missing.
+fn entry(entry: &mut Option<GitHistoryEntry>, timestamp: DateTime<Utc>) -> &mut GitHistoryEntry {
+    entry.get_or_insert_with(|| GitHistoryEntry {
+        hash: String::new(),
+        short_hash: String::new(),
+        author: "n/a".to_string(),
+        email: String::new(),
+        committer: String::new(),
+        committer_email: String::new(),
+        timestamp,
+        commit_timestamp: timestamp,
+        subject: String::new(),
+        trailers: Vec::new(),
+        lines_added: 0,
+        lines_removed: 0,
+    })
+}
+
+/// Finds the front matter block at the very beginning of a chapter: YAML between `---` lines
+/// or TOML between `+++` lines.
+///
+/// Returns the block, whether it is TOML, and the offset of the content following it.
+pub fn split(content: &str) -> Option<(&str, bool, usize)> {
+    let first = content.split_inclusive('\n').next()?;
+    let (delimiter, is_toml) = match first.trim_end() {
+        "---" => ("---", false),
+        "+++" => ("+++", true),
+        _ => return None,
+    };
+
+    let start = first.len();
+    let mut offset = start;
+    for line in content[start..].split_inclusive('\n') {
+        if line.trim_end() == delimiter {
+            return Some((&content[start..offset], is_toml, offset + line.len()));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// Parses the front matter at the beginning of a chapter, if any.
+pub fn parse(content: &str) -> Result<Option<FrontMatter>> {
+    let (block, is_toml, _) = match split(content) {
+        Some(front_matter) => front_matter,
+        None => return Ok(None),
+    };
+    let table = if is_toml {
+        block
+            .parse::<Value>()?
+            .as_table()
+            .cloned()
+            .context("The front matter is not a table")?
+    } else {
+        parse_yaml(block)?
+    };
+
+    let date = |key: &str| -> Result<Option<DateTime<Utc>>> {
+        table
+            .get(key)
+            .map(|value| {
+                let text = match value {
+                    Value::String(text) => text.clone(),
+                    Value::Datetime(datetime) => datetime.to_string(),
+                    _ => return Err(anyhow::anyhow!("{} must be a date", key)),
+                };
+                parse_date(&text).with_context(|| format!("Invalid date {:?} for {}", text, key))
+            })
+            .transpose()
+    };
+
+    let authors = match table.get("authors") {
+        None => None,
+        Some(Value::String(author)) => Some(vec![author.clone()]),
+        Some(Value::Array(authors)) => Some(
+            authors
+                .iter()
+                .map(|author| author.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+                .context("authors must be a list of names")?,
+        ),
+        Some(_) => return Err(anyhow::anyhow!("authors must be a list of names")),
+    };
+
+    Ok(Some(FrontMatter {
+        authors,
+        created: date("created")?,
+        last_edit: date("last_edit")?,
+        skip: table.get("git-info").and_then(Value::as_str) == Some("skip"),
+    }))
+}
+
+/// Values of the keys of [`GitInfoOptions::front_matter_keys`] for a chapter, in order.
+///
+/// Keys without value, like the commit of a date coming from front matter, are left out.
+pub fn emitted(info: &GitInfo, options: &GitInfoOptions) -> Vec<(&'static str, Value)> {
+    let name = |entry: &Option<GitHistoryEntry>| {
+        entry
+            .as_ref()
+            .map(|entry| entry.name(options.identity).to_string())
+    };
+    options
+        .front_matter_keys
+        .iter()
+        .filter_map(|key| {
+            let value = match key {
+                FrontMatterKey::Date => Value::String(
+                    info.last_edit
+                        .as_ref()?
+                        .timestamp
+                        .format("%Y-%m-%d")
+                        .to_string(),
+                ),
+                FrontMatterKey::Author => Value::String(name(&info.created)?),
+                FrontMatterKey::Contributors => {
+                    let mut contributors = Vec::new();
+                    let names = name(&info.created)
+                        .into_iter()
+                        .chain(name(&info.last_edit))
+                        .chain(info.other_contributors.iter().cloned());
+                    for name in names {
+                        if !contributors.contains(&name) {
+                            contributors.push(name);
+                        }
+                    }
+                    Value::Array(contributors.into_iter().map(Value::String).collect())
+                }
+                FrontMatterKey::GitCommit => Value::String(
+                    Some(&info.last_edit.as_ref()?.hash)
+                        .filter(|hash| !hash.is_empty())?
+                        .clone(),
+                ),
+            };
+            Some((key.name(), value))
+        })
+        .collect()
+}
+
+/// Adds keys to the front matter at the beginning of a chapter, creating a YAML block if there
+/// is none. Keys already present in the front matter are kept as they are.
+///
+/// Values are written as JSON, which is also valid YAML, so that any name stays valid.
+pub fn insert(content: &str, values: &[(&str, Value)]) -> Result<String> {
+    let (block, is_toml, block_end) = match split(content) {
+        Some((block, is_toml, _)) => {
+            // The block starts after the line of the opening delimiter
+            let start = content.find('\n').map_or(content.len(), |i| i + 1);
+            (Some(block), is_toml, start + block.len())
+        }
+        None => (None, false, 0),
+    };
+    let mut present = match block {
+        Some(block) if is_toml => block
+            .parse::<Value>()?
+            .as_table()
+            .cloned()
+            .context("The front matter is not a table")?,
+        Some(block) => parse_yaml(block)?,
+        None => Table::new(),
+    };
+
+    let mut lines = String::new();
+    for (key, value) in values {
+        if present.contains_key(*key) {
+            continue;
+        }
+        if is_toml {
+            lines.push_str(&format!("{} = {}\n", key, value));
+        } else {
+            lines.push_str(&format!("{}: {}\n", key, serde_json::to_string(value)?));
+        }
+        present.insert(key.to_string(), value.clone());
+    }
+
+    Ok(match block {
+        Some(_) => format!(
+            "{}{}{}",
+            &content[..block_end],
+            lines,
+            &content[block_end..]
+        ),
+        None if lines.is_empty() => content.to_string(),
+        None => format!("---\n{}---\n{}", lines, content),
+    })
+}
+
+/// Parses a date, either alone (`2018-05-01`, at midnight UTC) or with a time in RFC 3339 format.
+pub fn parse_date(text: &str) -> Result<DateTime<Utc>> {
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Ok(Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap()));
+    }
+    Ok(DateTime::parse_from_rfc3339(text)?.with_timezone(&Utc))
+}
+
+/// Parses the subset of YAML used in front matter: `key: value` pairs, where values are
+/// scalars, flow lists (`[a, b]`) or block lists (`- a` lines).
+fn parse_yaml(block: &str) -> Result<Table> {
+    let mut table = Table::new();
+    let mut list_key = None::<String>;
+    for line in block.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        if let Some(item) = trimmed.strip_prefix("- ") {
+            let key = list_key.as_ref().context("List item outside of a list")?;
+            if let Some(Value::Array(items)) = table.get_mut(key) {
+                items.push(yaml_scalar(item));
+            }
+            continue;
+        }
+
+        let (key, value) = trimmed
+            .split_once(':')
+            .with_context(|| format!("Expected `key: value`, found {:?}", trimmed))?;
+        let (key, value) = (key.trim().to_string(), value.trim());
+        list_key = None;
+        let value = if value.is_empty() {
+            list_key = Some(key.clone());
+            Value::Array(Vec::new())
+        } else if let Some(items) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            Value::Array(
+                items
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(yaml_scalar)
+                    .collect(),
+            )
+        } else {
+            yaml_scalar(value)
+        };
+        table.insert(key, value);
+    }
+    Ok(table)
+}
+
+fn yaml_scalar(text: &str) -> Value {
+    let text = text.trim();
+    for quote in ['"', '\''] {
+        if let Some(unquoted) = text.strip_prefix(quote).and_then(|t| t.strip_suffix(quote)) {
+            return Value::String(unquoted.to_string());
+        }
+    }
+    match text {
+        "true" => Value::Boolean(true),
+        "false" => Value::Boolean(false),
+        _ => Value::String(text.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> DateTime<Utc> {
+        text.parse().unwrap()
+    }
+
+    /// Aggregated history of a chapter created by Alice and last edited by Bob.
+    fn info() -> GitInfo {
+        let history = [
+            GitHistoryEntry::test("b", "Bob", "2024-03-05T12:00:00Z"),
+            GitHistoryEntry::test("a", "Alice", "2024-01-10T12:00:00Z"),
+        ];
+        GitInfo::from_history(&history, &GitInfoOptions::default())
+    }
+
+    #[test]
+    fn yaml_front_matter_is_parsed() -> Result<()> {
+        let front_matter = parse(
+            "---\n\
+             # Written before the repository existed\n\
+             title: \"Intro: the basics\"\n\
+             authors:\n\
+             \x20 - Ada Lovelace\n\
+             \x20 - 'Charles Babbage'\n\
+             created: 1843-09-01\n\
+             last_edit: \"2024-03-05T10:00:00+02:00\"\n\
+             ---\n\
+             # Intro\n",
+        )?
+        .unwrap();
+        assert_eq!(
+            front_matter.authors.unwrap(),
+            ["Ada Lovelace", "Charles Babbage"]
+        );
+        assert_eq!(front_matter.created, Some(date("1843-09-01T00:00:00Z")));
+        assert_eq!(front_matter.last_edit, Some(date("2024-03-05T08:00:00Z")));
+        assert!(!front_matter.skip);
+
+        let flow = parse("---\nauthors: [Ada, Charles]\ngit-info: skip\n---\n")?.unwrap();
+        assert_eq!(flow.authors.unwrap(), ["Ada", "Charles"]);
+        assert!(flow.skip);
+        Ok(())
+    }
+
+    #[test]
+    fn toml_front_matter_is_parsed() -> Result<()> {
+        let front_matter = parse(
+            "+++\n\
+             authors = \"Ada Lovelace\"\n\
+             created = 1843-09-01\n\
+             last_edit = \"2024-03-05\"\n\
+             git-info = \"skip\"\n\
+             +++\n\
+             # Intro\n",
+        )?
+        .unwrap();
+        assert_eq!(front_matter.authors.unwrap(), ["Ada Lovelace"]);
+        assert_eq!(front_matter.created, Some(date("1843-09-01T00:00:00Z")));
+        assert_eq!(front_matter.last_edit, Some(date("2024-03-05T00:00:00Z")));
+        assert!(front_matter.skip);
+        Ok(())
+    }
+
+    #[test]
+    fn chapters_without_front_matter_have_none() -> Result<()> {
+        for content in [
+            "# Intro\n",
+            "",
+            "# Intro\n---\nauthors: Ada\n---\n",
+            // Never closed
+            "---\nauthors: Ada\n# Intro\n",
+        ] {
+            assert!(parse(content)?.is_none(), "{:?}", content);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn malformed_front_matter_is_an_error() {
+        for content in [
+            "---\nnot a pair\n---\n",
+            "---\n- orphan item\n---\n",
+            "---\ncreated: yesterday\n---\n",
+            "+++\nauthors = 42\n+++\n",
+            "+++\nauthors = [\"Ada\", 1]\n+++\n",
+            "+++\nnot toml\n+++\n",
+            "+++\ncreated = true\n+++\n",
+        ] {
+            assert!(parse(content).is_err(), "{:?}", content);
+        }
+    }
+
+    #[test]
+    fn partial_front_matter_keeps_the_rest_of_git() -> Result<()> {
+        let mut info = info();
+        parse("---\ncreated: 2018-05-01\n---\n")?
+            .unwrap()
+            .apply(&mut info);
+
+        let created = info.created.as_ref().unwrap();
+        assert_eq!(created.timestamp, date("2018-05-01T00:00:00Z"));
+        assert_eq!(created.author, "Alice");
+        assert_eq!(created.hash, "a");
+        let last_edit = info.last_edit.as_ref().unwrap();
+        assert_eq!(last_edit.timestamp, date("2024-03-05T12:00:00Z"));
+        assert_eq!(last_edit.author, "Bob");
+        Ok(())
+    }
+
+    #[test]
+    fn full_front_matter_replaces_git() -> Result<()> {
+        let front_matter = parse(
+            "---\nauthors: [Ada, Charles]\ncreated: 2018-05-01\nlast_edit: 2019-06-02\n---\n",
+        )?
+        .unwrap();
+
+        let mut info = info();
+        front_matter.apply(&mut info);
+        let created = info.created.as_ref().unwrap();
+        assert_eq!(created.timestamp, date("2018-05-01T00:00:00Z"));
+        assert_eq!(
+            created.name(crate::config::Identity::Committer),
+            "Ada, Charles"
+        );
+        assert_eq!(
+            info.last_edit.as_ref().unwrap().timestamp,
+            date("2019-06-02T00:00:00Z")
+        );
+
+        // Chapters without history get the dates, without any commit
+        let mut info = GitInfo::from_history(&[], &GitInfoOptions::default());
+        front_matter.apply(&mut info);
+        let created = info.created.as_ref().unwrap();
+        assert_eq!(created.author, "Ada, Charles");
+        assert!(created.hash.is_empty());
+        assert_eq!(
+            info.last_edit.as_ref().unwrap().timestamp,
+            date("2019-06-02T00:00:00Z")
+        );
+        Ok(())
+    }
+}
@@ -1,65 +1,2459 @@
+use crate::config::{GitInfoOptions, Identity, MalformedLineAction};
 use anyhow::{Context, Result};
-use chrono::{DateTime, Utc};
-use std::path::Path;
-use std::process::{Command, Stdio};
+use chrono::{DateTime, TimeZone, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 /// A single entry of the git log.
-#[derive(Debug)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[non_exhaustive]
 pub struct GitHistoryEntry {
+    /// Full hash of the commit.
+    pub hash: String,
+    /// Abbreviated hash of the commit.
+    pub short_hash: String,
+    /// Author name.
     pub author: String,
+    /// Author email.
+    pub email: String,
+    /// Committer name.
+    #[serde(default)]
+    pub committer: String,
+    /// Committer email.
+    #[serde(default)]
+    pub committer_email: String,
+    /// Author date.
     pub timestamp: DateTime<Utc>,
+    /// Committer date.
+    pub commit_timestamp: DateTime<Utc>,
+    /// First line of the commit message.
+    pub subject: String,
+    /// Trailers of the commit message (e.g. `Reviewed-by: ...`), as key-value pairs.
+    #[serde(default)]
+    pub trailers: Vec<(String, String)>,
+    /// Lines added to the file by the commit, if [`GitInfoOptions::churn`] is enabled.
+    #[serde(default)]
+    pub lines_added: u64,
+    /// Lines removed from the file by the commit, if [`GitInfoOptions::churn`] is enabled.
+    #[serde(default)]
+    pub lines_removed: u64,
+}
+
+impl GitHistoryEntry {
+    /// Name of the author or of the committer, depending on who is credited for the commit.
+    ///
+    /// Entries without committer, like those cached by older versions, fall back to the author.
+    pub fn name(&self, identity: Identity) -> &str {
+        match identity {
+            Identity::Committer if !self.committer.is_empty() => &self.committer,
+            _ => &self.author,
+        }
+    }
+
+    /// Returns the values of the trailers with any of the given keys, compared case-insensitively.
+    pub fn trailer_values<'a>(&'a self, keys: &'a [String]) -> impl Iterator<Item = &'a str> {
+        self.trailers
+            .iter()
+            .filter(|(key, _)| keys.iter().any(|k| k.eq_ignore_ascii_case(key)))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[cfg(test)]
+impl GitHistoryEntry {
+    /// A commit by `author`, also its committer, dated `date` in RFC 3339 format.
+    pub(crate) fn test(hash: &str, author: &str, date: &str) -> GitHistoryEntry {
+        let date = DateTime::parse_from_rfc3339(date)
+            .unwrap()
+            .with_timezone(&Utc);
+        GitHistoryEntry {
+            hash: hash.to_string(),
+            short_hash: hash.chars().take(7).collect(),
+            author: author.to_string(),
+            email: format!("{}@example.com", author.to_lowercase()),
+            committer: author.to_string(),
+            committer_email: format!("{}@example.com", author.to_lowercase()),
+            timestamp: date,
+            commit_timestamp: date,
+            subject: format!("Commit {}", hash),
+            trailers: Vec::new(),
+            lines_added: 0,
+            lines_removed: 0,
+        }
+    }
+}
+
+/// Fields requested to `git log`, as `(name, placeholder)` pairs, in the order in which
+/// [`parse_line`] expects them.
+///
+/// Git versions older than [`MIN_GIT_VERSION`] do not know the trailers placeholder, and print
+/// the whole commit message instead (see [`pretty_format`]).
+const FIELDS: [(&str, &str); 10] = [
+    ("hash", "%H"),
+    ("short_hash", "%h"),
+    ("author", "%an"),
+    ("email", "%ae"),
+    ("committer", "%cn"),
+    ("committer_email", "%ce"),
+    // Seconds since the epoch, unlike `%aI` and `%cI`, are understood by every version of git
+    ("timestamp", "%at"),
+    ("commit_timestamp", "%ct"),
+    ("subject", "%s"),
+    ("trailers", "%(trailers:only,unfold,separator=%x1d)"),
+];
+
+/// Separator between the fields of a record (ASCII unit separator).
+const FIELD_SEPARATOR: char = '\x1f';
+
+/// Separator between the trailers of a commit (ASCII group separator).
+const TRAILER_SEPARATOR: char = '\x1d';
+
+/// Placeholder of the whole commit message, known to every supported version of git.
+const MESSAGE_PLACEHOLDER: &str = "%B";
+
+/// Builds the `--pretty` format string matching [`FIELDS`].
+fn pretty_format(options: &GitInfoOptions) -> String {
+    let placeholders = FIELDS
+        .iter()
+        .map(|(name, placeholder)| match *name {
+            "trailers" if trailers_from_message(options) => MESSAGE_PLACEHOLDER,
+            _ => *placeholder,
+        })
+        .collect::<Vec<_>>();
+    placeholders.join("%x1f")
+}
+
+/// Whether the git binary is too old to list the trailers of the commits, which are then
+/// parsed out of the whole commit messages.
+///
+/// Messages span several lines, so the records of the log are then separated by NUL characters.
+fn trailers_from_message(options: &GitInfoOptions) -> bool {
+    options
+        .git_version
+        .is_some_and(|version| version < MIN_GIT_VERSION)
 }
 
 /// Extracts the git history of the given file using `git log`.
-pub fn extract(path: impl AsRef<Path>) -> Result<Vec<GitHistoryEntry>> {
-    // Launch git to extract info
-    let output = Command::new("git")
+///
+/// When [`GitInfoOptions::max_history`] is set, the result is truncated as described
+/// in [`truncate_history`].
+pub fn extract(path: impl AsRef<Path>, options: &GitInfoOptions) -> Result<Vec<GitHistoryEntry>> {
+    extract_newest(path.as_ref(), options, options.max_history)
+}
+
+/// Extracts only the newest commit of the given file, followed by the commit which created it
+/// if different, for when [`GitInfoOptions::needs_full_history`] is false.
+///
+/// This takes two bounded queries instead of a walk of the whole history of the file.
+pub fn extract_endpoints(
+    path: impl AsRef<Path>,
+    options: &GitInfoOptions,
+) -> Result<Vec<GitHistoryEntry>> {
+    extract_newest(path.as_ref(), options, Some(1))
+}
+
+/// Extracts the newest `max_history` commits of the given file, followed by the commit which
+/// created it if not among them.
+fn extract_newest(
+    path: &Path,
+    options: &GitInfoOptions,
+    max_history: Option<usize>,
+) -> Result<Vec<GitHistoryEntry>> {
+    // Launch git to extract info, parsing its output as it comes
+    let command = log_command(path, options, max_history);
+    let mut history = run_with(command, "git log", options, |stdout| {
+        read_file_log(stdout, options, max_history)
+    })?;
+
+    // A truncated log does not reach the creation of the file, which has to be queried separately
+    if max_history == Some(history.len()) {
+        let created = extract_creation(path, options)?;
+        if let Some(created) = created {
+            if history.last().is_some_and(|last| last.hash != created.hash) {
+                history.push(created);
+            }
+        }
+    }
+
+    Ok(history)
+}
+
+/// Describes the `git log` invocation used by [`extract`] on the given file, for diagnostics.
+pub fn extract_command_line(path: impl AsRef<Path>, options: &GitInfoOptions) -> String {
+    let command = log_command(path.as_ref(), options, options.max_history);
+    let line = std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| arg.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ");
+    match command.get_current_dir() {
+        Some(dir) => format!("{} (in {})", line, dir.display()),
+        None => line,
+    }
+}
+
+fn log_command(path: &Path, options: &GitInfoOptions, max_history: Option<usize>) -> Command {
+    let mut command = git_command(options);
+    command.arg("log");
+    file_log_format(&mut command, options);
+    if let Some(max_history) = max_history {
+        command.arg("-n").arg(max_history.to_string());
+    }
+    if options.follow_renames {
+        command.arg("--follow");
+    }
+    let file = in_file_dir(&mut command, path);
+    command.arg("--").arg(file);
+    command
+}
+
+/// Finds the commit which added the given file.
+///
+/// If the file was added more than once, the oldest addition wins: the log is walked from the
+/// oldest commit, and only its first record is parsed.
+fn extract_creation(path: &Path, options: &GitInfoOptions) -> Result<Option<GitHistoryEntry>> {
+    let mut command = git_command(options);
+    command.arg("log").arg("--diff-filter=A");
+    file_log_format(&mut command, options);
+    // git finds nothing when `--follow` is combined with `--reverse`, so renamed files
+    // are walked from the newest addition instead
+    let reverse = !options.follow_renames;
+    if reverse {
+        command.arg("--reverse");
+    } else {
+        command.arg("--follow");
+    }
+    let file = in_file_dir(&mut command, path);
+    command.arg("--").arg(file);
+
+    Ok(run_with(command, "git log", options, |stdout| {
+        read_file_log(stdout, options, reverse.then_some(1))
+    })?
+    .pop())
+}
+
+/// Runs `command` in the directory of `path`, returning the name of the file relative to it,
+/// so that git finds the repository of the file whatever the current directory.
+fn in_file_dir<'a>(command: &mut Command, path: &'a Path) -> &'a OsStr {
+    match (path.parent(), path.file_name()) {
+        (Some(dir), Some(name)) if !dir.as_os_str().is_empty() => {
+            command.current_dir(dir);
+            name
+        }
+        _ => path.as_os_str(),
+    }
+}
+
+/// Requests the output format of the log of a single file, parsed by [`read_file_log`].
+///
+/// With [`GitInfoOptions::churn`], each commit is followed by its line counts.
+fn file_log_format(command: &mut Command, options: &GitInfoOptions) {
+    if options.churn {
+        command.arg("-z").arg("--numstat").arg(format!(
+            "--pretty=format:%x1e{}%x1e",
+            pretty_format(options)
+        ));
+    } else {
+        if trailers_from_message(options) {
+            command.arg("-z");
+        }
+        command.arg(format!("--pretty=format:{}", pretty_format(options)));
+    }
+}
+
+/// Reads the log of a single file, requested by [`file_log_format`], keeping at most `limit` entries.
+///
+/// Without [`GitInfoOptions::churn`], the log is parsed as it is read instead of being buffered.
+fn read_file_log(
+    stdout: &mut dyn BufRead,
+    options: &GitInfoOptions,
+    limit: Option<usize>,
+) -> Result<Vec<GitHistoryEntry>> {
+    if !options.churn {
+        return read_log(stdout, options, limit);
+    }
+
+    let mut buf = Vec::new();
+    stdout.read_to_end(&mut buf)?;
+    let mut log = parse_numstat_log(&buf, options)?;
+    if let Some(limit) = limit {
+        log.truncate(limit);
+    }
+    Ok(log
+        .into_iter()
+        .map(|(mut entry, changes)| {
+            for change in changes {
+                entry.lines_added += change.added;
+                entry.lines_removed += change.removed;
+            }
+            entry
+        })
+        .collect())
+}
+
+/// Truncates a complete history to the newest `max_history` commits, followed by the
+/// commit which created the file, so that the creation info is preserved.
+///
+/// A history is thus truncated exactly when it is longer than `max_history`.
+pub fn truncate_history(history: &mut Vec<GitHistoryEntry>, max_history: Option<usize>) {
+    if let Some(max_history) = max_history {
+        if history.len() > max_history + 1 {
+            let created = history.pop();
+            history.truncate(max_history);
+            history.extend(created);
+        }
+    }
+}
+
+/// Extracts the git history of every file under `dir` with a single `git log` invocation.
+///
+/// The keys of the returned map are paths relative to `dir`. When renames are followed,
+/// the history of a file also contains the commits made under its previous names.
+pub fn extract_all(
+    dir: impl AsRef<Path>,
+    options: &GitInfoOptions,
+) -> Result<HashMap<PathBuf, Vec<GitHistoryEntry>>> {
+    let dir = dir.as_ref();
+
+    let prefix = show_prefix(dir, options)?;
+
+    // Line counts replace the statuses, which git does not print along with them
+    let mut command = git_command(options);
+    command
+        .current_dir(dir)
+        .arg("log")
+        .arg("-z")
+        .arg(if options.churn {
+            "--numstat"
+        } else {
+            "--name-status"
+        })
+        .arg(if options.follow_renames {
+            "--find-renames"
+        } else {
+            "--no-renames"
+        })
+        .arg(format!(
+            "--pretty=format:%x1e{}%x1e",
+            pretty_format(options)
+        ))
+        .arg("--")
+        .arg(".");
+    let stdout = run(command, "git log", options)?;
+    let log = if options.churn {
+        parse_numstat_log(&stdout, options)?
+    } else {
+        parse_name_status_log(&stdout, options)?
+    };
+
+    // Walk the commits from the newest to the oldest, keeping track of the name
+    // each file had at that point in history
+    let mut histories = HashMap::<PathBuf, Vec<GitHistoryEntry>>::new();
+    let mut renamed_from = HashMap::<PathBuf, PathBuf>::new();
+    for (entry, changes) in log {
+        for change in changes {
+            let key = renamed_from
+                .get(&change.path)
+                .cloned()
+                .unwrap_or_else(|| change.path.clone());
+            if let Some(old_path) = change.old_path {
+                renamed_from.remove(&change.path);
+                renamed_from.insert(old_path, key.clone());
+            }
+            histories.entry(key).or_default().push(GitHistoryEntry {
+                lines_added: change.added,
+                lines_removed: change.removed,
+                ..entry.clone()
+            });
+        }
+    }
+
+    Ok(histories
+        .into_iter()
+        .filter_map(|(path, mut history)| {
+            truncate_history(&mut history, options.max_history);
+            Some((path.strip_prefix(&prefix).ok()?.to_path_buf(), history))
+        })
+        .collect())
+}
+
+/// Lists the newest `max_commits` commits touching any file under `dir`, newest first,
+/// along with the touched files relative to `dir`.
+///
+/// Merge commits are only listed when `include_merges` is set, and never report any file.
+pub fn recent_commits(
+    dir: impl AsRef<Path>,
+    options: &GitInfoOptions,
+    max_commits: usize,
+    include_merges: bool,
+) -> Result<Vec<(GitHistoryEntry, Vec<PathBuf>)>> {
+    let dir = dir.as_ref();
+    let prefix = show_prefix(dir, options)?;
+
+    let mut command = git_command(options);
+    command
+        .current_dir(dir)
+        .arg("log")
+        .arg("-z")
+        .arg("--name-status")
+        .arg("--no-renames")
+        .arg("-n")
+        .arg(max_commits.to_string())
+        .arg(format!(
+            "--pretty=format:%x1e{}%x1e",
+            pretty_format(options)
+        ));
+    if !include_merges {
+        command.arg("--no-merges");
+    }
+    command.arg("--").arg(".");
+    let stdout = run(command, "git log", options)?;
+
+    Ok(parse_name_status_log(&stdout, options)?
+        .into_iter()
+        .map(|(entry, changes)| {
+            let paths = changes
+                .into_iter()
+                .filter_map(|change| Some(change.path.strip_prefix(&prefix).ok()?.to_path_buf()))
+                .collect();
+            (entry, paths)
+        })
+        .collect())
+}
+
+/// Returns the path of `dir` relative to the root of its repository, which prefixes
+/// every path printed by git.
+fn show_prefix(dir: &Path, options: &GitInfoOptions) -> Result<PathBuf> {
+    let mut command = git_command(options);
+    command
+        .current_dir(dir)
+        .arg("rev-parse")
+        .arg("--show-prefix");
+    let prefix = String::from_utf8(run(command, "git rev-parse", options)?)
+        .context("Invalid UTF-8 output from git")?;
+    Ok(PathBuf::from(prefix.trim_end()))
+}
+
+/// Oldest version of git whose output is complete: older versions cannot list the trailers
+/// of the commits.
+pub const MIN_GIT_VERSION: GitVersion = GitVersion {
+    major: 2,
+    minor: 22,
+    patch: 0,
+};
+
+/// Oldest version of git which can be used at all, being the first to accept the `-c` options
+/// passed to every invocation.
+pub const OLDEST_GIT_VERSION: GitVersion = GitVersion {
+    major: 1,
+    minor: 7,
+    patch: 2,
+};
+
+/// Version of the `git` binary, as reported by `git --version`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct GitVersion {
+    /// Major version.
+    pub major: u32,
+    /// Minor version.
+    pub minor: u32,
+    /// Patch version.
+    pub patch: u32,
+}
+
+impl GitVersion {
+    /// Parses the output of `git --version`, like `git version 2.39.3 (Apple Git-146)`
+    /// or `git version 2.40.0.windows.1`.
+    pub fn parse(output: &str) -> Option<GitVersion> {
+        let version = output.trim().strip_prefix("git version ")?;
+        // Release candidates are numbered like `2.45.0-rc1`, so only leading digits count
+        let mut numbers = version.split_whitespace().next()?.split('.').map(|n| {
+            let end = n.find(|c: char| !c.is_ascii_digit()).unwrap_or(n.len());
+            n[..end].parse::<u32>().ok()
+        });
+        Some(GitVersion {
+            major: numbers.next()??,
+            minor: numbers.next()??,
+            patch: numbers.next().flatten().unwrap_or(0),
+        })
+    }
+}
+
+impl fmt::Display for GitVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Status of the signature of a commit, as reported by the `%G?` placeholder of `git log`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum SignatureStatus {
+    /// Good signature (`G`).
+    Good,
+    /// Good signature by a key of unknown validity (`U`).
+    UnknownValidity,
+    /// Bad signature (`B`).
+    Bad,
+    /// Good signature by an expired key, or expired signature (`X` and `Y`).
+    Expired,
+    /// Good signature by a revoked key (`R`).
+    Revoked,
+    /// The signature cannot be checked, usually because the key is missing (`E`).
+    MissingKey,
+    /// No signature (`N`).
+    Unsigned,
+}
+
+impl SignatureStatus {
+    /// Parses the letter printed by `%G?`.
+    pub fn from_letter(letter: &str) -> Option<SignatureStatus> {
+        match letter.trim() {
+            "G" => Some(SignatureStatus::Good),
+            "U" => Some(SignatureStatus::UnknownValidity),
+            "B" => Some(SignatureStatus::Bad),
+            "X" | "Y" => Some(SignatureStatus::Expired),
+            "R" => Some(SignatureStatus::Revoked),
+            "E" => Some(SignatureStatus::MissingKey),
+            "N" => Some(SignatureStatus::Unsigned),
+            _ => None,
+        }
+    }
+}
+
+/// The signature of a commit.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct CommitSignature {
+    /// Result of the verification.
+    pub status: SignatureStatus,
+    /// Signer of the commit, as reported by the verification (`%GS`), if known.
+    pub signer: Option<String>,
+}
+
+/// Verifies the signature of the commit `hash` of the repository containing `dir`.
+///
+/// Verification runs gpg or ssh, which makes it much slower than reading the commit.
+pub fn signature(
+    dir: impl AsRef<Path>,
+    hash: &str,
+    options: &GitInfoOptions,
+) -> Result<CommitSignature> {
+    let mut command = git_command(options);
+    command
+        .current_dir(dir)
+        .arg("log")
+        .arg("-1")
+        .arg("--format=%G?%x1f%GS")
+        .arg(hash)
+        .arg("--");
+    let stdout = run(command, "git log", options)?;
+    let stdout = decode(&stdout, options)?;
+    let (letter, signer) = stdout
+        .trim_end()
+        .split_once(FIELD_SEPARATOR)
+        .unwrap_or((&stdout, ""));
+    Ok(CommitSignature {
+        status: SignatureStatus::from_letter(letter).with_context(|| {
+            format!(
+                "Unexpected signature status {:?} of commit {}",
+                letter, hash
+            )
+        })?,
+        signer: Some(signer.trim().to_string()).filter(|signer| !signer.is_empty()),
+    })
+}
+
+/// Runs `git --version`, checking that the configured `git` binary can be used at all.
+pub fn version(options: &GitInfoOptions) -> Result<GitVersion> {
+    let mut command = git_command(options);
+    command.arg("--version");
+    let stdout = run(command, "git --version", options)?;
+    let stdout = String::from_utf8_lossy(&stdout);
+    GitVersion::parse(&stdout)
+        .with_context(|| format!("Unexpected output of `git --version`: {:?}", stdout.trim()))
+}
+
+/// Returns the hash of the `HEAD` commit of the repository containing `dir`.
+pub fn head(dir: impl AsRef<Path>, options: &GitInfoOptions) -> Result<String> {
+    let mut command = git_command(options);
+    command.current_dir(dir).arg("rev-parse").arg("HEAD");
+    let stdout = run(command, "git rev-parse", options)?;
+    Ok(String::from_utf8_lossy(&stdout).trim().to_string())
+}
+
+/// Returns the branch checked out in the repository containing `dir`, or when `HEAD` is
+/// detached, a tag pointing at it, if any.
+pub fn head_ref(dir: impl AsRef<Path>, options: &GitInfoOptions) -> Result<Option<String>> {
+    let dir = dir.as_ref();
+
+    // A detached `HEAD` is printed as is
+    let mut command = git_command(options);
+    command
+        .current_dir(dir)
+        .arg("rev-parse")
+        .arg("--abbrev-ref")
+        .arg("HEAD");
+    let stdout = run(command, "git rev-parse", options)?;
+    let branch = String::from_utf8_lossy(&stdout).trim().to_string();
+    if !branch.is_empty() && branch != "HEAD" {
+        return Ok(Some(branch));
+    }
+
+    let mut command = git_command(options);
+    command
+        .current_dir(dir)
+        .arg("tag")
+        .arg("--points-at")
+        .arg("HEAD");
+    let stdout = run(command, "git tag", options)?;
+    Ok(String::from_utf8_lossy(&stdout)
+        .lines()
+        .map(str::trim)
+        .find(|tag| !tag.is_empty())
+        .map(str::to_string))
+}
+
+/// Returns the canonical path of the root of the working tree containing `dir`.
+pub fn root(dir: impl AsRef<Path>, options: &GitInfoOptions) -> Result<PathBuf> {
+    let mut command = git_command(options);
+    command
+        .current_dir(dir)
+        .arg("rev-parse")
+        .arg("--show-toplevel");
+    let root =
+        PathBuf::from(String::from_utf8_lossy(&run(command, "git rev-parse", options)?).trim());
+    Ok(fs::canonicalize(&root).unwrap_or(root))
+}
+
+/// Whether the repository containing `dir` is a shallow clone, with a truncated history.
+pub fn is_shallow(dir: impl AsRef<Path>, options: &GitInfoOptions) -> Result<bool> {
+    let mut command = git_command(options);
+    command
+        .current_dir(dir)
+        .arg("rev-parse")
+        .arg("--is-shallow-repository");
+    let stdout = run(command, "git rev-parse", options)?;
+    Ok(String::from_utf8_lossy(&stdout).trim() == "true")
+}
+
+/// Returns the URL of the remote `name` of the repository containing `dir`,
+/// or `None` if there is no such remote.
+pub fn remote_url(
+    dir: impl AsRef<Path>,
+    name: &str,
+    options: &GitInfoOptions,
+) -> Result<Option<String>> {
+    let mut command = git_command(options);
+    command
+        .current_dir(dir)
+        .arg("ls-remote")
+        .arg("--get-url")
+        .arg(name);
+    let stdout = run(command, "git ls-remote", options)?;
+
+    // Unknown remotes are echoed back, as if they were URLs
+    let url = String::from_utf8_lossy(&stdout).trim().to_string();
+    Ok(Some(url).filter(|url| url != name))
+}
+
+/// Counts the lines of the given file last changed by each author, according to `git blame`,
+/// sorted by author name. Uncommitted lines are not counted.
+pub fn blame(path: impl AsRef<Path>, options: &GitInfoOptions) -> Result<Vec<(String, usize)>> {
+    let mut command = git_command(options);
+    let file = in_file_dir(&mut command, path.as_ref());
+    command
+        .arg("blame")
+        .arg("--line-porcelain")
+        .arg("--")
+        .arg(file);
+    let stdout = run(command, "git blame", options)?;
+    Ok(parse_blame(&decode(&stdout, options)?, options.identity))
+}
+
+/// Parses the output of `git blame --line-porcelain`, where each line of the file is preceded
+/// by a header starting with the hash of its commit and listing its author and committer.
+fn parse_blame(output: &str, identity: Identity) -> Vec<(String, usize)> {
+    let prefix = match identity {
+        Identity::Committer => "committer ",
+        _ => "author ",
+    };
+    let mut counts = BTreeMap::<&str, usize>::new();
+    let mut uncommitted = false;
+    for line in output.lines() {
+        // Lines of the file are prefixed with a tab, so they cannot be mistaken for headers
+        if line.starts_with('\t') {
+            continue;
+        }
+        if let Some(author) = line.strip_prefix(prefix) {
+            if !uncommitted {
+                *counts.entry(author).or_default() += 1;
+            }
+        } else if let Some(hash) = line
+            .split(' ')
+            .next()
+            .filter(|hash| hash.len() >= 40 && hash.bytes().all(|b| b.is_ascii_hexdigit()))
+        {
+            uncommitted = hash.bytes().all(|b| b == b'0');
+        }
+    }
+    counts
+        .into_iter()
+        .map(|(author, count)| (author.to_string(), count))
+        .collect()
+}
+
+/// Returns the text of the notes attached to commits under `notes_ref` (e.g. `reviews`
+/// for `refs/notes/reviews`), by full hash of the commit.
+pub fn notes(
+    dir: impl AsRef<Path>,
+    notes_ref: &str,
+    options: &GitInfoOptions,
+) -> Result<HashMap<String, String>> {
+    let dir = dir.as_ref();
+
+    // Each line lists a note object and the annotated object
+    let mut command = git_command(options);
+    command
+        .current_dir(dir)
+        .arg("notes")
+        .arg(format!("--ref={}", notes_ref))
+        .arg("list");
+    let stdout = run(command, "git notes", options)?;
+    let commits = String::from_utf8_lossy(&stdout)
+        .lines()
+        .filter_map(|line| Some(line.split_whitespace().nth(1)?.to_string()))
+        .collect::<Vec<_>>();
+    if commits.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    // Notes span several lines, so records are delimited by the record separator
+    let mut command = git_command(options);
+    command
+        .current_dir(dir)
         .arg("log")
-        .arg("--pretty=\"%an%x09%aI\"")
+        .arg("--no-walk=unsorted")
+        .arg(format!("--notes={}", notes_ref))
+        .arg("--pretty=format:%x1e%H%x1f%N")
+        .args(&commits);
+    let stdout = run(command, "git log", options)?;
+    Ok(decode(&stdout, options)?
+        .split('\x1e')
+        .filter_map(|record| {
+            let (hash, note) = record.split_once(FIELD_SEPARATOR)?;
+            Some((hash.to_string(), note.trim_end().to_string()))
+        })
+        .collect())
+}
+
+/// Returns the full hashes of the commits of `HEAD` which `published_ref` does not contain,
+/// merges excluded, or `None` if the ref does not exist.
+pub fn unpublished_commits(
+    dir: impl AsRef<Path>,
+    published_ref: &str,
+    options: &GitInfoOptions,
+) -> Result<Option<HashSet<String>>> {
+    let dir = dir.as_ref();
+
+    // `--verify --quiet` only fails, silently, when the ref does not name a commit
+    let mut command = git_command(options);
+    command
+        .current_dir(dir)
+        .arg("rev-parse")
+        .arg("--verify")
+        .arg("--quiet")
+        .arg(format!("{}^{{commit}}", published_ref));
+    if run(command, "git rev-parse", options).is_err() {
+        return Ok(None);
+    }
+
+    let mut command = git_command(options);
+    command
+        .current_dir(dir)
+        .arg("rev-list")
+        .arg("--no-merges")
+        .arg(format!("{}..HEAD", published_ref))
+        .arg("--");
+    let stdout = run(command, "git rev-list", options)?;
+    Ok(Some(
+        String::from_utf8_lossy(&stdout)
+            .lines()
+            .map(str::to_string)
+            .collect(),
+    ))
+}
+
+/// Returns the first tag matching the glob `pattern` which contains the commit `hash`,
+/// according to `git describe --contains`, or `None` if no such tag exists yet.
+pub fn first_tag_containing(
+    dir: impl AsRef<Path>,
+    hash: &str,
+    pattern: &str,
+    options: &GitInfoOptions,
+) -> Result<Option<String>> {
+    // With `--always`, the abbreviated hash is printed instead of failing when there is no tag
+    let mut command = git_command(options);
+    command
+        .current_dir(dir)
+        .arg("describe")
+        .arg("--contains")
+        .arg("--always")
+        .arg("--match")
+        .arg(pattern)
+        .arg(hash);
+    let stdout = run(command, "git describe", options)?;
+    let name = String::from_utf8_lossy(&stdout);
+    let name = name.trim();
+    if name.is_empty() || hash.starts_with(name) {
+        return Ok(None);
+    }
+
+    // Names like `v2.3.0~2^2` locate the commit relative to the tag
+    Ok(Some(
+        name.split(['~', '^']).next().unwrap_or(name).to_string(),
+    ))
+}
+
+/// Returns the canonical paths of the files under `dir` with uncommitted changes.
+pub fn dirty_files(dir: impl AsRef<Path>, options: &GitInfoOptions) -> Result<HashSet<PathBuf>> {
+    let dir = dir.as_ref();
+
+    // Paths printed by git are relative to the root of the repository
+    let root = root(dir, options)?;
+
+    let mut command = git_command(options);
+    command
+        .current_dir(dir)
+        .arg("status")
+        .arg("--porcelain")
+        .arg("-z")
         .arg("--")
-        .arg(path.as_ref())
+        .arg(".");
+    let stdout = run(command, "git status", options)?;
+
+    // Each entry is `XY <path>`, and renames are followed by the original path
+    let mut dirty = HashSet::new();
+    let mut tokens = stdout.split(|b| *b == 0).filter(|token| !token.is_empty());
+    while let Some(token) = tokens.next() {
+        if token.len() > 3 {
+            dirty.insert(root.join(&*String::from_utf8_lossy(&token[3..])));
+        }
+        if token[0] == b'R' || token[0] == b'C' {
+            tokens.next();
+        }
+    }
+
+    Ok(dirty)
+}
+
+/// A file touched by a commit, as reported by `git log --name-status` or `git log --numstat`.
+#[derive(Debug)]
+struct Change {
+    path: PathBuf,
+    /// Previous path of the file, for renames.
+    old_path: Option<PathBuf>,
+    /// Lines added and removed, only reported by `--numstat`.
+    added: u64,
+    removed: u64,
+}
+
+/// Parses the output of `git log -z --name-status`, where each commit header is wrapped in
+/// `\x1e` characters and followed by the NUL-separated list of touched files.
+fn parse_name_status_log(
+    stdout: &[u8],
+    options: &GitInfoOptions,
+) -> Result<Vec<(GitHistoryEntry, Vec<Change>)>> {
+    let mut log = Vec::new();
+
+    // Each header is `\x1e<fields>\x1e`, followed by the NUL-separated status and paths
+    for chunk in stdout
+        .split(|b| *b == 0x1e)
+        .skip(1)
+        .collect::<Vec<_>>()
+        .chunks(2)
+    {
+        let (header, files) = match chunk {
+            [header, files] => (*header, *files),
+            [header] => (*header, &[][..]),
+            _ => unreachable!(),
+        };
+
+        // Paths are only meaningful for a valid commit
+        let entry = match parse_record(header, options)? {
+            Some(entry) => entry,
+            None => continue,
+        };
+
+        let mut changes = Vec::new();
+        let mut tokens = files
+            .split(|b| *b == 0)
+            .map(|token| decode(token.strip_prefix(b"\n").unwrap_or(token), options))
+            .filter(|token| !matches!(token, Ok(token) if token.is_empty()));
+        while let Some(status) = tokens.next() {
+            let status = status?;
+            let mut next_path = || -> Result<PathBuf> {
+                Ok(PathBuf::from(
+                    tokens
+                        .next()
+                        .context("Unexpected end of the git output")??
+                        .as_ref(),
+                ))
+            };
+            let change = if status.starts_with('R') {
+                let old_path = next_path()?;
+                Change {
+                    path: next_path()?,
+                    old_path: Some(old_path),
+                    added: 0,
+                    removed: 0,
+                }
+            } else if status.starts_with('C') {
+                // Copies leave the original in place, so only the new path is affected
+                next_path()?;
+                Change {
+                    path: next_path()?,
+                    old_path: None,
+                    added: 0,
+                    removed: 0,
+                }
+            } else {
+                Change {
+                    path: next_path()?,
+                    old_path: None,
+                    added: 0,
+                    removed: 0,
+                }
+            };
+            changes.push(change);
+        }
+
+        log.push((entry, changes));
+    }
+
+    Ok(log)
+}
+
+/// Parses the output of `git log -z --numstat`, where each commit header is wrapped in
+/// `\x1e` characters and followed by NUL-terminated `<added>\t<removed>\t<path>` lines.
+///
+/// Renames have an empty path, followed by the old and new paths as separate tokens.
+/// Binary files count `-` lines, which are taken as zero.
+fn parse_numstat_log(
+    stdout: &[u8],
+    options: &GitInfoOptions,
+) -> Result<Vec<(GitHistoryEntry, Vec<Change>)>> {
+    let mut log = Vec::new();
+
+    for chunk in stdout
+        .split(|b| *b == 0x1e)
+        .skip(1)
+        .collect::<Vec<_>>()
+        .chunks(2)
+    {
+        let (header, files) = match chunk {
+            [header, files] => (*header, *files),
+            [header] => (*header, &[][..]),
+            _ => unreachable!(),
+        };
+
+        // Stats are only meaningful for a valid commit
+        let entry = match parse_record(header, options)? {
+            Some(entry) => entry,
+            None => continue,
+        };
+
+        let mut changes = Vec::new();
+        let mut tokens = files
+            .split(|b| *b == 0)
+            .map(|token| decode(token.strip_prefix(b"\n").unwrap_or(token), options))
+            .filter(|token| !matches!(token, Ok(token) if token.is_empty()));
+        while let Some(stat) = tokens.next() {
+            let stat = stat?;
+            let mut fields = stat.splitn(3, '\t');
+            let mut count = || -> Result<u64> {
+                match fields
+                    .next()
+                    .context("Malformed line counts in the git output")?
+                {
+                    "-" => Ok(0),
+                    count => count.parse().with_context(|| {
+                        format!("Invalid line count {:?} in the git output", count)
+                    }),
+                }
+            };
+            let (added, removed) = (count()?, count()?);
+
+            let mut next_path = || -> Result<PathBuf> {
+                Ok(PathBuf::from(
+                    tokens
+                        .next()
+                        .context("Unexpected end of the git output")??
+                        .as_ref(),
+                ))
+            };
+            let change = match fields.next().filter(|path| !path.is_empty()) {
+                Some(path) => Change {
+                    path: PathBuf::from(path),
+                    old_path: None,
+                    added,
+                    removed,
+                },
+                None => {
+                    let old_path = next_path()?;
+                    Change {
+                        path: next_path()?,
+                        old_path: Some(old_path),
+                        added,
+                        removed,
+                    }
+                }
+            };
+            changes.push(change);
+        }
+
+        log.push((entry, changes));
+    }
+
+    Ok(log)
+}
+
+/// Variables inherited from the environment which would point git at another repository,
+/// or make it run a pager.
+const SANITIZED_ENV: [&str; 4] = ["GIT_DIR", "GIT_WORK_TREE", "GIT_INDEX_FILE", "GIT_PAGER"];
+
+/// Creates a `git` command with the options shared by every invocation.
+///
+/// Every git invocation must be created here, so that the output is not affected by the
+/// environment or by the configuration of the user.
+fn git_command(options: &GitInfoOptions) -> Command {
+    let mut command = Command::new(&options.git_binary);
+    command.arg("--no-pager");
+    for var in SANITIZED_ENV {
+        command.env_remove(var);
+    }
+    command.args(&options.git_args);
+
+    // Commits declaring a different encoding are re-encoded by git itself
+    command.arg("-c").arg("i18n.logOutputEncoding=UTF-8");
+
+    // Settings which change the output, after the extra arguments so that they always win
+    command
+        .arg("-c")
+        .arg("log.showSignature=false")
+        .arg("-c")
+        .arg("color.ui=never")
+        .arg("-c")
+        .arg("core.quotePath=false");
+    command
+}
+
+/// Runs a git command to completion, returning its standard output.
+///
+/// The command is killed if it does not complete within the configured timeout.
+fn run(command: Command, description: &str, options: &GitInfoOptions) -> Result<Vec<u8>> {
+    run_with(command, description, options, |stdout| {
+        let mut buf = Vec::new();
+        stdout.read_to_end(&mut buf)?;
+        Ok(buf)
+    })
+}
+
+/// Runs a git command to completion, handing its standard output to `read_stdout` while it runs.
+///
+/// The command is killed if it does not complete within the configured timeout.
+/// Errors of `read_stdout` take precedence over the exit status of the command, since giving up
+/// on the output may make the command fail.
+fn run_with<T: Send>(
+    mut command: Command,
+    description: &str,
+    options: &GitInfoOptions,
+    read_stdout: impl FnOnce(&mut dyn BufRead) -> Result<T> + Send,
+) -> Result<T> {
+    let program = command.get_program().to_os_string();
+    let mut child = command
         .stdin(Stdio::null())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
-        .context("Failed to launch `git log`. Is git installed and available in $PATH?")?
-        .wait_with_output()
-        .context("Failed to wait on `git log`")?;
-
-    // Check the result of the invocation
-    if !output.status.success() {
-        return Err(anyhow::anyhow!(
-            "Git log failed. Exit code: {}.\nSTDOUT: {}\nSTDERR: {}",
-            output.status.code().unwrap_or(-1),
-            String::from_utf8_lossy(output.stdout.as_slice()),
-            String::from_utf8_lossy(output.stderr.as_slice())
-        ));
+        .with_context(|| {
+            format!(
+                "Failed to launch `{}` using {:?}. Is git installed and available in $PATH?",
+                description, program
+            )
+        })?;
+
+    // Drain both pipes while waiting, so that the child never blocks on a full pipe
+    let stdout = child.stdout.take();
+    let stderr = drain(child.stderr.take());
+    thread::scope(|scope| {
+        let stdout = scope.spawn(move || match stdout {
+            Some(stdout) => read_stdout(&mut BufReader::new(stdout)),
+            None => read_stdout(&mut io::empty()),
+        });
+        let status = match options.git_timeout_secs {
+            Some(secs) => wait_timeout(&mut child, Duration::from_secs(secs))
+                .with_context(|| format!("Failed to wait on `{}`", description))?
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "`{}` did not complete within {} seconds and was killed. \
+                     Common causes are credential prompts and slow fsmonitor hooks.\nCommand: {:?}",
+                        description,
+                        secs,
+                        command
+                    )
+                })?,
+            None => child
+                .wait()
+                .with_context(|| format!("Failed to wait on `{}`", description))?,
+        };
+        let stdout = stdout.join().expect("Reader thread panicked")?;
+        let stderr = stderr.join().expect("Reader thread panicked")?;
+
+        // Check the result of the invocation
+        if !status.success() {
+            return Err(anyhow::anyhow!(
+                "`{}` failed (using {:?}). Exit code: {}.\nSTDERR: {}",
+                description,
+                program,
+                status.code().unwrap_or(-1),
+                String::from_utf8_lossy(stderr.as_slice())
+            ));
+        }
+
+        Ok(stdout)
+    })
+}
+
+/// Reads a pipe to the end on a separate thread.
+fn drain(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            pipe.read_to_end(&mut buf)?;
+        }
+        Ok(buf)
+    })
+}
+
+/// Waits for a child process for at most `timeout`, killing and reaping it when exceeded.
+/// Returns `None` on timeout.
+fn wait_timeout(child: &mut Child, timeout: Duration) -> io::Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    let mut interval = Duration::from_millis(1);
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            child.kill()?;
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(interval);
+        interval = (interval * 2).min(Duration::from_millis(50));
     }
+}
 
-    // Parse the git output
-    let log = String::from_utf8(output.stdout)
-        .context("Invalid UTF-8 output from git")?
-        .lines()
-        .map(|line| history_entry_from_iter(line.trim_matches('"').split('\t')))
-        .collect::<Result<Vec<_>>>()?;
+/// Parses the raw output of `git log` as it is read, one record per line, keeping at most `limit` entries.
+/// Records are separated by NUL characters instead when they hold whole commit messages
+/// (see [`trailers_from_message`]).
+///
+/// Each record is decoded on its own, so that a commit with invalid UTF-8 in it
+/// does not prevent the others from being parsed. Once the limit is reached, the rest of the
+/// output is read without being parsed, so that git can complete normally.
+fn read_log(
+    stdout: &mut dyn BufRead,
+    options: &GitInfoOptions,
+    limit: Option<usize>,
+) -> Result<Vec<GitHistoryEntry>> {
+    let separator = if trailers_from_message(options) {
+        b'\0'
+    } else {
+        b'\n'
+    };
+    let mut log = Vec::new();
+    let mut record = Vec::new();
+    while limit.is_none_or(|limit| log.len() < limit) {
+        record.clear();
+        if stdout.read_until(separator, &mut record)? == 0 {
+            return Ok(log);
+        }
+        if record.last() == Some(&separator) {
+            record.pop();
+        }
+        if let Some(entry) = parse_record(&record, options)? {
+            log.push(entry);
+        }
+    }
 
+    io::copy(stdout, &mut io::sink())?;
     Ok(log)
 }
 
-fn history_entry_from_iter<'a, T: IntoIterator<Item = &'a str>>(
-    iter: T,
-) -> Result<GitHistoryEntry> {
-    let mut it = iter.into_iter();
-    let author = it
-        .next()
-        .context("Unexpected git output format")?
-        .to_string();
-    let timestamp = it.next().context("Unexpected git output format")?;
+/// Decodes a chunk of the git output according to the UTF-8 strictness setting.
+fn decode<'a>(bytes: &'a [u8], options: &GitInfoOptions) -> Result<Cow<'a, str>> {
+    if options.strict_utf8 {
+        Ok(Cow::Borrowed(
+            std::str::from_utf8(bytes).context("Invalid UTF-8 output from git")?,
+        ))
+    } else {
+        Ok(String::from_utf8_lossy(bytes))
+    }
+}
+
+/// Decodes and parses a single record, returning `None` for blank records and,
+/// if so configured, for malformed ones.
+fn parse_record(record: &[u8], options: &GitInfoOptions) -> Result<Option<GitHistoryEntry>> {
+    let line = decode(record, options)?;
+
+    // Blank lines carry no information
+    if line.trim().is_empty() {
+        return Ok(None);
+    }
+
+    match parse_line(&line) {
+        Ok(mut entry) if trailers_from_message(options) => {
+            // The last field holds the whole message rather than the trailers
+            let message = line.rsplit(FIELD_SEPARATOR).next().unwrap_or_default();
+            entry.trailers = message_trailers(message);
+            Ok(Some(entry))
+        }
+        Ok(entry) => Ok(Some(entry)),
+        Err(e) => match options.on_malformed_line {
+            MalformedLineAction::Skip => {
+                warn!("Skipping malformed git output: {}", e);
+                Ok(None)
+            }
+            MalformedLineAction::Fail => Err(e.into()),
+        },
+    }
+}
+
+/// Parses a single record of the `git log` output.
+pub fn parse_line(line: &str) -> Result<GitHistoryEntry, ParseError> {
+    let error = |kind| ParseError {
+        line: line.to_string(),
+        kind,
+    };
+
+    let mut fields = [""; FIELDS.len()];
+    let mut it = line.split(FIELD_SEPARATOR);
+    for (i, field) in fields.iter_mut().enumerate() {
+        *field = it
+            .next()
+            .ok_or_else(|| error(ParseErrorKind::MissingField(FIELDS[i].0)))?;
+    }
+    if it.next().is_some() {
+        return Err(error(ParseErrorKind::ExtraFields));
+    }
+
+    // Destructuring makes sure that this stays in sync with `FIELDS`
+    let [hash, short_hash, author, email, committer, committer_email, timestamp, commit_timestamp, subject, trailers] =
+        fields;
+    for (name, value) in [
+        ("hash", hash),
+        ("short_hash", short_hash),
+        ("author", author),
+    ] {
+        if value.is_empty() {
+            return Err(error(ParseErrorKind::MissingField(name)));
+        }
+    }
+    let parse_date = |name, value: &str| {
+        value
+            .parse::<i64>()
+            .ok()
+            .and_then(|seconds| Utc.timestamp_opt(seconds, 0).single())
+            .ok_or_else(|| error(ParseErrorKind::InvalidDate(name, value.to_string())))
+    };
 
     Ok(GitHistoryEntry {
-        author,
-        timestamp: DateTime::parse_from_rfc3339(timestamp)
-            .unwrap()
-            .with_timezone(&Utc),
+        hash: hash.to_string(),
+        short_hash: short_hash.to_string(),
+        author: author.to_string(),
+        email: email.to_string(),
+        committer: committer.to_string(),
+        committer_email: committer_email.to_string(),
+        timestamp: parse_date("timestamp", timestamp)?,
+        commit_timestamp: parse_date("commit_timestamp", commit_timestamp)?,
+        subject: subject.to_string(),
+        trailers: trailers
+            .split(TRAILER_SEPARATOR)
+            .filter_map(parse_trailer)
+            .collect(),
+        lines_added: 0,
+        lines_removed: 0,
     })
 }
+
+/// Returns the type of a commit following the conventional commits specification, from the prefix
+/// of its subject: `feat` for `feat: ...`, `feat(parser): ...` or `feat!: ...`.
+pub fn commit_type(subject: &str) -> Option<&str> {
+    let (prefix, _) = subject.split_once(": ")?;
+    let prefix = prefix.strip_suffix('!').unwrap_or(prefix);
+    let kind = match prefix.split_once('(') {
+        Some((kind, scope)) => {
+            let scope = scope.strip_suffix(')')?;
+            if scope.is_empty() || scope.contains(['(', ')']) {
+                return None;
+            }
+            kind
+        }
+        None => prefix,
+    };
+    Some(kind).filter(|kind| !kind.is_empty() && kind.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+/// Returns the trailers of a full commit message: the `Key: value` lines of its last
+/// paragraph, unless it is also the first one.
+///
+/// Like git, the paragraph only holds trailers if all of its lines are trailers, except for
+/// the indented continuation lines, which are unfolded.
+pub fn message_trailers(message: &str) -> Vec<(String, String)> {
+    let message = message.trim_end();
+    let paragraph = match message.rfind("\n\n") {
+        Some(start) => &message[start + 2..],
+        None => return Vec::new(),
+    };
+    let mut trailers = Vec::<(String, String)>::new();
+    for line in paragraph.lines() {
+        match (trailers.last_mut(), parse_trailer(line)) {
+            (Some((_, value)), _) if line.starts_with(char::is_whitespace) => {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+            (_, Some(trailer)) => trailers.push(trailer),
+            (_, None) => return Vec::new(),
+        }
+    }
+    trailers
+}
+
+/// Parses a `Key: value` trailer line.
+fn parse_trailer(line: &str) -> Option<(String, String)> {
+    let (key, value) = line.split_once(':')?;
+    let key = key.trim();
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((key.to_string(), value.trim().to_string()))
+}
+
+/// Error returned when a line of the `git log` output cannot be parsed.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ParseError {
+    /// The offending line.
+    pub line: String,
+    /// What is wrong with the line.
+    pub kind: ParseErrorKind,
+}
+
+/// The reason why a line of the `git log` output could not be parsed.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ParseErrorKind {
+    /// A field is absent or empty.
+    MissingField(&'static str),
+    /// A date field, holding the given value, is not a valid number of seconds since the epoch.
+    InvalidDate(&'static str, String),
+    /// The line has more fields than expected.
+    ExtraFields,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Malformed git log line {:?}: ", self.line)?;
+        match &self.kind {
+            ParseErrorKind::MissingField(field) => write!(f, "missing field `{}`", field),
+            ParseErrorKind::InvalidDate(field, value) => write!(
+                f,
+                "invalid field `{}` ({:?} is not a timestamp)",
+                field, value
+            ),
+            ParseErrorKind::ExtraFields => write!(f, "unexpected extra fields"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::IgnoredCommitEffect;
+    use crate::test_support::{FixtureRepo, TempDir};
+
+    /// A record of the `git log` output, with the fields of [`FIELDS`] in order.
+    fn record(fields: [&[u8]; FIELDS.len()]) -> Vec<u8> {
+        fields.join(&(FIELD_SEPARATOR as u8))
+    }
+
+    /// A valid record of a commit by `author`.
+    fn commit(hash: &str, author: &[u8]) -> Vec<u8> {
+        record([
+            hash.as_bytes(),
+            &hash.as_bytes()[..7],
+            author,
+            b"author@example.com",
+            author,
+            b"author@example.com",
+            b"1704110400",
+            b"1704110400",
+            b"Subject",
+            b"",
+        ])
+    }
+
+    fn log(records: &[Vec<u8>]) -> Vec<u8> {
+        records.join(&b'\n')
+    }
+
+    #[test]
+    fn invalid_utf8_is_replaced_without_affecting_other_records() {
+        let output = log(&[
+            commit("1111111111", b"Alice"),
+            commit("2222222222", b"Ren\xe9 Latin-1"),
+            commit("3333333333", b"Bob"),
+        ]);
+
+        let history = read_log(&mut &output[..], &GitInfoOptions::default(), None).unwrap();
+
+        let authors = history
+            .iter()
+            .map(|e| e.author.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(authors, ["Alice", "Ren\u{fffd} Latin-1", "Bob"]);
+        assert_eq!(history[1].hash, "2222222222");
+        assert_eq!(history[1].committer, "Ren\u{fffd} Latin-1");
+    }
+
+    #[test]
+    fn invalid_utf8_fails_with_strict_utf8() {
+        let options = GitInfoOptions::builder().strict_utf8(true).build();
+        let output = log(&[
+            commit("1111111111", b"Alice"),
+            commit("2222222222", b"Ren\xe9"),
+        ]);
+
+        let error = read_log(&mut &output[..], &options, None).unwrap_err();
+
+        assert!(format!("{:#}", error).contains("Invalid UTF-8"));
+    }
+
+    #[test]
+    fn valid_utf8_is_accepted_with_strict_utf8() {
+        let options = GitInfoOptions::builder().strict_utf8(true).build();
+        let output = log(&[commit("1111111111", "Zoë".as_bytes())]);
+
+        let history = read_log(&mut &output[..], &options, None).unwrap();
+
+        assert_eq!(history[0].author, "Zoë");
+    }
+
+    #[test]
+    fn invalid_utf8_in_paths_is_replaced() {
+        let mut output = b"\x1e".to_vec();
+        output.extend(commit("1111111111", b"Alice"));
+        output.extend(b"\x1e\nM\0caf\xe9.md\0");
+
+        let log = parse_name_status_log(&output, &GitInfoOptions::default()).unwrap();
+
+        assert_eq!(log[0].1[0].path, Path::new("caf\u{fffd}.md"));
+        let strict = GitInfoOptions::builder().strict_utf8(true).build();
+        assert!(parse_name_status_log(&output, &strict).is_err());
+    }
+
+    /// Fields of a valid line, in the order of [`FIELDS`].
+    const LINE: [&str; FIELDS.len()] = [
+        "0123456789abcdef",
+        "0123456",
+        "Alice",
+        "alice@example.com",
+        "Bob",
+        "bob@example.com",
+        "1704110400",
+        "1704114000",
+        "Add the intro",
+        "",
+    ];
+
+    fn line(fields: &[&str]) -> String {
+        fields.join(&FIELD_SEPARATOR.to_string())
+    }
+
+    fn with_field(name: &str, value: &'static str) -> String {
+        let mut fields = LINE;
+        fields[FIELDS.iter().position(|(n, _)| *n == name).unwrap()] = value;
+        line(&fields)
+    }
+
+    #[test]
+    fn parse_line_reads_every_field() {
+        let entry = parse_line(&line(&LINE)).unwrap();
+        assert_eq!(entry.hash, "0123456789abcdef");
+        assert_eq!(entry.short_hash, "0123456");
+        assert_eq!(entry.author, "Alice");
+        assert_eq!(entry.email, "alice@example.com");
+        assert_eq!(entry.committer, "Bob");
+        assert_eq!(entry.committer_email, "bob@example.com");
+        assert_eq!(entry.timestamp.to_rfc3339(), "2024-01-01T12:00:00+00:00");
+        assert_eq!(
+            entry.commit_timestamp.to_rfc3339(),
+            "2024-01-01T13:00:00+00:00"
+        );
+        assert_eq!(entry.subject, "Add the intro");
+        assert!(entry.trailers.is_empty());
+    }
+
+    #[test]
+    fn parse_line_rejects_a_missing_timestamp() {
+        let error = parse_line(&line(&LINE[..6])).unwrap_err();
+        assert!(matches!(
+            error.kind,
+            ParseErrorKind::MissingField("timestamp")
+        ));
+
+        let error = parse_line(&with_field("timestamp", "")).unwrap_err();
+        assert!(
+            matches!(&error.kind, ParseErrorKind::InvalidDate("timestamp", value) if value.is_empty())
+        );
+    }
+
+    #[test]
+    fn parse_line_rejects_a_garbage_timestamp() {
+        let error = parse_line(&with_field("commit_timestamp", "yesterday")).unwrap_err();
+        assert!(matches!(
+            &error.kind,
+            ParseErrorKind::InvalidDate("commit_timestamp", value) if value == "yesterday"
+        ));
+        assert!(error
+            .to_string()
+            .contains("\"yesterday\" is not a timestamp"));
+
+        // Out of the range of dates
+        let error = parse_line(&with_field("timestamp", "99999999999999999")).unwrap_err();
+        assert!(matches!(
+            error.kind,
+            ParseErrorKind::InvalidDate("timestamp", _)
+        ));
+    }
+
+    #[test]
+    fn parse_line_rejects_extra_fields() {
+        let error = parse_line(&format!("{}{}extra", line(&LINE), FIELD_SEPARATOR)).unwrap_err();
+        assert!(matches!(error.kind, ParseErrorKind::ExtraFields));
+        assert!(error.to_string().contains("extra"));
+    }
+
+    #[test]
+    fn parse_line_rejects_an_empty_author() {
+        let error = parse_line(&with_field("author", "")).unwrap_err();
+        assert!(matches!(error.kind, ParseErrorKind::MissingField("author")));
+        assert!(error.to_string().contains("missing field `author`"));
+    }
+
+    #[test]
+    fn malformed_lines_fail_or_are_skipped_as_configured() {
+        let output = [line(&LINE), with_field("author", ""), line(&LINE)].join("\n");
+
+        let error = read_log(&mut output.as_bytes(), &GitInfoOptions::default(), None);
+        assert!(error.is_err());
+
+        let options = GitInfoOptions::builder()
+            .on_malformed_line(MalformedLineAction::Skip)
+            .build();
+        let history = read_log(&mut output.as_bytes(), &options, None).unwrap();
+        assert_eq!(history.len(), 2);
+    }
+
+    #[test]
+    fn long_logs_are_read_as_a_stream() {
+        // Thousands of records, with a malformed one every hundred
+        let mut records = Vec::new();
+        for i in 0..5000 {
+            if i % 100 == 50 {
+                records.push(b"not a record".to_vec());
+            }
+            records.push(commit(&format!("{:010}", i), b"Alice"));
+        }
+        let output = log(&records);
+
+        let options = GitInfoOptions::builder()
+            .on_malformed_line(MalformedLineAction::Skip)
+            .build();
+        let history = read_log(&mut io::Cursor::new(&output), &options, None).unwrap();
+        assert_eq!(history.len(), 5000);
+        assert_eq!(history[0].hash, "0000000000");
+        assert_eq!(history[4999].hash, "0000004999");
+
+        // The limit stops the parsing, but the rest is still read
+        let mut cursor = io::Cursor::new(&output);
+        let history = read_log(&mut cursor, &options, Some(1000)).unwrap();
+        assert_eq!(history.len(), 1000);
+        assert_eq!(history[999].hash, "0000000999");
+        assert_eq!(cursor.position(), output.len() as u64);
+
+        // The first malformed record is fatal
+        let options = GitInfoOptions::builder()
+            .on_malformed_line(MalformedLineAction::Fail)
+            .build();
+        let error = read_log(&mut io::Cursor::new(&output), &options, None).unwrap_err();
+        assert!(
+            format!("{:#}", error).contains("not a record"),
+            "{:#}",
+            error
+        );
+
+        // Unless it comes after the limit
+        let history = read_log(&mut io::Cursor::new(&output), &options, Some(50)).unwrap();
+        assert_eq!(history.len(), 50);
+    }
+
+    #[test]
+    fn blank_lines_are_ignored() {
+        let output = format!("\n{}\n\n  \n{}\n", line(&LINE), line(&LINE));
+
+        let history = read_log(&mut output.as_bytes(), &GitInfoOptions::default(), None).unwrap();
+
+        assert_eq!(history.len(), 2);
+    }
+
+    #[test]
+    fn extract_reads_back_every_field_of_a_commit() -> Result<()> {
+        let mut repo = FixtureRepo::new()?;
+        repo.file("src/intro.md", "# Intro\n")?;
+        let hash = repo.commit(
+            "Add the intro\n\nWith some details.\n\nReviewed-by: Dave <dave@example.com>\nRefs: #12",
+            "Zoë",
+            "zoe@example.com",
+            "2024-01-01T12:00:00Z".parse()?,
+        )?;
+
+        let history = extract(repo.path().join("src/intro.md"), &GitInfoOptions::default())?;
+
+        assert_eq!(history.len(), 1);
+        let entry = &history[0];
+        assert_eq!(entry.hash, hash);
+        assert_eq!(
+            entry.short_hash,
+            repo.git(&["rev-parse", "--short", &hash])?
+        );
+        assert_eq!(entry.author, "Zoë");
+        assert_eq!(entry.email, "zoe@example.com");
+        assert_eq!(entry.committer, "Zoë");
+        assert_eq!(entry.committer_email, "zoe@example.com");
+        assert_eq!(entry.timestamp.to_rfc3339(), "2024-01-01T12:00:00+00:00");
+        assert_eq!(entry.commit_timestamp, entry.timestamp);
+        assert_eq!(entry.subject, "Add the intro");
+        assert_eq!(
+            entry.trailers,
+            [
+                (
+                    "Reviewed-by".to_string(),
+                    "Dave <dave@example.com>".to_string()
+                ),
+                ("Refs".to_string(), "#12".to_string()),
+            ]
+        );
+        assert_eq!((entry.lines_added, entry.lines_removed), (0, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn extract_all_matches_extract_with_renames_and_merges() -> Result<()> {
+        let date = |text: &str| text.parse::<DateTime<Utc>>();
+        let mut repo = FixtureRepo::new()?;
+        repo.file("src/intro.md", "# Intro\n\nFirst.\n")?
+            .file("src/guide.md", "# Guide\n\nFirst.\n")?;
+        repo.commit(
+            "Add the chapters",
+            "Alice",
+            "alice@example.com",
+            date("2024-01-01T12:00:00Z")?,
+        )?;
+        repo.branch("side")?;
+        repo.file("src/intro.md", "# Intro\n\nFirst.\nSecond.\n")?;
+        repo.commit(
+            "Expand the intro",
+            "Bob",
+            "bob@example.com",
+            date("2024-02-01T12:00:00Z")?,
+        )?;
+        repo.checkout("main")?;
+        repo.remove("src/guide.md")?
+            .file("src/tutorial.md", "# Guide\n\nFirst.\n")?;
+        repo.commit(
+            "Rename the guide",
+            "Carol",
+            "carol@example.com",
+            date("2024-03-01T12:00:00Z")?,
+        )?;
+        repo.merge(
+            "side",
+            "Merge the side branch",
+            "Carol",
+            "carol@example.com",
+            date("2024-04-01T12:00:00Z")?,
+        )?;
+        repo.file("src/tutorial.md", "# Tutorial\n\nFirst.\n")?;
+        repo.commit(
+            "Retitle the tutorial",
+            "Dave",
+            "dave@example.com",
+            date("2024-05-01T12:00:00Z")?,
+        )?;
+        let src = repo.path().join("src");
+
+        for follow_renames in [true, false] {
+            let options = GitInfoOptions::builder()
+                .follow_renames(follow_renames)
+                .build();
+            let all = extract_all(&src, &options)?;
+
+            // Deleted files have histories too, but are never chapters
+            for path in ["intro.md", "tutorial.md"] {
+                let history = &all[Path::new(path)];
+                let single = extract(src.join(path), &options)?;
+                assert_eq!(
+                    history
+                        .iter()
+                        .map(|entry| (&entry.hash, &entry.author))
+                        .collect::<Vec<_>>(),
+                    single
+                        .iter()
+                        .map(|entry| (&entry.hash, &entry.author))
+                        .collect::<Vec<_>>(),
+                    "{} with follow_renames = {}",
+                    path,
+                    follow_renames
+                );
+            }
+            // The edit on the side branch reaches the intro through the merge
+            assert_eq!(all[Path::new("intro.md")].len(), 2);
+            assert_eq!(
+                all[Path::new("tutorial.md")].len(),
+                if follow_renames { 3 } else { 2 }
+            );
+        }
+        Ok(())
+    }
+
+    /// A commit in the output of `git log -z --numstat` or `--name-status`, followed by `files`.
+    fn with_files(header: Vec<u8>, files: &[u8]) -> Vec<u8> {
+        [&b"\x1e"[..], &header, b"\x1e", files].concat()
+    }
+
+    fn changes(
+        log: &[(GitHistoryEntry, Vec<Change>)],
+    ) -> Vec<(&str, &str, Option<&str>, u64, u64)> {
+        log.iter()
+            .flat_map(|(entry, changes)| {
+                changes.iter().map(move |change| {
+                    (
+                        entry.hash.as_str(),
+                        change.path.to_str().unwrap(),
+                        change
+                            .old_path
+                            .as_deref()
+                            .map(|path| path.to_str().unwrap()),
+                        change.added,
+                        change.removed,
+                    )
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn numstat_counts_the_lines_of_each_file() {
+        let output = [
+            with_files(
+                commit("2222222222", b"Bob"),
+                b"\n3\t1\tsrc/intro.md\x0012\t0\tsrc/guide.md\x00",
+            ),
+            with_files(commit("1111111111", b"Alice"), b"\n1\t0\tsrc/intro.md\x00"),
+        ]
+        .concat();
+
+        let log = parse_numstat_log(&output, &GitInfoOptions::default()).unwrap();
+
+        assert_eq!(
+            changes(&log),
+            [
+                ("2222222222", "src/intro.md", None, 3, 1),
+                ("2222222222", "src/guide.md", None, 12, 0),
+                ("1111111111", "src/intro.md", None, 1, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn numstat_counts_no_lines_for_binary_files() {
+        let output = with_files(
+            commit("1111111111", b"Alice"),
+            b"\n-\t-\tsrc/cover.png\x002\t0\tsrc/intro.md\x00",
+        );
+
+        let log = parse_numstat_log(&output, &GitInfoOptions::default()).unwrap();
+
+        assert_eq!(
+            changes(&log),
+            [
+                ("1111111111", "src/cover.png", None, 0, 0),
+                ("1111111111", "src/intro.md", None, 2, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn numstat_reads_renames_as_separate_paths() {
+        let output = [
+            with_files(
+                commit("2222222222", b"Bob"),
+                b"\n1\t1\t\x00src/guide.md\x00src/tutorial.md\x00-\t-\t\x00old.png\x00new.png\x00",
+            ),
+            // Commits touching no file, like merges, have no stats at all
+            with_files(commit("1111111111", b"Alice"), b""),
+        ]
+        .concat();
+
+        let log = parse_numstat_log(&output, &GitInfoOptions::default()).unwrap();
+
+        assert_eq!(log.len(), 2);
+        assert_eq!(
+            changes(&log),
+            [
+                ("2222222222", "src/tutorial.md", Some("src/guide.md"), 1, 1),
+                ("2222222222", "new.png", Some("old.png"), 0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn numstat_rejects_malformed_counts() {
+        let options = GitInfoOptions::default();
+        let invalid = with_files(
+            commit("1111111111", b"Alice"),
+            b"\nmany\t0\tsrc/intro.md\x00",
+        );
+        let error = parse_numstat_log(&invalid, &options).unwrap_err();
+        assert!(error.to_string().contains("Invalid line count \"many\""));
+
+        let truncated = with_files(
+            commit("1111111111", b"Alice"),
+            b"\n1\t0\t\x00src/guide.md\x00",
+        );
+        assert!(parse_numstat_log(&truncated, &options).is_err());
+    }
+
+    #[test]
+    fn numstat_matches_real_git_output() -> Result<()> {
+        let mut repo = FixtureRepo::new()?;
+        repo.file("src/guide.md", "# Guide\n\nFirst.\nSecond.\n")?
+            .file("src/cover.png", b"\x89PNG\r\n\x1a\n\x00\x00")?;
+        repo.commit(
+            "Add the guide",
+            "Alice",
+            "alice@example.com",
+            "2024-01-01T12:00:00Z".parse()?,
+        )?;
+        repo.remove("src/guide.md")?
+            .file("src/tutorial.md", "# Guide\n\nFirst.\nSecond.\nThird.\n")?;
+        repo.commit(
+            "Rename the guide",
+            "Bob",
+            "bob@example.com",
+            "2024-02-01T12:00:00Z".parse()?,
+        )?;
+
+        let options = GitInfoOptions::default();
+        let mut command = git_command(&options);
+        command
+            .current_dir(repo.path())
+            .args(["log", "-z", "--numstat", "--find-renames"])
+            .arg(format!(
+                "--pretty=format:%x1e{}%x1e",
+                pretty_format(&options)
+            ));
+        let log = parse_numstat_log(&run(command, "git log", &options)?, &options)?;
+
+        let changes = changes(&log)
+            .into_iter()
+            .map(|(_, path, old_path, added, removed)| (path, old_path, added, removed))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            [
+                ("src/tutorial.md", Some("src/guide.md"), 1, 0),
+                ("src/cover.png", None, 0, 0),
+                ("src/guide.md", None, 4, 0),
+            ]
+        );
+        Ok(())
+    }
+
+    /// The output of `git blame --line-porcelain` for one line of a file.
+    fn blame_line(hash: char, line: usize, author: &str, committer: &str, content: &str) -> String {
+        format!(
+            "{hash} {line} {line}\nauthor {author}\nauthor-mail <{email}>\nauthor-time 1704110400\n\
+             author-tz +0000\ncommitter {committer}\ncommitter-mail <{email}>\n\
+             committer-time 1704110400\ncommitter-tz +0000\nsummary Subject\nfilename intro.md\n\t{content}\n",
+            hash = hash.to_string().repeat(40),
+            line = line,
+            author = author,
+            committer = committer,
+            email = "someone@example.com",
+            content = content,
+        )
+    }
+
+    #[test]
+    fn blame_counts_the_lines_of_repeated_commits() {
+        let output = [
+            blame_line('a', 1, "Bob", "Bob", "# Intro"),
+            blame_line('b', 2, "Alice", "Alice", ""),
+            blame_line('a', 3, "Bob", "Bob", "First."),
+            blame_line('a', 4, "Bob", "Bob", "Second."),
+        ]
+        .concat();
+
+        assert_eq!(
+            parse_blame(&output, Identity::Author),
+            [("Alice".to_string(), 1), ("Bob".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn blame_skips_uncommitted_lines() {
+        let output = [
+            blame_line('a', 1, "Alice", "Alice", "# Intro"),
+            blame_line('0', 2, "Not Committed Yet", "Not Committed Yet", "Draft."),
+            blame_line('a', 3, "Alice", "Alice", "First."),
+            blame_line('0', 4, "Not Committed Yet", "Not Committed Yet", ""),
+        ]
+        .concat();
+
+        assert_eq!(
+            parse_blame(&output, Identity::Author),
+            [("Alice".to_string(), 2)]
+        );
+        assert!(parse_blame(
+            &blame_line('0', 1, "Not Committed Yet", "Not Committed Yet", ""),
+            Identity::Author
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn blame_ignores_lines_looking_like_headers() {
+        let output = [
+            blame_line('a', 1, "Alice", "Carol", "author Mallory"),
+            blame_line('b', 2, "Bob", "Carol", &"0".repeat(40)),
+            blame_line('b', 3, "Bob", "Carol", "Third."),
+        ]
+        .concat();
+
+        assert_eq!(
+            parse_blame(&output, Identity::Author),
+            [("Alice".to_string(), 1), ("Bob".to_string(), 2)]
+        );
+        assert_eq!(
+            parse_blame(&output, Identity::Committer),
+            [("Carol".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn every_signature_letter_is_recognized() {
+        let letters = [
+            ("G", SignatureStatus::Good),
+            ("U", SignatureStatus::UnknownValidity),
+            ("B", SignatureStatus::Bad),
+            ("X", SignatureStatus::Expired),
+            ("Y", SignatureStatus::Expired),
+            ("R", SignatureStatus::Revoked),
+            ("E", SignatureStatus::MissingKey),
+            ("N", SignatureStatus::Unsigned),
+        ];
+
+        for (letter, status) in letters {
+            assert_eq!(
+                SignatureStatus::from_letter(letter),
+                Some(status),
+                "{}",
+                letter
+            );
+        }
+        // As printed by git, with a trailing newline
+        assert_eq!(
+            SignatureStatus::from_letter("G\n"),
+            Some(SignatureStatus::Good)
+        );
+    }
+
+    #[test]
+    fn unknown_signature_letters_are_rejected() {
+        for letter in ["Z", "g", "", "GG"] {
+            assert_eq!(SignatureStatus::from_letter(letter), None, "{:?}", letter);
+        }
+    }
+
+    #[test]
+    fn unsigned_commits_have_no_signer() -> Result<()> {
+        let mut repo = FixtureRepo::new()?;
+        repo.file("intro.md", "# Intro\n")?;
+        let hash = repo.commit(
+            "Add the intro",
+            "Alice",
+            "alice@example.com",
+            "2024-01-01T12:00:00Z".parse()?,
+        )?;
+
+        let signature = signature(repo.path(), &hash, &GitInfoOptions::default())?;
+
+        assert_eq!(signature.status, SignatureStatus::Unsigned);
+        assert_eq!(signature.signer, None);
+        Ok(())
+    }
+
+    fn git_version(output: &str) -> Option<(u32, u32, u32)> {
+        GitVersion::parse(output).map(|version| (version.major, version.minor, version.patch))
+    }
+
+    #[test]
+    fn git_versions_are_parsed_with_vendor_suffixes() {
+        assert_eq!(git_version("git version 2.43.0\n"), Some((2, 43, 0)));
+        assert_eq!(
+            git_version("git version 2.39.3 (Apple Git-146)"),
+            Some((2, 39, 3))
+        );
+        assert_eq!(
+            git_version("git version 2.40.0.windows.1"),
+            Some((2, 40, 0))
+        );
+        assert_eq!(git_version("git version 2.45.0-rc1"), Some((2, 45, 0)));
+        assert_eq!(git_version("git version 2.45.0.rc2"), Some((2, 45, 0)));
+        assert_eq!(git_version("git version 1.8"), Some((1, 8, 0)));
+    }
+
+    #[test]
+    fn unexpected_version_outputs_are_rejected() {
+        assert_eq!(git_version(""), None);
+        assert_eq!(git_version("2.43.0"), None);
+        assert_eq!(git_version("hub version 2.14.2"), None);
+        assert_eq!(git_version("git version two"), None);
+    }
+
+    #[test]
+    fn git_versions_are_ordered_by_number() {
+        let version = |output| GitVersion::parse(output).unwrap();
+
+        assert!(version("git version 2.9.5") < version("git version 2.22.0"));
+        assert!(version("git version 2.22.0-rc0") >= MIN_GIT_VERSION);
+        assert!(version("git version 2.21.4 (Apple Git-122)") < MIN_GIT_VERSION);
+        assert!(version("git version 1.7.1") < OLDEST_GIT_VERSION);
+    }
+
+    /// Options of a git binary too old to list the trailers of the commits.
+    fn old_git() -> GitInfoOptions {
+        GitInfoOptions {
+            git_version: GitVersion::parse("git version 2.17.1"),
+            ..GitInfoOptions::default()
+        }
+    }
+
+    #[test]
+    fn old_git_versions_are_asked_for_whole_messages() {
+        assert!(pretty_format(&GitInfoOptions::default())
+            .ends_with("%(trailers:only,unfold,separator=%x1d)"));
+        assert!(pretty_format(&old_git()).ends_with("%x1f%B"));
+
+        let options = GitInfoOptions {
+            git_version: Some(MIN_GIT_VERSION),
+            ..GitInfoOptions::default()
+        };
+        assert_eq!(
+            pretty_format(&options),
+            pretty_format(&GitInfoOptions::default())
+        );
+    }
+
+    #[test]
+    fn old_git_trailers_are_parsed_from_the_messages() {
+        let message = |subject: &str, body: &str| {
+            let mut fields = LINE;
+            fields[8] = "Subject";
+            line(&fields[..9]) + &format!("{}{}\n\n{}\n", FIELD_SEPARATOR, subject, body)
+        };
+        let output = [
+            message(
+                "Expand the intro",
+                "Key: value in the body.\n\nReviewed-by: Dave <dave@example.com>\nRefs: #12",
+            ),
+            message("Fix a typo", "Just a typo: nothing to review."),
+        ]
+        .join("\0");
+
+        let history = read_log(&mut output.as_bytes(), &old_git(), None).unwrap();
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(
+            history[0].trailers,
+            [
+                (
+                    "Reviewed-by".to_string(),
+                    "Dave <dave@example.com>".to_string()
+                ),
+                ("Refs".to_string(), "#12".to_string()),
+            ]
+        );
+        assert!(history[1].trailers.is_empty());
+    }
+
+    #[test]
+    fn old_git_formats_give_the_same_histories() -> Result<()> {
+        let mut repo = FixtureRepo::new()?;
+        repo.file("src/intro.md", "# Intro\n")?;
+        repo.commit(
+            "Add the intro\n\nSee: the guide\nfor details.",
+            "Alice",
+            "alice@example.com",
+            "2024-01-01T12:00:00Z".parse()?,
+        )?;
+        repo.file("src/intro.md", "# Intro\n\nMore.\n")?;
+        repo.commit(
+            "Expand the intro\n\nWith some details.\n\nReviewed-by: Dave <dave@example.com>",
+            "Bob",
+            "bob@example.com",
+            "2024-02-01T12:00:00Z".parse()?,
+        )?;
+        let src = repo.path().join("src");
+        let intro = src.join("intro.md");
+
+        for churn in [false, true] {
+            let new = GitInfoOptions {
+                churn,
+                ..GitInfoOptions::default()
+            };
+            let old = GitInfoOptions { churn, ..old_git() };
+
+            // Entries are compared through their serialized form, which has every field
+            let history = extract(&intro, &old)?;
+            assert_eq!(
+                serde_json::to_value(&history)?,
+                serde_json::to_value(extract(&intro, &new)?)?,
+                "churn = {}",
+                churn
+            );
+            assert_eq!(history[0].trailers.len(), 1);
+            assert!(history[1].trailers.is_empty());
+            assert_eq!(
+                serde_json::to_value(&extract_all(&src, &old)?[Path::new("intro.md")])?,
+                serde_json::to_value(&history)?,
+                "churn = {}",
+                churn
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn message_trailers_follow_the_rules_of_git() {
+        assert_eq!(
+            message_trailers("Subject\n\nBody.\n\nReviewed-by: Dave\nCo-authored-by: Erin\n  <erin@example.com>\n"),
+            [
+                ("Reviewed-by".to_string(), "Dave".to_string()),
+                (
+                    "Co-authored-by".to_string(),
+                    "Erin <erin@example.com>".to_string()
+                ),
+            ]
+        );
+        // A subject is never a trailer, nor a paragraph with other lines
+        assert!(message_trailers("Fix: the build").is_empty());
+        assert!(message_trailers("Subject\n\nNote: this\nis prose.").is_empty());
+    }
+
+    /// A repository where Alice creates the intro, which Bob then edits `edits` times.
+    fn long_history(edits: usize) -> Result<(FixtureRepo, String)> {
+        let mut repo = FixtureRepo::new()?;
+        repo.file("src/intro.md", "# Intro\n")?;
+        let created = repo.commit(
+            "Add the intro",
+            "Alice",
+            "alice@example.com",
+            "2024-01-01T12:00:00Z".parse()?,
+        )?;
+        for i in 1..=edits {
+            repo.file("src/intro.md", format!("# Intro\n\nEdit {}.\n", i))?;
+            repo.commit(
+                &format!("Edit {}", i),
+                "Bob",
+                "bob@example.com",
+                format!("2024-02-{:02}T12:00:00Z", i).parse()?,
+            )?;
+        }
+        Ok((repo, created))
+    }
+
+    #[test]
+    fn max_history_keeps_the_creation_exact() -> Result<()> {
+        let (repo, created) = long_history(19)?;
+        let path = repo.path().join("src/intro.md");
+
+        for (follow_renames, churn) in [(true, false), (false, false), (false, true)] {
+            let options = GitInfoOptions::builder()
+                .max_history(5)
+                .follow_renames(follow_renames)
+                .churn(churn)
+                .build();
+
+            let history = extract(&path, &options)?;
+            let subjects = history
+                .iter()
+                .map(|e| e.subject.as_str())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                subjects,
+                [
+                    "Edit 19",
+                    "Edit 18",
+                    "Edit 17",
+                    "Edit 16",
+                    "Edit 15",
+                    "Add the intro"
+                ]
+            );
+            let creation = &history[5];
+            assert_eq!(creation.hash, created);
+            assert_eq!(creation.author, "Alice");
+            assert_eq!(creation.timestamp.to_rfc3339(), "2024-01-01T12:00:00+00:00");
+            if churn {
+                assert_eq!(creation.lines_added, 1);
+            }
+
+            let endpoints = extract_endpoints(&path, &options)?;
+            assert_eq!(endpoints.len(), 2);
+            assert_eq!(endpoints[0].subject, "Edit 19");
+            assert_eq!(endpoints[1].hash, created);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn the_oldest_addition_is_the_creation() -> Result<()> {
+        let (mut repo, created) = long_history(1)?;
+        repo.remove("src/intro.md")?;
+        repo.commit(
+            "Remove the intro",
+            "Bob",
+            "bob@example.com",
+            "2024-03-01T12:00:00Z".parse()?,
+        )?;
+        repo.file("src/intro.md", "# Intro again\n")?;
+        repo.commit(
+            "Restore the intro",
+            "Carol",
+            "carol@example.com",
+            "2024-03-02T12:00:00Z".parse()?,
+        )?;
+        let path = repo.path().join("src/intro.md");
+
+        for follow_renames in [true, false] {
+            let options = GitInfoOptions::builder()
+                .follow_renames(follow_renames)
+                .build();
+            let endpoints = extract_endpoints(&path, &options)?;
+            assert_eq!(endpoints.len(), 2);
+            assert_eq!(endpoints[0].subject, "Restore the intro");
+            assert_eq!(endpoints[1].hash, created);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn commit_types_follow_conventional_commits() {
+        assert_eq!(commit_type("feat: add a parser"), Some("feat"));
+        assert_eq!(commit_type("fix(parser): handle tabs"), Some("fix"));
+        assert_eq!(commit_type("feat!: drop the old API"), Some("feat"));
+        assert_eq!(commit_type("refactor(core)!: rename"), Some("refactor"));
+        assert_eq!(commit_type("CHORE: tidy"), Some("CHORE"));
+        assert_eq!(commit_type("Docs(guide): typo"), Some("Docs"));
+
+        for subject in [
+            "Add a parser",
+            "fix:no space",
+            "feat(): empty scope",
+            "feat(a)(b): two scopes",
+            "feat(a: unclosed scope",
+            "(scope): no type",
+            "build-system: hyphen",
+            "Merge branch 'fix': conflicts",
+            "v2: digits",
+        ] {
+            assert_eq!(commit_type(subject), None, "{:?}", subject);
+        }
+    }
+
+    #[test]
+    fn ignored_types_are_matched_case_insensitively() {
+        let options = GitInfoOptions::builder()
+            .ignore_commit_types(["chore", " Style "])
+            .build();
+        let entry = |subject: &str| {
+            let mut entry = GitHistoryEntry::test("a", "Alice", "2024-01-01T12:00:00Z");
+            entry.subject = subject.to_string();
+            entry
+        };
+
+        for subject in ["chore: tidy", "CHORE(deps)!: bump", "style: format"] {
+            assert!(
+                options.is_ignored_commit(&entry(subject), IgnoredCommitEffect::LastEdit),
+                "{:?}",
+                subject
+            );
+        }
+        for subject in ["feat: add", "chores: tidy", "chore tidy"] {
+            assert!(
+                !options.is_ignored_commit(&entry(subject), IgnoredCommitEffect::LastEdit),
+                "{:?}",
+                subject
+            );
+        }
+        // Only for the configured effects
+        assert!(!options.is_ignored_commit(&entry("chore: tidy"), IgnoredCommitEffect::Totals));
+    }
+
+    /// Writes an executable shell script standing in for git.
+    #[cfg(unix)]
+    fn shim(dir: &TempDir, script: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.path().join("git");
+        fs::write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn extra_arguments_come_before_the_enforced_settings() -> Result<()> {
+        let dir = TempDir::new()?;
+        let args = dir.path().join("args");
+        let git = shim(
+            &dir,
+            &format!(
+                "printf '%s\\n' \"$@\" > '{}'\necho 'git version 2.43.0'\n",
+                args.display()
+            ),
+        );
+        let options = GitInfoOptions::builder()
+            .git_binary(&git)
+            .git_args(["-c", "color.ui=always", "-c", "safe.directory=*"])
+            .build();
+
+        assert_eq!(
+            version(&options)?,
+            GitVersion::parse("git version 2.43.0").unwrap()
+        );
+        let recorded = fs::read_to_string(&args)?;
+        assert_eq!(
+            recorded.lines().collect::<Vec<_>>(),
+            [
+                "--no-pager",
+                "-c",
+                "color.ui=always",
+                "-c",
+                "safe.directory=*",
+                "-c",
+                "i18n.logOutputEncoding=UTF-8",
+                "-c",
+                "log.showSignature=false",
+                "-c",
+                "color.ui=never",
+                "-c",
+                "core.quotePath=false",
+                "--version",
+            ]
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn slow_commands_are_killed_at_the_timeout() -> Result<()> {
+        let dir = TempDir::new()?;
+        // `exec`, so that killing the shim also closes its pipes
+        let git = shim(&dir, "exec sleep 30\n");
+        let options = GitInfoOptions::builder()
+            .git_binary(&git)
+            .git_timeout_secs(1)
+            .build();
+
+        let start = Instant::now();
+        let error = format!("{:#}", version(&options).unwrap_err());
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "{:?}",
+            start.elapsed()
+        );
+        assert!(
+            error.contains("`git --version` did not complete within 1 seconds and was killed"),
+            "{}",
+            error
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_commands_report_their_stderr() -> Result<()> {
+        let dir = TempDir::new()?;
+        let git = shim(&dir, "echo 'fatal: not a git repository' >&2\nexit 128\n");
+        let options = GitInfoOptions::builder().git_binary(&git).build();
+
+        let error = format!("{:#}", head(dir.path(), &options).unwrap_err());
+        assert!(error.contains("fatal: not a git repository"), "{}", error);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_binaries_cannot_be_launched() {
+        let options = GitInfoOptions::builder()
+            .git_binary("/nonexistent/git")
+            .build();
+        let error = format!("{:#}", version(&options).unwrap_err());
+        assert!(
+            error.contains("Failed to launch `git --version`"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn inherited_repository_variables_are_removed() {
+        let command = git_command(&GitInfoOptions::default());
+        let removed = command
+            .get_envs()
+            .filter(|(_, value)| value.is_none())
+            .map(|(name, _)| name.to_string_lossy().into_owned())
+            .collect::<HashSet<_>>();
+        for var in SANITIZED_ENV {
+            assert!(removed.contains(var), "{}", var);
+        }
+    }
+}
@@ -0,0 +1,624 @@
+use crate::git_history::GitHistoryEntry;
+use crate::metadata;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Base URL of the GitHub REST API.
+const API_URL: &str = "https://api.github.com";
+
+/// Environment variable holding the token the API is queried with.
+pub const TOKEN_VAR: &str = "GITHUB_TOKEN";
+
+/// Domain of the private commit emails of GitHub, like `123+login@users.noreply.github.com`.
+const NOREPLY_DOMAIN: &str = "@users.noreply.github.com";
+
+/// Version of the on-disk format of the cache, bumped on incompatible changes.
+const CACHE_VERSION: u32 = 1;
+
+/// Longest time a single request may take, in seconds.
+const REQUEST_TIMEOUT: u32 = 10;
+
+/// GitHub account of a contributor.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct GitHubProfile {
+    /// Login of the account.
+    pub login: String,
+    /// URL of the avatar of the account.
+    pub avatar_url: String,
+}
+
+impl GitHubProfile {
+    /// Checks what the API returned, since it ends up in links: logins are made of ASCII
+    /// letters, digits and hyphens, avatars are served over HTTPS.
+    fn new(login: &str, avatar_url: String) -> Option<GitHubProfile> {
+        let valid = !login.is_empty()
+            && login
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+            && avatar_url.starts_with("https://")
+            && !avatar_url.contains(char::is_whitespace);
+        valid.then(|| GitHubProfile {
+            login: login.to_string(),
+            avatar_url,
+        })
+    }
+
+    /// URL of the profile page of the account.
+    pub fn url(&self) -> String {
+        format!("https://github.com/{}", self.login)
+    }
+
+    /// URL of the avatar, scaled to `size` pixels.
+    pub fn avatar(&self, size: u32) -> String {
+        let separator = if self.avatar_url.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        format!("{}{}s={}", self.avatar_url, separator, size)
+    }
+
+    /// Profile of the account owning a private commit email, without asking the API.
+    fn from_noreply(email: &str) -> Option<GitHubProfile> {
+        let local = email
+            .len()
+            .checked_sub(NOREPLY_DOMAIN.len())
+            .filter(|i| email.is_char_boundary(*i))
+            .filter(|i| email[*i..].eq_ignore_ascii_case(NOREPLY_DOMAIN))
+            .map(|i| &email[..i])?;
+        // Older addresses have no id
+        let (avatar_url, login) = match local.split_once('+') {
+            Some((id, login)) if id.bytes().all(|b| b.is_ascii_digit()) => (
+                format!("https://avatars.githubusercontent.com/u/{}?v=4", id),
+                login,
+            ),
+            _ => (format!("https://github.com/{}.png", local), local),
+        };
+        GitHubProfile::new(login, avatar_url)
+    }
+}
+
+/// Response to an HTTP request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    /// Status code.
+    pub status: u16,
+    /// Headers, with lowercase names.
+    pub headers: Vec<(String, String)>,
+    /// Body, decoded as UTF-8.
+    pub body: String,
+}
+
+impl HttpResponse {
+    /// Value of a header, whose name is compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Performs the requests to the GitHub API, so that they can be replaced in tests.
+pub trait HttpClient: Debug + Send + Sync {
+    /// Sends a `GET` request with the given headers. Only failures to get a response are errors.
+    fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<HttpResponse>;
+}
+
+/// Client which runs the `curl` command line tool.
+#[derive(Debug, Default)]
+pub struct CurlClient;
+
+impl HttpClient for CurlClient {
+    fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<HttpResponse> {
+        // Headers are read from stdin, so that the token never shows up in the process list
+        let mut child = Command::new("curl")
+            .args(["--silent", "--show-error", "--location", "--include"])
+            .arg("--max-time")
+            .arg(REQUEST_TIMEOUT.to_string())
+            .args(["--header", "@-"])
+            .arg(url)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Cannot run curl")?;
+        if let Some(mut stdin) = child.stdin.take() {
+            for (name, value) in headers {
+                writeln!(stdin, "{}: {}", name, value).context("Cannot write to curl")?;
+            }
+        }
+        let output = child.wait_with_output().context("Cannot run curl")?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "curl failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        parse_response(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+/// Parses the output of `curl --include`, where the headers of every response of a chain
+/// of redirects precede the body of the last one.
+fn parse_response(output: &str) -> Result<HttpResponse> {
+    let mut rest = output;
+    loop {
+        let (head, body) = rest
+            .split_once("\r\n\r\n")
+            .or_else(|| rest.split_once("\n\n"))
+            .unwrap_or((rest, ""));
+        let mut lines = head.lines();
+        let status = lines
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|status| status.parse::<u16>().ok())
+            .with_context(|| format!("Invalid HTTP response: {:?}", head))?;
+        if body.starts_with("HTTP/") {
+            rest = body;
+            continue;
+        }
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+            .collect();
+        return Ok(HttpResponse {
+            status,
+            headers,
+            body: body.to_string(),
+        });
+    }
+}
+
+/// Extracts `owner/repo` from the URL of a remote hosted on GitHub, in any of the forms
+/// accepted by git (`https://github.com/owner/repo.git`, `git@github.com:owner/repo`, ...).
+pub fn repository_from_url(url: &str) -> Option<String> {
+    let url = url.trim();
+    let path = ["github.com/", "github.com:"]
+        .iter()
+        .find_map(|host| url.find(host).map(|i| &url[i + host.len()..]))?;
+    let path = path.trim_end_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    match path.split('/').collect::<Vec<_>>().as_slice() {
+        [owner, repo] if !owner.is_empty() && !repo.is_empty() => {
+            Some(format!("{}/{}", owner, repo))
+        }
+        _ => None,
+    }
+}
+
+#[derive(Default, Deserialize, Serialize)]
+struct CacheContents {
+    version: u32,
+    /// Accounts by email, including the emails without any account.
+    profiles: HashMap<String, CachedProfile>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct CachedProfile {
+    profile: Option<GitHubProfile>,
+    fetched: DateTime<Utc>,
+}
+
+/// Whose account a commit is looked up for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Author,
+    Committer,
+}
+
+/// Resolves the emails of the commits to GitHub accounts, with the API of the repository
+/// hosting them, caching the results on disk.
+///
+/// Nothing here fails the build: whatever cannot be resolved is simply left out.
+pub struct ProfileResolver<'a> {
+    client: &'a dyn HttpClient,
+    repository: String,
+    token: String,
+    cache_path: PathBuf,
+    ttl: Duration,
+    max_requests: usize,
+}
+
+impl<'a> ProfileResolver<'a> {
+    /// Creates a resolver querying the commits of `repository` (`owner/repo`), sending at most
+    /// `max_requests` requests, and caching the results in `cache_path` for `ttl_days` days.
+    pub fn new(
+        client: &'a dyn HttpClient,
+        repository: String,
+        token: String,
+        cache_path: PathBuf,
+        ttl_days: u32,
+        max_requests: usize,
+    ) -> ProfileResolver<'a> {
+        ProfileResolver {
+            client,
+            repository,
+            token,
+            cache_path,
+            ttl: Duration::days(i64::from(ttl_days)),
+            max_requests,
+        }
+    }
+
+    /// Resolves the emails of the authors and of the committers of the given commits,
+    /// returning the accounts found by email.
+    pub fn resolve<'e>(
+        &self,
+        entries: impl Iterator<Item = &'e GitHistoryEntry>,
+    ) -> HashMap<String, GitHubProfile> {
+        let mut cache = fs::read(&self.cache_path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<CacheContents>(&bytes).ok())
+            .filter(|cache| cache.version == CACHE_VERSION)
+            .unwrap_or_default();
+        let now = Utc::now();
+
+        // One commit is enough to find the account of an email
+        let mut commits = HashMap::<&str, (&str, Role)>::new();
+        for entry in entries {
+            commits
+                .entry(entry.email.as_str())
+                .or_insert((entry.hash.as_str(), Role::Author));
+            if !entry.committer_email.is_empty() {
+                commits
+                    .entry(entry.committer_email.as_str())
+                    .or_insert((entry.hash.as_str(), Role::Committer));
+            }
+        }
+        let mut commits = commits.into_iter().collect::<Vec<_>>();
+        commits.sort_unstable_by_key(|(email, _)| *email);
+
+        let mut profiles = HashMap::new();
+        let mut requests = 0;
+        let mut stopped = false;
+        let mut modified = false;
+        for (email, (hash, role)) in commits {
+            if email.is_empty() {
+                continue;
+            }
+            if let Some(profile) = GitHubProfile::from_noreply(email) {
+                profiles.insert(email.to_string(), profile);
+                continue;
+            }
+            let cached = cache.profiles.get(email);
+            let fresh = cached.is_some_and(|cached| now - cached.fetched < self.ttl);
+            if !fresh && !stopped && requests < self.max_requests {
+                requests += 1;
+                match self.fetch(hash, role) {
+                    Ok((profile, exhausted)) => {
+                        cache.profiles.insert(
+                            email.to_string(),
+                            CachedProfile {
+                                profile,
+                                fetched: now,
+                            },
+                        );
+                        modified = true;
+                        // The remaining requests are not worth the wait for a reset
+                        if exhausted {
+                            debug!("Not querying the GitHub API any further: rate limit reached");
+                            stopped = true;
+                        }
+                    }
+                    Err(Fetch::Skip(reason)) => {
+                        debug!("Cannot find the GitHub account of {}: {}", email, reason)
+                    }
+                    Err(Fetch::Stop(reason)) => {
+                        debug!("Not querying the GitHub API any further: {:#}", reason);
+                        stopped = true;
+                    }
+                }
+            }
+            // Outdated accounts are still better than none
+            if let Some(profile) = cache
+                .profiles
+                .get(email)
+                .and_then(|cached| cached.profile.clone())
+            {
+                profiles.insert(email.to_string(), profile);
+            }
+        }
+        if requests == self.max_requests && !stopped {
+            debug!(
+                "Sent the maximum of {} requests to the GitHub API",
+                self.max_requests
+            );
+        }
+
+        if modified {
+            cache.version = CACHE_VERSION;
+            let written = serde_json::to_vec(&cache)
+                .map_err(anyhow::Error::from)
+                .and_then(|json| metadata::write_atomically(&self.cache_path, &json));
+            if let Err(e) = written {
+                debug!(
+                    "Cannot write the GitHub cache {}: {:#}",
+                    self.cache_path.display(),
+                    e
+                );
+            }
+        }
+        profiles
+    }
+
+    /// Looks up the account of the author or the committer of a commit, along with whether
+    /// the rate limit is exhausted.
+    fn fetch(&self, hash: &str, role: Role) -> Result<(Option<GitHubProfile>, bool), Fetch> {
+        let url = format!("{}/repos/{}/commits/{}", API_URL, self.repository, hash);
+        let authorization = format!("Bearer {}", self.token);
+        let response = self
+            .client
+            .get(
+                &url,
+                &[
+                    ("Accept", "application/vnd.github+json"),
+                    ("Authorization", &authorization),
+                    ("User-Agent", "mdbook-git-info"),
+                    ("X-GitHub-Api-Version", "2022-11-28"),
+                ],
+            )
+            .map_err(Fetch::Stop)?;
+
+        let rate_limited = response.status == 429
+            || (response.status == 403 && response.header("x-ratelimit-remaining") == Some("0"));
+        match response.status {
+            _ if rate_limited => {
+                return Err(Fetch::Stop(anyhow::anyhow!(
+                    "rate limited until {}",
+                    response.header("x-ratelimit-reset").unwrap_or("later")
+                )))
+            }
+            200 => {}
+            // The commit may not have been pushed yet
+            404 | 422 => return Err(Fetch::Skip(format!("status {}", response.status))),
+            status => {
+                return Err(Fetch::Stop(anyhow::anyhow!(
+                    "status {} for {}",
+                    status,
+                    url
+                )))
+            }
+        }
+
+        let commit = serde_json::from_str::<serde_json::Value>(&response.body)
+            .map_err(|e| Fetch::Stop(e.into()))?;
+        let account = &commit[match role {
+            Role::Author => "author",
+            Role::Committer => "committer",
+        }];
+        let profile = match (account["login"].as_str(), account["avatar_url"].as_str()) {
+            (Some(login), Some(avatar_url)) => GitHubProfile::new(login, avatar_url.to_string()),
+            // The email belongs to no account
+            _ => None,
+        };
+        Ok((
+            profile,
+            response.header("x-ratelimit-remaining") == Some("0"),
+        ))
+    }
+}
+
+/// Why an account could not be looked up.
+enum Fetch {
+    /// Only this commit is affected.
+    Skip(String),
+    /// Further requests would fail the same way.
+    Stop(anyhow::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use std::sync::Mutex;
+
+    /// Answers with the queued responses, in order, and records the requested URLs.
+    #[derive(Debug, Default)]
+    struct StubClient {
+        responses: Mutex<Vec<HttpResponse>>,
+        requests: Mutex<Vec<String>>,
+    }
+
+    impl StubClient {
+        fn new(responses: Vec<HttpResponse>) -> StubClient {
+            StubClient {
+                responses: Mutex::new(responses),
+                requests: Mutex::default(),
+            }
+        }
+
+        fn requests(&self) -> Vec<String> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    impl HttpClient for StubClient {
+        fn get(&self, url: &str, _headers: &[(&str, &str)]) -> Result<HttpResponse> {
+            self.requests.lock().unwrap().push(url.to_string());
+            let mut responses = self.responses.lock().unwrap();
+            anyhow::ensure!(!responses.is_empty(), "unexpected request to {}", url);
+            Ok(responses.remove(0))
+        }
+    }
+
+    fn response(status: u16, remaining: &str, body: &str) -> HttpResponse {
+        HttpResponse {
+            status,
+            headers: vec![
+                ("x-ratelimit-remaining".to_string(), remaining.to_string()),
+                ("x-ratelimit-reset".to_string(), "1700000000".to_string()),
+            ],
+            body: body.to_string(),
+        }
+    }
+
+    fn account(login: &str) -> HttpResponse {
+        let body = serde_json::json!({
+            "author": {
+                "login": login,
+                "avatar_url": format!("https://avatars.githubusercontent.com/{}", login),
+            },
+            "committer": null,
+        });
+        response(200, "10", &body.to_string())
+    }
+
+    fn resolve(
+        client: &StubClient,
+        dir: &TempDir,
+        max_requests: usize,
+        entries: &[GitHistoryEntry],
+    ) -> HashMap<String, GitHubProfile> {
+        ProfileResolver::new(
+            client,
+            "owner/repo".to_string(),
+            "token".to_string(),
+            dir.path().join("github.json"),
+            7,
+            max_requests,
+        )
+        .resolve(entries.iter())
+    }
+
+    /// Commits whose committers have no email, so that only the authors are looked up.
+    fn commits(authors: &[&str]) -> Vec<GitHistoryEntry> {
+        authors
+            .iter()
+            .enumerate()
+            .map(|(i, author)| {
+                let mut entry =
+                    GitHistoryEntry::test(&format!("c{}", i), author, "2024-01-10T12:00:00Z");
+                entry.committer_email.clear();
+                entry
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cached_accounts_are_not_requested_again() -> Result<()> {
+        let dir = TempDir::new()?;
+        let entries = commits(&["Alice"]);
+
+        let client = StubClient::new(vec![account("alice")]);
+        let profiles = resolve(&client, &dir, 10, &entries);
+        assert_eq!(
+            client.requests(),
+            ["https://api.github.com/repos/owner/repo/commits/c0"]
+        );
+        assert_eq!(profiles["alice@example.com"].login, "alice");
+
+        let client = StubClient::new(Vec::new());
+        let profiles = resolve(&client, &dir, 10, &entries);
+        assert!(client.requests().is_empty());
+        assert_eq!(profiles["alice@example.com"].login, "alice");
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_users_are_cached_without_an_account() -> Result<()> {
+        let dir = TempDir::new()?;
+        let entries = commits(&["Alice"]);
+
+        let client = StubClient::new(vec![response(
+            200,
+            "10",
+            r#"{"author": null, "committer": null}"#,
+        )]);
+        assert!(resolve(&client, &dir, 10, &entries).is_empty());
+        assert_eq!(client.requests().len(), 1);
+
+        let client = StubClient::new(Vec::new());
+        assert!(resolve(&client, &dir, 10, &entries).is_empty());
+        assert!(client.requests().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn unpushed_commits_are_skipped() -> Result<()> {
+        let dir = TempDir::new()?;
+        let entries = commits(&["Alice", "Bob"]);
+
+        let client = StubClient::new(vec![response(422, "10", "{}"), account("bob")]);
+        let profiles = resolve(&client, &dir, 10, &entries);
+        assert_eq!(client.requests().len(), 2);
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles["bob@example.com"].login, "bob");
+
+        // Nothing was cached for the first email, which is looked up again
+        let client = StubClient::new(vec![account("alice")]);
+        let profiles = resolve(&client, &dir, 10, &entries);
+        assert_eq!(
+            client.requests(),
+            ["https://api.github.com/repos/owner/repo/commits/c0"]
+        );
+        assert_eq!(profiles.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn rate_limits_stop_the_lookups() -> Result<()> {
+        let entries = commits(&["Alice", "Bob", "Carol"]);
+
+        // Refused requests
+        for limited in [response(403, "0", "{}"), response(429, "5", "{}")] {
+            let dir = TempDir::new()?;
+            let client = StubClient::new(vec![limited]);
+            assert!(resolve(&client, &dir, 10, &entries).is_empty());
+            assert_eq!(client.requests().len(), 1);
+        }
+
+        // The last allowed request is still used
+        let dir = TempDir::new()?;
+        let mut last = account("alice");
+        last.headers[0].1 = "0".to_string();
+        let client = StubClient::new(vec![last]);
+        let profiles = resolve(&client, &dir, 10, &entries);
+        assert_eq!(client.requests().len(), 1);
+        assert_eq!(profiles["alice@example.com"].login, "alice");
+
+        // The remaining emails are looked up on the next run
+        let client = StubClient::new(vec![account("bob"), account("carol")]);
+        let profiles = resolve(&client, &dir, 10, &entries);
+        assert_eq!(client.requests().len(), 2);
+        assert_eq!(profiles.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn requests_are_capped() -> Result<()> {
+        let dir = TempDir::new()?;
+        let entries = commits(&["Alice", "Bob", "Carol"]);
+
+        let client = StubClient::new(vec![account("alice"), account("bob")]);
+        let profiles = resolve(&client, &dir, 2, &entries);
+        assert_eq!(client.requests().len(), 2);
+        assert_eq!(profiles.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn noreply_emails_need_no_request() -> Result<()> {
+        let dir = TempDir::new()?;
+        let mut entries = commits(&["Alice"]);
+        entries[0].email = "123+alice@users.noreply.github.com".to_string();
+
+        let client = StubClient::new(Vec::new());
+        let profiles = resolve(&client, &dir, 10, &entries);
+        assert!(client.requests().is_empty());
+        assert_eq!(
+            profiles["123+alice@users.noreply.github.com"].avatar(40),
+            "https://avatars.githubusercontent.com/u/123?v=4&s=40"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+use crate::config::{ExtractionMode, GitInfoOptions};
+use crate::git_history::GitHistoryEntry;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path};
+
+/// Version of the format of history files, bumped on incompatible changes.
+pub const VERSION: u32 = 1;
+
+/// Pre-computed histories of the chapters of a book, which allow building it without git.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct HistoryFile {
+    /// Version of the format, see [`VERSION`].
+    pub version: u32,
+    /// Histories by normalized source path of the chapter (see [`normalize`]).
+    pub histories: BTreeMap<String, Vec<GitHistoryEntry>>,
+}
+
+impl HistoryFile {
+    /// Extracts the histories of the given chapter source paths, relative to `src_dir`,
+    /// the same way the preprocessor does.
+    pub fn extract<'a, I>(
+        src_dir: &Path,
+        source_paths: I,
+        options: &GitInfoOptions,
+    ) -> Result<HistoryFile>
+    where
+        I: IntoIterator<Item = &'a Path>,
+    {
+        let provider = options.provider()?;
+        let all = match options.extraction {
+            ExtractionMode::Batch => Some(
+                provider
+                    .all_histories(src_dir, options)
+                    .context("Cannot extract git history")?,
+            ),
+            ExtractionMode::PerFile => None,
+        };
+
+        let mut histories = BTreeMap::new();
+        for source_path in source_paths {
+            let history = match &all {
+                Some(all) => all.get(source_path).cloned().unwrap_or_default(),
+                None => provider
+                    .file_history(&src_dir.join(source_path), options)
+                    .with_context(|| {
+                        format!(
+                            "Cannot extract the git history of {}",
+                            source_path.display()
+                        )
+                    })?,
+            };
+            histories.insert(normalize(source_path), history);
+        }
+
+        Ok(HistoryFile {
+            version: VERSION,
+            histories,
+        })
+    }
+
+    /// Reads a history file, rejecting unknown versions of the format.
+    pub fn load(path: &Path) -> Result<HistoryFile> {
+        let bytes = fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+        let file = serde_json::from_slice::<HistoryFile>(&bytes)
+            .with_context(|| format!("Invalid history file {}", path.display()))?;
+        if file.version != VERSION {
+            return Err(anyhow::anyhow!(
+                "Unsupported version {} of history file {} (expected {})",
+                file.version,
+                path.display(),
+                VERSION
+            ));
+        }
+        Ok(file)
+    }
+
+    /// Writes the history file to `path`.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self).context("Cannot serialize the histories")?;
+        fs::write(path, json).with_context(|| format!("Cannot write {}", path.display()))
+    }
+
+    /// Returns the history of the chapter with the given source path.
+    pub fn get(&self, source_path: &Path) -> Option<&Vec<GitHistoryEntry>> {
+        self.histories.get(&normalize(source_path))
+    }
+}
+
+/// Turns a source path into the key used by history files, which does not depend on the platform:
+/// components are separated by `/` and `.` components are dropped.
+pub fn normalize(source_path: &Path) -> String {
+    source_path
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn history_file() -> HistoryFile {
+        let mut histories = BTreeMap::new();
+        histories.insert(
+            "guide/intro.md".to_string(),
+            vec![GitHistoryEntry::test("a", "Alice", "2024-01-10T12:00:00Z")],
+        );
+        HistoryFile {
+            version: VERSION,
+            histories,
+        }
+    }
+
+    #[test]
+    fn saved_files_are_loaded_back() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("history.json");
+        history_file().save(&path)?;
+
+        let loaded = HistoryFile::load(&path)?;
+        assert_eq!(
+            serde_json::to_value(&loaded)?,
+            serde_json::to_value(history_file())?
+        );
+        let history = loaded.get(Path::new("./guide/intro.md")).unwrap();
+        assert_eq!(history[0].author, "Alice");
+        assert!(loaded.get(Path::new("guide/missing.md")).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn other_versions_are_rejected() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("history.json");
+        let mut file = history_file();
+        file.version = VERSION + 1;
+        file.save(&path)?;
+
+        let error = HistoryFile::load(&path).unwrap_err().to_string();
+        assert!(error.starts_with(&format!("Unsupported version {}", VERSION + 1)));
+
+        fs::write(&path, r#"{"histories": {}}"#)?;
+        assert!(HistoryFile::load(&path).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+use log::warn;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directives of mdbook's `links` preprocessor which pull another file into a chapter.
+const DIRECTIVES: [&str; 2] = ["include", "rustdoc_include"];
+
+/// Returns the canonical paths of the files included by `path`, directly or through
+/// other included files, in discovery order.
+///
+/// Missing files and include cycles are reported as warnings and skipped.
+pub fn find(path: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    // Targets are canonical, so the chapter must be too for cycles back to it to be noticed
+    let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    visit(path, &mut vec![canonical], &mut found);
+    found
+}
+
+fn visit(path: &Path, stack: &mut Vec<PathBuf>, found: &mut Vec<PathBuf>) {
+    // Binary or unreadable files cannot include anything
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(_) => return,
+    };
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    for target in targets(&content) {
+        let target = match fs::canonicalize(dir.join(target)) {
+            Ok(target) => target,
+            Err(_) => {
+                warn!(
+                    "Ignoring the missing file {} included by {}",
+                    target,
+                    path.display()
+                );
+                continue;
+            }
+        };
+        if stack.contains(&target) {
+            warn!(
+                "Ignoring the include cycle between {} and {}",
+                path.display(),
+                target.display()
+            );
+            continue;
+        }
+        if found.contains(&target) {
+            continue;
+        }
+
+        found.push(target.clone());
+        stack.push(target.clone());
+        visit(&target, stack, found);
+        stack.pop();
+    }
+}
+
+/// Returns the paths referenced by the include directives of `content`, like
+/// `{{#include file.md}}`, `{{#include file.rs:10:20}}` or `{{#include file.rs:anchor}}`.
+///
+/// Escaped directives (`\{{#include ...}}`) and directives inside code fences are ignored.
+fn targets(content: &str) -> Vec<&str> {
+    let mut targets = Vec::new();
+    for segment in unfenced(content) {
+        directives(segment, &mut targets);
+    }
+    targets
+}
+
+/// Adds the paths referenced by the include directives of `content` to `targets`.
+fn directives<'a>(content: &'a str, targets: &mut Vec<&'a str>) {
+    let mut rest = content;
+    while let Some(start) = rest.find("{{") {
+        let escaped = rest[..start].ends_with('\\');
+        rest = &rest[start + 2..];
+        let end = match rest.find("}}") {
+            Some(end) => end,
+            None => break,
+        };
+        if escaped {
+            continue;
+        }
+
+        let directive = rest[..end].trim_start();
+        let mut words = directive
+            .strip_prefix('#')
+            .unwrap_or_default()
+            .split_whitespace();
+        if let (Some(name), Some(argument)) = (words.next(), words.next()) {
+            if DIRECTIVES.contains(&name) {
+                // Line ranges and anchors follow the path after a colon
+                targets.push(argument.split(':').next().unwrap_or(argument));
+            }
+        }
+        rest = &rest[end + 2..];
+    }
+}
+
+/// Splits `content` around its code fences (` ``` ` or `~~~`, indented by at most three spaces),
+/// returning the text outside of them. An unclosed fence runs to the end of the content.
+fn unfenced(content: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    let mut fence = None::<(char, usize)>;
+    for line in content.split_inclusive('\n') {
+        let text = line.trim_end();
+        let marker = text.trim_start_matches(' ');
+        let indent = text.len() - marker.len();
+        let kind = marker.chars().next().filter(|c| *c == '`' || *c == '~');
+        if let (Some(kind), true) = (kind, indent <= 3) {
+            let length = marker.len() - marker.trim_start_matches(kind).len();
+            match fence {
+                None if length >= 3 => {
+                    segments.push(&content[start..offset]);
+                    fence = Some((kind, length));
+                }
+                // Closing fences are at least as long as the opening one, with nothing after
+                Some((open, open_length))
+                    if kind == open && length >= open_length && marker.len() == length =>
+                {
+                    start = offset + line.len();
+                    fence = None;
+                }
+                _ => {}
+            }
+        }
+        offset += line.len();
+    }
+    if fence.is_none() {
+        segments.push(&content[start..]);
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn targets_strip_ranges_and_anchors() {
+        let content =
+            "{{#include a.md}}\n{{ #rustdoc_include b.rs:10:20 }}\n{{#include c.rs:anchor}}\n\
+                       {{#title Nothing}} \\{{#include escaped.md}}";
+        assert_eq!(targets(content), ["a.md", "b.rs", "c.rs"]);
+    }
+
+    #[test]
+    fn targets_skip_code_fences() {
+        let content = "{{#include a.md}}\n\
+                       ```markdown\n{{#include b.md}}\n```\n\
+                       ~~~~\n```\n{{#include c.md}}\n~~~~\n\
+                       {{#include d.md}}\n\
+                       ```\n{{#include e.md}}\n";
+        assert_eq!(targets(content), ["a.md", "d.md"]);
+    }
+
+    #[test]
+    fn cycles_and_missing_files_are_skipped() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let chapter = dir.path().join("chapter.md");
+        let shared = dir.path().join("shared.md");
+        fs::write(
+            &chapter,
+            "{{#include shared.md}}\n{{#include missing.md}}\n",
+        )?;
+        fs::write(&shared, "{{#include chapter.md}}\n{{#include shared.md}}\n")?;
+
+        assert_eq!(find(&chapter), [shared]);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cycles_back_to_a_chapter_behind_a_symlink_are_skipped() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        fs::write(dir.path().join("chapter.md"), "{{#include shared.md}}\n")?;
+        fs::write(dir.path().join("shared.md"), "{{#include chapter.md}}\n")?;
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(dir.path(), &link)?;
+
+        assert_eq!(
+            find(&link.join("chapter.md")),
+            [dir.path().join("shared.md")]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,402 @@
+use crate::activity::{self, MonthlyActivity};
+use crate::config::{ActivityGraph, GitInfoOptions, Identity, IgnoredCommitEffect};
+use crate::git_history::{CommitSignature, GitHistoryEntry};
+use crate::github::GitHubProfile;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Information about a chapter, aggregated from its git history.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct GitInfo {
+    /// The commit which created the file.
+    pub created: Option<GitHistoryEntry>,
+    /// The most recent commit which touched the file.
+    pub last_edit: Option<GitHistoryEntry>,
+    /// Sorted authors of the file, except for the creator and the last editor.
+    pub other_contributors: Vec<String>,
+    /// Whether the history was truncated, so contributors may be missing.
+    pub truncated: bool,
+    /// Number of commits in the history.
+    pub commit_count: usize,
+    /// Owners of the file according to CODEOWNERS, if enabled.
+    pub owners: Vec<String>,
+    /// Sorted reviewers of the commits in the history, from the trailers listed in
+    /// [`GitInfoOptions::reviewers_from`].
+    #[serde(default)]
+    pub reviewers: Vec<String>,
+    /// Most recent editorial review of the file, recorded in the notes of
+    /// [`GitInfoOptions::notes_ref`].
+    #[serde(default)]
+    pub last_reviewed: Option<Review>,
+    /// Author of most of the current lines of the file, if [`GitInfoOptions::primary_author`] is enabled.
+    #[serde(default)]
+    pub primary_author: Option<PrimaryAuthor>,
+    /// Lines added by the commits in the history, if [`GitInfoOptions::churn`] is enabled.
+    #[serde(default)]
+    pub lines_added: u64,
+    /// Lines removed by the commits in the history, if [`GitInfoOptions::churn`] is enabled.
+    #[serde(default)]
+    pub lines_removed: u64,
+    /// Commits in the history which [`GitInfoOptions::published_ref`] does not contain yet.
+    #[serde(default)]
+    pub unpublished_changes: usize,
+    /// Commits by month, oldest first, if [`GitInfoOptions::activity_graph`] is enabled.
+    #[serde(default)]
+    pub activity: Vec<MonthlyActivity>,
+    /// Copyright line, if [`GitInfoOptions::copyright`] is enabled.
+    #[serde(default)]
+    pub copyright: Option<String>,
+    /// Date of the most recent last edit of any chapter of the book.
+    #[serde(default)]
+    pub book_last_edit: Option<DateTime<Utc>>,
+    /// First release containing the last edit, if [`GitInfoOptions::show_version`] is enabled
+    /// and the last edit is released.
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Signature of the last edit, if [`GitInfoOptions::show_signature_status`] is enabled.
+    #[serde(default)]
+    pub signature: Option<CommitSignature>,
+    /// GitHub accounts of the contributors, by name, if [`GitInfoOptions::github_api`] is enabled.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, GitHubProfile>,
+    /// Revision the book is built from, if [`GitInfoOptions::provenance`] shows it in every footer.
+    #[serde(default)]
+    pub provenance: Option<BuildProvenance>,
+}
+
+/// Revision of the repository a book is built from.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct BuildProvenance {
+    /// Full hash of the `HEAD` commit.
+    pub commit: String,
+    /// Branch checked out, or tag of a detached `HEAD`.
+    pub reference: Option<String>,
+    /// Time of the build, or `SOURCE_DATE_EPOCH` if set.
+    pub built_at: DateTime<Utc>,
+    /// Whether the working tree has uncommitted changes.
+    pub dirty: bool,
+}
+
+/// The author of most of the current lines of a file, according to `git blame`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct PrimaryAuthor {
+    /// Name of the author.
+    pub name: String,
+    /// Lines of the file last changed by the author.
+    pub lines: usize,
+    /// Committed lines of the file.
+    pub total_lines: usize,
+}
+
+impl PrimaryAuthor {
+    /// Picks the author with the most lines out of the lines per author, if any.
+    ///
+    /// Ties go to the first author in alphabetical order.
+    pub fn from_blame(counts: &[(String, usize)]) -> Option<PrimaryAuthor> {
+        let (name, lines) = counts
+            .iter()
+            .filter(|(_, lines)| *lines > 0)
+            .min_by(|(a, a_lines), (b, b_lines)| b_lines.cmp(a_lines).then(a.cmp(b)))?;
+        Some(PrimaryAuthor {
+            name: name.clone(),
+            lines: *lines,
+            total_lines: counts.iter().map(|(_, lines)| lines).sum(),
+        })
+    }
+
+    /// Share of the lines last changed by the author, as a rounded percentage.
+    pub fn percentage(&self) -> usize {
+        (self.lines * 100 + self.total_lines / 2) / self.total_lines.max(1)
+    }
+}
+
+/// An editorial review, recorded in a git note attached to a commit.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct Review {
+    /// Date of the review.
+    pub date: DateTime<Utc>,
+    /// Name of the reviewer, if recorded.
+    pub reviewer: Option<String>,
+}
+
+impl GitInfo {
+    /// Aggregates a history, as returned by `git log`.
+    ///
+    /// The creation and the last edit are found by date, since rebases leave the author dates
+    /// out of the order of the log. Truncated histories (see [`GitInfoOptions::max_history`]) are recognized by their length.
+    pub fn from_history(history: &[GitHistoryEntry], options: &GitInfoOptions) -> GitInfo {
+        // Commits of ignored types are left out only where configured
+        let counted = |effect| {
+            history
+                .iter()
+                .filter(move |entry| !options.is_ignored_commit(entry, effect))
+        };
+        let last_edit = last_edit(history, options);
+        let created = created(history);
+
+        // Both the creator and the last editor have their own cells
+        let identity = options.identity;
+        let other_contributors = counted(IgnoredCommitEffect::Contributors)
+            .map(|entry| entry.name(identity))
+            .filter(|name| {
+                created.is_none_or(|c| c.name(identity) != *name)
+                    && last_edit.is_none_or(|c| c.name(identity) != *name)
+            })
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+
+        GitInfo {
+            created: created.cloned(),
+            last_edit: last_edit.cloned(),
+            other_contributors,
+            truncated: options
+                .max_history
+                .is_some_and(|max_history| history.len() > max_history),
+            commit_count: counted(IgnoredCommitEffect::Totals).count(),
+            owners: Vec::new(),
+            last_reviewed: None,
+            primary_author: None,
+            unpublished_changes: 0,
+            copyright: None,
+            book_last_edit: None,
+            version: None,
+            signature: None,
+            profiles: BTreeMap::new(),
+            provenance: None,
+            lines_added: counted(IgnoredCommitEffect::Totals)
+                .map(|entry| entry.lines_added)
+                .sum(),
+            lines_removed: counted(IgnoredCommitEffect::Totals)
+                .map(|entry| entry.lines_removed)
+                .sum(),
+            activity: if options.activity_graph == ActivityGraph::None {
+                Vec::new()
+            } else {
+                activity::monthly(
+                    counted(IgnoredCommitEffect::Totals),
+                    Utc::now(),
+                    options.activity_graph_months,
+                )
+            },
+            reviewers: history
+                .iter()
+                .flat_map(|entry| entry.trailer_values(&options.reviewers_from))
+                .map(reviewer_name)
+                .filter(|name| !name.is_empty())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+}
+
+impl GitInfo {
+    /// Whether nothing is known about the history of the file.
+    pub fn is_empty(&self) -> bool {
+        self.created.is_none() && self.last_edit.is_none()
+    }
+
+    /// Number of distinct authors shown: the creator, the last editor and the other contributors.
+    ///
+    /// When the history is truncated, this is only a lower bound.
+    pub fn contributor_count(&self, identity: Identity) -> usize {
+        self.created
+            .iter()
+            .chain(&self.last_edit)
+            .map(|entry| entry.name(identity))
+            .chain(self.other_contributors.iter().map(String::as_str))
+            .collect::<HashSet<_>>()
+            .len()
+    }
+}
+
+/// The commit of a history with the most recent author date counting as its last edit,
+/// whatever the order of the history.
+///
+/// Commits of the types ignored for the last edit are skipped, unless there are only such commits.
+/// Commits with the same date are told apart by their hashes, the opposite way of [`created`],
+/// so that two commits made in the same second are never both the creation and the last edit.
+pub fn last_edit<'a>(
+    history: &'a [GitHistoryEntry],
+    options: &GitInfoOptions,
+) -> Option<&'a GitHistoryEntry> {
+    let newest = |entries: &mut dyn Iterator<Item = &'a GitHistoryEntry>| {
+        entries.max_by(|a, b| a.timestamp.cmp(&b.timestamp).then(a.hash.cmp(&b.hash)))
+    };
+    newest(
+        &mut history
+            .iter()
+            .filter(|entry| !options.is_ignored_commit(entry, IgnoredCommitEffect::LastEdit)),
+    )
+    .or_else(|| newest(&mut history.iter()))
+}
+
+/// The commit of a history with the oldest author date, whatever the order of the history.
+///
+/// Commits with the same date are told apart by their hashes.
+pub fn created(history: &[GitHistoryEntry]) -> Option<&GitHistoryEntry> {
+    history
+        .iter()
+        .min_by(|a, b| a.timestamp.cmp(&b.timestamp).then(a.hash.cmp(&b.hash)))
+}
+
+/// Strips the email from a trailer value like `Jane Doe <jane@example.com>`.
+fn reviewer_name(value: &str) -> &str {
+    value.split('<').next().unwrap_or(value).trim()
+}
+
+/// A contributor to the whole book, aggregated from the histories of all its chapters.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct Contributor {
+    /// Name of the author.
+    pub name: String,
+    /// Number of distinct commits of the author touching any chapter.
+    pub commit_count: usize,
+    /// Names and paths of the chapters touched by the author, in book order.
+    pub chapters: Vec<(String, PathBuf)>,
+}
+
+impl Contributor {
+    /// Aggregates the histories of the chapters of a book, given as `(name, path, history)`
+    /// in book order, crediting each commit to `identity`.
+    ///
+    /// Contributors are sorted by decreasing number of commits, then by name.
+    pub fn aggregate<'a, I>(chapters: I, identity: Identity) -> Vec<Contributor>
+    where
+        I: IntoIterator<Item = (&'a str, &'a Path, &'a [GitHistoryEntry])>,
+    {
+        let mut by_author = BTreeMap::<&str, (HashSet<&str>, Vec<(String, PathBuf)>)>::new();
+        for (name, path, history) in chapters {
+            for entry in history {
+                let (commits, chapters) = by_author.entry(entry.name(identity)).or_default();
+                commits.insert(&entry.hash);
+                // The same file can be listed more than once in the summary
+                if !chapters.iter().any(|(_, p)| p == path) {
+                    chapters.push((name.to_string(), path.to_path_buf()));
+                }
+            }
+        }
+
+        let mut contributors = by_author
+            .into_iter()
+            .map(|(name, (commits, chapters))| Contributor {
+                name: name.to_string(),
+                commit_count: commits.len(),
+                chapters,
+            })
+            .collect::<Vec<_>>();
+        // The sort is stable, so authors with the same count stay sorted by name
+        contributors.sort_by_key(|contributor| std::cmp::Reverse(contributor.commit_count));
+        contributors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(history: &[GitHistoryEntry]) -> GitInfo {
+        GitInfo::from_history(history, &GitInfoOptions::default())
+    }
+
+    fn hash(entry: &Option<GitHistoryEntry>) -> Option<&str> {
+        entry.as_ref().map(|entry| entry.hash.as_str())
+    }
+
+    #[test]
+    fn empty_history() {
+        let info = info(&[]);
+
+        assert!(info.is_empty());
+        assert_eq!(info.commit_count, 0);
+        assert!(info.other_contributors.is_empty());
+        assert!(!info.truncated);
+        assert_eq!(info.contributor_count(Identity::Author), 0);
+    }
+
+    #[test]
+    fn single_commit_is_both_the_creation_and_the_last_edit() {
+        let info = info(&[GitHistoryEntry::test("a", "Alice", "2024-01-10T12:00:00Z")]);
+
+        assert_eq!(hash(&info.created), Some("a"));
+        assert_eq!(hash(&info.last_edit), Some("a"));
+        assert!(info.other_contributors.is_empty());
+        assert_eq!(info.commit_count, 1);
+        assert_eq!(info.contributor_count(Identity::Author), 1);
+    }
+
+    #[test]
+    fn two_commits_are_found_by_date_whatever_the_order() {
+        let history = [
+            GitHistoryEntry::test("a", "Alice", "2024-01-10T12:00:00Z"),
+            GitHistoryEntry::test("b", "Bob", "2024-03-05T12:00:00Z"),
+        ];
+
+        let info = info(&history);
+
+        assert_eq!(hash(&info.created), Some("a"));
+        assert_eq!(hash(&info.last_edit), Some("b"));
+        assert!(info.other_contributors.is_empty());
+        assert_eq!(info.commit_count, 2);
+        assert_eq!(info.contributor_count(Identity::Author), 2);
+    }
+
+    #[test]
+    fn many_authors_are_sorted_and_deduplicated() {
+        let history = [
+            GitHistoryEntry::test("f", "Alice", "2024-06-01T12:00:00Z"),
+            GitHistoryEntry::test("e", "Dave", "2024-05-01T12:00:00Z"),
+            GitHistoryEntry::test("d", "Carol", "2024-04-01T12:00:00Z"),
+            GitHistoryEntry::test("c", "Dave", "2024-03-01T12:00:00Z"),
+            GitHistoryEntry::test("b", "Alice", "2024-02-01T12:00:00Z"),
+            GitHistoryEntry::test("a", "Bob", "2024-01-01T12:00:00Z"),
+        ];
+
+        let info = info(&history);
+
+        assert_eq!(hash(&info.created), Some("a"));
+        assert_eq!(hash(&info.last_edit), Some("f"));
+        assert_eq!(info.other_contributors, ["Carol", "Dave"]);
+        assert_eq!(info.commit_count, 6);
+        assert_eq!(info.contributor_count(Identity::Author), 4);
+    }
+
+    #[test]
+    fn truncated_histories_are_recognized_by_their_length() {
+        let history = [
+            GitHistoryEntry::test("b", "Bob", "2024-03-05T12:00:00Z"),
+            GitHistoryEntry::test("a", "Alice", "2024-01-10T12:00:00Z"),
+        ];
+
+        let options = GitInfoOptions::builder().max_history(1).build();
+        assert!(GitInfo::from_history(&history, &options).truncated);
+        let options = GitInfoOptions::builder().max_history(2).build();
+        assert!(!GitInfo::from_history(&history, &options).truncated);
+    }
+
+    #[test]
+    fn commits_of_the_same_second_are_told_apart_by_hash() {
+        let history = [
+            GitHistoryEntry::test("b", "Bob", "2024-01-10T12:00:00Z"),
+            GitHistoryEntry::test("a", "Alice", "2024-01-10T12:00:00Z"),
+        ];
+        let mut reversed = history.clone();
+        reversed.reverse();
+        let options = GitInfoOptions::default();
+
+        for history in [&history[..], &reversed[..]] {
+            assert_eq!(created(history).unwrap().hash, "a");
+            assert_eq!(last_edit(history, &options).unwrap().hash, "b");
+        }
+    }
+}
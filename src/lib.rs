@@ -0,0 +1,46 @@
+//! A mdbook preprocessor which extracts metadata from Git and adds it to the chapters of the book.
+//!
+//! Besides being used as a standalone preprocessor binary, the crate can be embedded in a custom
+//! mdbook driver:
+//!
+//! ```no_run
+//! use mdbook::MDBook;
+//! use mdbook_git_info::GitInfoPreprocessor;
+//!
+//! let mut book = MDBook::load(".")?;
+//! book.with_preprocessor(GitInfoPreprocessor::new());
+//! book.build()?;
+//! # Ok::<(), mdbook::errors::Error>(())
+//! ```
+//!
+//! The building blocks are public as well: [`git_history::extract`] runs `git log` on a file,
+//! [`GitInfo::from_history`] aggregates the result and [`render::footer`] turns it into markdown.
+
+pub mod activity;
+mod cache;
+pub mod codeowners;
+pub mod config;
+mod copyright;
+mod feed;
+mod front_matter;
+pub mod git_history;
+pub mod github;
+pub mod history_file;
+mod includes;
+pub mod info;
+mod locale;
+mod metadata;
+mod notes;
+mod preprocessor;
+mod progress;
+pub mod provider;
+pub mod render;
+pub mod sitemap;
+pub mod stylesheet;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_support;
+
+pub use crate::config::GitInfoOptions;
+pub use crate::git_history::GitHistoryEntry;
+pub use crate::info::{Contributor, GitInfo};
+pub use crate::preprocessor::GitInfoPreprocessor;
@@ -0,0 +1,360 @@
+use chrono::{DateTime, Datelike, Utc};
+
+/// Names of the months and of the weekdays, Monday first, in a language.
+struct Names {
+    months: [&'static str; 12],
+    short_months: [&'static str; 12],
+    weekdays: [&'static str; 7],
+    short_weekdays: [&'static str; 7],
+}
+
+/// Supported languages, by code.
+const LOCALES: [(&str, Names); 7] = [
+    (
+        "de",
+        Names {
+            months: [
+                "Januar",
+                "Februar",
+                "März",
+                "April",
+                "Mai",
+                "Juni",
+                "Juli",
+                "August",
+                "September",
+                "Oktober",
+                "November",
+                "Dezember",
+            ],
+            short_months: [
+                "Jan.", "Feb.", "März", "Apr.", "Mai", "Juni", "Juli", "Aug.", "Sep.", "Okt.",
+                "Nov.", "Dez.",
+            ],
+            weekdays: [
+                "Montag",
+                "Dienstag",
+                "Mittwoch",
+                "Donnerstag",
+                "Freitag",
+                "Samstag",
+                "Sonntag",
+            ],
+            short_weekdays: ["Mo.", "Di.", "Mi.", "Do.", "Fr.", "Sa.", "So."],
+        },
+    ),
+    (
+        "en",
+        Names {
+            months: [
+                "January",
+                "February",
+                "March",
+                "April",
+                "May",
+                "June",
+                "July",
+                "August",
+                "September",
+                "October",
+                "November",
+                "December",
+            ],
+            short_months: [
+                "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+            ],
+            weekdays: [
+                "Monday",
+                "Tuesday",
+                "Wednesday",
+                "Thursday",
+                "Friday",
+                "Saturday",
+                "Sunday",
+            ],
+            short_weekdays: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+        },
+    ),
+    (
+        "es",
+        Names {
+            months: [
+                "enero",
+                "febrero",
+                "marzo",
+                "abril",
+                "mayo",
+                "junio",
+                "julio",
+                "agosto",
+                "septiembre",
+                "octubre",
+                "noviembre",
+                "diciembre",
+            ],
+            short_months: [
+                "ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sept", "oct", "nov", "dic",
+            ],
+            weekdays: [
+                "lunes",
+                "martes",
+                "miércoles",
+                "jueves",
+                "viernes",
+                "sábado",
+                "domingo",
+            ],
+            short_weekdays: ["lun", "mar", "mié", "jue", "vie", "sáb", "dom"],
+        },
+    ),
+    (
+        "fr",
+        Names {
+            months: [
+                "janvier",
+                "février",
+                "mars",
+                "avril",
+                "mai",
+                "juin",
+                "juillet",
+                "août",
+                "septembre",
+                "octobre",
+                "novembre",
+                "décembre",
+            ],
+            short_months: [
+                "janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.",
+                "nov.", "déc.",
+            ],
+            weekdays: [
+                "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche",
+            ],
+            short_weekdays: ["lun.", "mar.", "mer.", "jeu.", "ven.", "sam.", "dim."],
+        },
+    ),
+    (
+        "it",
+        Names {
+            months: [
+                "gennaio",
+                "febbraio",
+                "marzo",
+                "aprile",
+                "maggio",
+                "giugno",
+                "luglio",
+                "agosto",
+                "settembre",
+                "ottobre",
+                "novembre",
+                "dicembre",
+            ],
+            short_months: [
+                "gen", "feb", "mar", "apr", "mag", "giu", "lug", "ago", "set", "ott", "nov", "dic",
+            ],
+            weekdays: [
+                "lunedì",
+                "martedì",
+                "mercoledì",
+                "giovedì",
+                "venerdì",
+                "sabato",
+                "domenica",
+            ],
+            short_weekdays: ["lun", "mar", "mer", "gio", "ven", "sab", "dom"],
+        },
+    ),
+    (
+        "nl",
+        Names {
+            months: [
+                "januari",
+                "februari",
+                "maart",
+                "april",
+                "mei",
+                "juni",
+                "juli",
+                "augustus",
+                "september",
+                "oktober",
+                "november",
+                "december",
+            ],
+            short_months: [
+                "jan", "feb", "mrt", "apr", "mei", "jun", "jul", "aug", "sep", "okt", "nov", "dec",
+            ],
+            weekdays: [
+                "maandag",
+                "dinsdag",
+                "woensdag",
+                "donderdag",
+                "vrijdag",
+                "zaterdag",
+                "zondag",
+            ],
+            short_weekdays: ["ma", "di", "wo", "do", "vr", "za", "zo"],
+        },
+    ),
+    (
+        "pt",
+        Names {
+            months: [
+                "janeiro",
+                "fevereiro",
+                "março",
+                "abril",
+                "maio",
+                "junho",
+                "julho",
+                "agosto",
+                "setembro",
+                "outubro",
+                "novembro",
+                "dezembro",
+            ],
+            short_months: [
+                "jan", "fev", "mar", "abr", "mai", "jun", "jul", "ago", "set", "out", "nov", "dez",
+            ],
+            weekdays: [
+                "segunda-feira",
+                "terça-feira",
+                "quarta-feira",
+                "quinta-feira",
+                "sexta-feira",
+                "sábado",
+                "domingo",
+            ],
+            short_weekdays: ["seg", "ter", "qua", "qui", "sex", "sáb", "dom"],
+        },
+    ),
+];
+
+/// Codes of the supported languages.
+pub fn supported() -> Vec<&'static str> {
+    LOCALES.iter().map(|(code, _)| *code).collect()
+}
+
+/// Whether a locale (e.g. `de`, `de-AT` or `de_AT`) is supported. Only its language matters.
+pub fn is_supported(locale: &str) -> bool {
+    names(locale).is_some()
+}
+
+fn names(locale: &str) -> Option<&'static Names> {
+    let language = locale.split(['-', '_']).next().unwrap_or(locale);
+    LOCALES
+        .iter()
+        .find(|(code, _)| code.eq_ignore_ascii_case(language))
+        .map(|(_, names)| names)
+}
+
+/// Abbreviated name of a month, starting from 1, in the language of `locale` (English by default).
+pub fn short_month(month: u32, locale: Option<&str>) -> &'static str {
+    let names = locale
+        .and_then(names)
+        .unwrap_or_else(|| names("en").expect("English is supported"));
+    names.short_months[month as usize - 1]
+}
+
+/// Formats a date like [`DateTime::format`], with the names of the months and weekdays
+/// (`%B`, `%b`, `%h`, `%A` and `%a`) in the language of `locale`.
+pub fn format(date: &DateTime<Utc>, format: &str, locale: Option<&str>) -> String {
+    let names = match locale.and_then(names) {
+        Some(names) => names,
+        None => return date.format(format).to_string(),
+    };
+    let month = date.month0() as usize;
+    let weekday = date.weekday().num_days_from_monday() as usize;
+
+    // Replace the names in the format itself, leaving the other specifiers to chrono
+    let mut localized = String::with_capacity(format.len());
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            localized.push(c);
+            continue;
+        }
+        // Padding flags have no effect on names
+        let flag = chars.next_if(|c| matches!(c, '-' | '_' | '0'));
+        let name = match chars.peek() {
+            Some('B') => Some(names.months[month]),
+            Some('b' | 'h') => Some(names.short_months[month]),
+            Some('A') => Some(names.weekdays[weekday]),
+            Some('a') => Some(names.short_weekdays[weekday]),
+            _ => None,
+        };
+        match name {
+            Some(name) => localized.push_str(name),
+            None => {
+                localized.push('%');
+                localized.extend(flag);
+                localized.extend(chars.peek());
+            }
+        }
+        chars.next();
+    }
+    date.format(&localized).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GitInfoOptions;
+
+    fn date() -> DateTime<Utc> {
+        "2024-03-05T14:30:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn dates_are_written_in_german() {
+        assert_eq!(
+            format(&date(), "%A, %-d. %B %Y %H:%M", Some("de")),
+            "Dienstag, 5. März 2024 14:30"
+        );
+        assert_eq!(
+            format(&date(), "%a %d %b %y", Some("de-AT")),
+            "Di. 05 März 24"
+        );
+    }
+
+    #[test]
+    fn dates_are_written_in_french() {
+        assert_eq!(
+            format(&date(), "%A %-d %B %Y", Some("fr")),
+            "mardi 5 mars 2024"
+        );
+        assert_eq!(format(&date(), "%a %e %h", Some("fr_CA")), "mar.  5 mars");
+        assert_eq!(short_month(2, Some("fr")), "févr.");
+    }
+
+    #[test]
+    fn other_specifiers_are_left_to_chrono() {
+        assert_eq!(format(&date(), "%%B %B", Some("de")), "%B März");
+        assert_eq!(
+            format(&date(), "%B %d", None),
+            format(&date(), "%B %d", Some("en"))
+        );
+    }
+
+    #[test]
+    fn unknown_locales_are_rejected() {
+        assert!(!is_supported("xx"));
+        assert_eq!(short_month(3, Some("xx")), "Mar");
+
+        let error = GitInfoOptions::builder()
+            .locale("xx-YY")
+            .build()
+            .validate()
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("\"xx-YY\""), "{}", error);
+        assert!(error.contains(&supported().join(", ")), "{}", error);
+        assert!(GitInfoOptions::builder()
+            .locale("DE")
+            .build()
+            .validate()
+            .is_ok());
+    }
+}
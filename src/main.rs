@@ -1,19 +1,200 @@
-mod git_history;
-mod preprocessor;
-
-use crate::preprocessor::GitInfoPreprocessor;
-use anyhow::Result;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
 use clap::{App, Arg, ArgMatches, SubCommand};
-use mdbook::preprocess::{CmdPreprocessor, Preprocessor};
+use log::{error, info, warn};
+use mdbook::book::Book;
+use mdbook::preprocess::{Preprocessor, PreprocessorContext};
+use mdbook::renderer::RenderContext;
+use mdbook::{BookItem, Config, MDBook};
+use mdbook_git_info::config::{Identity, Lint, LintLevel};
+use mdbook_git_info::history_file::HistoryFile;
+use mdbook_git_info::{
+    git_history, render, sitemap, GitHistoryEntry, GitInfo, GitInfoOptions, GitInfoPreprocessor,
+};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
 use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    // Logs go to stderr, since stdout carries the processed book
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        // The failed rules are already reported
+        Err(e) if e.is::<LintFailure>() => ExitCode::from(LINT_FAILURE_EXIT_CODE),
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
 
-fn main() -> Result<()> {
+fn run() -> Result<()> {
     let matches =  App::new("mdbook-git-info")
         .about("A mdbook preprocessor which extracts metadata from Git and adds it to the chapters of the book")
+        .arg(
+            Arg::with_name("quiet-version-check")
+                .long("quiet-version-check")
+                .help("Do not warn when called from a version of mdbook incompatible with the one this was built against")
+        )
+        .arg(
+            Arg::with_name("set")
+                .long("set")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("KEY=VALUE")
+                .help("Override an option of [preprocessor.git-info], with the value written as in book.toml (unquoted text is a string)")
+        )
         .subcommand(
             SubCommand::with_name("supports")
                 .arg(Arg::with_name("renderer").required(true))
                 .about("Check whether a renderer is supported by this preprocessor")
+        )
+        .subcommand(
+            SubCommand::with_name("inspect")
+                .arg(Arg::with_name("path").required(true))
+                .arg(book_dir_arg())
+                .arg(Arg::with_name("json").long("json").help("Print the result as JSON"))
+                .about("Show the git history and info extracted for a single file")
+        )
+        .subcommand(
+            SubCommand::with_name("stats")
+                .arg(book_dir_arg())
+                .arg(
+                    Arg::with_name("sort")
+                        .long("sort")
+                        .takes_value(true)
+                        .possible_values(&["title", "path", "created", "last-edit", "commits", "contributors"])
+                        .help("Sort the chapters by this column, in ascending order, instead of book order")
+                )
+                .arg(
+                    Arg::with_name("stale-days")
+                        .long("stale-days")
+                        .takes_value(true)
+                        .value_name("N")
+                        .help("Only show the chapters which were not edited in the last N days")
+                )
+                .arg(Arg::with_name("json").long("json").help("Print the result as JSON"))
+                .about("Summarize the git history of every chapter of the book")
+        )
+        .subcommand(
+            SubCommand::with_name("install")
+                .arg(
+                    Arg::with_name("dir")
+                        .long("dir")
+                        .takes_value(true)
+                        .default_value(".")
+                        .help("Directory of the book")
+                )
+                .arg(
+                    Arg::with_name("css")
+                        .long("css")
+                        .takes_value(true)
+                        .value_name("PATH")
+                        .help("Also write the bundled stylesheet to PATH and register it as additional CSS")
+                )
+                .arg(Arg::with_name("dry-run").long("dry-run").help("Print the changes without writing them"))
+                .about("Add the preprocessor to the book.toml of a book")
+        )
+        .subcommand(
+            SubCommand::with_name("export-history")
+                .arg(
+                    Arg::with_name("book-dir")
+                        .default_value(".")
+                        .help("Directory of the book")
+                )
+                .arg(
+                    Arg::with_name("out")
+                        .long("out")
+                        .takes_value(true)
+                        .required(true)
+                        .value_name("FILE")
+                        .help("Path of the history file to write")
+                )
+                .about("Write the histories of every chapter to a file, for builds without git (see the history_file option)")
+        )
+        .subcommand(
+            SubCommand::with_name("preview")
+                .arg(
+                    Arg::with_name("book-dir")
+                        .default_value(".")
+                        .help("Directory of the book")
+                )
+                .arg(
+                    Arg::with_name("only-injected")
+                        .long("only-injected")
+                        .conflicts_with("diff")
+                        .help("Print only what is added to each chapter (the default)")
+                )
+                .arg(Arg::with_name("diff").long("diff").help("Print a unified diff of each chapter"))
+                .arg(
+                    Arg::with_name("chapter")
+                        .long("chapter")
+                        .takes_value(true)
+                        .value_name("GLOB")
+                        .help("Only show the chapters whose source path matches GLOB")
+                )
+                .arg(
+                    Arg::with_name("renderer")
+                        .long("renderer")
+                        .takes_value(true)
+                        .default_value("html")
+                        .help("Renderer to preview the chapters for")
+                )
+                .about("Show what a build would add to each chapter, failing if any chapter would fail")
+        )
+        .subcommand(
+            SubCommand::with_name("lint")
+                .arg(
+                    Arg::with_name("book-dir")
+                        .default_value(".")
+                        .help("Directory of the book")
+                )
+                .arg(
+                    Arg::with_name("max-age-days")
+                        .long("max-age-days")
+                        .takes_value(true)
+                        .value_name("N")
+                        .help("Fail if the last edit of a chapter is older than N days")
+                )
+                .arg(
+                    Arg::with_name("require-history")
+                        .long("require-history")
+                        .help("Fail if a chapter has no git history")
+                )
+                .arg(
+                    Arg::with_name("min-contributors")
+                        .long("min-contributors")
+                        .takes_value(true)
+                        .value_name("K")
+                        .help("Report the chapters edited by fewer than K people")
+                )
+                .arg(
+                    Arg::with_name("min-contributors-level")
+                        .long("min-contributors-level")
+                        .takes_value(true)
+                        .possible_values(&["warn", "error"])
+                        .help("Whether too few contributors only warn or fail")
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["text", "json"])
+                        .default_value("text")
+                        .help("Output format")
+                )
+                .about(
+                    "Check the chapters against the rules of [preprocessor.git-info.lint] and of the flags, \
+                     exiting with code 2 if any rule fails"
+                )
         ).get_matches();
 
     let preprocessor = GitInfoPreprocessor::new();
@@ -21,33 +202,333 @@ fn main() -> Result<()> {
     // Dispatch to the correct function
     if let Some(sub_args) = matches.subcommand_matches("supports") {
         handle_supports(preprocessor, sub_args)
+    } else if let Some(sub_args) = matches.subcommand_matches("inspect") {
+        handle_inspect(sub_args)
+    } else if let Some(sub_args) = matches.subcommand_matches("stats") {
+        handle_stats(sub_args)
+    } else if let Some(sub_args) = matches.subcommand_matches("install") {
+        handle_install(sub_args)
+    } else if let Some(sub_args) = matches.subcommand_matches("export-history") {
+        handle_export_history(sub_args)
+    } else if let Some(sub_args) = matches.subcommand_matches("preview") {
+        handle_preview(preprocessor, sub_args)
+    } else if let Some(sub_args) = matches.subcommand_matches("lint") {
+        handle_lint(sub_args)
     } else {
-        handle_preprocessing(preprocessor)
+        let overrides = matches
+            .values_of("set")
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        handle_invocation(
+            preprocessor,
+            &overrides,
+            matches.is_present("quiet-version-check"),
+        )
     }
 }
 
-/// Pre-processor starter, taken straight out of the mdbook book
-fn handle_preprocessing(pre: impl Preprocessor) -> Result<()> {
-    let (ctx, book) = CmdPreprocessor::parse_input(io::stdin())?;
-
-    if ctx.mdbook_version != mdbook::MDBOOK_VERSION {
-        // We should probably use the `semver` crate to check compatibility
-        // here...
-        eprintln!(
-            "Warning: The {} plugin was built against version {} of mdbook, \
+/// Tells apart the two ways mdbook runs the binary by its input: a `[context, book]` array
+/// when run as a preprocessor, a single render context when run as a renderer
+fn handle_invocation(
+    pre: GitInfoPreprocessor,
+    overrides: &[&str],
+    quiet_version_check: bool,
+) -> Result<()> {
+    let input = serde_json::from_reader::<_, serde_json::Value>(io::stdin())
+        .context("Unable to parse the input")?;
+    if input.is_object() {
+        let mut ctx = serde_json::from_value::<RenderContext>(input)
+            .context("Unable to parse the render context")?;
+        check_version(&pre, &ctx.version, quiet_version_check);
+        apply_overrides(&mut ctx.config, overrides)?;
+        handle_rendering(pre, ctx)
+    } else {
+        let (mut ctx, book) = serde_json::from_value::<(PreprocessorContext, Book)>(input)
+            .context("Unable to parse the input")?;
+        check_version(&pre, &ctx.mdbook_version, quiet_version_check);
+        apply_overrides(&mut ctx.config, overrides)?;
+        handle_preprocessing(pre, ctx, book)
+    }
+}
+
+/// Writes the `--set KEY=VALUE` overrides into the configuration of the preprocessor, in order,
+/// so that they are validated like book.toml and win over it
+fn apply_overrides(config: &mut Config, overrides: &[&str]) -> Result<()> {
+    if overrides.is_empty() {
+        return Ok(());
+    }
+    // Every option is listed when serialized as JSON, even the unset ones
+    let known = serde_json::to_value(GitInfoOptions::default())?;
+
+    for assignment in overrides {
+        let (key, value) = assignment
+            .split_once('=')
+            .with_context(|| format!("Invalid --set {:?}: expected KEY=VALUE", assignment))?;
+        let (key, value) = (key.trim(), value.trim());
+        let option = key.split('.').next().unwrap_or(key);
+        if known.get(option).is_none() {
+            return Err(anyhow::anyhow!(
+                "Invalid --set {:?}: unknown option {:?}",
+                assignment,
+                option
+            ));
+        }
+
+        // Typed like in book.toml, falling back to a string for unquoted text
+        let value = format!("value = {}", value)
+            .parse::<toml::Value>()
+            .ok()
+            .and_then(|table| table.get("value").cloned())
+            .unwrap_or_else(|| toml::Value::String(value.to_string()));
+        config.set(format!("preprocessor.git-info.{}", key), value)?;
+    }
+    Ok(())
+}
+
+fn check_version(pre: &impl Preprocessor, mdbook_version: &str, quiet_version_check: bool) {
+    if !quiet_version_check && !is_compatible(mdbook::MDBOOK_VERSION, mdbook_version) {
+        warn!(
+            "The {} plugin was built against version {} of mdbook, \
              but we're being called from version {}",
             pre.name(),
             mdbook::MDBOOK_VERSION,
-            ctx.mdbook_version
+            mdbook_version
         );
     }
+}
 
+/// Pre-processor starter, taken straight out of the mdbook book
+fn handle_preprocessing(
+    pre: impl Preprocessor,
+    ctx: PreprocessorContext,
+    book: Book,
+) -> Result<()> {
     let processed_book = pre.run(&ctx, book)?;
     serde_json::to_writer(io::stdout(), &processed_book)?;
 
     Ok(())
 }
 
+/// Name of the renderer, from `[output.git-info]`.
+const RENDERER_NAME: &str = "git-info";
+
+/// Per-chapter data of the report written as a renderer.
+#[derive(Serialize)]
+struct ChapterReport {
+    title: String,
+    path: PathBuf,
+    created: Option<DateTime<Utc>>,
+    created_by: Option<String>,
+    last_edit: Option<DateTime<Utc>>,
+    last_edit_by: Option<String>,
+    contributors: Vec<String>,
+    commit_count: usize,
+}
+
+/// Writes a report of the git metadata of the book into the destination of the renderer:
+/// `git-info.json` with the data of every chapter, and `index.html` summarizing it, along with
+/// the sitemap if enabled
+fn handle_rendering(pre: GitInfoPreprocessor, ctx: RenderContext) -> Result<()> {
+    let mut config = ctx.config.clone();
+    let sitemap = GitInfoOptions::from_config(&config, "git-info")?
+        .sitemap
+        .filter(|sitemap| sitemap.enabled);
+
+    // Only the data is needed: nothing is written alongside the chapters
+    if let Some(toml::Value::Table(table)) = config.get_mut("preprocessor.git-info") {
+        for key in ["metadata_output", "feed", "sitemap", "write_css"] {
+            table.remove(key);
+        }
+    }
+    config.set("preprocessor.git-info.renderers", [RENDERER_NAME])?;
+    let options = GitInfoOptions::from_config(&config, "git-info")?;
+    let pre_ctx = serde_json::from_value::<PreprocessorContext>(serde_json::json!({
+        "root": ctx.root,
+        "config": config,
+        "renderer": RENDERER_NAME,
+        "mdbook_version": ctx.version,
+    }))
+    .context("Cannot create the preprocessor context")?;
+
+    // Renderers run in their destination, but git runs from the root of the book
+    // as for preprocessors
+    std::env::set_current_dir(&ctx.root)
+        .with_context(|| format!("Cannot enter {}", ctx.root.display()))?;
+    let (_, metadata) = pre.run_with_metadata(&pre_ctx, ctx.book.clone())?;
+
+    let name =
+        |entry: Option<&GitHistoryEntry>| entry.map(|e| e.name(options.identity).to_string());
+    let chapters = ctx
+        .book
+        .iter()
+        .filter_map(|item| match item {
+            BookItem::Chapter(chapter) => {
+                let path = chapter.source_path.as_ref()?;
+                let info = metadata.get(path)?;
+                Some((chapter, path, info))
+            }
+            _ => None,
+        })
+        .map(|(chapter, path, info)| ChapterReport {
+            title: chapter.name.clone(),
+            path: path.clone(),
+            created: info.created.as_ref().map(|e| e.timestamp),
+            created_by: name(info.created.as_ref()),
+            last_edit: info.last_edit.as_ref().map(|e| e.timestamp),
+            last_edit_by: name(info.last_edit.as_ref()),
+            contributors: name(info.created.as_ref())
+                .into_iter()
+                .chain(name(info.last_edit.as_ref()))
+                .chain(info.other_contributors.iter().cloned())
+                .fold(Vec::new(), |mut contributors, contributor| {
+                    if !contributors.contains(&contributor) {
+                        contributors.push(contributor);
+                    }
+                    contributors
+                }),
+            commit_count: info.commit_count,
+        })
+        .collect::<Vec<_>>();
+
+    fs::create_dir_all(&ctx.destination)
+        .with_context(|| format!("Cannot create {}", ctx.destination.display()))?;
+    let json = serde_json::to_vec_pretty(&chapters).context("Cannot serialize the report")?;
+    let path = ctx.destination.join("git-info.json");
+    fs::write(&path, json).with_context(|| format!("Cannot write {}", path.display()))?;
+
+    let title = ctx.config.book.title.as_deref().unwrap_or("Book");
+    let path = ctx.destination.join("index.html");
+    fs::write(&path, report_html(title, &chapters, &options))
+        .with_context(|| format!("Cannot write {}", path.display()))?;
+
+    if let Some(settings) = sitemap {
+        let site_url = settings
+            .site_url
+            .as_ref()
+            .or(options.site_url.as_ref())
+            .context("The sitemap requires site_url to be set")?;
+        let pages = ctx
+            .book
+            .iter()
+            .filter_map(|item| match item {
+                BookItem::Chapter(chapter) => {
+                    let info = metadata.get(chapter.source_path.as_ref()?)?;
+                    let last_edit = info.last_edit.as_ref().map(|e| e.timestamp);
+                    Some((chapter.path.as_deref()?, last_edit))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        sitemap::write(&ctx.destination.join(&settings.path), site_url, &pages)?;
+    }
+    Ok(())
+}
+
+/// Summarizes the report as a standalone HTML page
+fn report_html(title: &str, chapters: &[ChapterReport], options: &GitInfoOptions) -> String {
+    let date = |date: Option<DateTime<Utc>>| {
+        date.map_or_else(|| "n/a".to_string(), |date| options.format_date(&date))
+    };
+    let cell = |text: &str| format!("<td>{}</td>", render::escape_html(text));
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Git info of {title}</title>\n</head>\n<body>\n<h1>Git info of {title}</h1>\n\
+         <table>\n<tr><th>Chapter</th><th>Path</th><th>Created</th><th>Created by</th>\
+         <th>Last edit</th><th>Last edit by</th><th>Contributors</th><th>Commits</th></tr>\n",
+        title = render::escape_html(title)
+    );
+    for chapter in chapters {
+        html.push_str("<tr>");
+        html.push_str(&cell(&chapter.title));
+        html.push_str(&cell(&chapter.path.display().to_string()));
+        html.push_str(&cell(&date(chapter.created)));
+        html.push_str(&cell(chapter.created_by.as_deref().unwrap_or("n/a")));
+        html.push_str(&cell(&date(chapter.last_edit)));
+        html.push_str(&cell(chapter.last_edit_by.as_deref().unwrap_or("n/a")));
+        html.push_str(&cell(&chapter.contributors.join(", ")));
+        html.push_str(&cell(&chapter.commit_count.to_string()));
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+/// Whether the `running` version of mdbook satisfies the caret requirement `^built`,
+/// as cargo would: same major version (or same minor for 0.x, same patch for 0.0.x), and not older.
+///
+/// Pre-release versions only satisfy the requirement if `built` is a pre-release of the same
+/// version. Unparsable versions are never compatible.
+fn is_compatible(built: &str, running: &str) -> bool {
+    let (built, running) = match (parse_version(built), parse_version(running)) {
+        (Some(built), Some(running)) => (built, running),
+        _ => return false,
+    };
+    let (built_numbers, built_pre) = built;
+    let (running_numbers, running_pre) = running;
+
+    let same_series = match built_numbers {
+        [0, 0, _] => running_numbers == built_numbers,
+        [0, minor, _] => running_numbers[..2] == [0, minor],
+        [major, _, _] => running_numbers[0] == major,
+    };
+    if !same_series {
+        return false;
+    }
+
+    match (built_pre, running_pre) {
+        (_, None) => running_numbers >= built_numbers,
+        (Some(built_pre), Some(running_pre)) => {
+            running_numbers == built_numbers && compare_pre_release(running_pre, built_pre).is_ge()
+        }
+        (None, Some(_)) => false,
+    }
+}
+
+/// Parses `major.minor.patch[-pre][+build]` into the numbers and the pre-release identifiers
+fn parse_version(version: &str) -> Option<([u64; 3], Option<&str>)> {
+    let version = version.trim();
+    let version = version.split('+').next()?;
+    let (numbers, pre) = match version.split_once('-') {
+        Some((numbers, pre)) => (numbers, Some(pre)),
+        None => (version, None),
+    };
+
+    let mut parts = numbers.split('.').map(|part| part.parse::<u64>().ok());
+    let numbers = [parts.next()??, parts.next()??, parts.next()??];
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((numbers, pre))
+}
+
+/// Compares pre-release identifiers following the semver precedence rules
+fn compare_pre_release(a: &str, b: &str) -> Ordering {
+    let mut a = a.split('.');
+    let mut b = b.split('.');
+    loop {
+        return match (a.next(), b.next()) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (Some(a), Some(b)) => {
+                // Numeric identifiers are lower than alphanumeric ones
+                let ordering = match (a.parse::<u64>(), b.parse::<u64>()) {
+                    (Ok(a), Ok(b)) => a.cmp(&b),
+                    (Ok(_), Err(_)) => Ordering::Less,
+                    (Err(_), Ok(_)) => Ordering::Greater,
+                    (Err(_), Err(_)) => a.cmp(b),
+                };
+                if ordering == Ordering::Equal {
+                    continue;
+                }
+                ordering
+            }
+        };
+    }
+}
+
 /// Check to see if we support the processor, taken straight out of the mdbook book
 fn handle_supports(pre: impl Preprocessor, sub_args: &ArgMatches) -> Result<()> {
     let renderer = sub_args.value_of("renderer").expect("Required argument");
@@ -58,3 +539,1146 @@ fn handle_supports(pre: impl Preprocessor, sub_args: &ArgMatches) -> Result<()>
         Err(anyhow::anyhow!("Unsupported renderer {}", renderer))
     }
 }
+
+fn book_dir_arg() -> Arg<'static, 'static> {
+    Arg::with_name("book-dir")
+        .long("book-dir")
+        .takes_value(true)
+        .default_value(".")
+        .help("Directory of the book whose book.toml configures the preprocessor")
+}
+
+/// Reads the options from the `book.toml` of the given book, falling back to the defaults.
+fn load_options(book_dir: &Path) -> Result<GitInfoOptions> {
+    let config_file = book_dir.join("book.toml");
+    if !config_file.exists() {
+        return Ok(GitInfoOptions::default());
+    }
+    let config = Config::from_disk(&config_file)
+        .with_context(|| format!("Cannot read {}", config_file.display()))?;
+    GitInfoOptions::from_config(&config, "git-info")
+}
+
+/// Prints everything the preprocessor computes for a single file
+fn handle_inspect(sub_args: &ArgMatches) -> Result<()> {
+    let path = Path::new(sub_args.value_of("path").expect("Required argument"));
+    let options = load_options(Path::new(
+        sub_args.value_of("book-dir").expect("Has default"),
+    ))?;
+
+    let resolved = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let command = git_history::extract_command_line(&resolved, &options);
+    let history = git_history::extract(&resolved, &options)
+        .with_context(|| format!("Cannot extract the git history of {}", path.display()))?;
+    let info = GitInfo::from_history(&history, &options);
+
+    if sub_args.is_present("json") {
+        let output = serde_json::json!({
+            "path": resolved,
+            "command": command,
+            "entries": history,
+            "info": info,
+        });
+        serde_json::to_writer_pretty(io::stdout(), &output)?;
+        println!();
+        return Ok(());
+    }
+
+    println!("Path: {}", resolved.display());
+    println!("Command: {}", command);
+    println!("Entries: {}", history.len());
+    for entry in &history {
+        println!(
+            "  {} {} {} <{}> {}",
+            entry.short_hash,
+            entry.timestamp.to_rfc3339(),
+            entry.name(options.identity),
+            match options.identity {
+                Identity::Committer => &entry.committer_email,
+                _ => &entry.email,
+            },
+            entry.subject
+        );
+    }
+    let describe = |entry: Option<&GitHistoryEntry>| {
+        entry.map_or_else(
+            || "n/a".to_string(),
+            |e| {
+                format!(
+                    "{} by {}",
+                    options.format_date(&e.timestamp),
+                    e.name(options.identity)
+                )
+            },
+        )
+    };
+    println!("Created: {}", describe(info.created.as_ref()));
+    println!("Last edit: {}", describe(info.last_edit.as_ref()));
+    println!(
+        "Other contributors: {}{}",
+        info.other_contributors.join(", "),
+        if info.truncated { " (truncated)" } else { "" }
+    );
+    println!("Commits: {}", info.commit_count);
+
+    Ok(())
+}
+
+/// Summary of a chapter printed by `stats`.
+#[derive(Serialize)]
+struct ChapterStats {
+    title: String,
+    path: PathBuf,
+    created: Option<DateTime<Utc>>,
+    last_edit: Option<DateTime<Utc>>,
+    commit_count: usize,
+    contributor_count: usize,
+}
+
+/// Summarizes the git history of every chapter of a book, except for the excluded ones and the
+/// drafts, along with the number of chapters whose history cannot be extracted
+fn chapter_stats(
+    book: &MDBook,
+    book_dir: &Path,
+    options: &GitInfoOptions,
+) -> Result<(Vec<ChapterStats>, usize)> {
+    let provider = options.provider()?;
+    let src_dir = book_dir.join(&book.config.book.src);
+
+    // Failing chapters are reported, but do not stop the listing
+    let mut stats = Vec::new();
+    let mut failures = 0;
+    for item in book.iter() {
+        let chapter = match item {
+            BookItem::Chapter(chapter) => chapter,
+            _ => continue,
+        };
+        let source_path = match &chapter.source_path {
+            Some(path) if !options.is_excluded(path) => path,
+            _ => continue,
+        };
+
+        let history = match provider.file_history(&src_dir.join(source_path), options) {
+            Ok(history) => history,
+            Err(e) => {
+                error!("Chapter {}: {:#}", chapter.name, e);
+                failures += 1;
+                continue;
+            }
+        };
+        let info = GitInfo::from_history(&history, options);
+        stats.push(ChapterStats {
+            title: chapter.name.clone(),
+            path: source_path.clone(),
+            created: info.created.map(|e| e.timestamp),
+            last_edit: info.last_edit.map(|e| e.timestamp),
+            commit_count: info.commit_count,
+            contributor_count: history
+                .iter()
+                .map(|e| e.name(options.identity))
+                .collect::<HashSet<_>>()
+                .len(),
+        });
+    }
+
+    Ok((stats, failures))
+}
+
+/// Prints a summary of the git history of every chapter
+fn handle_stats(sub_args: &ArgMatches) -> Result<()> {
+    let book_dir = Path::new(sub_args.value_of("book-dir").expect("Has default"));
+    let stale_days = sub_args
+        .value_of("stale-days")
+        .map(|days| {
+            days.parse::<u32>()
+                .context("--stale-days must be a number of days")
+        })
+        .transpose()?;
+
+    let book = MDBook::load(book_dir)
+        .with_context(|| format!("Cannot load the book in {}", book_dir.display()))?;
+    let options = GitInfoOptions::from_config(&book.config, "git-info")?;
+    let (mut stats, failures) = chapter_stats(&book, book_dir, &options)?;
+    select_stats(
+        &mut stats,
+        stale_days,
+        sub_args.value_of("sort"),
+        Utc::now(),
+    );
+
+    if sub_args.is_present("json") {
+        serde_json::to_writer_pretty(io::stdout(), &stats)?;
+        println!();
+    } else {
+        let date = |date: Option<DateTime<Utc>>| {
+            date.map_or_else(|| "n/a".to_string(), |date| options.format_date(&date))
+        };
+        let mut rows = vec![[
+            "Chapter".to_string(),
+            "Path".to_string(),
+            "Created".to_string(),
+            "Last edit".to_string(),
+            "Commits".to_string(),
+            "Contributors".to_string(),
+        ]];
+        rows.extend(stats.iter().map(|chapter| {
+            [
+                chapter.title.clone(),
+                chapter.path.display().to_string(),
+                date(chapter.created),
+                date(chapter.last_edit),
+                chapter.commit_count.to_string(),
+                chapter.contributor_count.to_string(),
+            ]
+        }));
+        print_table(&rows);
+    }
+
+    if failures > 0 {
+        return Err(anyhow::anyhow!(
+            "Cannot extract the git history of {} chapter(s)",
+            failures
+        ));
+    }
+    Ok(())
+}
+
+/// Keeps the chapters not edited in the `stale_days` before `now`, if given, and sorts them by the
+/// column `sort`, if any
+fn select_stats(
+    stats: &mut Vec<ChapterStats>,
+    stale_days: Option<u32>,
+    sort: Option<&str>,
+    now: DateTime<Utc>,
+) {
+    if let Some(days) = stale_days {
+        // Nothing can be older than a threshold too far in the past to be represented
+        let threshold = now.checked_sub_signed(Duration::days(days.into()));
+        stats.retain(|chapter| {
+            threshold
+                .is_some_and(|threshold| chapter.last_edit.is_none_or(|edited| edited < threshold))
+        });
+    }
+    match sort {
+        Some("title") => stats.sort_by(|a, b| a.title.cmp(&b.title)),
+        Some("path") => stats.sort_by(|a, b| a.path.cmp(&b.path)),
+        Some("created") => stats.sort_by_key(|chapter| chapter.created),
+        Some("last-edit") => stats.sort_by_key(|chapter| chapter.last_edit),
+        Some("commits") => stats.sort_by_key(|chapter| chapter.commit_count),
+        Some("contributors") => stats.sort_by_key(|chapter| chapter.contributor_count),
+        _ => {}
+    }
+}
+
+/// Prints rows of cells as left-aligned columns
+fn print_table<const N: usize>(rows: &[[String; N]]) {
+    let mut widths = [0; N];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in rows {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+}
+
+/// Exit code of `lint` when a rule fails, distinct from the one of the errors of the command.
+const LINT_FAILURE_EXIT_CODE: u8 = 2;
+
+/// Error of `lint` when rules fail, mapped to [`LINT_FAILURE_EXIT_CODE`] by `main`.
+#[derive(Debug)]
+struct LintFailure {
+    errors: usize,
+}
+
+impl fmt::Display for LintFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} lint error(s)", self.errors)
+    }
+}
+
+impl std::error::Error for LintFailure {}
+
+/// A chapter breaking a rule of `lint`.
+#[derive(Serialize)]
+struct Violation {
+    title: String,
+    path: PathBuf,
+    rule: &'static str,
+    level: LintLevel,
+    /// Offending value, if the rule has one
+    value: Option<i64>,
+    /// Limit set by the rule, if any
+    limit: Option<i64>,
+    message: String,
+}
+
+/// Checks the chapters against the lint rules, failing with [`LintFailure`] if any fails
+fn handle_lint(sub_args: &ArgMatches) -> Result<()> {
+    let book_dir = Path::new(sub_args.value_of("book-dir").expect("Has default"));
+    let book = MDBook::load(book_dir)
+        .with_context(|| format!("Cannot load the book in {}", book_dir.display()))?;
+    let options = GitInfoOptions::from_config(&book.config, "git-info")?;
+
+    // Flags override the rules of book.toml
+    let mut rules = options.lint.clone();
+    if let Some(days) = sub_args.value_of("max-age-days") {
+        rules.max_age_days = Some(
+            days.parse::<u32>()
+                .context("--max-age-days must be a number of days")?,
+        );
+    }
+    if sub_args.is_present("require-history") {
+        rules.require_history = true;
+    }
+    if let Some(count) = sub_args.value_of("min-contributors") {
+        rules.min_contributors = match count.parse::<usize>() {
+            Ok(count) if count > 0 => Some(count),
+            _ => anyhow::bail!("--min-contributors must be a positive number"),
+        };
+    }
+    match sub_args.value_of("min-contributors-level") {
+        Some("warn") => rules.min_contributors_level = LintLevel::Warn,
+        Some("error") => rules.min_contributors_level = LintLevel::Error,
+        _ => {}
+    }
+
+    let (stats, failures) = chapter_stats(&book, book_dir, &options)?;
+    if failures > 0 {
+        return Err(anyhow::anyhow!(
+            "Cannot extract the git history of {} chapter(s)",
+            failures
+        ));
+    }
+
+    let violations = lint_violations(&stats, &rules, &options, Utc::now());
+    let errors = violations
+        .iter()
+        .filter(|violation| violation.level == LintLevel::Error)
+        .count();
+    if sub_args.value_of("format") == Some("json") {
+        serde_json::to_writer_pretty(io::stdout(), &violations)?;
+        println!();
+    } else {
+        for violation in &violations {
+            println!(
+                "{}: {} ({}): {}",
+                match violation.level {
+                    LintLevel::Warn => "warning",
+                    _ => "error",
+                },
+                violation.path.display(),
+                violation.title,
+                violation.message
+            );
+        }
+        println!(
+            "Checked {} chapters: {} error(s), {} warning(s)",
+            stats.len(),
+            errors,
+            violations.len() - errors
+        );
+    }
+
+    if errors > 0 {
+        return Err(LintFailure { errors }.into());
+    }
+    Ok(())
+}
+
+/// Checks the chapters against the lint rules at the time `now`, in book order
+fn lint_violations(
+    stats: &[ChapterStats],
+    rules: &Lint,
+    options: &GitInfoOptions,
+    now: DateTime<Utc>,
+) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    for chapter in stats {
+        let mut violation = |rule, level, value, limit: Option<i64>, message| {
+            violations.push(Violation {
+                title: chapter.title.clone(),
+                path: chapter.path.clone(),
+                rule,
+                level,
+                value,
+                limit,
+                message,
+            })
+        };
+
+        let edited = match chapter.last_edit {
+            Some(edited) => edited,
+            None => {
+                if rules.require_history {
+                    violation(
+                        "require-history",
+                        LintLevel::Error,
+                        None,
+                        None,
+                        "no git history".to_string(),
+                    );
+                }
+                continue;
+            }
+        };
+        if let Some(max_age) = rules.max_age_days {
+            let age = (now - edited).num_days();
+            if age > i64::from(max_age) {
+                violation(
+                    "max-age-days",
+                    LintLevel::Error,
+                    Some(age),
+                    Some(max_age.into()),
+                    format!(
+                        "last edited {} days ago ({}), more than {}",
+                        age,
+                        options.format_date(&edited),
+                        max_age
+                    ),
+                );
+            }
+        }
+        if let Some(min) = rules.min_contributors {
+            if chapter.contributor_count < min {
+                violation(
+                    "min-contributors",
+                    rules.min_contributors_level,
+                    Some(chapter.contributor_count as i64),
+                    Some(min as i64),
+                    format!(
+                        "{} contributor(s), fewer than {}",
+                        chapter.contributor_count, min
+                    ),
+                );
+            }
+        }
+    }
+
+    violations
+}
+
+/// Commented-out examples of the main options, added along with the preprocessor table.
+const INSTALL_TEMPLATE: &str = "\
+# Options of mdbook-git-info, with example values
+# date_format = \"%d %b %Y\"
+# exclude = []
+# renderers = [\"html\", \"markdown\", \"epub\"]
+# output = \"markdown\"
+# max_history = 100
+# cache_file = \".git-info-cache.json\"
+";
+
+/// Adds the preprocessor table to book.toml, appending text to leave the rest of the file untouched
+fn handle_install(sub_args: &ArgMatches) -> Result<()> {
+    let config_file = Path::new(sub_args.value_of("dir").expect("Has default")).join("book.toml");
+    let original = fs::read_to_string(&config_file)
+        .with_context(|| format!("Cannot read {}", config_file.display()))?;
+    let css = sub_args.value_of("css");
+
+    let updated = install(&original, css)
+        .with_context(|| format!("Cannot update {}", config_file.display()))?;
+    if updated == original {
+        println!("{} is already set up", config_file.display());
+        return Ok(());
+    }
+
+    if sub_args.is_present("dry-run") {
+        print_additions(&original, &updated);
+    } else {
+        fs::write(&config_file, &updated)
+            .with_context(|| format!("Cannot write {}", config_file.display()))?;
+        println!("Updated {}", config_file.display());
+    }
+    Ok(())
+}
+
+/// Returns the contents of book.toml with the preprocessor (and stylesheet) configured.
+///
+/// Lines are only ever added, so comments and formatting are preserved.
+fn install(original: &str, css: Option<&str>) -> Result<String> {
+    let parsed = original
+        .parse::<toml::Value>()
+        .context("book.toml is not valid TOML")?;
+    let get = |keys: &[&str]| keys.iter().try_fold(&parsed, |value, key| value.get(key));
+
+    let mut lines = original.lines().map(str::to_string).collect::<Vec<_>>();
+    let mut appended = Vec::new();
+
+    if get(&["preprocessor", "git-info"]).is_none() {
+        appended.push("[preprocessor.git-info]".to_string());
+        if let Some(css) = css {
+            appended.push(format!("write_css = {}", toml::Value::from(css)));
+        }
+        appended.extend(INSTALL_TEMPLATE.lines().map(str::to_string));
+    }
+
+    if let Some(css) = css {
+        let css_entry = toml::Value::from(css);
+        match get(&["output", "html"]) {
+            None => {
+                appended.push(String::new());
+                appended.push("[output.html]".to_string());
+                appended.push(format!("additional-css = [{}]", css_entry));
+            }
+            Some(html) => match html.get("additional-css") {
+                None => {
+                    // Only a table header can be extended reliably without a TOML editor
+                    let header = lines
+                        .iter()
+                        .position(|line| line.trim() == "[output.html]")
+                        .context("Cannot locate the [output.html] table")?;
+                    lines.insert(header + 1, format!("additional-css = [{}]", css_entry));
+                }
+                Some(list)
+                    if list
+                        .as_array()
+                        .is_some_and(|list| list.contains(&css_entry)) => {}
+                Some(_) => warn!("Add {} to output.html.additional-css by hand", css_entry),
+            },
+        }
+    }
+
+    if !appended.is_empty() {
+        if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+            lines.push(String::new());
+        }
+        lines.extend(appended);
+    }
+
+    if lines.len() == original.lines().count() {
+        // Nothing was added: keep the file byte for byte
+        return Ok(original.to_string());
+    }
+    // Windows editors save with CRLF, which must not be mixed with LF
+    let newline = match original.find('\n') {
+        Some(end) if original[..end].ends_with('\r') => "\r\n",
+        _ => "\n",
+    };
+    let mut updated = lines.join(newline);
+    updated.push_str(newline);
+    updated
+        .parse::<toml::Value>()
+        .context("The updated book.toml would not be valid TOML")?;
+    Ok(updated)
+}
+
+/// Prints the lines of `updated` missing from `original`, which can only differ by added lines
+fn print_additions(original: &str, updated: &str) {
+    let mut original = original.lines().peekable();
+    for line in updated.lines() {
+        if original.peek() == Some(&line) {
+            original.next();
+            println!("  {}", line);
+        } else {
+            println!("+ {}", line);
+        }
+    }
+}
+
+/// Extracts the histories of every chapter into a history file
+fn handle_export_history(sub_args: &ArgMatches) -> Result<()> {
+    let book_dir = Path::new(sub_args.value_of("book-dir").expect("Has default"));
+    let out = Path::new(sub_args.value_of("out").expect("Required argument"));
+
+    let book = MDBook::load(book_dir)
+        .with_context(|| format!("Cannot load the book in {}", book_dir.display()))?;
+    let options = GitInfoOptions::from_config(&book.config, "git-info")?;
+    let src_dir = book_dir.join(&book.config.book.src);
+
+    let source_paths = book
+        .iter()
+        .filter_map(|item| match item {
+            BookItem::Chapter(chapter) => chapter.source_path.as_deref(),
+            _ => None,
+        })
+        .filter(|path| !options.is_excluded(path));
+    let history_file = HistoryFile::extract(&src_dir, source_paths, &options)?;
+    history_file.save(out)?;
+
+    info!(
+        "Wrote the histories of {} files to {}",
+        history_file.histories.len(),
+        out.display()
+    );
+    Ok(())
+}
+
+/// Runs the preprocessor on a book as a build would, and prints what it adds to each chapter
+fn handle_preview(pre: impl Preprocessor, sub_args: &ArgMatches) -> Result<()> {
+    let book_dir = Path::new(sub_args.value_of("book-dir").expect("Has default"));
+    let renderer = sub_args.value_of("renderer").expect("Has default");
+    let filter = sub_args
+        .value_of("chapter")
+        .map(|pattern| {
+            glob::Pattern::new(pattern).with_context(|| format!("Invalid --chapter {:?}", pattern))
+        })
+        .transpose()?;
+
+    let mut book = MDBook::load(book_dir)
+        .with_context(|| format!("Cannot load the book in {}", book_dir.display()))?;
+
+    // Files written alongside the chapters are left alone, since nothing is built
+    if let Some(toml::Value::Table(table)) = book.config.get_mut("preprocessor.git-info") {
+        for key in ["metadata_output", "feed", "sitemap", "write_css"] {
+            table.remove(key);
+        }
+    }
+    let ctx = serde_json::from_value::<PreprocessorContext>(serde_json::json!({
+        "root": book.root,
+        "config": book.config,
+        "renderer": renderer,
+        "mdbook_version": mdbook::MDBOOK_VERSION,
+    }))
+    .context("Cannot create the preprocessor context")?;
+
+    let original = book
+        .book
+        .iter()
+        .filter_map(|item| match item {
+            BookItem::Chapter(chapter) => Some((chapter.path.clone()?, chapter.content.clone())),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+    let processed = pre.run(&ctx, book.book.clone())?;
+
+    for item in processed.iter() {
+        let chapter = match item {
+            BookItem::Chapter(chapter) => chapter,
+            _ => continue,
+        };
+        let path = match &chapter.path {
+            Some(path) => path,
+            None => continue,
+        };
+        let source_path = chapter.source_path.as_deref().unwrap_or(path);
+        if filter
+            .as_ref()
+            .is_some_and(|filter| !filter.matches_path(source_path))
+        {
+            continue;
+        }
+
+        // Generated chapters are entirely new
+        let before = original.get(path).map_or("", String::as_str);
+        if before == chapter.content {
+            continue;
+        }
+        if sub_args.is_present("diff") {
+            print!("{}", diff(source_path, before, &chapter.content));
+        } else {
+            println!("==> {} ({})", chapter.name, source_path.display());
+            println!("{}", injected(before, &chapter.content).trim_matches('\n'));
+            println!();
+        }
+    }
+    Ok(())
+}
+
+/// Returns the part of `after` which is not in `before`, assuming a single insertion or replacement
+fn injected<'a>(before: &str, after: &'a str) -> &'a str {
+    let prefix = before
+        .char_indices()
+        .zip(after.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(before.len().min(after.len()), |((i, _), _)| i);
+    let suffix = before[prefix..]
+        .chars()
+        .rev()
+        .zip(after[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum::<usize>();
+    &after[prefix..after.len() - suffix]
+}
+
+/// Returns the unified diff of two versions of a chapter, with three lines of context
+fn diff(path: &Path, before: &str, after: &str) -> String {
+    const CONTEXT: usize = 3;
+    let before = before.lines().collect::<Vec<_>>();
+    let after = after.lines().collect::<Vec<_>>();
+
+    // Longest common subsequence of the lines between the common prefix and suffix
+    let prefix = before
+        .iter()
+        .zip(&after)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = before[prefix..]
+        .iter()
+        .rev()
+        .zip(after[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old, new) = (
+        &before[prefix..before.len() - suffix],
+        &after[prefix..after.len() - suffix],
+    );
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    // Each line of the diff is a marker, a line of `before` or `after`, and the text
+    let mut lines = (0..prefix)
+        .map(|i| (' ', i, i, before[i]))
+        .collect::<Vec<_>>();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', prefix + i, prefix + j, old[i]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            lines.push(('+', prefix + i, prefix + j, new[j]));
+            j += 1;
+        } else {
+            lines.push(('-', prefix + i, prefix + j, old[i]));
+            i += 1;
+        }
+    }
+    lines.extend((0..suffix).map(|k| {
+        let (i, j) = (before.len() - suffix + k, after.len() - suffix + k);
+        (' ', i, j, before[i])
+    }));
+
+    let mut diff = format!("--- a/{}\n+++ b/{}\n", path.display(), path.display());
+    let changes = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.0 != ' ')
+        .map(|(k, _)| k)
+        .collect::<Vec<_>>();
+    let mut k = 0;
+    while k < changes.len() {
+        // Changes closer than twice the context share a hunk
+        let start = changes[k].saturating_sub(CONTEXT);
+        let mut end = changes[k];
+        while k < changes.len() && changes[k] <= end + 2 * CONTEXT {
+            end = changes[k];
+            k += 1;
+        }
+        let hunk = &lines[start..(end + CONTEXT + 1).min(lines.len())];
+        let count = |marker| {
+            hunk.iter()
+                .filter(|line| line.0 == ' ' || line.0 == marker)
+                .count()
+        };
+        let (old_count, new_count) = (count('-'), count('+'));
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            hunk[0].1 + usize::from(old_count > 0),
+            old_count,
+            hunk[0].2 + usize::from(new_count > 0),
+            new_count
+        ));
+        for (marker, _, _, text) in hunk {
+            diff.push_str(&format!("{}{}\n", marker, text));
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOOK_TOML: &str = "[book]\ntitle = \"Book\"\n\n[preprocessor.links]\n\n[preprocessor.index]\nbefore = [\"links\"]\n";
+
+    #[test]
+    fn install_appends_the_preprocessor() {
+        let updated = install(BOOK_TOML, None).unwrap();
+
+        let (kept, added) = updated.split_at(BOOK_TOML.len());
+        assert_eq!(kept, BOOK_TOML);
+        assert!(added.starts_with("\n[preprocessor.git-info]\n# Options of mdbook-git-info"));
+        let parsed = updated.parse::<toml::Value>().unwrap();
+        assert!(parsed["preprocessor"].get("links").is_some());
+        assert!(parsed["preprocessor"].get("index").is_some());
+        assert!(parsed["preprocessor"].get("git-info").is_some());
+    }
+
+    #[test]
+    fn install_is_idempotent() {
+        let css = Some("theme/git-info.css");
+        let updated = install(BOOK_TOML, css).unwrap();
+
+        assert_eq!(install(&updated, css).unwrap(), updated);
+        assert_eq!(install(&updated, None).unwrap(), updated);
+        let parsed = updated.parse::<toml::Value>().unwrap();
+        assert_eq!(
+            parsed["preprocessor"]["git-info"]["write_css"].as_str(),
+            css
+        );
+        assert_eq!(
+            parsed["output"]["html"]["additional-css"],
+            toml::Value::from(vec!["theme/git-info.css"])
+        );
+    }
+
+    #[test]
+    fn install_extends_an_existing_html_output() {
+        let original = format!("{}\n[output.html]\ndefault-theme = \"light\"\n", BOOK_TOML);
+
+        let updated = install(&original, Some("git-info.css")).unwrap();
+
+        assert!(
+            updated.contains("[output.html]\nadditional-css = [\"git-info.css\"]\ndefault-theme")
+        );
+        assert_eq!(updated.matches("[output.html]").count(), 1);
+    }
+
+    #[test]
+    fn install_keeps_crlf_line_endings() {
+        let original = BOOK_TOML.replace('\n', "\r\n");
+
+        let updated = install(&original, Some("git-info.css")).unwrap();
+
+        assert!(updated.starts_with(&original));
+        assert!(updated.contains("[preprocessor.git-info]\r\n"));
+        assert_eq!(
+            updated.matches('\n').count(),
+            updated.matches("\r\n").count()
+        );
+        assert_eq!(install(&updated, Some("git-info.css")).unwrap(), updated);
+    }
+
+    #[test]
+    fn equal_versions_are_compatible() {
+        assert!(is_compatible("0.4.15", "0.4.15"));
+        assert!(is_compatible("1.2.3", "1.2.3"));
+    }
+
+    #[test]
+    fn newer_patches_are_compatible() {
+        assert!(is_compatible("0.4.15", "0.4.40"));
+        assert!(is_compatible("1.2.3", "1.2.4"));
+        assert!(!is_compatible("0.0.3", "0.0.4"));
+    }
+
+    #[test]
+    fn newer_minors_are_compatible_after_0_x() {
+        assert!(is_compatible("1.2.3", "1.3.0"));
+        assert!(!is_compatible("0.4.15", "0.5.0"));
+        assert!(!is_compatible("1.2.3", "2.0.0"));
+    }
+
+    #[test]
+    fn older_versions_are_incompatible() {
+        assert!(!is_compatible("0.4.15", "0.4.14"));
+        assert!(!is_compatible("1.2.3", "1.1.9"));
+        assert!(!is_compatible("1.2.3", "0.9.0"));
+    }
+
+    #[test]
+    fn pre_releases_only_satisfy_pre_releases_of_the_same_version() {
+        assert!(is_compatible("0.5.0-alpha.1", "0.5.0-alpha.1"));
+        assert!(is_compatible("0.5.0-alpha.1", "0.5.0-alpha.2"));
+        assert!(is_compatible("0.5.0-alpha.1", "0.5.0-beta"));
+        assert!(is_compatible("0.5.0-alpha.1", "0.5.0"));
+        assert!(!is_compatible("0.5.0-beta", "0.5.0-alpha.2"));
+        assert!(!is_compatible("0.4.15", "0.4.16-rc.1"));
+        assert!(!is_compatible("1.0.0-rc.1", "1.0.1-rc.1"));
+    }
+
+    #[test]
+    fn unparsable_versions_are_incompatible() {
+        assert!(!is_compatible("0.4.15", "0.4"));
+        assert!(!is_compatible("latest", "0.4.15"));
+    }
+
+    #[test]
+    fn versions_are_parsed_without_build_metadata() {
+        assert_eq!(parse_version("0.4.15"), Some(([0, 4, 15], None)));
+        assert_eq!(parse_version(" 1.2.3\n"), Some(([1, 2, 3], None)));
+        assert_eq!(
+            parse_version("1.0.0-rc.1+build.5"),
+            Some(([1, 0, 0], Some("rc.1")))
+        );
+        assert_eq!(parse_version("1.0.0+build-5"), Some(([1, 0, 0], None)));
+        assert_eq!(parse_version("1.2"), None);
+        assert_eq!(parse_version("1.2.3.4"), None);
+        assert_eq!(parse_version("1.x.3"), None);
+    }
+
+    #[test]
+    fn pre_releases_follow_semver_precedence() {
+        assert_eq!(compare_pre_release("alpha", "alpha"), Ordering::Equal);
+        assert_eq!(compare_pre_release("alpha", "alpha.1"), Ordering::Less);
+        assert_eq!(compare_pre_release("alpha.1", "alpha.beta"), Ordering::Less);
+        assert_eq!(compare_pre_release("alpha.2", "alpha.10"), Ordering::Less);
+        assert_eq!(compare_pre_release("beta", "alpha.1"), Ordering::Greater);
+        assert_eq!(compare_pre_release("rc.1", "beta.11"), Ordering::Greater);
+    }
+
+    fn date(text: &str) -> DateTime<Utc> {
+        text.parse().unwrap()
+    }
+
+    fn chapter(title: &str, last_edit: Option<&str>, commits: usize) -> ChapterStats {
+        ChapterStats {
+            title: title.to_string(),
+            path: PathBuf::from(format!("{}.md", title.to_lowercase())),
+            created: last_edit.map(|_| date("2023-01-01T00:00:00Z")),
+            last_edit: last_edit.map(date),
+            commit_count: commits,
+            contributor_count: commits.min(3),
+        }
+    }
+
+    #[test]
+    fn overrides_are_typed_like_book_toml() {
+        let mut config = Config::default();
+        apply_overrides(
+            &mut config,
+            &[
+                "max_history=5",
+                " date_format = %Y-%m-%d",
+                "renderers=[\"html\", \"epub\"]",
+                "feed.site_url=https://example.com",
+                "feed.title=\"Quoted\"",
+            ],
+        )
+        .unwrap();
+
+        let option = |key: &str| {
+            config
+                .get(&format!("preprocessor.git-info.{}", key))
+                .cloned()
+        };
+        assert_eq!(option("max_history"), Some(toml::Value::Integer(5)));
+        assert_eq!(option("date_format"), Some(toml::Value::from("%Y-%m-%d")));
+        assert_eq!(
+            option("feed.site_url"),
+            Some(toml::Value::from("https://example.com"))
+        );
+
+        let options = GitInfoOptions::from_config(&config, "git-info").unwrap();
+        assert_eq!(options.max_history, Some(5));
+        assert_eq!(options.date_format, "%Y-%m-%d");
+        assert_eq!(options.renderers, ["html", "epub"]);
+        let feed = options.feed.unwrap();
+        assert_eq!(feed.site_url.as_deref(), Some("https://example.com"));
+        assert_eq!(feed.title.as_deref(), Some("Quoted"));
+    }
+
+    #[test]
+    fn later_overrides_win() {
+        let mut config = Config::default();
+        config
+            .set("preprocessor.git-info.date_format", "%B %Y")
+            .unwrap();
+        apply_overrides(&mut config, &["date_format=%Y", "date_format=%d/%m/%Y"]).unwrap();
+        let options = GitInfoOptions::from_config(&config, "git-info").unwrap();
+        assert_eq!(options.date_format, "%d/%m/%Y");
+    }
+
+    #[test]
+    fn unknown_overrides_are_rejected() {
+        let error = |assignment| {
+            apply_overrides(&mut Config::default(), &[assignment])
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(error("colour=red").contains("unknown option \"colour\""));
+        assert!(error("colour.shade=dark").contains("unknown option \"colour\""));
+        assert!(error("max_history").contains("expected KEY=VALUE"));
+    }
+
+    #[test]
+    fn injected_text_is_found_anywhere() {
+        assert_eq!(injected("# Intro\n", "# Intro\n\nfooter\n"), "\nfooter\n");
+        assert_eq!(injected("a\nc\n", "a\nb\nc\n"), "b\n");
+        assert_eq!(injected("", "# New\n"), "# New\n");
+        assert_eq!(injected("same", "same"), "");
+        // Replacements only return the new text, and never split characters
+        assert_eq!(injected("date: {{x}} é", "date: 1 Jan é"), "1 Jan");
+        assert_eq!(injected("café", "cafés"), "s");
+    }
+
+    #[test]
+    fn diffs_have_unified_hunk_headers() {
+        assert_eq!(
+            diff(Path::new("intro.md"), "a\nb\nc\n", "a\nb\nc\n\nfooter\n"),
+            "--- a/intro.md\n+++ b/intro.md\n@@ -1,3 +1,5 @@\n a\n b\n c\n+\n+footer\n"
+        );
+        assert_eq!(
+            diff(Path::new("new.md"), "", "# New\n"),
+            "--- a/new.md\n+++ b/new.md\n@@ -0,0 +1,1 @@\n+# New\n"
+        );
+
+        // Distant changes get a hunk each, with three lines of context
+        let before = (1..=20).map(|i| format!("{}\n", i)).collect::<String>();
+        let after = before
+            .replace("\n2\n", "\ntwo\n")
+            .replace("\n19\n", "\nnineteen\n");
+        let diff = diff(Path::new("long.md"), &before, &after);
+        let headers = diff
+            .lines()
+            .filter(|line| line.starts_with("@@"))
+            .collect::<Vec<_>>();
+        assert_eq!(headers, ["@@ -1,5 +1,5 @@", "@@ -16,5 +16,5 @@"]);
+        assert!(!diff.contains("\n 6\n"));
+        assert!(diff.contains("\n 16\n"));
+    }
+
+    #[test]
+    fn lint_rules_are_evaluated_per_chapter() {
+        let mut rules = Lint::default();
+        rules.max_age_days = Some(30);
+        rules.require_history = true;
+        rules.min_contributors = Some(2);
+        rules.min_contributors_level = LintLevel::Warn;
+        let stats = [
+            chapter("Fresh", Some("2024-05-20T00:00:00Z"), 2),
+            chapter("Stale", Some("2024-01-01T00:00:00Z"), 1),
+            // Exactly at the limit
+            chapter("Limit", Some("2024-05-02T00:00:00Z"), 2),
+            chapter("Empty", None, 0),
+        ];
+
+        let violations = lint_violations(
+            &stats,
+            &rules,
+            &GitInfoOptions::default(),
+            date("2024-06-01T00:00:00Z"),
+        );
+
+        let summary = violations
+            .iter()
+            .map(|v| (v.title.as_str(), v.rule, v.level, v.value, v.limit))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (
+                    "Stale",
+                    "max-age-days",
+                    LintLevel::Error,
+                    Some(152),
+                    Some(30)
+                ),
+                (
+                    "Stale",
+                    "min-contributors",
+                    LintLevel::Warn,
+                    Some(1),
+                    Some(2)
+                ),
+                ("Empty", "require-history", LintLevel::Error, None, None),
+            ]
+        );
+        assert_eq!(
+            violations[0].message,
+            "last edited 152 days ago (01 Jan 2024), more than 30"
+        );
+    }
+
+    #[test]
+    fn lint_rules_are_off_by_default() {
+        let stats = [
+            chapter("Stale", Some("2000-01-01T00:00:00Z"), 1),
+            chapter("Empty", None, 0),
+        ];
+        let violations = lint_violations(
+            &stats,
+            &Lint::default(),
+            &GitInfoOptions::default(),
+            date("2024-06-01T00:00:00Z"),
+        );
+        assert!(violations.is_empty());
+    }
+
+    #[test]
+    fn lint_failures_are_typed() {
+        let error = anyhow::Error::from(LintFailure { errors: 2 });
+        assert!(error.is::<LintFailure>());
+        assert_eq!(error.to_string(), "2 lint error(s)");
+    }
+
+    #[test]
+    fn stats_keep_the_stale_chapters() {
+        let mut stats = vec![
+            chapter("Fresh", Some("2024-05-20T00:00:00Z"), 2),
+            chapter("Stale", Some("2024-01-01T00:00:00Z"), 1),
+            chapter("Empty", None, 0),
+        ];
+        select_stats(&mut stats, Some(30), None, date("2024-06-01T00:00:00Z"));
+        let titles = stats.iter().map(|c| c.title.as_str()).collect::<Vec<_>>();
+        assert_eq!(titles, ["Stale", "Empty"]);
+
+        // Thresholds before the representable dates keep nothing
+        select_stats(
+            &mut stats,
+            Some(u32::MAX),
+            None,
+            date("2024-06-01T00:00:00Z"),
+        );
+        assert!(stats.is_empty());
+    }
+
+    #[test]
+    fn stats_are_sorted_by_column() {
+        let stats = || {
+            vec![
+                chapter("Beta", Some("2024-05-20T00:00:00Z"), 2),
+                chapter("Alpha", Some("2024-01-01T00:00:00Z"), 5),
+                chapter("Gamma", None, 0),
+                chapter("Delta", Some("2024-03-01T00:00:00Z"), 2),
+            ]
+        };
+        let sorted = |sort| {
+            let mut stats = stats();
+            select_stats(&mut stats, None, sort, Utc::now());
+            stats.into_iter().map(|c| c.title).collect::<Vec<_>>()
+        };
+        assert_eq!(sorted(None), ["Beta", "Alpha", "Gamma", "Delta"]);
+        assert_eq!(sorted(Some("title")), ["Alpha", "Beta", "Delta", "Gamma"]);
+        assert_eq!(sorted(Some("path")), ["Alpha", "Beta", "Delta", "Gamma"]);
+        // Chapters without history come first, ties keep the book order
+        assert_eq!(
+            sorted(Some("last-edit")),
+            ["Gamma", "Alpha", "Delta", "Beta"]
+        );
+        assert_eq!(sorted(Some("commits")), ["Gamma", "Beta", "Delta", "Alpha"]);
+        assert_eq!(
+            sorted(Some("contributors")),
+            ["Gamma", "Beta", "Delta", "Alpha"]
+        );
+    }
+
+    #[test]
+    fn reports_escape_titles_and_cells() {
+        let report = ChapterReport {
+            title: "Q&A <draft>".to_string(),
+            path: PathBuf::from("q&a.md"),
+            created: Some(date("2024-01-10T12:00:00Z")),
+            created_by: Some("Alice".to_string()),
+            last_edit: None,
+            last_edit_by: None,
+            contributors: vec!["Alice".to_string(), "Bob <b>".to_string()],
+            commit_count: 3,
+        };
+
+        let html = report_html("Fish & Chips", &[report], &GitInfoOptions::default());
+
+        assert!(html.contains("<title>Git info of Fish &amp; Chips</title>"));
+        assert!(html.contains(
+            "<tr><td>Q&amp;A &lt;draft&gt;</td><td>q&amp;a.md</td><td>10 Jan 2024</td>\
+             <td>Alice</td><td>n/a</td><td>n/a</td><td>Alice, Bob &lt;b&gt;</td><td>3</td></tr>"
+        ));
+    }
+}
@@ -0,0 +1,53 @@
+use crate::info::GitInfo;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Writes the git metadata of the chapters, keyed by source path, as a JSON document.
+///
+/// The file is replaced atomically, so readers never see a partial document.
+pub fn write(path: &Path, chapters: &BTreeMap<PathBuf, GitInfo>) -> Result<()> {
+    let json = serde_json::to_vec_pretty(chapters).context("Cannot serialize the metadata")?;
+    write_atomically(path, &json).with_context(|| format!("Cannot write {}", path.display()))
+}
+
+/// Writes `contents` to a temporary file next to `path`, then renames it over `path`.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Appended to the whole name, so that `feed.xml` and `feed.json` never share it
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn files_sharing_a_stem_are_written_independently() -> Result<()> {
+        let dir = TempDir::new()?;
+        let xml = dir.path().join("feed.xml");
+        let json = dir.path().join("feed.json");
+        fs::write(dir.path().join("feed.tmp"), "unrelated")?;
+
+        write_atomically(&xml, b"<feed />")?;
+        write_atomically(&json, b"{}")?;
+
+        assert_eq!(fs::read_to_string(&xml)?, "<feed />");
+        assert_eq!(fs::read_to_string(&json)?, "{}");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("feed.tmp"))?,
+            "unrelated"
+        );
+        assert!(!dir.path().join("feed.xml.tmp").exists());
+        Ok(())
+    }
+}
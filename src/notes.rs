@@ -0,0 +1,96 @@
+use crate::front_matter;
+use crate::info::Review;
+
+/// Finds the most recent review recorded in the text of a note, on lines like
+/// `Reviewed: 2024-06-01 by Alice`.
+///
+/// Parsing is lenient: the key is compared case-insensitively and followed by `:` or `=`,
+/// the reviewer is optional, and lines which do not match are ignored.
+pub fn parse_review(note: &str, key: &str) -> Option<Review> {
+    note.lines()
+        .filter_map(|line| {
+            let (line_key, value) = line.split_once([':', '='])?;
+            let line_key = line_key.trim().trim_start_matches(['-', '*']).trim();
+            if !line_key.eq_ignore_ascii_case(key.trim()) {
+                return None;
+            }
+
+            let value = value.trim();
+            let (date, rest) = value.split_once(char::is_whitespace).unwrap_or((value, ""));
+            let date = front_matter::parse_date(date.trim_end_matches(',')).ok()?;
+            let rest = rest.trim();
+            let reviewer = match rest.get(..3) {
+                Some(by) if by.eq_ignore_ascii_case("by ") => rest[3..].trim(),
+                _ => rest,
+            };
+            Some(Review {
+                date,
+                reviewer: Some(reviewer.to_string()).filter(|reviewer| !reviewer.is_empty()),
+            })
+        })
+        .max_by_key(|review| review.date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn review(date: &str, reviewer: Option<&str>) -> Option<Review> {
+        Some(Review {
+            date: date.parse().unwrap(),
+            reviewer: reviewer.map(str::to_string),
+        })
+    }
+
+    #[test]
+    fn reviews_are_read_from_key_value_lines() {
+        assert_eq!(
+            parse_review("Reviewed: 2024-06-01 by Alice", "Reviewed"),
+            review("2024-06-01T00:00:00Z", Some("Alice"))
+        );
+        assert_eq!(
+            parse_review(
+                "  - reviewed = 2024-06-01T10:30:00+02:00, BY Bob Smith ",
+                "Reviewed"
+            ),
+            review("2024-06-01T08:30:00Z", Some("Bob Smith"))
+        );
+        assert_eq!(
+            parse_review("Reviewed: 2024-06-01 Carol", "Reviewed"),
+            review("2024-06-01T00:00:00Z", Some("Carol"))
+        );
+        assert_eq!(
+            parse_review("Reviewed: 2024-06-01", "Reviewed"),
+            review("2024-06-01T00:00:00Z", None)
+        );
+    }
+
+    #[test]
+    fn the_most_recent_review_wins() {
+        let note = "Reviewed: 2024-01-01 by Alice\n\
+                    Some discussion: not a review\n\
+                    Reviewed: 2024-03-01 by Bob\n\
+                    Approved: 2024-05-01 by Carol\n\
+                    Reviewed: 2024-02-01 by Dave\n";
+
+        assert_eq!(
+            parse_review(note, "Reviewed"),
+            review("2024-03-01T00:00:00Z", Some("Bob"))
+        );
+        assert_eq!(
+            parse_review(note, "approved"),
+            review("2024-05-01T00:00:00Z", Some("Carol"))
+        );
+    }
+
+    #[test]
+    fn notes_without_valid_reviews_have_none() {
+        assert_eq!(parse_review("", "Reviewed"), None);
+        assert_eq!(parse_review("Looks good to me", "Reviewed"), None);
+        assert_eq!(
+            parse_review("Reviewed: yesterday by Alice", "Reviewed"),
+            None
+        );
+        assert_eq!(parse_review("Reviewed by: 2024-06-01", "Reviewed"), None);
+    }
+}
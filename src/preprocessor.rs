@@ -1,92 +1,1836 @@
-use crate::git_history;
+use crate::cache::HistoryCache;
+use crate::codeowners::CodeOwners;
+use crate::config::{
+    Backend, ChapterPosition, ExtractionMode, GitInfoOptions, IgnoredCommitEffect, MissingHeading,
+    MissingHistory, PathMapMerge, ProvenancePosition, ENABLED_ENV_VAR,
+};
+use crate::copyright;
+use crate::feed;
+use crate::front_matter;
+use crate::git_history::{self, truncate_history, GitHistoryEntry};
+use crate::github::{self, CurlClient, GitHubProfile, HttpClient, ProfileResolver};
+use crate::history_file::HistoryFile;
+use crate::includes;
+use crate::info::{self, BuildProvenance, Contributor, GitInfo, PrimaryAuthor};
+use crate::metadata;
+use crate::notes;
+use crate::progress::Progress;
+use crate::provider::HistoryProvider;
+use crate::render::{self, Flavor};
+use crate::sitemap;
+use crate::stylesheet;
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
 use mdbook::book::{Book, Chapter};
 use mdbook::preprocess::{Preprocessor, PreprocessorContext};
 use mdbook::BookItem;
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Variable replaced with the date of the last edit of the whole book.
+const BOOK_LAST_EDIT_VARIABLE: &str = "{{#git_book_last_edit_date}}";
+
+/// Variable replaced with the first release containing the last edit of the chapter.
+const VERSION_VARIABLE: &str = "{{#git_version}}";
 
 /// Preprocessor for mdBook that extracts info from the git metadata of each chapter of the book.
-pub struct GitInfoPreprocessor;
+#[derive(Debug, Default)]
+pub struct GitInfoPreprocessor {
+    options: GitInfoOptions,
+    http_client: Option<Arc<dyn HttpClient>>,
+    provider: Option<Arc<dyn HistoryProvider>>,
+}
 
 impl GitInfoPreprocessor {
+    /// Creates a preprocessor configured only through `book.toml`.
     pub fn new() -> GitInfoPreprocessor {
-        GitInfoPreprocessor
+        GitInfoPreprocessor::default()
     }
-}
 
-impl Preprocessor for GitInfoPreprocessor {
-    fn name(&self) -> &str {
-        "git-info"
+    /// Creates a preprocessor with programmatically supplied options.
+    ///
+    /// Options set in `book.toml` take precedence over these.
+    pub fn with_options(options: GitInfoOptions) -> GitInfoPreprocessor {
+        GitInfoPreprocessor {
+            options,
+            http_client: None,
+            provider: None,
+        }
     }
 
-    fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book> {
-        // Visit each chapter of the book and accumulate and stop at the first error
-        let mut error = None;
+    /// Sends the requests to the GitHub API (see [`GitInfoOptions::github_api`]) with `client`,
+    /// rather than with curl.
+    pub fn with_http_client(mut self, client: impl HttpClient + 'static) -> GitInfoPreprocessor {
+        self.http_client = Some(Arc::new(client));
+        self
+    }
+
+    /// Reads the repository with `provider`, rather than with the backend chosen by
+    /// [`GitInfoOptions::backend`].
+    pub fn with_provider(
+        mut self,
+        provider: impl HistoryProvider + 'static,
+    ) -> GitInfoPreprocessor {
+        self.provider = Some(Arc::new(provider));
+        self
+    }
+
+    /// Runs the preprocessor like [`Preprocessor::run`], also returning the info of every enriched
+    /// chapter, keyed by source path.
+    pub fn run_with_metadata(
+        &self,
+        ctx: &PreprocessorContext,
+        mut book: Book,
+    ) -> Result<(Book, BTreeMap<PathBuf, GitInfo>)> {
+        let mut options = self.options.merge_config(&ctx.config, self.name())?;
+
+        let env = env::var(ENABLED_ENV_VAR).ok();
+        if !options.is_enabled(env.as_deref())? {
+            info!(
+                "Disabled by {}, leaving the book untouched",
+                if env.as_deref().is_some_and(|env| !env.trim().is_empty()) {
+                    ENABLED_ENV_VAR
+                } else {
+                    "the `enabled` option"
+                }
+            );
+            return Ok((book, BTreeMap::new()));
+        }
+
+        // `supports` is called before the configuration is known, so this is where renderers are filtered
+        if !options.renderers.contains(&ctx.renderer) {
+            debug!(
+                "Renderer {} is not listed in `renderers`, leaving the book untouched",
+                ctx.renderer
+            );
+            return Ok((book, BTreeMap::new()));
+        }
+
+        debug!("Options: {:?}", options);
+        let start = Instant::now();
+        let mut summary = Summary::default();
+
+        // Without git, every chapter would fail the same way: check it once, upfront
+        if self.provider.is_none()
+            && options.backend == Backend::Cli
+            && options.history_file.is_none()
+        {
+            match git_history::version(&options) {
+                Ok(version) if version < git_history::OLDEST_GIT_VERSION => {
+                    return Err(anyhow::anyhow!(
+                        "git {} is too old: mdbook-git-info needs git {} or later, \
+                         and works best with git {} or later",
+                        version,
+                        git_history::OLDEST_GIT_VERSION,
+                        git_history::MIN_GIT_VERSION
+                    ));
+                }
+                Ok(version) if version < git_history::MIN_GIT_VERSION => {
+                    warn!(
+                        "git {} is older than git {}, the oldest supported version: \
+                         the trailers of the commits, like reviewers, are read from their messages",
+                        version,
+                        git_history::MIN_GIT_VERSION
+                    );
+                    options.git_version = Some(version);
+                }
+                Ok(version) => {
+                    debug!("Using git {}", version);
+                    options.git_version = Some(version);
+                }
+                Err(e) if !options.require_git && !options.strict => {
+                    warn!(
+                        "Cannot run git, leaving the book untouched (set `require_git = true` to fail instead): {:#}",
+                        e
+                    );
+                    return Ok((book, BTreeMap::new()));
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!(
+                        "Cannot run git using {:?}. Install git (https://git-scm.com/downloads) \
+                             and make sure it is in $PATH, set `git_binary` to its location, \
+                             or set `require_git = false` to build the book without git info",
+                        options.git_binary
+                    )
+                    })
+                }
+            }
+        }
+
+        let src_dir = ctx.root.join(&ctx.config.book.src);
+        let provider = match &self.provider {
+            Some(provider) => Arc::clone(provider),
+            None => Arc::from(options.provider()?),
+        };
+        let flavor = Flavor::for_renderer(&ctx.renderer);
+
+        // Commits before the cut of a shallow clone are missing, so creation info is wrong
+        if options.history_file.is_none()
+            && timed(&mut summary.git_time, || {
+                provider.is_shallow(&src_dir, &options)
+            })
+            .unwrap_or(false)
+        {
+            warn!("The repository is a shallow clone: creation dates and authors may be wrong");
+        }
+
+        if let (Some(path), Flavor::Html) = (&options.write_css, flavor) {
+            stylesheet::write(&ctx.root.join(path))?;
+        }
+
+        // Collect the chapters to enrich, in visiting order, along with their front matter
+        let mut source_paths = Vec::new();
+        let mut front_matters = Vec::new();
+        // Chapters failing strict mode, with their resolved path and the problem
+        let mut unusable = Vec::<(String, PathBuf, &str)>::new();
         book.for_each_mut(|book_item| {
-            if error.is_some() {
-                return;
+            if let BookItem::Chapter(chapter) = book_item {
+                let front_matter = front_matter::parse(&chapter.content).unwrap_or_else(|e| {
+                    warn!(
+                        "Ignoring the invalid front matter of chapter {}: {:#}",
+                        chapter.name, e
+                    );
+                    None
+                });
+                if options.strip_front_matter {
+                    if let Some((_, _, end)) = front_matter::split(&chapter.content) {
+                        chapter.content.drain(..end);
+                    }
+                }
+
+                let source_path = if front_matter.as_ref().is_some_and(|f| f.skip) {
+                    Err(SkipReason::FrontMatter)
+                } else {
+                    source_path(chapter, &src_dir, &options)
+                };
+                if let Err(reason) = source_path {
+                    info!("Skipping chapter {}: {}", chapter.name, reason);
+                    summary.skip(reason);
+                }
+                if let (Err(SkipReason::MissingFile), Some(path)) =
+                    (&source_path, &chapter.source_path)
+                {
+                    if options.strict {
+                        unusable.push((chapter.name.clone(), src_dir.join(path), "file not found"));
+                    }
+                }
+                source_paths.push(source_path);
+                front_matters.push(front_matter);
             }
+        });
+
+        // Several chapters may point at the same file: extract each file only once.
+        // `unique` holds the source and canonical paths of each distinct file.
+        let mut unique = Vec::<(PathBuf, PathBuf)>::new();
+        let mut seen = HashMap::<PathBuf, usize>::new();
+        let indices = source_paths
+            .into_iter()
+            .map(|path| {
+                let path = path.ok()?;
+                let resolved = src_dir.join(&path);
+                let resolved = fs::canonicalize(&resolved).unwrap_or(resolved);
+                debug!("{} resolves to {}", path.display(), resolved.display());
+                Some(*seen.entry(resolved.clone()).or_insert_with(|| {
+                    unique.push((path, resolved));
+                    unique.len() - 1
+                }))
+            })
+            .collect::<Vec<_>>();
+        let chapters = unique.len();
+
+        // Files mapped from chapters are extracted like chapters, then replace or merge with their histories.
+        // `mapped` holds the index in `unique` of the file mapped from each chapter file.
+        let mapped = (0..chapters)
+            .map(|i| {
+                let target = options.path_map.map(&unique[i].0)?;
+                let resolved = match fs::canonicalize(src_dir.join(&target)) {
+                    Ok(resolved) => resolved,
+                    Err(_) => {
+                        warn!(
+                            "Ignoring the mapping of {} to the missing file {}",
+                            unique[i].0.display(),
+                            target.display()
+                        );
+                        return None;
+                    }
+                };
+                debug!(
+                    "{} is mapped to {}",
+                    unique[i].0.display(),
+                    resolved.display()
+                );
+                Some(*seen.entry(resolved.clone()).or_insert_with(|| {
+                    unique.push((target, resolved));
+                    unique.len() - 1
+                }))
+            })
+            .collect::<Vec<_>>();
+
+        // Included files are extracted like chapters, then merged into the histories of the chapters.
+        // `includes` holds the indices in `unique` of the files included by each chapter file.
+        let canonical_src_dir = fs::canonicalize(&src_dir).unwrap_or_else(|_| src_dir.clone());
+        let includes = if options.include_includes {
+            (0..chapters)
+                .map(|i| {
+                    includes::find(&unique[i].1)
+                        .into_iter()
+                        .map(|target| {
+                            *seen.entry(target.clone()).or_insert_with(|| {
+                                let source = target
+                                    .strip_prefix(&canonical_src_dir)
+                                    .map_or_else(|_| target.clone(), Path::to_path_buf);
+                                debug!("Including the history of {}", target.display());
+                                unique.push((source, target));
+                                unique.len() - 1
+                            })
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        } else {
+            Vec::new()
+        };
+
+        // A pre-computed history file replaces git entirely
+        let history_file = options
+            .history_file
+            .as_ref()
+            .map(|path| HistoryFile::load(&ctx.root.join(path)))
+            .transpose()?;
+
+        // Reuse the histories cached by a previous run
+        let mut cache = options
+            .cache_file
+            .as_ref()
+            .filter(|_| history_file.is_none())
+            .and_then(|path| {
+                timed(&mut summary.git_time, || {
+                    HistoryCache::open(ctx.root.join(path), &src_dir, &options, provider.as_ref())
+                })
+                .map_err(|e| warn!("Not using the history cache: {:#}", e))
+                .ok()
+            });
+        // Files are looked up by their canonical path, so that symlinked chapters match the paths
+        // recorded in commits. Those resolving outside of the repository have no history at all.
+        let root = if history_file.is_none() {
+            timed(&mut summary.git_time, || provider.root(&src_dir, &options))
+                .map_err(|e| debug!("Cannot locate the root of the repository: {:#}", e))
+                .ok()
+        } else {
+            None
+        };
+
+        // Old locations of the chapter files are extracted like chapters, then merged into their histories.
+        // `historical` holds the indices in `unique` of the old locations of each chapter file.
+        let historical = (0..chapters)
+            .map(|i| {
+                let root = match &root {
+                    Some(root) => root,
+                    None => return Vec::new(),
+                };
+                options
+                    .historical_paths(&unique[i].0)
+                    .into_iter()
+                    .map(|old| {
+                        let resolved = root.join(&old);
+                        *seen.entry(resolved.clone()).or_insert_with(|| {
+                            debug!(
+                                "Including the history of the old location {}",
+                                old.display()
+                            );
+                            unique.push((old, resolved));
+                            unique.len() - 1
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut histories = unique
+            .iter()
+            .map(|(source, resolved)| {
+                if let Some(history_file) = &history_file {
+                    return Some(Ok(history_file.get(source).cloned().unwrap_or_default()));
+                }
+                let cached = cache.as_ref().and_then(|cache| cache.get(resolved));
+                cached.map(|history| Ok(history.clone()))
+            })
+            .collect::<Vec<_>>();
+        if let Some(root) = &root {
+            for (i, (source, resolved)) in unique.iter().enumerate() {
+                if histories[i].is_none() && !resolved.starts_with(root) {
+                    warn!(
+                        "{} resolves to {}, outside of the repository: it has no git history",
+                        source.display(),
+                        resolved.display()
+                    );
+                    histories[i] = Some(Ok(Vec::new()));
+                }
+            }
+        }
+
+        let progress = Progress::new(&options, unique.len());
+        progress.advance(histories.iter().flatten().count(), "");
 
+        // Extract the missing histories, either all at once in batch mode or concurrently per file.
+        // Files outside of the source directory, like symlink targets, are never part of the batch.
+        let (batch, per_file) = (0..unique.len())
+            .filter(|i| histories[*i].is_none())
+            .partition::<Vec<_>, _>(|i| {
+                options.extraction == ExtractionMode::Batch
+                    && unique[*i].1.starts_with(&canonical_src_dir)
+            });
+        if !batch.is_empty() {
+            let start = Instant::now();
+            let all = provider
+                .all_histories(&src_dir, &options)
+                .context("Cannot extract git history")?;
+            summary.git_time += start.elapsed();
+            debug!(
+                "Extracted the history of {} in {:?}",
+                src_dir.display(),
+                start.elapsed()
+            );
+            for i in batch {
+                let path = unique[i]
+                    .1
+                    .strip_prefix(&canonical_src_dir)
+                    .expect("Only files in the source directory are in the batch");
+                histories[i] = Some(Ok(lookup(&all, path)));
+                progress.advance(1, &unique[i].0.to_string_lossy());
+            }
+        }
+        if !per_file.is_empty() {
+            let full_history = options.needs_full_history();
+            let extracted = parallel_map(&per_file, options.jobs(), |i| {
+                let start = Instant::now();
+                let history = if full_history {
+                    provider.file_history(&unique[*i].1, &options)
+                } else {
+                    provider.file_endpoints(&unique[*i].1, &options)
+                }
+                .context("Cannot extract git history");
+                debug!(
+                    "Extracted the history of {} in {:?}",
+                    unique[*i].1.display(),
+                    start.elapsed()
+                );
+                progress.advance(1, &unique[*i].0.to_string_lossy());
+                (history, start.elapsed())
+            });
+            for (i, (history, elapsed)) in per_file.into_iter().zip(extracted) {
+                histories[i] = Some(history);
+                summary.git_time += elapsed;
+            }
+        }
+        progress.finish();
+        let mut histories = histories.into_iter().flatten().collect::<Vec<_>>();
+
+        // Count the current lines of each author of the chapters, except for large files
+        let mut blames = vec![None; chapters];
+        if options.primary_author && history_file.is_none() {
+            let mut missing = Vec::new();
+            for (i, (_, resolved)) in unique.iter().enumerate().take(chapters) {
+                let size = fs::metadata(resolved).map_or(u64::MAX, |metadata| metadata.len());
+                if size > options.blame_max_bytes {
+                    debug!(
+                        "Not blaming {}: larger than blame_max_bytes",
+                        resolved.display()
+                    );
+                    continue;
+                }
+                match cache.as_ref().and_then(|cache| cache.get_blame(resolved)) {
+                    Some(blame) => blames[i] = Some(blame.clone()),
+                    None => missing.push(i),
+                }
+            }
+
+            let extracted = parallel_map(&missing, options.jobs(), |i| {
+                let start = Instant::now();
+                (provider.blame(&unique[*i].1, &options), start.elapsed())
+            });
+            for (i, (blame, elapsed)) in missing.into_iter().zip(extracted) {
+                summary.git_time += elapsed;
+                match blame {
+                    Ok(blame) => {
+                        if let Some(cache) = &mut cache {
+                            cache.insert_blame(unique[i].1.clone(), blame.clone());
+                        }
+                        blames[i] = Some(blame);
+                    }
+                    Err(e) => warn!("Cannot blame {}: {:#}", unique[i].1.display(), e),
+                }
+            }
+        }
+
+        if let Some(cache) = &mut cache {
+            for ((_, resolved), history) in unique.iter().zip(&histories) {
+                if let Ok(history) = history {
+                    cache.insert(resolved.clone(), history.clone());
+                }
+            }
+            if let Err(e) = cache.save() {
+                warn!("Cannot write the history cache: {:#}", e);
+            }
+        }
+
+        // Merge the histories of the old locations into the ones of the chapters, creation included
+        for (i, old) in historical.iter().enumerate() {
+            let history = match &histories[i] {
+                Ok(history) if !old.is_empty() => history,
+                _ => continue,
+            };
+            let old = old.iter().filter_map(|j| match &histories[*j] {
+                Ok(history) => Some(history.as_slice()),
+                Err(e) => {
+                    warn!(
+                        "Ignoring the history of the old location {}: {:#}",
+                        unique[*j].0.display(),
+                        e
+                    );
+                    None
+                }
+            });
+            histories[i] = Ok(merge_histories(history, old, None, &options));
+        }
+
+        // Show the histories of the mapped files instead of, or along with, the ones of the chapters
+        let mut merged = Vec::new();
+        for (i, target) in mapped.iter().enumerate() {
+            let (history, target) = match (&histories[i], target) {
+                (Ok(history), Some(target)) if *target != i => (history, *target),
+                _ => continue,
+            };
+            match (&histories[target], options.path_map.merge) {
+                (Ok(original), PathMapMerge::Replace) => merged.push((i, original.clone())),
+                (Ok(original), _) => merged.push((
+                    i,
+                    merge_histories(history, [original.as_slice()], None, &options),
+                )),
+                (Err(e), _) => warn!(
+                    "Ignoring the history of {}, mapped from {}: {:#}",
+                    unique[target].1.display(),
+                    unique[i].0.display(),
+                    e
+                ),
+            }
+        }
+        for (i, history) in merged {
+            histories[i] = Ok(history);
+        }
+
+        // Merge the histories of the included files into the ones of the chapters.
+        // Unless included files affect the creation, only their commits after it count.
+        for (i, included) in includes.iter().enumerate() {
+            let history = match &histories[i] {
+                Ok(history) if !included.is_empty() => history,
+                _ => continue,
+            };
+            let included = included.iter().filter_map(|j| match &histories[*j] {
+                Ok(history) => Some(history.as_slice()),
+                Err(e) => {
+                    warn!(
+                        "Ignoring the history of the included file {}: {:#}",
+                        unique[*j].1.display(),
+                        e
+                    );
+                    None
+                }
+            });
+            let since = info::created(history)
+                .filter(|_| !options.includes_affect_creation)
+                .map(|created| created.timestamp);
+            histories[i] = Ok(merge_histories(history, included, since, &options));
+        }
+
+        // Reviews are recorded in notes, which change without `HEAD` moving, so they are never cached
+        let commit_notes = match (&options.notes_ref, &history_file) {
+            (Some(notes_ref), None) => timed(&mut summary.git_time, || {
+                provider.notes(&src_dir, notes_ref, &options)
+            })
+            .context("Cannot read the git notes")?,
+            _ => HashMap::new(),
+        };
+
+        // A missing ref only disables the feature, since the ref may not be fetched everywhere
+        let unpublished = match (&options.published_ref, &history_file) {
+            (Some(published_ref), None) => {
+                let unpublished = timed(&mut summary.git_time, || {
+                    provider.unpublished_commits(&src_dir, published_ref, &options)
+                })
+                .context("Cannot compare with the published ref")?;
+                if unpublished.is_none() {
+                    warn!(
+                        "The published ref {} does not exist: pending changes are not shown",
+                        published_ref
+                    );
+                }
+                unpublished
+            }
+            _ => None,
+        };
+
+        // Every chapter shows the last edit of the whole book
+        let book_last_edit = indices
+            .iter()
+            .flatten()
+            .filter_map(|i| histories[*i].as_ref().ok())
+            .filter_map(|history| info::last_edit(history, &options))
+            .map(|entry| entry.timestamp)
+            .max();
+
+        // Releases and signatures are looked up once for each distinct last edit
+        let last_edits = if history_file.is_none() {
+            indices
+                .iter()
+                .flatten()
+                .filter_map(|i| histories[*i].as_ref().ok())
+                .filter_map(|history| info::last_edit(history, &options))
+                .map(|entry| entry.hash.as_str())
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect::<Vec<_>>()
+        } else {
+            Vec::new()
+        };
+        let versions = if options.show_version {
+            for_each_commit(&last_edits, &options, &mut summary, |hash| {
+                provider
+                    .first_tag_containing(&src_dir, hash, &options.version_pattern, &options)
+                    .with_context(|| format!("Cannot find the release of commit {}", hash))
+            })?
+        } else {
+            HashMap::new()
+        };
+        let signatures = if options.show_signature_status {
+            for_each_commit(&last_edits, &options, &mut summary, |hash| {
+                provider
+                    .signature(&src_dir, hash, &options)
+                    .with_context(|| format!("Cannot verify the signature of commit {}", hash))
+            })?
+        } else {
+            HashMap::new()
+        };
+
+        // Reproducible builds must not depend on the current year
+        let source_date_year = if options.copyright.is_some() {
+            copyright::source_date_year()?
+        } else {
+            None
+        };
+
+        // The revision is the same for every chapter
+        let provenance = match &options.provenance {
+            Some(_) if history_file.is_some() => {
+                warn!("The provenance stamp needs git, and is left out when using history_file");
+                None
+            }
+            Some(_) => Some(
+                timed(&mut summary.git_time, || {
+                    build_provenance(provider.as_ref(), &src_dir, &options)
+                })
+                .context("Cannot determine the revision the book is built from")?,
+            ),
+            None => None,
+        };
+
+        // Accounts are looked up once for every email of the book
+        let profiles = if options.github_api {
+            github_profiles(
+                &options,
+                self.http_client.as_deref().unwrap_or(&CurlClient),
+                provider.as_ref(),
+                &ctx.root,
+                &src_dir,
+                histories.iter().flatten().flatten(),
+            )
+        } else {
+            HashMap::new()
+        };
+
+        // Owners are looked up relative to the root of the repository
+        let codeowners = if options.codeowners {
+            let root = match root {
+                Some(root) => root,
+                None => timed(&mut summary.git_time, || provider.root(&src_dir, &options))
+                    .context("Cannot locate the root of the repository")?,
+            };
+            let codeowners = CodeOwners::find(&root)?;
+            if codeowners.is_none() {
+                warn!("No CODEOWNERS file found in {}", root.display());
+            }
+            codeowners.map(|codeowners| (root, codeowners))
+        } else {
+            None
+        };
+
+        // Apply the results to the chapters in the same order, and stop at the first error
+        let mut indices = indices.into_iter();
+        let mut front_matters = front_matters.into_iter();
+        let mut error = None;
+        let mut metadata = BTreeMap::new();
+        let mut enriched = Vec::new();
+        // Whether a chapter links to the bundled stylesheet, see `GitInfoOptions::inline_css`
+        let mut links_stylesheet = false;
+        let mut without_history = Vec::new();
+        let generated = options
+            .contributors_chapter
+            .as_ref()
+            .map(|settings| settings.path.as_path())
+            .into_iter()
+            .chain(
+                options
+                    .recently_updated
+                    .as_ref()
+                    .map(|settings| settings.path.as_path()),
+            )
+            .chain(
+                options
+                    .changelog
+                    .as_ref()
+                    .map(|settings| settings.path.as_path()),
+            )
+            .chain(
+                options
+                    .provenance
+                    .as_ref()
+                    .filter(|settings| settings.position == ProvenancePosition::Colophon)
+                    .map(|settings| settings.path.as_path()),
+            )
+            .collect::<Vec<_>>();
+        book.for_each_mut(|book_item| {
             if let BookItem::Chapter(chapter) = book_item {
-                if let Err(e) = enrich_chapter(ctx, chapter) {
-                    error = Some(e.context(format!("Chapter name: {}", chapter.name)));
+                let index = indices.next().flatten();
+                let front_matter = front_matters.next().flatten();
+                if error.is_some() {
+                    return;
+                }
+                // Generated chapters are filled in afterwards and get no footer
+                if chapter
+                    .path
+                    .as_deref()
+                    .is_some_and(|path| generated.contains(&path))
+                {
+                    return;
+                }
+
+                match index.map(|i| &mut histories[i]) {
+                    Some(Ok(history)) => {
+                        // The footer of each chapter follows the overrides matching its path
+                        let options = match chapter
+                            .source_path
+                            .as_deref()
+                            .map(|path| options.for_chapter(path))
+                            .transpose()
+                        {
+                            Ok(chapter_options) => {
+                                chapter_options.unwrap_or(Cow::Borrowed(&options))
+                            }
+                            Err(e) => {
+                                summary.failed += 1;
+                                error = Some(e.context(format!("Chapter name: {}", chapter.name)));
+                                return;
+                            }
+                        };
+
+                        // Aggregate the logs and build the output
+                        let mut info = GitInfo::from_history(history, &options);
+                        if let Some(created) = chapter
+                            .source_path
+                            .as_deref()
+                            .and_then(|path| options.created_override(path))
+                        {
+                            created.apply(&mut info);
+                        }
+                        if let Some(front_matter) = &front_matter {
+                            front_matter.apply(&mut info);
+                        }
+                        if options.strict && (history.is_empty() || info.is_empty()) {
+                            // Reported all at once after the other chapters
+                            summary.failed += 1;
+                            let resolved = index.map(|i| unique[i].1.clone()).unwrap_or_default();
+                            unusable.push((chapter.name.clone(), resolved, "no git history"));
+                            return;
+                        }
+                        if info.is_empty() {
+                            // Listed all at once after the other chapters
+                            if options.missing_history == MissingHistory::Error {
+                                summary.failed += 1;
+                                without_history.push(chapter.name.clone());
+                                return;
+                            }
+                            warn!(
+                                "Chapter {} has no git history: is its file committed?",
+                                chapter.name
+                            );
+                        }
+                        info.book_last_edit = book_last_edit;
+                        info.version = info
+                            .last_edit
+                            .as_ref()
+                            .and_then(|entry| versions.get(&entry.hash).cloned())
+                            .flatten();
+                        info.signature = info
+                            .last_edit
+                            .as_ref()
+                            .and_then(|entry| signatures.get(&entry.hash).cloned());
+                        if let Some(settings) = &options.copyright {
+                            info.copyright = copyright::line(&info, settings, source_date_year);
+                        }
+                        if options
+                            .provenance
+                            .as_ref()
+                            .is_some_and(|p| p.position == ProvenancePosition::EveryChapter)
+                        {
+                            info.provenance = provenance.clone();
+                        }
+                        for entry in history.iter() {
+                            let identities = [
+                                (&entry.author, &entry.email),
+                                (&entry.committer, &entry.committer_email),
+                            ];
+                            for (name, email) in identities {
+                                if let Some(profile) = profiles.get(email) {
+                                    info.profiles
+                                        .entry(name.clone())
+                                        .or_insert_with(|| profile.clone());
+                                }
+                            }
+                        }
+                        info.primary_author = index
+                            .and_then(|i| blames[i].as_deref())
+                            .and_then(PrimaryAuthor::from_blame);
+                        if let Some(unpublished) = &unpublished {
+                            info.unpublished_changes = history
+                                .iter()
+                                .filter(|entry| unpublished.contains(&entry.hash))
+                                .filter(|entry| {
+                                    !options.is_ignored_commit(entry, IgnoredCommitEffect::Totals)
+                                })
+                                .count();
+                        }
+                        info.last_reviewed = history
+                            .iter()
+                            .filter_map(|entry| commit_notes.get(&entry.hash))
+                            .filter_map(|note| notes::parse_review(note, &options.notes_key))
+                            .max_by_key(|review| review.date);
+                        if let (Some((root, codeowners)), Some(i)) = (&codeowners, index) {
+                            if let Ok(path) = unique[i].1.strip_prefix(root) {
+                                info.owners = codeowners.owners(path).to_vec();
+                            }
+                        }
+                        if let Err(e) = enrich_chapter(&options, flavor, &info, chapter) {
+                            summary.failed += 1;
+                            error = Some(e.context(format!("Chapter name: {}", chapter.name)));
+                            return;
+                        }
+                        summary.enriched += 1;
+                        links_stylesheet |= options.inline_css && !options.hide_footer;
+                        if let Some(source_path) = &chapter.source_path {
+                            metadata.insert(source_path.clone(), info.clone());
+                        }
+                        if let (Some(path), Some(index)) = (&chapter.path, index) {
+                            enriched.push(EnrichedChapter {
+                                name: chapter.name.clone(),
+                                path: path.clone(),
+                                index,
+                                info,
+                            });
+                        }
+                    }
+                    Some(result) => {
+                        // Errors cannot be cloned, but the first one aborts the run anyway
+                        let e = mem::replace(result, Ok(Vec::new())).unwrap_err();
+                        summary.failed += 1;
+                        error = Some(e.context(format!("Chapter name: {}", chapter.name)));
+                    }
+                    None => {}
                 }
             }
         });
 
-        error.map_or_else(|| Ok(book), Err)
+        if error.is_none() && !unusable.is_empty() {
+            error = Some(strict_report(&unusable));
+        }
+        if error.is_none() && !without_history.is_empty() {
+            error = Some(anyhow::anyhow!(
+                "Chapters without git history (are their files committed?): {}",
+                without_history.join(", ")
+            ));
+        }
+        if let Some(e) = error {
+            summary.report(&options, start);
+            return Err(e);
+        }
+
+        if links_stylesheet && flavor == Flavor::Html {
+            stylesheet::write(&src_dir.join(stylesheet::LINKED_FILE))?;
+        }
+
+        if let Some(path) = &options.metadata_output {
+            metadata::write(&ctx.root.join(path), &metadata)?;
+        }
+
+        if let Some(settings) = &options.contributors_chapter {
+            let contributors = Contributor::aggregate(
+                enriched.iter().filter_map(|chapter| {
+                    let history = histories[chapter.index].as_ref().ok()?;
+                    Some((
+                        chapter.name.as_str(),
+                        chapter.path.as_path(),
+                        history.as_slice(),
+                    ))
+                }),
+                options.identity,
+            );
+            let content =
+                render::contributors_chapter(&settings.title, &settings.path, &contributors);
+            add_generated_chapter(
+                &mut book,
+                &settings.title,
+                &settings.path,
+                ChapterPosition::Back,
+                content,
+            );
+        }
+
+        if let Some(settings) = &options.recently_updated {
+            // Windows too long for chrono to represent include every chapter
+            let since = settings.window_days.and_then(|days| {
+                let days = i64::try_from(days)
+                    .ok()
+                    .filter(|days| *days <= 100_000_000)?;
+                Utc::now().checked_sub_signed(chrono::Duration::days(days))
+            });
+            let recent = recent_chapters(&enriched, since, settings.max_entries);
+
+            let rows = recent
+                .iter()
+                .map(|chapter| (chapter.name.as_str(), chapter.path.as_path(), &chapter.info))
+                .collect::<Vec<_>>();
+            let content = render::recently_updated_chapter(settings, &rows, &options);
+            add_generated_chapter(
+                &mut book,
+                &settings.title,
+                &settings.path,
+                settings.position,
+                content,
+            );
+        }
+
+        if let Some(settings) = &options.feed {
+            let site_url = settings
+                .site_url
+                .as_ref()
+                .or(options.site_url.as_ref())
+                .context("The feed requires site_url to be set")?;
+            let title = settings
+                .title
+                .as_ref()
+                .or(ctx.config.book.title.as_ref())
+                .map_or("Updates", String::as_str);
+            let entries = recent_chapters(&enriched, None, settings.max_entries)
+                .into_iter()
+                .map(|chapter| (chapter.name.as_str(), chapter.path.as_path(), &chapter.info))
+                .collect::<Vec<_>>();
+            let xml = feed::render(title, site_url, &settings.path, &entries, options.identity);
+
+            // The feed lives in the source directory to be copied to the built book,
+            // so rewriting it when unchanged would retrigger `mdbook serve`
+            let path = src_dir.join(&settings.path);
+            if fs::read(&path).ok().as_deref() != Some(xml.as_bytes()) {
+                metadata::write_atomically(&path, xml.as_bytes())
+                    .with_context(|| format!("Cannot write {}", path.display()))?;
+            }
+        }
+
+        if let Some(settings) = options.sitemap.as_ref().filter(|s| s.enabled) {
+            let site_url = settings
+                .site_url
+                .as_ref()
+                .or(options.site_url.as_ref())
+                .context("The sitemap requires site_url to be set")?;
+            let chapters = enriched
+                .iter()
+                .map(|chapter| {
+                    let last_edit = chapter.info.last_edit.as_ref().map(|e| e.timestamp);
+                    (chapter.path.as_path(), last_edit)
+                })
+                .collect::<Vec<_>>();
+            sitemap::write(&src_dir.join(&settings.path), site_url, &chapters)?;
+        }
+
+        if let Some(settings) = &options.changelog {
+            let mut commits = timed(&mut summary.git_time, || {
+                provider.recent_commits(
+                    &src_dir,
+                    &options,
+                    settings.max_commits,
+                    settings.include_merges,
+                )
+            })
+            .context("Cannot extract the changelog")?;
+            if let Some(max_months) = settings.max_months {
+                let mut months = Vec::new();
+                commits.retain(|(entry, _)| {
+                    let month = entry.timestamp.format("%Y-%m").to_string();
+                    if !months.contains(&month) {
+                        months.push(month);
+                    }
+                    months.len() <= max_months
+                });
+            }
+
+            // Map the touched files back to the chapters, in book order
+            let entries = commits
+                .iter()
+                .map(|(entry, paths)| {
+                    let mut chapters = Vec::<(&str, &Path)>::new();
+                    for chapter in &enriched {
+                        if paths.contains(&chapter.path)
+                            && !chapters.iter().any(|(_, path)| *path == chapter.path)
+                        {
+                            chapters.push((&chapter.name, &chapter.path));
+                        }
+                    }
+                    (entry, chapters)
+                })
+                .collect::<Vec<_>>();
+            let content = render::changelog_chapter(settings, &entries, &options);
+            add_generated_chapter(
+                &mut book,
+                &settings.title,
+                &settings.path,
+                settings.position,
+                content,
+            );
+        }
+
+        if let (Some(settings), Some(provenance)) = (&options.provenance, &provenance) {
+            let stamp = render::provenance(provenance, &options, flavor);
+            match settings.position {
+                ProvenancePosition::FirstChapter => {
+                    // Nested chapters are visited first by `for_each_mut`
+                    let first = book.sections.iter_mut().find_map(|item| match item {
+                        BookItem::Chapter(chapter) if chapter.path.is_some() => Some(chapter),
+                        _ => None,
+                    });
+                    if let Some(chapter) = first {
+                        close_fence(chapter);
+                        chapter.content.push_str(&format!("\n\n{}\n", stamp));
+                    }
+                }
+                ProvenancePosition::Colophon => {
+                    let content = format!(
+                        "# {}\n\n{}\n",
+                        render::escape_markdown(&settings.title),
+                        stamp
+                    );
+                    add_generated_chapter(
+                        &mut book,
+                        &settings.title,
+                        &settings.path,
+                        ChapterPosition::Back,
+                        content,
+                    );
+                }
+                // Already in the footers
+                ProvenancePosition::EveryChapter => {}
+            }
+        }
+
+        summary.report(&options, start);
+        Ok((book, metadata))
+    }
+}
+
+impl Preprocessor for GitInfoPreprocessor {
+    fn name(&self) -> &str {
+        "git-info"
+    }
+
+    fn run(&self, ctx: &PreprocessorContext, book: Book) -> Result<Book> {
+        self.run_with_metadata(ctx, book).map(|(book, _)| book)
     }
 
-    fn supports_renderer(&self, renderer: &str) -> bool {
-        renderer == "html"
+    /// Every renderer is accepted here, since the `renderers` option can only be read in `run`.
+    fn supports_renderer(&self, _renderer: &str) -> bool {
+        true
     }
 }
 
-fn enrich_chapter(ctx: &PreprocessorContext, chapter: &mut Chapter) -> Result<()> {
-    let history = git_history::extract(ctx.root.join(chapter.source_path.as_ref().unwrap()))
-        .context("Cannot extract git history")?;
+/// Why a chapter is left untouched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SkipReason {
+    /// The chapter matches one of the `exclude` patterns.
+    Excluded,
+    /// The chapter has no file.
+    Draft,
+    /// The file of the chapter does not exist.
+    MissingFile,
+    /// The front matter of the chapter says `git-info: skip`.
+    FrontMatter,
+}
 
-    // Aggregate the logs
-    let last_commit = history.first();
-    let first_commit = history.last();
-    let mut other_contributors = history
-        .iter()
-        .skip(1)
-        .take(history.len().saturating_sub(2))
-        .filter_map(|entry| match last_commit {
-            Some(last_commit) if last_commit.author == entry.author => None,
-            _ => Some(entry.author.as_str()),
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SkipReason::Excluded => "it is excluded",
+            SkipReason::Draft => "it is a draft",
+            SkipReason::MissingFile => "its file does not exist",
+            SkipReason::FrontMatter => "its front matter asks to skip it",
         })
-        .collect::<HashSet<_>>()
-        .into_iter()
-        .collect::<Vec<_>>();
-    other_contributors.sort_unstable();
-
-    // Build the output
-    chapter.content.push_str(&format!(
-        "\n\
-        \n\
-        <br>\n\
-        \n\
-        ---\n\
-        \n\
-        <br>\n\
-        \n\
-        | Created on | Created by | Last edit on | Last edit by | Other contributors |\n\
-        | :---: | :---: | :---: | :---: | --- |\n\
-        | **{}** | **{}** | **{}** | **{}** | {} |\n",
-        first_commit.map_or_else(
-            || "n/a".to_string(),
-            |c| c.timestamp.format("%d %b %Y").to_string()
-        ),
-        first_commit.map_or("n/a", |c| c.author.as_str()),
-        last_commit.map_or_else(
-            || "n/a".to_string(),
-            |c| c.timestamp.format("%d %b %Y").to_string()
-        ),
-        last_commit.map_or("n/a", |c| c.author.as_str()),
-        other_contributors.join("<br>")
-    ));
+    }
+}
+
+/// Returns the source path of a chapter to enrich, or why it has to be skipped.
+fn source_path(
+    chapter: &Chapter,
+    src_dir: &Path,
+    options: &GitInfoOptions,
+) -> Result<PathBuf, SkipReason> {
+    match &chapter.source_path {
+        None => Err(SkipReason::Draft),
+        Some(path) if options.is_excluded(path) => Err(SkipReason::Excluded),
+        Some(path) if !src_dir.join(path).is_file() => Err(SkipReason::MissingFile),
+        Some(path) => Ok(path.clone()),
+    }
+}
 
+/// Counters reported at the end of a run.
+#[derive(Debug, Default)]
+struct Summary {
+    enriched: usize,
+    excluded: usize,
+    drafts: usize,
+    missing_files: usize,
+    failed: usize,
+    /// Cumulative time spent reading the repository, which exceeds the wall-clock time
+    /// when extracting concurrently.
+    git_time: Duration,
+}
+
+impl Summary {
+    fn skip(&mut self, reason: SkipReason) {
+        match reason {
+            SkipReason::Excluded | SkipReason::FrontMatter => self.excluded += 1,
+            SkipReason::Draft => self.drafts += 1,
+            SkipReason::MissingFile => self.missing_files += 1,
+        }
+    }
+
+    /// Logs the summary of a run started at `start`, unless the options ask for silence.
+    fn report(&self, options: &GitInfoOptions, start: Instant) {
+        if !options.quiet {
+            info!(
+                "git-info: enriched {} chapters, skipped {} ({} excluded, {} drafts, {} missing file), \
+                 {} errors, {:.1}s (git time {:.1}s)",
+                self.enriched,
+                self.excluded + self.drafts + self.missing_files,
+                self.excluded,
+                self.drafts,
+                self.missing_files,
+                self.failed,
+                start.elapsed().as_secs_f64(),
+                self.git_time.as_secs_f64()
+            );
+        }
+    }
+}
+
+/// Lists the chapters failing [`GitInfoOptions::strict`], as `(name, resolved path, problem)`,
+/// along with the usual causes.
+fn strict_report(unusable: &[(String, PathBuf, &str)]) -> anyhow::Error {
+    let mut report = format!(
+        "{} chapter{} without usable git history:\n",
+        unusable.len(),
+        if unusable.len() == 1 { "" } else { "s" }
+    );
+    for (name, path, problem) in unusable {
+        report.push_str(&format!("  - {} ({}): {}\n", name, path.display(), problem));
+    }
+    report.push_str(
+        "Usual causes: the file was never committed (`git add` it), `book.src` or `path_map` \
+         point at the wrong files, or the repository is a shallow clone (fetch its whole history)",
+    );
+    anyhow::anyhow!(report)
+}
+
+/// Queries the revision of the repository containing `src_dir`, once for the whole book.
+fn build_provenance(
+    provider: &dyn HistoryProvider,
+    src_dir: &Path,
+    options: &GitInfoOptions,
+) -> Result<BuildProvenance> {
+    let root = provider.root(src_dir, options)?;
+    Ok(BuildProvenance {
+        commit: provider.head(&root, options)?,
+        reference: provider.head_ref(&root, options)?,
+        built_at: copyright::source_date()?.unwrap_or_else(Utc::now),
+        dirty: !provider.dirty_files(&root, options)?.is_empty(),
+    })
+}
+
+/// Looks up the GitHub accounts of the authors and committers of `entries`, by email.
+///
+/// Without a token or a repository on GitHub, nothing is looked up.
+fn github_profiles<'a>(
+    options: &GitInfoOptions,
+    client: &dyn HttpClient,
+    provider: &dyn HistoryProvider,
+    root: &Path,
+    src_dir: &Path,
+    entries: impl Iterator<Item = &'a GitHistoryEntry>,
+) -> HashMap<String, GitHubProfile> {
+    let token = match env::var(github::TOKEN_VAR) {
+        Ok(token) if !token.trim().is_empty() => token.trim().to_string(),
+        _ => {
+            debug!(
+                "{} is not set, not looking up the GitHub accounts",
+                github::TOKEN_VAR
+            );
+            return HashMap::new();
+        }
+    };
+    let repository = match &options.github_repository {
+        Some(repository) => Some(repository.clone()),
+        None => provider
+            .remote_url(src_dir, "origin", options)
+            .map_err(|e| debug!("Cannot read the origin remote: {:#}", e))
+            .ok()
+            .flatten()
+            .and_then(|url| github::repository_from_url(&url)),
+    };
+    let repository = match repository {
+        Some(repository) => repository,
+        None => {
+            debug!("The repository is not on GitHub, not looking up the GitHub accounts");
+            return HashMap::new();
+        }
+    };
+
+    ProfileResolver::new(
+        client,
+        repository,
+        token,
+        root.join(&options.github_cache),
+        options.github_cache_ttl_days,
+        options.github_max_requests,
+    )
+    .resolve(entries)
+}
+
+/// Runs `f`, adding the time it takes to `total`.
+fn timed<T>(total: &mut Duration, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    *total += start.elapsed();
+    result
+}
+
+/// Returns the distinct chapters last edited after `since`, most recent first.
+fn recent_chapters(
+    enriched: &[EnrichedChapter],
+    since: Option<DateTime<Utc>>,
+    max_entries: usize,
+) -> Vec<&EnrichedChapter> {
+    let mut recent = Vec::<&EnrichedChapter>::new();
+    for chapter in enriched {
+        let edited = chapter.info.last_edit.as_ref().map(|e| e.timestamp);
+        if edited.is_some_and(|edited| since.is_none_or(|since| edited >= since))
+            && !recent.iter().any(|c| c.path == chapter.path)
+        {
+            recent.push(chapter);
+        }
+    }
+    // Most recent first, then in book order, which the sort being stable preserves
+    recent.sort_by_key(|chapter| Reverse(chapter.info.last_edit.as_ref().map(|e| e.timestamp)));
+    recent.truncate(max_entries);
+    recent
+}
+
+/// A chapter which got a footer, kept around for the generated chapters.
+struct EnrichedChapter {
+    name: String,
+    path: PathBuf,
+    /// Index of the history of the chapter.
+    index: usize,
+    info: GitInfo,
+}
+
+/// Fills in the chapter listed at `path` in the summary, or adds a new one to the book.
+fn add_generated_chapter(
+    book: &mut Book,
+    title: &str,
+    path: &Path,
+    position: ChapterPosition,
+    content: String,
+) {
+    let mut found = false;
+    book.for_each_mut(|book_item| {
+        if let BookItem::Chapter(chapter) = book_item {
+            if chapter.path.as_deref() == Some(path) {
+                chapter.content = content.clone();
+                found = true;
+            }
+        }
+    });
+    if found {
+        return;
+    }
+
+    // Not backed by a file, so that renderers do not link to it, e.g. for editing
+    let mut chapter = Chapter::new(title, content, path, Vec::new());
+    chapter.source_path = None;
+    let chapter = BookItem::Chapter(chapter);
+    match position {
+        ChapterPosition::Front => book.sections.insert(0, chapter),
+        ChapterPosition::Back => {
+            // Keep the chapter out of the last part of the book
+            if !matches!(book.sections.last(), None | Some(BookItem::Separator)) {
+                book.push_item(BookItem::Separator);
+            }
+            book.push_item(chapter);
+        }
+    }
+}
+
+/// Looks up the history of a chapter in the result of a batch extraction.
+fn lookup(
+    histories: &HashMap<PathBuf, Vec<GitHistoryEntry>>,
+    source_path: &Path,
+) -> Vec<GitHistoryEntry> {
+    match histories.get(source_path) {
+        Some(history) => history.clone(),
+        // On case-insensitive filesystems the casing on disk may differ from the committed one
+        None => {
+            let source_path = source_path.to_string_lossy().to_lowercase();
+            histories
+                .iter()
+                .find(|(path, _)| path.to_string_lossy().to_lowercase() == source_path)
+                .map(|(_, history)| history.clone())
+                .unwrap_or_default()
+        }
+    }
+}
+
+/// Merges the histories of other files into the one of a chapter, newest commit first.
+///
+/// Only the commits to the other files after `since` are kept.
+fn merge_histories<'a>(
+    history: &[GitHistoryEntry],
+    others: impl IntoIterator<Item = &'a [GitHistoryEntry]>,
+    since: Option<DateTime<Utc>>,
+    options: &GitInfoOptions,
+) -> Vec<GitHistoryEntry> {
+    let mut hashes = history
+        .iter()
+        .map(|entry| entry.hash.clone())
+        .collect::<HashSet<_>>();
+    let mut merged = history.to_vec();
+    for entry in others.into_iter().flatten() {
+        if since.is_none_or(|since| entry.timestamp > since) && hashes.insert(entry.hash.clone()) {
+            merged.push(entry.clone());
+        }
+    }
+    merged.sort_by_key(|entry| Reverse(entry.timestamp));
+    truncate_history(&mut merged, options.max_history);
+    merged
+}
+
+/// Runs `f` concurrently on each of the given commits, counting the time spent as git time.
+fn for_each_commit<R, F>(
+    hashes: &[&str],
+    options: &GitInfoOptions,
+    summary: &mut Summary,
+    f: F,
+) -> Result<HashMap<String, R>>
+where
+    R: Send,
+    F: Fn(&str) -> Result<R> + Sync,
+{
+    let results = parallel_map(hashes, options.jobs(), |hash| {
+        let start = Instant::now();
+        (f(hash), start.elapsed())
+    });
+    let mut by_hash = HashMap::new();
+    for (hash, (result, elapsed)) in hashes.iter().zip(results) {
+        summary.git_time += elapsed;
+        by_hash.insert(hash.to_string(), result?);
+    }
+    Ok(by_hash)
+}
+
+/// Maps `f` over `items` using at most `jobs` threads, preserving the order of the results.
+fn parallel_map<T, R, F>(items: &[T], jobs: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let mut results = thread::scope(|scope| {
+        let workers = (0..jobs.clamp(1, items.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        match items.get(i) {
+                            Some(item) => results.push((i, f(item))),
+                            None => return results,
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("Worker thread panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    results.sort_unstable_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+fn enrich_chapter(
+    options: &GitInfoOptions,
+    flavor: Flavor,
+    info: &GitInfo,
+    chapter: &mut Chapter,
+) -> Result<()> {
+    if options.copyright.is_some() {
+        chapter.content = render::replace_variable(
+            &chapter.content,
+            copyright::VARIABLE,
+            info.copyright.as_deref().unwrap_or(""),
+        );
+    }
+    if options.show_version {
+        // Tag names come from the repository, so they are escaped like in the footer
+        let version = render::version(info, options);
+        chapter.content = render::replace_variable(
+            &chapter.content,
+            VERSION_VARIABLE,
+            &match flavor {
+                Flavor::Markdown => render::escape_markdown(version),
+                Flavor::Html | Flavor::Xhtml => render::escape_html(version),
+            },
+        );
+    }
+    if let Some(date) = info.book_last_edit {
+        chapter.content = render::replace_variable(
+            &chapter.content,
+            BOOK_LAST_EDIT_VARIABLE,
+            &options.format_date(&date),
+        );
+    }
+
+    // Chapters without history only get a placeholder, if anything
+    if info.is_empty() && options.missing_history != MissingHistory::Placeholder {
+        return Ok(());
+    }
+
+    if options.emit_front_matter && !info.is_empty() {
+        chapter.content =
+            front_matter::insert(&chapter.content, &front_matter::emitted(info, options))
+                .context("Cannot add to the front matter")?;
+    }
+
+    if !options.hide_footer {
+        let mut footer = if info.is_empty() {
+            render::placeholder(info, options, flavor)
+        } else {
+            render::footer(info, options, flavor)
+        };
+        if let (true, Flavor::Html, Some(path)) = (options.inline_css, flavor, &chapter.path) {
+            // Both start with a blank line, which separates them from the chapter
+            footer = format!(
+                "\n\n{}{}",
+                render::stylesheet_link(path),
+                footer.trim_start_matches('\n')
+            );
+        }
+        insert_footer(options, &footer, chapter)?;
+    }
+
+    // Scripts are appended to the chapter, so an open code block would swallow them
+    if (options.data_attributes && flavor != Flavor::Markdown)
+        || (options.json_ld && flavor == Flavor::Html)
+    {
+        close_fence(chapter);
+    }
+    if options.data_attributes && flavor != Flavor::Markdown {
+        chapter.content.push_str(&render::data_script(info));
+    }
+    if options.json_ld && flavor == Flavor::Html && !info.is_empty() {
+        let url = options
+            .site_url
+            .as_ref()
+            .zip(chapter.path.as_ref())
+            .map(|(site_url, path)| render::page_url(site_url, path));
+        chapter
+            .content
+            .push_str(&render::json_ld(info, url.as_deref(), options.identity));
+    }
     Ok(())
 }
+
+/// Inserts the footer at the end of the chapter, or of the section of
+/// [`GitInfoOptions::insert_under_heading`].
+fn insert_footer(options: &GitInfoOptions, footer: &str, chapter: &mut Chapter) -> Result<()> {
+    // Find the end of the section the footer goes into, if any
+    let section_end = match &options.insert_under_heading {
+        Some(heading) => {
+            match render::section_end(&chapter.content, heading, options.heading_case_sensitive) {
+                Some(end) => Some(end),
+                None => match options.missing_heading {
+                    MissingHeading::Append => None,
+                    MissingHeading::Skip => return Ok(()),
+                    MissingHeading::Fail => {
+                        return Err(anyhow::anyhow!("Cannot find the heading {:?}", heading))
+                    }
+                },
+            }
+        }
+        None => None,
+    };
+
+    match section_end.filter(|end| *end < chapter.content.len()) {
+        // The footer starts with a blank line, so only the blank line before the next heading is missing
+        Some(end) => chapter.content.insert_str(end, &format!("{}\n", footer)),
+        None => {
+            // The footer starts with a blank line, so that it never continues the last block of the chapter,
+            // except for code blocks, which must be closed
+            close_fence(chapter);
+            chapter.content.push_str(footer);
+        }
+    }
+    Ok(())
+}
+
+/// Closes the fenced code block left open at the end of the chapter, if any.
+fn close_fence(chapter: &mut Chapter) {
+    if let Some(fence) = render::unclosed_fence(&chapter.content) {
+        chapter.content.push('\n');
+        chapter.content.push_str(&fence);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Separator;
+
+    fn hashes(history: &[GitHistoryEntry]) -> Vec<&str> {
+        history.iter().map(|entry| entry.hash.as_str()).collect()
+    }
+
+    fn date(text: &str) -> DateTime<Utc> {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn merged_histories_are_sorted_newest_first() {
+        let history = [
+            GitHistoryEntry::test("d", "Dave", "2024-04-01T12:00:00Z"),
+            GitHistoryEntry::test("b", "Bob", "2024-02-01T12:00:00Z"),
+        ];
+        let old = [
+            GitHistoryEntry::test("c", "Carol", "2024-03-01T12:00:00Z"),
+            GitHistoryEntry::test("a", "Alice", "2024-01-01T12:00:00Z"),
+        ];
+
+        let merged = merge_histories(&history, [&old[..]], None, &GitInfoOptions::default());
+
+        assert_eq!(hashes(&merged), ["d", "c", "b", "a"]);
+    }
+
+    #[test]
+    fn commits_shared_with_other_histories_are_kept_once() {
+        let history = [
+            GitHistoryEntry::test("b", "Bob", "2024-02-01T12:00:00Z"),
+            GitHistoryEntry::test("a", "Alice", "2024-01-01T12:00:00Z"),
+        ];
+        // A commit moving the file is in both histories, as are the commits of two old
+        // locations mapping to the same file
+        let old = [GitHistoryEntry::test("a", "Alice", "2024-01-01T12:00:00Z")];
+
+        let merged = merge_histories(
+            &history,
+            [&old[..], &old[..]],
+            None,
+            &GitInfoOptions::default(),
+        );
+
+        assert_eq!(hashes(&merged), ["b", "a"]);
+    }
+
+    #[test]
+    fn only_commits_to_other_files_after_since_are_merged() {
+        let history = [GitHistoryEntry::test("b", "Bob", "2024-02-01T12:00:00Z")];
+        let include = [
+            GitHistoryEntry::test("c", "Carol", "2024-03-01T12:00:00Z"),
+            GitHistoryEntry::test("x", "Xavier", "2024-02-01T12:00:00Z"),
+            GitHistoryEntry::test("a", "Alice", "2024-01-01T12:00:00Z"),
+        ];
+
+        let merged = merge_histories(
+            &history,
+            [&include[..]],
+            Some(date("2024-02-01T12:00:00Z")),
+            &GitInfoOptions::default(),
+        );
+
+        assert_eq!(hashes(&merged), ["c", "b"]);
+    }
+
+    #[test]
+    fn merged_histories_are_truncated_keeping_the_creation() {
+        let history = [
+            GitHistoryEntry::test("c", "Carol", "2024-03-01T12:00:00Z"),
+            GitHistoryEntry::test("b", "Bob", "2024-02-01T12:00:00Z"),
+        ];
+        let old = [
+            GitHistoryEntry::test("d", "Dave", "2024-04-01T12:00:00Z"),
+            GitHistoryEntry::test("a", "Alice", "2024-01-01T12:00:00Z"),
+        ];
+        let options = GitInfoOptions::builder().max_history(2).build();
+
+        let merged = merge_histories(&history, [&old[..]], None, &options);
+
+        assert_eq!(hashes(&merged), ["d", "c", "a"]);
+    }
+
+    #[test]
+    fn historical_paths_are_resolved_from_the_builder() {
+        let options = GitInfoOptions::builder()
+            .historical_paths("**/*.md", ["docs/{stem}.md"])
+            .historical_paths("guide/*.md", ["old/{path}", "docs/{stem}.md"])
+            .build();
+
+        assert_eq!(
+            options.historical_paths(Path::new("guide/setup.md")),
+            [
+                PathBuf::from("docs/setup.md"),
+                PathBuf::from("old/guide/setup.md")
+            ]
+        );
+        assert_eq!(
+            options.historical_paths(Path::new("intro.md")),
+            [PathBuf::from("docs/intro.md")]
+        );
+        assert!(options.historical_paths(Path::new("image.png")).is_empty());
+    }
+
+    #[test]
+    fn parallel_map_preserves_the_order_of_slow_extractions() {
+        let items = (0..16).collect::<Vec<u64>>();
+        let running = AtomicUsize::new(0);
+        let most_running = AtomicUsize::new(0);
+
+        // The first items are the slowest, so they finish last
+        let results = parallel_map(&items, 4, |i| {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            most_running.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(2 * (16 - i)));
+            running.fetch_sub(1, Ordering::SeqCst);
+            format!("chapter {}", i)
+        });
+
+        assert_eq!(
+            results,
+            items
+                .iter()
+                .map(|i| format!("chapter {}", i))
+                .collect::<Vec<_>>()
+        );
+        assert!(most_running.load(Ordering::SeqCst) > 1);
+    }
+
+    #[test]
+    fn parallel_map_runs_with_any_number_of_jobs() {
+        let items = ["a", "b", "c"];
+
+        for jobs in [0, 1, 3, 8] {
+            assert_eq!(
+                parallel_map(&items, jobs, |item| item.to_uppercase()),
+                ["A", "B", "C"]
+            );
+        }
+        assert!(parallel_map(&[] as &[&str], 4, |item| item.len()).is_empty());
+    }
+
+    #[test]
+    fn version_variables_are_escaped() {
+        let info = GitInfo {
+            version: Some("v1.0_<b>rc</b>".to_string()),
+            ..GitInfo::default()
+        };
+        let options = GitInfoOptions::builder().show_version(true).build();
+
+        for (flavor, expected) in [
+            (Flavor::Html, "Since v1.0_&lt;b&gt;rc&lt;/b&gt;.\n"),
+            (Flavor::Xhtml, "Since v1.0_&lt;b&gt;rc&lt;/b&gt;.\n"),
+            (Flavor::Markdown, "Since v1.0\\_&lt;b&gt;rc&lt;/b&gt;.\n"),
+        ] {
+            let mut chapter = Chapter::new(
+                "Intro",
+                "Since {{#git_version}}.\n".to_string(),
+                "intro.md",
+                Vec::new(),
+            );
+            enrich_chapter(&options, flavor, &info, &mut chapter).unwrap();
+            assert_eq!(chapter.content, expected, "{:?}", flavor);
+        }
+    }
+
+    /// The kinds of the blocks of `markdown`, with the text of code blocks.
+    fn blocks(markdown: &str) -> Vec<String> {
+        use pulldown_cmark::{Event, Options, Parser, Tag};
+
+        let mut blocks = Vec::new();
+        let mut code = None::<String>;
+        for event in Parser::new_ext(markdown, Options::ENABLE_TABLES) {
+            match event {
+                Event::Start(Tag::CodeBlock(_)) => code = Some(String::new()),
+                Event::Text(text) if code.is_some() => code.as_mut().unwrap().push_str(&text),
+                Event::End(Tag::CodeBlock(_)) => {
+                    blocks.push(format!("code {:?}", code.take().unwrap()))
+                }
+                Event::Start(Tag::Paragraph) => blocks.push("Paragraph".to_string()),
+                Event::Start(Tag::Heading(..)) => blocks.push("Heading".to_string()),
+                Event::Start(Tag::List(_)) => blocks.push("List".to_string()),
+                Event::Start(Tag::Table(_)) => blocks.push("Table".to_string()),
+                Event::Rule => blocks.push("Rule".to_string()),
+                _ => {}
+            }
+        }
+        blocks
+    }
+
+    #[test]
+    fn footers_never_merge_with_the_end_of_the_chapter() {
+        let info = GitInfo::from_history(
+            &[GitHistoryEntry::test("a", "Alice", "2024-01-01T12:00:00Z")],
+            &GitInfoOptions::default(),
+        );
+        let endings = [
+            ("A paragraph without a newline", vec!["Paragraph"]),
+            ("```rust\nfn main() {}", vec!["code \"fn main() {}\\n\""]),
+            ("- one\n- two\n", vec!["List"]),
+        ];
+
+        for (separator, text, separator_blocks) in [
+            (Separator::Hr, "", vec!["Rule"]),
+            (Separator::None, "", vec![]),
+            (Separator::Custom, "Page history", vec!["Paragraph"]),
+        ] {
+            let options = GitInfoOptions::builder()
+                .separator(separator)
+                .separator_text(text)
+                .build();
+            for (content, content_blocks) in &endings {
+                let mut chapter =
+                    Chapter::new("Intro", content.to_string(), "intro.md", Vec::new());
+                enrich_chapter(&options, Flavor::Markdown, &info, &mut chapter).unwrap();
+
+                let mut expected = content_blocks.clone();
+                expected.extend(&separator_blocks);
+                expected.push("Table");
+                assert_eq!(
+                    blocks(&chapter.content),
+                    expected,
+                    "{:?}\n{}",
+                    separator,
+                    chapter.content
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn footers_go_at_the_end_of_the_designated_section() {
+        let info = GitInfo::from_history(
+            &[GitHistoryEntry::test("a", "Alice", "2024-01-01T12:00:00Z")],
+            &GitInfoOptions::default(),
+        );
+        let options = GitInfoOptions::builder()
+            .insert_under_heading("About this page")
+            .build();
+        let enrich = |content: &str| {
+            let mut chapter = Chapter::new("Intro", content.to_string(), "intro.md", Vec::new());
+            enrich_chapter(&options, Flavor::Markdown, &info, &mut chapter).unwrap();
+            chapter.content
+        };
+
+        // In the middle of the document, after the subsections, and never in a code block
+        let content = enrich(
+            "# Intro\n\n\
+             ```\n## About this page\n```\n\n\
+             ## about THIS page\n\nSee below.\n\n\
+             ### Details\n\nMore.\n\n\
+             ## Next\n\nThe end.\n",
+        );
+        assert_eq!(
+            blocks(&content),
+            [
+                "Heading",
+                "code \"## About this page\\n\"",
+                "Heading",
+                "Paragraph",
+                "Heading",
+                "Paragraph",
+                "Rule",
+                "Table",
+                "Heading",
+                "Paragraph"
+            ],
+            "{}",
+            content
+        );
+        assert!(
+            content.ends_with("|\n\n## Next\n\nThe end.\n"),
+            "{}",
+            content
+        );
+
+        // At the end of the document
+        let content = enrich("# Intro\n\nText.\n\n## About this page\n\nSee below.");
+        assert_eq!(
+            blocks(&content),
+            [
+                "Heading",
+                "Paragraph",
+                "Heading",
+                "Paragraph",
+                "Rule",
+                "Table"
+            ],
+            "{}",
+            content
+        );
+
+        // Missing, besides in a code block
+        let content = enrich("# Intro\n\n```\n## About this page\n```\n");
+        assert_eq!(
+            blocks(&content),
+            ["Heading", "code \"## About this page\\n\"", "Rule", "Table"],
+            "{}",
+            content
+        );
+    }
+}
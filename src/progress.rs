@@ -0,0 +1,213 @@
+use crate::config::{GitInfoOptions, ProgressMode};
+use std::io::{self, IsTerminal, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Minimum time between two redraws of the progress bar.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Minimum time between two progress lines, which end up in the build logs.
+const LINE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Width of the progress bar, in characters.
+const BAR_WIDTH: usize = 30;
+
+/// Longest file name shown next to the progress bar, in characters.
+const NAME_WIDTH: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    /// A single line redrawn in place.
+    Bar,
+    /// Plain lines, for logs.
+    Lines,
+}
+
+#[derive(Debug)]
+struct State {
+    done: usize,
+    last_print: Instant,
+    drawn: bool,
+}
+
+/// Progress of a task over a known number of files, printed to stderr.
+///
+/// It can be advanced from several threads at once: every update is printed whole,
+/// so that the output is never interleaved. The progress bar is cleared when dropped.
+#[derive(Debug)]
+pub struct Progress {
+    style: Option<Style>,
+    total: usize,
+    state: Mutex<State>,
+}
+
+impl Progress {
+    /// Starts the progress over `total` files, shown according to the options.
+    pub fn new(options: &GitInfoOptions, total: usize) -> Progress {
+        let style = match options.progress {
+            ProgressMode::Never => None,
+            ProgressMode::Always => Some(Style::Bar),
+            ProgressMode::Auto if options.quiet => None,
+            ProgressMode::Auto if io::stderr().is_terminal() => Some(Style::Bar),
+            ProgressMode::Auto => Some(Style::Lines),
+        };
+        Progress {
+            style,
+            total,
+            state: Mutex::new(State {
+                done: 0,
+                last_print: Instant::now(),
+                drawn: false,
+            }),
+        }
+    }
+
+    /// Counts `count` more files as done, `current` being the last one.
+    pub fn advance(&self, count: usize, current: &str) {
+        if let Some(line) = self.update(count, current) {
+            // Progress is not worth failing the build for
+            let _ = io::stderr().lock().write_all(line.as_bytes());
+        }
+    }
+
+    /// Counts the files done, and returns the text to print, unless it is too early to print again.
+    fn update(&self, count: usize, current: &str) -> Option<String> {
+        let style = self.style?;
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.done = (state.done + count).min(self.total);
+
+        // Fast builds print nothing at all
+        let interval = match style {
+            Style::Bar => REDRAW_INTERVAL,
+            Style::Lines => LINE_INTERVAL,
+        };
+        if state.last_print.elapsed() < interval {
+            return None;
+        }
+        state.last_print = Instant::now();
+
+        Some(match style {
+            Style::Bar => {
+                let filled = BAR_WIDTH * state.done / self.total.max(1);
+                state.drawn = true;
+                format!(
+                    "\r\x1b[2Kgit-info [{}{}] {}/{} {}",
+                    "=".repeat(filled),
+                    " ".repeat(BAR_WIDTH - filled),
+                    state.done,
+                    self.total,
+                    truncate(current)
+                )
+            }
+            Style::Lines => format!(
+                "git-info: extracted the history of {}/{} files\n",
+                state.done, self.total
+            ),
+        })
+    }
+
+    /// Clears the progress bar, if drawn.
+    pub fn finish(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.drawn {
+            state.drawn = false;
+            let _ = io::stderr().lock().write_all(b"\r\x1b[2K");
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Keeps the end of long names, which tells files apart.
+fn truncate(name: &str) -> String {
+    let chars = name.chars().count();
+    if chars <= NAME_WIDTH {
+        return name.to_string();
+    }
+    let tail = name
+        .chars()
+        .skip(chars - (NAME_WIDTH - 1))
+        .collect::<String>();
+    format!("…{}", tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(mode: ProgressMode, total: usize) -> Progress {
+        let options = GitInfoOptions::builder().progress(mode).build();
+        Progress::new(&options, total)
+    }
+
+    /// Pretends the last print happened `ago`.
+    fn printed(progress: &Progress, ago: Duration) {
+        progress.state.lock().unwrap().last_print = Instant::now() - ago;
+    }
+
+    #[test]
+    fn updates_are_throttled() {
+        let progress = progress(ProgressMode::Always, 60);
+
+        // Nothing right after the start
+        assert_eq!(progress.update(10, "intro.md"), None);
+
+        printed(&progress, REDRAW_INTERVAL);
+        assert_eq!(
+            progress.update(20, "guide.md").as_deref(),
+            Some("\r\x1b[2Kgit-info [===============               ] 30/60 guide.md")
+        );
+        assert!(progress.state.lock().unwrap().drawn);
+
+        // Counted, but not redrawn until the interval has elapsed again
+        assert_eq!(progress.update(10, "faq.md"), None);
+        printed(&progress, REDRAW_INTERVAL);
+        assert!(progress
+            .update(100, "end.md")
+            .unwrap()
+            .contains("[==============================] 60/60 end.md"));
+
+        // Not cleared when dropped, which would write to the output of the tests
+        progress.state.lock().unwrap().drawn = false;
+    }
+
+    #[test]
+    fn logs_get_plain_lines_less_often() {
+        // As when stderr is not a terminal
+        let mut progress = progress(ProgressMode::Always, 3);
+        progress.style = Some(Style::Lines);
+
+        printed(&progress, REDRAW_INTERVAL);
+        assert_eq!(progress.update(1, "intro.md"), None);
+        printed(&progress, LINE_INTERVAL);
+        assert_eq!(
+            progress.update(1, "guide.md").as_deref(),
+            Some("git-info: extracted the history of 2/3 files\n")
+        );
+        assert!(!progress.state.lock().unwrap().drawn);
+    }
+
+    #[test]
+    fn disabled_progress_prints_nothing() {
+        let progress = progress(ProgressMode::Never, 3);
+        printed(&progress, LINE_INTERVAL);
+        assert_eq!(progress.update(1, "intro.md"), None);
+
+        let options = GitInfoOptions::builder().quiet(true).build();
+        assert_eq!(Progress::new(&options, 3).style, None);
+    }
+
+    #[test]
+    fn long_names_keep_their_end() {
+        assert_eq!(truncate("intro.md"), "intro.md");
+        let name = format!("{}/chapter.md", "a".repeat(50));
+        let truncated = truncate(&name);
+        assert_eq!(truncated.chars().count(), NAME_WIDTH);
+        assert!(truncated.starts_with('…'));
+        assert!(truncated.ends_with("aaa/chapter.md"));
+    }
+}
@@ -0,0 +1,221 @@
+use crate::config::GitInfoOptions;
+use crate::git_history::{self, CommitSignature, GitHistoryEntry};
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "libgit2")]
+mod libgit2;
+
+#[cfg(feature = "libgit2")]
+pub use self::libgit2::Libgit2Provider;
+
+/// A source of git histories.
+///
+/// Every backend must return the same data for the same repository.
+pub trait HistoryProvider: Debug + Send + Sync {
+    /// Extracts the history of a single file, newest commit first.
+    fn file_history(&self, path: &Path, options: &GitInfoOptions) -> Result<Vec<GitHistoryEntry>>;
+
+    /// Extracts only the newest commit of a file, followed by the commit which created it if
+    /// different, for when [`GitInfoOptions::needs_full_history`] is false.
+    ///
+    /// Defaults to the whole history, which backends can improve upon.
+    fn file_endpoints(
+        &self,
+        path: &Path,
+        options: &GitInfoOptions,
+    ) -> Result<Vec<GitHistoryEntry>> {
+        self.file_history(path, options)
+    }
+
+    /// Extracts the history of every file under `dir`, keyed by path relative to `dir`.
+    fn all_histories(
+        &self,
+        dir: &Path,
+        options: &GitInfoOptions,
+    ) -> Result<HashMap<PathBuf, Vec<GitHistoryEntry>>>;
+
+    /// Lists the newest `max_commits` commits touching any file under `dir`, newest first,
+    /// along with the touched files relative to `dir`.
+    ///
+    /// Merge commits are only listed when `include_merges` is set, and never report any file.
+    fn recent_commits(
+        &self,
+        dir: &Path,
+        options: &GitInfoOptions,
+        max_commits: usize,
+        include_merges: bool,
+    ) -> Result<Vec<(GitHistoryEntry, Vec<PathBuf>)>>;
+
+    /// Returns the hash of the `HEAD` commit of the repository containing `dir`.
+    fn head(&self, dir: &Path, options: &GitInfoOptions) -> Result<String>;
+
+    /// Returns the branch checked out in the repository containing `dir`, or when `HEAD` is
+    /// detached, a tag pointing at it, if any.
+    fn head_ref(&self, dir: &Path, options: &GitInfoOptions) -> Result<Option<String>>;
+
+    /// Returns the canonical paths of the files under `dir` with uncommitted changes.
+    fn dirty_files(&self, dir: &Path, options: &GitInfoOptions) -> Result<HashSet<PathBuf>>;
+
+    /// Whether the repository containing `dir` is a shallow clone, with a truncated history.
+    fn is_shallow(&self, dir: &Path, options: &GitInfoOptions) -> Result<bool>;
+
+    /// Returns the canonical path of the root of the working tree containing `dir`.
+    fn root(&self, dir: &Path, options: &GitInfoOptions) -> Result<PathBuf>;
+
+    /// Returns the URL of the remote `name` of the repository containing `dir`,
+    /// or `None` if there is no such remote.
+    fn remote_url(
+        &self,
+        dir: &Path,
+        name: &str,
+        options: &GitInfoOptions,
+    ) -> Result<Option<String>>;
+
+    /// Counts the lines of a file last changed by each author, sorted by author name.
+    /// Uncommitted lines are not counted.
+    fn blame(&self, path: &Path, options: &GitInfoOptions) -> Result<Vec<(String, usize)>>;
+
+    /// Returns the full hashes of the commits of `HEAD` which `published_ref` does not contain,
+    /// merges excluded, or `None` if the ref does not exist.
+    fn unpublished_commits(
+        &self,
+        dir: &Path,
+        published_ref: &str,
+        options: &GitInfoOptions,
+    ) -> Result<Option<HashSet<String>>>;
+
+    /// Returns the first tag matching the glob `pattern` which contains the commit `hash`,
+    /// or `None` if no such tag exists yet.
+    fn first_tag_containing(
+        &self,
+        dir: &Path,
+        hash: &str,
+        pattern: &str,
+        options: &GitInfoOptions,
+    ) -> Result<Option<String>>;
+
+    /// Verifies the signature of the commit `hash`.
+    fn signature(
+        &self,
+        dir: &Path,
+        hash: &str,
+        options: &GitInfoOptions,
+    ) -> Result<CommitSignature>;
+
+    /// Returns the text of the notes attached to commits under `notes_ref`, by full hash of the commit.
+    fn notes(
+        &self,
+        dir: &Path,
+        notes_ref: &str,
+        options: &GitInfoOptions,
+    ) -> Result<HashMap<String, String>>;
+}
+
+/// Provider which runs the `git` command line tool.
+#[derive(Debug, Default)]
+pub struct CliProvider;
+
+impl HistoryProvider for CliProvider {
+    fn file_history(&self, path: &Path, options: &GitInfoOptions) -> Result<Vec<GitHistoryEntry>> {
+        git_history::extract(path, options)
+    }
+
+    fn file_endpoints(
+        &self,
+        path: &Path,
+        options: &GitInfoOptions,
+    ) -> Result<Vec<GitHistoryEntry>> {
+        git_history::extract_endpoints(path, options)
+    }
+
+    fn all_histories(
+        &self,
+        dir: &Path,
+        options: &GitInfoOptions,
+    ) -> Result<HashMap<PathBuf, Vec<GitHistoryEntry>>> {
+        git_history::extract_all(dir, options)
+    }
+
+    fn recent_commits(
+        &self,
+        dir: &Path,
+        options: &GitInfoOptions,
+        max_commits: usize,
+        include_merges: bool,
+    ) -> Result<Vec<(GitHistoryEntry, Vec<PathBuf>)>> {
+        git_history::recent_commits(dir, options, max_commits, include_merges)
+    }
+
+    fn head(&self, dir: &Path, options: &GitInfoOptions) -> Result<String> {
+        git_history::head(dir, options)
+    }
+
+    fn head_ref(&self, dir: &Path, options: &GitInfoOptions) -> Result<Option<String>> {
+        git_history::head_ref(dir, options)
+    }
+
+    fn dirty_files(&self, dir: &Path, options: &GitInfoOptions) -> Result<HashSet<PathBuf>> {
+        git_history::dirty_files(dir, options)
+    }
+
+    fn is_shallow(&self, dir: &Path, options: &GitInfoOptions) -> Result<bool> {
+        git_history::is_shallow(dir, options)
+    }
+
+    fn root(&self, dir: &Path, options: &GitInfoOptions) -> Result<PathBuf> {
+        git_history::root(dir, options)
+    }
+
+    fn remote_url(
+        &self,
+        dir: &Path,
+        name: &str,
+        options: &GitInfoOptions,
+    ) -> Result<Option<String>> {
+        git_history::remote_url(dir, name, options)
+    }
+
+    fn blame(&self, path: &Path, options: &GitInfoOptions) -> Result<Vec<(String, usize)>> {
+        git_history::blame(path, options)
+    }
+
+    fn unpublished_commits(
+        &self,
+        dir: &Path,
+        published_ref: &str,
+        options: &GitInfoOptions,
+    ) -> Result<Option<HashSet<String>>> {
+        git_history::unpublished_commits(dir, published_ref, options)
+    }
+
+    fn first_tag_containing(
+        &self,
+        dir: &Path,
+        hash: &str,
+        pattern: &str,
+        options: &GitInfoOptions,
+    ) -> Result<Option<String>> {
+        git_history::first_tag_containing(dir, hash, pattern, options)
+    }
+
+    fn signature(
+        &self,
+        dir: &Path,
+        hash: &str,
+        options: &GitInfoOptions,
+    ) -> Result<CommitSignature> {
+        git_history::signature(dir, hash, options)
+    }
+
+    fn notes(
+        &self,
+        dir: &Path,
+        notes_ref: &str,
+        options: &GitInfoOptions,
+    ) -> Result<HashMap<String, String>> {
+        git_history::notes(dir, notes_ref, options)
+    }
+}
@@ -0,0 +1,452 @@
+use super::HistoryProvider;
+use crate::config::{GitInfoOptions, Identity};
+use crate::git_history::{message_trailers, truncate_history, CommitSignature, GitHistoryEntry};
+use anyhow::{Context, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use git2::{Commit, DiffOptions, ErrorCode, Oid, Repository, Sort, StatusOptions, Time};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Provider which reads the repository in-process through libgit2, without needing a `git` binary.
+///
+/// Renames are not followed: `follow_renames` must be disabled to use it.
+#[derive(Debug, Default)]
+pub struct Libgit2Provider;
+
+impl HistoryProvider for Libgit2Provider {
+    fn file_history(&self, path: &Path, options: &GitInfoOptions) -> Result<Vec<GitHistoryEntry>> {
+        let (repo, relative) = open(path)?;
+
+        let mut history = Vec::new();
+        for commit in walk(&repo)? {
+            let commit = commit?;
+            let id = entry_id(&commit, &relative);
+
+            // Like `git log -- <path>`: a commit is listed when the file differs from
+            // every one of its parents (or, for root commits, when the file exists)
+            let changed = if commit.parent_count() == 0 {
+                id.is_some()
+            } else {
+                commit
+                    .parents()
+                    .all(|parent| entry_id(&parent, &relative) != id)
+            };
+            if changed {
+                let mut entry = to_entry(&commit, options)?;
+                if options.churn {
+                    (entry.lines_added, entry.lines_removed) =
+                        churn(&repo, commit.parents().next().as_ref(), &commit, &relative)?;
+                }
+                history.push(entry);
+            }
+        }
+
+        truncate_history(&mut history, options.max_history);
+        Ok(history)
+    }
+
+    fn all_histories(
+        &self,
+        dir: &Path,
+        options: &GitInfoOptions,
+    ) -> Result<HashMap<PathBuf, Vec<GitHistoryEntry>>> {
+        let (repo, prefix) = open(dir)?;
+
+        let mut histories = HashMap::<PathBuf, Vec<GitHistoryEntry>>::new();
+        for commit in walk(&repo)? {
+            let commit = commit?;
+
+            // Like `git log --name-status`, merges do not report any file
+            if commit.parent_count() > 1 {
+                continue;
+            }
+
+            let entry = to_entry(&commit, options)?;
+            let parent = commit.parents().next();
+            for path in changed_paths(&repo, parent.as_ref(), &commit, &prefix)? {
+                let mut entry = entry.clone();
+                if options.churn {
+                    (entry.lines_added, entry.lines_removed) =
+                        churn(&repo, parent.as_ref(), &commit, &prefix.join(&path))?;
+                }
+                histories.entry(path).or_default().push(entry);
+            }
+        }
+
+        for history in histories.values_mut() {
+            truncate_history(history, options.max_history);
+        }
+        Ok(histories)
+    }
+
+    fn recent_commits(
+        &self,
+        dir: &Path,
+        options: &GitInfoOptions,
+        max_commits: usize,
+        include_merges: bool,
+    ) -> Result<Vec<(GitHistoryEntry, Vec<PathBuf>)>> {
+        let (repo, prefix) = open(dir)?;
+
+        let mut commits = Vec::new();
+        for commit in walk(&repo)? {
+            if commits.len() == max_commits {
+                break;
+            }
+            let commit = commit?;
+
+            // Like `git log -- <dir>`, merges are listed when they differ from every parent,
+            // but they do not report any file
+            let paths = if commit.parent_count() > 1 {
+                if !include_merges {
+                    continue;
+                }
+                let mut treesame = false;
+                for parent in commit.parents() {
+                    treesame |= changed_paths(&repo, Some(&parent), &commit, &prefix)?.is_empty();
+                }
+                if treesame {
+                    continue;
+                }
+                Vec::new()
+            } else {
+                let paths =
+                    changed_paths(&repo, commit.parents().next().as_ref(), &commit, &prefix)?;
+                if paths.is_empty() {
+                    continue;
+                }
+                paths
+            };
+            commits.push((to_entry(&commit, options)?, paths));
+        }
+        Ok(commits)
+    }
+
+    fn head(&self, dir: &Path, _options: &GitInfoOptions) -> Result<String> {
+        let repo = Repository::discover(dir).context("Cannot open the git repository")?;
+        let head = repo.head()?.peel_to_commit()?;
+        Ok(head.id().to_string())
+    }
+
+    fn dirty_files(&self, dir: &Path, _options: &GitInfoOptions) -> Result<HashSet<PathBuf>> {
+        let (repo, prefix) = open(dir)?;
+        let root = workdir(&repo)?;
+
+        let mut status_options = StatusOptions::new();
+        status_options.include_untracked(true);
+        if !prefix.as_os_str().is_empty() {
+            status_options.pathspec(prefix.as_path());
+        }
+        let statuses = repo.statuses(Some(&mut status_options))?;
+        let dirty = statuses
+            .iter()
+            .filter_map(|entry| entry.path().map(|path| root.join(path)))
+            .collect();
+        Ok(dirty)
+    }
+
+    fn head_ref(&self, dir: &Path, _options: &GitInfoOptions) -> Result<Option<String>> {
+        let repo = Repository::discover(dir).context("Cannot open the git repository")?;
+        let head = repo.head()?;
+        if head.is_branch() {
+            return Ok(head.shorthand().map(str::to_string));
+        }
+
+        // Like `git tag --points-at`, in the order of the names
+        let target = head.peel_to_commit()?.id();
+        for name in repo.tag_names(None)?.iter().flatten() {
+            let tag = repo.revparse_single(&format!("refs/tags/{}", name))?;
+            if tag.peel_to_commit().map(|commit| commit.id()).ok() == Some(target) {
+                return Ok(Some(name.to_string()));
+            }
+        }
+        Ok(None)
+    }
+
+    fn is_shallow(&self, dir: &Path, _options: &GitInfoOptions) -> Result<bool> {
+        let repo = Repository::discover(dir).context("Cannot open the git repository")?;
+        Ok(repo.is_shallow())
+    }
+
+    fn root(&self, dir: &Path, _options: &GitInfoOptions) -> Result<PathBuf> {
+        let repo = Repository::discover(dir).context("Cannot open the git repository")?;
+        workdir(&repo)
+    }
+
+    fn remote_url(
+        &self,
+        dir: &Path,
+        name: &str,
+        _options: &GitInfoOptions,
+    ) -> Result<Option<String>> {
+        let repo = Repository::discover(dir).context("Cannot open the git repository")?;
+        let remote = match repo.find_remote(name) {
+            Ok(remote) => remote,
+            Err(e) if e.code() == ErrorCode::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(remote.url().map(str::to_string))
+    }
+
+    fn blame(&self, path: &Path, options: &GitInfoOptions) -> Result<Vec<(String, usize)>> {
+        let (repo, relative) = open(path)?;
+
+        let mut counts = BTreeMap::<String, usize>::new();
+        for hunk in repo.blame_file(&relative, None)?.iter() {
+            // Uncommitted lines are attributed to the null commit
+            if hunk.final_commit_id().is_zero() {
+                continue;
+            }
+            // Blame hunks only carry the author
+            let signature = match options.identity {
+                Identity::Committer => repo
+                    .find_commit(hunk.final_commit_id())?
+                    .committer()
+                    .to_owned(),
+                _ => hunk.final_signature(),
+            };
+            let author = String::from_utf8_lossy(signature.name_bytes()).into_owned();
+            *counts.entry(author).or_default() += hunk.lines_in_hunk();
+        }
+        Ok(counts.into_iter().collect())
+    }
+
+    fn unpublished_commits(
+        &self,
+        dir: &Path,
+        published_ref: &str,
+        _options: &GitInfoOptions,
+    ) -> Result<Option<HashSet<String>>> {
+        let repo = Repository::discover(dir).context("Cannot open the git repository")?;
+        let published = match repo.revparse_single(published_ref) {
+            Ok(object) => object.peel_to_commit()?.id(),
+            Err(e) if e.code() == ErrorCode::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut revwalk = repo.revwalk()?;
+        revwalk.push_head()?;
+        revwalk.hide(published)?;
+        let mut commits = HashSet::new();
+        for oid in revwalk {
+            let oid = oid?;
+            // Like `git rev-list --no-merges`
+            if repo.find_commit(oid)?.parent_count() <= 1 {
+                commits.insert(oid.to_string());
+            }
+        }
+        Ok(Some(commits))
+    }
+
+    fn first_tag_containing(
+        &self,
+        dir: &Path,
+        hash: &str,
+        pattern: &str,
+        _options: &GitInfoOptions,
+    ) -> Result<Option<String>> {
+        let repo = Repository::discover(dir).context("Cannot open the git repository")?;
+        let commit = Oid::from_str(hash)?;
+
+        // The tag pointing at the oldest commit containing the commit came first
+        let mut first = None::<(i64, String)>;
+        for name in repo.tag_names(Some(pattern))?.iter().flatten() {
+            let tagged = repo
+                .revparse_single(&format!("refs/tags/{}", name))?
+                .peel_to_commit()?;
+            if tagged.id() != commit && !repo.graph_descendant_of(tagged.id(), commit)? {
+                continue;
+            }
+            let time = tagged.time().seconds();
+            if first.as_ref().is_none_or(|(first, _)| time < *first) {
+                first = Some((time, name.to_string()));
+            }
+        }
+        Ok(first.map(|(_, name)| name))
+    }
+
+    fn signature(
+        &self,
+        _dir: &Path,
+        _hash: &str,
+        _options: &GitInfoOptions,
+    ) -> Result<CommitSignature> {
+        // Rejected by `GitInfoOptions::validate`
+        Err(anyhow::anyhow!(
+            "The libgit2 backend cannot verify signatures"
+        ))
+    }
+
+    fn notes(
+        &self,
+        dir: &Path,
+        notes_ref: &str,
+        _options: &GitInfoOptions,
+    ) -> Result<HashMap<String, String>> {
+        let repo = Repository::discover(dir).context("Cannot open the git repository")?;
+
+        // Like `git notes --ref`, short names live under `refs/notes/`
+        let notes_ref = if notes_ref.starts_with("refs/") {
+            notes_ref.to_string()
+        } else {
+            format!("refs/notes/{}", notes_ref)
+        };
+        let iter = match repo.notes(Some(&notes_ref)) {
+            Ok(iter) => iter,
+            Err(e) if e.code() == ErrorCode::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut notes = HashMap::new();
+        for note in iter {
+            let (_, annotated) = note?;
+            let note = repo.find_note(Some(&notes_ref), annotated)?;
+            notes.insert(
+                annotated.to_string(),
+                String::from_utf8_lossy(note.message_bytes())
+                    .trim_end()
+                    .to_string(),
+            );
+        }
+        Ok(notes)
+    }
+}
+
+/// Opens the repository containing `path`, returning it along with the path relative to its root.
+fn open(path: &Path) -> Result<(Repository, PathBuf)> {
+    let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let start = if canonical.is_dir() {
+        canonical.as_path()
+    } else {
+        canonical.parent().unwrap_or(&canonical)
+    };
+    let repo = Repository::discover(start).context("Cannot open the git repository")?;
+    let relative = canonical
+        .strip_prefix(workdir(&repo)?)
+        .with_context(|| format!("{} is outside of the repository", path.display()))?
+        .to_path_buf();
+    Ok((repo, relative))
+}
+
+fn workdir(repo: &Repository) -> Result<PathBuf> {
+    let workdir = repo
+        .workdir()
+        .context("Bare repositories are not supported")?;
+    Ok(fs::canonicalize(workdir).unwrap_or_else(|_| workdir.to_path_buf()))
+}
+
+/// Walks the commits reachable from `HEAD` in the same order as `git log`.
+fn walk(repo: &Repository) -> Result<impl Iterator<Item = Result<Commit<'_>>>> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TIME)?;
+    revwalk.push_head()?;
+    Ok(revwalk.map(move |id| Ok(repo.find_commit(id?)?)))
+}
+
+/// Returns the files under `prefix` which differ between `parent` and `commit`,
+/// relative to `prefix`.
+fn changed_paths(
+    repo: &Repository,
+    parent: Option<&Commit>,
+    commit: &Commit,
+    prefix: &Path,
+) -> Result<Vec<PathBuf>> {
+    let parent_tree = match parent {
+        Some(parent) => Some(parent.tree()?),
+        None => None,
+    };
+    let mut diff_options = DiffOptions::new();
+    if !prefix.as_os_str().is_empty() {
+        diff_options.pathspec(prefix);
+    }
+    let diff = repo.diff_tree_to_tree(
+        parent_tree.as_ref(),
+        Some(&commit.tree()?),
+        Some(&mut diff_options),
+    )?;
+
+    Ok(diff
+        .deltas()
+        .filter_map(|delta| {
+            let path = delta
+                .new_file()
+                .path()
+                .or_else(|| delta.old_file().path())?;
+            Some(path.strip_prefix(prefix).ok()?.to_path_buf())
+        })
+        .collect())
+}
+
+/// Returns the lines added and removed to the file at `path` between `parent` and `commit`.
+fn churn(
+    repo: &Repository,
+    parent: Option<&Commit>,
+    commit: &Commit,
+    path: &Path,
+) -> Result<(u64, u64)> {
+    let parent_tree = match parent {
+        Some(parent) => Some(parent.tree()?),
+        None => None,
+    };
+    let mut diff_options = DiffOptions::new();
+    diff_options.pathspec(path);
+    let stats = repo
+        .diff_tree_to_tree(
+            parent_tree.as_ref(),
+            Some(&commit.tree()?),
+            Some(&mut diff_options),
+        )?
+        .stats()?;
+    Ok((stats.insertions() as u64, stats.deletions() as u64))
+}
+
+/// Returns the id of the object at `path` in the tree of `commit`, if any.
+fn entry_id(commit: &Commit, path: &Path) -> Option<Oid> {
+    commit
+        .tree()
+        .ok()?
+        .get_path(path)
+        .ok()
+        .map(|entry| entry.id())
+}
+
+fn to_entry(commit: &Commit, options: &GitInfoOptions) -> Result<GitHistoryEntry> {
+    let decode = |bytes: &[u8]| -> Result<String> {
+        if options.strict_utf8 {
+            Ok(std::str::from_utf8(bytes)
+                .context("Invalid UTF-8 in commit metadata")?
+                .to_string())
+        } else {
+            Ok(String::from_utf8_lossy(bytes).into_owned())
+        }
+    };
+    let author = commit.author();
+    let committer = commit.committer();
+
+    Ok(GitHistoryEntry {
+        hash: commit.id().to_string(),
+        short_hash: commit
+            .as_object()
+            .short_id()?
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        author: decode(author.name_bytes())?,
+        email: decode(author.email_bytes())?,
+        committer: decode(committer.name_bytes())?,
+        committer_email: decode(committer.email_bytes())?,
+        timestamp: to_datetime(author.when())?,
+        commit_timestamp: to_datetime(committer.when())?,
+        subject: decode(commit.summary_bytes().unwrap_or_default())?,
+        trailers: message_trailers(&decode(commit.message_bytes())?),
+        lines_added: 0,
+        lines_removed: 0,
+    })
+}
+
+fn to_datetime(time: Time) -> Result<DateTime<Utc>> {
+    Utc.timestamp_opt(time.seconds(), 0)
+        .single()
+        .with_context(|| format!("Commit timestamp {} is out of range", time.seconds()))
+}
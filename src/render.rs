@@ -0,0 +1,1932 @@
+use crate::activity::{self, MonthlyActivity};
+use crate::config::{
+    ActivityGraph, Changelog, GitInfoOptions, Identity, OutputFormat, RecentlyUpdated, Separator,
+};
+use crate::git_history::{GitHistoryEntry, SignatureStatus};
+use crate::github::GitHubProfile;
+use crate::info::{BuildProvenance, Contributor, GitInfo};
+use crate::locale;
+use crate::stylesheet;
+use chrono::SecondsFormat;
+use pulldown_cmark::{Event, Parser, Tag};
+use serde_json::json;
+use std::path::Path;
+
+/// Size of the avatars of the contributors, in pixels.
+const AVATAR_SIZE: u32 = 20;
+
+/// Kind of markup accepted by the renderer the book is being prepared for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Flavor {
+    /// Markdown mixed with raw HTML.
+    Html,
+    /// Markdown mixed with raw HTML which is also well-formed XML, as required by EPUB.
+    /// Elements which are not universally supported by e-readers are avoided.
+    Xhtml,
+    /// Pure markdown, without any raw HTML.
+    Markdown,
+}
+
+impl Flavor {
+    /// Picks the flavor suited to the given mdbook renderer.
+    pub fn for_renderer(renderer: &str) -> Flavor {
+        match renderer {
+            "markdown" => Flavor::Markdown,
+            "epub" => Flavor::Xhtml,
+            _ => Flavor::Html,
+        }
+    }
+
+    /// Separator between the items of a list inside a single table cell.
+    fn cell_separator(self) -> &'static str {
+        match self {
+            Flavor::Html => "<br>",
+            Flavor::Xhtml => "<br />",
+            Flavor::Markdown => ", ",
+        }
+    }
+}
+
+/// Renders the footer appended to each chapter.
+///
+/// This is the only place where git-derived strings are interpolated into the output,
+/// so every value is escaped here exactly once.
+pub fn footer(info: &GitInfo, options: &GitInfoOptions, flavor: Flavor) -> String {
+    if options.output == OutputFormat::Html && flavor != Flavor::Markdown {
+        return html_footer(info, options, flavor);
+    }
+
+    let preamble = preamble(options, flavor);
+
+    // Each column is a header, an alignment, a cell and whether there is anything to show in it,
+    // decided before formatting since missing values are rendered as placeholders like "n/a"
+    let mut columns = match single_edit(info, options) {
+        Some(entry) => vec![
+            (
+                "Written on",
+                ":---:",
+                format!("**{}**", date_cell(Some(entry), options)),
+                true,
+            ),
+            (
+                "Written by",
+                ":---:",
+                format!("**{}**", author_cell(Some(entry), info, options, flavor)),
+                true,
+            ),
+        ],
+        None => vec![
+            (
+                "Created on",
+                ":---:",
+                format!("**{}**", date_cell(info.created.as_ref(), options)),
+                info.created.is_some(),
+            ),
+            (
+                "Created by",
+                ":---:",
+                format!(
+                    "**{}**",
+                    author_cell(info.created.as_ref(), info, options, flavor)
+                ),
+                info.created.is_some(),
+            ),
+            (
+                "Last edit on",
+                ":---:",
+                format!("**{}**", date_cell(info.last_edit.as_ref(), options)),
+                info.last_edit.is_some(),
+            ),
+            (
+                "Last edit by",
+                ":---:",
+                format!(
+                    "**{}**",
+                    author_cell(info.last_edit.as_ref(), info, options, flavor)
+                ),
+                info.last_edit.is_some(),
+            ),
+        ],
+    };
+    if options.show_signature_status {
+        let signature = signature(info, options);
+        columns.push((
+            "Signature",
+            ":---:",
+            signature.map_or_else(String::new, |(label, _)| escape_markdown(label)),
+            signature.is_some(),
+        ));
+    }
+    if options.show_other_contributors {
+        columns.push((
+            "Other contributors",
+            "---",
+            contributors_cell(info, options, flavor),
+            !info.other_contributors.is_empty() || info.truncated,
+        ));
+    }
+    if options.notes_ref.is_some() {
+        let review = info.last_reviewed.as_ref();
+        let reviewer = review.and_then(|r| r.reviewer.as_deref());
+        columns.push((
+            "Last reviewed on",
+            ":---:",
+            review.map_or_else(
+                || "n/a".to_string(),
+                |r| escape_markdown(&options.format_date(&r.date)),
+            ),
+            review.is_some(),
+        ));
+        columns.push((
+            "Last reviewed by",
+            ":---:",
+            escape_markdown(reviewer.unwrap_or("n/a")),
+            reviewer.is_some(),
+        ));
+    }
+    if options.primary_author {
+        columns.push((
+            "Primary author",
+            ":---:",
+            escape_markdown(&primary_author(info, options)),
+            info.primary_author.is_some(),
+        ));
+    }
+    if options.churn {
+        columns.push((
+            "Changes",
+            "---",
+            escape_markdown(&churn(info)),
+            info.commit_count > 0,
+        ));
+    }
+    if options.published_ref.is_some() {
+        columns.push((
+            "Unpublished",
+            ":---:",
+            unpublished_changes(info),
+            info.unpublished_changes > 0,
+        ));
+    }
+    if options.show_version {
+        columns.push((
+            "Released in",
+            ":---:",
+            escape_markdown(version(info, options)),
+            info.version.is_some(),
+        ));
+    }
+    if options.show_contributor_count {
+        columns.push((
+            "Contributors",
+            ":---:",
+            escape_markdown(&contributor_count(info, options)),
+            true,
+        ));
+    }
+    if options.show_reviewers {
+        columns.push((
+            "Reviewed by",
+            "---",
+            names_cell(&info.reviewers, flavor),
+            !info.reviewers.is_empty(),
+        ));
+    }
+    if options.codeowners {
+        columns.push((
+            "Owners",
+            "---",
+            names_cell(&info.owners, flavor),
+            !info.owners.is_empty(),
+        ));
+    }
+    if let Some(graph) = activity_graph(info, options, flavor) {
+        columns.push(("Activity", ":---:", graph, !info.activity.is_empty()));
+    }
+    if options.hide_empty_columns {
+        columns.retain(|(_, _, _, has_content)| *has_content);
+    }
+    let row = |cells: Vec<&str>| format!("| {} |\n", cells.join(" | "));
+    let mut table = format!(
+        "{}{}{}",
+        row(columns.iter().map(|(header, _, _, _)| *header).collect()),
+        row(columns.iter().map(|(_, align, _, _)| *align).collect()),
+        row(columns
+            .iter()
+            .map(|(_, _, cell, _)| cell.as_str())
+            .collect())
+    );
+    if let Some(date) = book_last_update(info, options) {
+        table.push_str(&format!(
+            "\nBook last updated: {}\n",
+            escape_markdown(&date)
+        ));
+    }
+    if let Some(copyright) = copyright_line(info, options) {
+        table.push_str(&format!("\n{}\n", escape_markdown(copyright)));
+    }
+    if let Some(provenance) = &info.provenance {
+        table.push_str(&format!("\n{}\n", provenance_markdown(provenance, options)));
+    }
+
+    match flavor {
+        Flavor::Markdown => format!("\n\n{}{}", preamble, table),
+        Flavor::Html | Flavor::Xhtml => {
+            // Blank lines around the inner markdown keep it from being swallowed by the HTML block
+            format!(
+                "\n\n{}{}",
+                style_block(options, flavor),
+                details(
+                    format!(
+                        "<div class=\"git-info-footer\">\n\n{}{}\n</div>\n",
+                        preamble, table
+                    ),
+                    options,
+                    flavor
+                )
+            )
+        }
+    }
+}
+
+/// Renders the note shown instead of the footer of a chapter without history,
+/// see [`MissingHistory::Placeholder`](crate::config::MissingHistory::Placeholder).
+pub fn placeholder(info: &GitInfo, options: &GitInfoOptions, flavor: Flavor) -> String {
+    let preamble = preamble(options, flavor);
+    let mut text = options.missing_history_text.trim().to_string();
+    if let Some(copyright) = copyright_line(info, options) {
+        text.push_str(&format!("\n\n{}", escape_markdown(copyright)));
+    }
+    match flavor {
+        Flavor::Markdown => format!("\n\n{}{}\n", preamble, text),
+        Flavor::Html | Flavor::Xhtml => format!(
+            "\n\n{}<div class=\"git-info-footer git-info-missing\">\n\n{}{}\n\n</div>\n",
+            style_block(options, flavor),
+            preamble,
+            text
+        ),
+    }
+}
+
+/// The formatted date of the last edit of the whole book, if shown in the footer.
+fn book_last_update(info: &GitInfo, options: &GitInfoOptions) -> Option<String> {
+    info.book_last_edit
+        .filter(|_| options.show_book_last_update)
+        .map(|date| options.format_date(&date))
+}
+
+/// Renders the stamp with the revision the book is built from, on its own line, like
+/// "Built on 15 Oct 2026 from commit abc1234 (main), with uncommitted changes."
+pub fn provenance(
+    provenance: &BuildProvenance,
+    options: &GitInfoOptions,
+    flavor: Flavor,
+) -> String {
+    match flavor {
+        Flavor::Markdown => provenance_markdown(provenance, options),
+        Flavor::Html | Flavor::Xhtml => provenance_html(provenance, options),
+    }
+}
+
+fn provenance_markdown(provenance: &BuildProvenance, options: &GitInfoOptions) -> String {
+    let commit = format!("`{}`", short_commit(provenance));
+    let commit = match provenance_url(provenance, options) {
+        Some(url) => format!("[{}]({})", commit, url),
+        None => commit,
+    };
+    format!(
+        "Built on {} from commit {}{}{}.",
+        escape_markdown(&options.format_date(&provenance.built_at)),
+        commit,
+        provenance
+            .reference
+            .as_ref()
+            .map(|reference| format!(" ({})", escape_markdown(reference)))
+            .unwrap_or_default(),
+        if provenance.dirty {
+            ", with uncommitted changes"
+        } else {
+            ""
+        }
+    )
+}
+
+fn provenance_html(provenance: &BuildProvenance, options: &GitInfoOptions) -> String {
+    let commit = format!("<code>{}</code>", escape_html(short_commit(provenance)));
+    let commit = match provenance_url(provenance, options) {
+        Some(url) => format!("<a href=\"{}\">{}</a>", escape_html(&url), commit),
+        None => commit,
+    };
+    format!(
+        "<p class=\"git-info-provenance\">Built on <time datetime=\"{}\">{}</time> \
+         from commit {}{}{}.</p>\n",
+        provenance
+            .built_at
+            .to_rfc3339_opts(SecondsFormat::Secs, false),
+        escape_html(&options.format_date(&provenance.built_at)),
+        commit,
+        provenance
+            .reference
+            .as_ref()
+            .map(|reference| format!(" ({})", escape_html(reference)))
+            .unwrap_or_default(),
+        if provenance.dirty {
+            ", <span class=\"git-info-dirty\">with uncommitted changes</span>"
+        } else {
+            ""
+        }
+    )
+}
+
+fn short_commit(provenance: &BuildProvenance) -> &str {
+    provenance.commit.get(..7).unwrap_or(&provenance.commit)
+}
+
+/// Link to the commit of the build, from the template of the provenance settings or else
+/// of the changelog.
+fn provenance_url(provenance: &BuildProvenance, options: &GitInfoOptions) -> Option<String> {
+    options
+        .provenance
+        .as_ref()
+        .and_then(|settings| settings.commit_url.as_ref())
+        .or(options
+            .changelog
+            .as_ref()
+            .and_then(|changelog| changelog.commit_url.as_ref()))
+        .map(|url| url.replace("{hash}", &provenance.commit))
+}
+
+/// The copyright line of the chapter, if shown in the footer.
+fn copyright_line<'a>(info: &'a GitInfo, options: &GitInfoOptions) -> Option<&'a str> {
+    info.copyright
+        .as_deref()
+        .filter(|_| options.copyright.as_ref().is_some_and(|c| c.footer))
+}
+
+/// Separates the footer from the content of the chapter.
+fn preamble(options: &GitInfoOptions, flavor: Flavor) -> String {
+    match (options.separator, flavor) {
+        (Separator::Hr, Flavor::Html) => "<br>\n\n---\n\n<br>\n\n".to_string(),
+        (Separator::Hr, Flavor::Xhtml) => "<br />\n\n---\n\n<br />\n\n".to_string(),
+        (Separator::Hr, Flavor::Markdown) => "---\n\n".to_string(),
+        (Separator::None, _) => String::new(),
+        (Separator::Custom, _) => format!("{}\n\n", options.separator_text.trim_end()),
+    }
+}
+
+/// Renders the footer as semantic HTML, with stable class names for theming:
+///
+/// ```html
+/// <footer class="git-info git-info-footer">
+/// <dl>
+/// <dt class="git-info-created">Created</dt>
+/// <dd class="git-info-created"><time datetime="...">...</time> by <span class="git-info-author">...</span></dd>
+/// <dt class="git-info-last-edit">Last edit</dt>
+/// <dd class="git-info-last-edit"><time datetime="...">...</time> by <span class="git-info-author">...</span>
+///   <span class="git-info-signature git-info-signature-verified" title="Signed by ...">verified</span></dd>
+/// <dt class="git-info-contributors">Other contributors</dt>
+/// <dd class="git-info-contributors"><ul><li>...</li></ul></dd>
+/// <dt class="git-info-last-reviewed">Last reviewed</dt>
+/// <dd class="git-info-last-reviewed"><time datetime="...">...</time> by <span class="git-info-author">...</span></dd>
+/// <dt class="git-info-primary-author">Primary author</dt>
+/// <dd class="git-info-primary-author">Jane Doe (72%)</dd>
+/// <dt class="git-info-churn">Changes</dt>
+/// <dd class="git-info-churn">1,450 lines added / 600 removed across 27 commits</dd>
+/// <dt class="git-info-unpublished">Unpublished</dt>
+/// <dd class="git-info-unpublished">3 changes pending publication</dd>
+/// <dt class="git-info-version">Released in</dt>
+/// <dd class="git-info-version">v2.3.0</dd>
+/// <dt class="git-info-contributor-count">Contributors</dt>
+/// <dd class="git-info-contributor-count">5 contributors</dd>
+/// <dt class="git-info-reviewers">Reviewed by</dt>
+/// <dd class="git-info-reviewers"><ul><li>...</li></ul></dd>
+/// <dt class="git-info-owners">Owners</dt>
+/// <dd class="git-info-owners"><ul><li>...</li></ul></dd>
+/// <dt class="git-info-activity">Activity</dt>
+/// <dd class="git-info-activity"><svg ...>...</svg></dd>
+/// </dl>
+/// <p class="git-info-book-last-update">Book last updated: <time datetime="...">...</time></p>
+/// <p class="git-info-copyright">© 2019–2025 Example Corp</p>
+/// </footer>
+/// ```
+fn html_footer(info: &GitInfo, options: &GitInfoOptions, flavor: Flavor) -> String {
+    let commit = |entry: Option<&GitHistoryEntry>| match entry {
+        Some(entry) => format!(
+            "<time datetime=\"{}\">{}</time> {}",
+            entry.timestamp.to_rfc3339(),
+            escape_html(&options.format_date(&entry.timestamp)),
+            match both_identities(entry, options) {
+                Some((author, committer)) => format!(
+                    "authored by <span class=\"git-info-author\">{}</span>, \
+                     committed by <span class=\"git-info-committer\">{}</span>",
+                    person_html(author, info, flavor),
+                    person_html(committer, info, flavor)
+                ),
+                None => format!(
+                    "by <span class=\"git-info-author\">{}</span>",
+                    person_html(entry.name(options.identity), info, flavor)
+                ),
+            }
+        ),
+        None => "n/a".to_string(),
+    };
+
+    let mut contributors = info
+        .other_contributors
+        .iter()
+        .map(|author| format!("<li>{}</li>", person_html(author, info, flavor)))
+        .collect::<String>();
+    if info.truncated {
+        contributors.push_str(&format!(
+            "<li class=\"git-info-incomplete\">{}</li>",
+            escape_html(&options.incomplete_suffix)
+        ));
+    }
+    let contributors = if options.show_other_contributors
+        && !(options.hide_empty_columns && contributors.is_empty())
+    {
+        format!(
+            "<dt class=\"git-info-contributors\">Other contributors</dt>\n\
+            <dd class=\"git-info-contributors\"><ul>{}</ul></dd>\n",
+            contributors
+        )
+    } else {
+        String::new()
+    };
+
+    // Optional lists of names, each with its own class
+    let list = |enabled: bool, class: &str, title: &str, names: &[String]| {
+        if !enabled || (options.hide_empty_columns && names.is_empty()) {
+            return String::new();
+        }
+        let names = names
+            .iter()
+            .map(|name| format!("<li>{}</li>", escape_html(name)))
+            .collect::<String>();
+        format!(
+            "<dt class=\"{0}\">{1}</dt>\n\
+            <dd class=\"{0}\"><ul>{2}</ul></dd>\n",
+            class, title, names
+        )
+    };
+    let review = match (&options.notes_ref, &info.last_reviewed) {
+        (None, _) => String::new(),
+        (Some(_), None) if options.hide_empty_columns => String::new(),
+        (Some(_), review) => format!(
+            "<dt class=\"git-info-last-reviewed\">Last reviewed</dt>\n\
+            <dd class=\"git-info-last-reviewed\">{}</dd>\n",
+            review.as_ref().map_or_else(
+                || "n/a".to_string(),
+                |review| {
+                    let date = format!(
+                        "<time datetime=\"{}\">{}</time>",
+                        review.date.to_rfc3339(),
+                        escape_html(&options.format_date(&review.date))
+                    );
+                    match &review.reviewer {
+                        Some(reviewer) => format!(
+                            "{} by <span class=\"git-info-author\">{}</span>",
+                            date,
+                            escape_html(reviewer)
+                        ),
+                        None => date,
+                    }
+                }
+            )
+        ),
+    };
+    let primary = if options.primary_author
+        && !(options.hide_empty_columns && info.primary_author.is_none())
+    {
+        format!(
+            "<dt class=\"git-info-primary-author\">Primary author</dt>\n\
+            <dd class=\"git-info-primary-author\">{}</dd>\n",
+            escape_html(&primary_author(info, options))
+        )
+    } else {
+        String::new()
+    };
+    let unpublished = if options.published_ref.is_some() && info.unpublished_changes > 0 {
+        format!(
+            "<dt class=\"git-info-unpublished\">Unpublished</dt>\n\
+            <dd class=\"git-info-unpublished\">{}</dd>\n",
+            unpublished_changes(info)
+        )
+    } else {
+        String::new()
+    };
+    let version = if options.show_version && !(options.hide_empty_columns && info.version.is_none())
+    {
+        format!(
+            "<dt class=\"git-info-version\">Released in</dt>\n\
+            <dd class=\"git-info-version\">{}</dd>\n",
+            escape_html(version(info, options))
+        )
+    } else {
+        String::new()
+    };
+    let changes = if options.churn {
+        format!(
+            "<dt class=\"git-info-churn\">Changes</dt>\n\
+            <dd class=\"git-info-churn\">{}</dd>\n",
+            escape_html(&churn(info))
+        )
+    } else {
+        String::new()
+    };
+    let count = if options.show_contributor_count {
+        format!(
+            "<dt class=\"git-info-contributor-count\">Contributors</dt>\n\
+            <dd class=\"git-info-contributor-count\">{}</dd>\n",
+            escape_html(&contributor_count(info, options))
+        )
+    } else {
+        String::new()
+    };
+    let extra = review
+        + &primary
+        + &changes
+        + &unpublished
+        + &version
+        + &count
+        + &list(
+            options.show_reviewers,
+            "git-info-reviewers",
+            "Reviewed by",
+            &info.reviewers,
+        )
+        + &list(
+            options.codeowners,
+            "git-info-owners",
+            "Owners",
+            &info.owners,
+        )
+        + &activity_graph(info, options, flavor)
+            .filter(|_| !(options.hide_empty_columns && info.activity.is_empty()))
+            .map(|graph| {
+                format!(
+                    "<dt class=\"git-info-activity\">Activity</dt>\n\
+                    <dd class=\"git-info-activity\">{}</dd>\n",
+                    graph
+                )
+            })
+            .unwrap_or_default();
+
+    let signature_badge = signature(info, options)
+        .map(|(label, class)| {
+            let signer = info
+                .signature
+                .as_ref()
+                .and_then(|signature| signature.signer.as_deref())
+                .map(|signer| format!(" title=\"Signed by {}\"", escape_html(signer)))
+                .unwrap_or_default();
+            format!(
+                " <span class=\"git-info-signature {}\"{}>{}</span>",
+                class,
+                signer,
+                escape_html(label)
+            )
+        })
+        .unwrap_or_default();
+    let edits = match single_edit(info, options) {
+        Some(entry) => format!(
+            "<dt class=\"git-info-written\">Written</dt>\n\
+            <dd class=\"git-info-written\">{}</dd>\n",
+            commit(Some(entry)) + &signature_badge
+        ),
+        None => format!(
+            "<dt class=\"git-info-created\">Created</dt>\n\
+            <dd class=\"git-info-created\">{}</dd>\n\
+            <dt class=\"git-info-last-edit\">Last edit</dt>\n\
+            <dd class=\"git-info-last-edit\">{}</dd>\n",
+            commit(info.created.as_ref()),
+            commit(info.last_edit.as_ref()) + &signature_badge
+        ),
+    };
+
+    // No blank lines, otherwise markdown would take over in the middle of the HTML block
+    let footer = format!(
+        "<footer class=\"git-info git-info-footer\">\n\
+        <dl>\n\
+        {}{}{}</dl>\n\
+        {}{}</footer>\n",
+        edits,
+        contributors,
+        extra,
+        info.book_last_edit
+            .filter(|_| options.show_book_last_update)
+            .map(|date| format!(
+                "<p class=\"git-info-book-last-update\">Book last updated: \
+                 <time datetime=\"{}\">{}</time></p>\n",
+                date.to_rfc3339(),
+                escape_html(&options.format_date(&date))
+            ))
+            .unwrap_or_default(),
+        copyright_line(info, options)
+            .map(|copyright| format!(
+                "<p class=\"git-info-copyright\">{}</p>\n",
+                escape_html(copyright)
+            ))
+            .unwrap_or_default()
+            + &info
+                .provenance
+                .as_ref()
+                .map(|provenance| provenance_html(provenance, options))
+                .unwrap_or_default()
+    );
+    format!(
+        "\n\n{}{}",
+        style_block(options, flavor),
+        details(footer, options, flavor)
+    )
+}
+
+/// Replaces the unescaped occurrences of `variable` (like `{{#git_copyright}}`) in `content`
+/// with `value`.
+///
+/// Escaped variables (`\{{#git_copyright}}`) are left alone, like mdbook does for its own.
+pub fn replace_variable(content: &str, variable: &str, value: &str) -> String {
+    let mut replaced = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find(variable) {
+        replaced.push_str(&rest[..start]);
+        if rest[..start].ends_with('\\') {
+            replaced.push_str(variable);
+        } else {
+            replaced.push_str(value);
+        }
+        rest = &rest[start + variable.len()..];
+    }
+    replaced.push_str(rest);
+    replaced
+}
+
+/// Returns the line closing the fenced code block left open at the end of `content`, if any,
+/// which would otherwise swallow anything appended to it.
+pub fn unclosed_fence(content: &str) -> Option<String> {
+    let mut open = None::<(char, usize)>;
+    for line in content.lines() {
+        // Fences are indented by at most three spaces
+        let trimmed = line.trim_start_matches(' ');
+        if line.len() - trimmed.len() > 3 {
+            continue;
+        }
+        let marker = match trimmed.chars().next() {
+            Some(marker @ ('`' | '~')) => marker,
+            _ => continue,
+        };
+        let length = trimmed.chars().take_while(|c| *c == marker).count();
+        if length < 3 {
+            continue;
+        }
+        let rest = &trimmed[length..];
+
+        open = match open {
+            None if marker == '~' || !rest.contains('`') => Some((marker, length)),
+            Some((open_marker, open_length))
+                if marker == open_marker && length >= open_length && rest.trim().is_empty() =>
+            {
+                None
+            }
+            open => open,
+        };
+    }
+    open.map(|(marker, length)| marker.to_string().repeat(length))
+}
+
+/// Returns the offset where the section under the first heading with the given text ends:
+/// the start of the next heading of the same or a higher level, or the end of `content`.
+pub fn section_end(content: &str, heading: &str, case_sensitive: bool) -> Option<usize> {
+    let matches = |text: &str| {
+        let text = text.trim();
+        if case_sensitive {
+            text == heading.trim()
+        } else {
+            text.to_lowercase() == heading.trim().to_lowercase()
+        }
+    };
+
+    // The level of the matching heading, once found, and the text of the current heading
+    let mut found = None;
+    let mut text = None::<String>;
+    for (event, range) in Parser::new(content).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading(level, ..)) => {
+                if found.is_some_and(|found| level <= found) {
+                    return Some(range.start);
+                }
+                text = Some(String::new());
+            }
+            Event::Text(t) | Event::Code(t) => {
+                if let Some(text) = &mut text {
+                    text.push_str(&t);
+                }
+            }
+            Event::End(Tag::Heading(level, ..))
+                if found.is_none() && text.take().is_some_and(|text| matches(&text)) =>
+            {
+                found = Some(level);
+            }
+            _ => {}
+        }
+    }
+    found.map(|_| content.len())
+}
+
+/// Wraps the footer in a `<details class="git-info">` element, if so configured.
+///
+/// EPUB readers do not all support `<details>`, so the footer of [`Flavor::Xhtml`] is never wrapped.
+fn details(footer: String, options: &GitInfoOptions, flavor: Flavor) -> String {
+    if !options.collapsible || flavor != Flavor::Html {
+        return footer;
+    }
+
+    format!(
+        "<details class=\"git-info\"{}>\n<summary>{}</summary>\n{}</details>\n",
+        if options.open_by_default { " open" } else { "" },
+        escape_html(&options.collapsible_summary),
+        footer
+    )
+}
+
+/// Style rules injected along with the footer, if so configured: the rule hiding the footer
+/// from `print.html`.
+fn style_block(options: &GitInfoOptions, flavor: Flavor) -> String {
+    if flavor != Flavor::Html || !options.hide_in_print {
+        return String::new();
+    }
+    "<style class=\"git-info-style\">\n\
+     @media print { .git-info-footer { display: none; } }\n\
+     </style>\n\n"
+        .to_string()
+}
+
+/// Links the chapter at `chapter_path` (relative to the source directory) to the bundled
+/// stylesheet of [`GitInfoOptions::inline_css`], as an HTML block.
+///
+/// The stylesheet is linked rather than inlined, so that `print.html`, which concatenates
+/// every chapter, loads it once instead of embedding a copy per chapter.
+pub fn stylesheet_link(chapter_path: &Path) -> String {
+    let depth = chapter_path.components().count().saturating_sub(1);
+    format!(
+        "<link rel=\"stylesheet\" class=\"git-info-style\" href=\"{}{}\">\n\n",
+        "../".repeat(depth),
+        url_path(Path::new(stylesheet::LINKED_FILE))
+    )
+}
+
+fn contributors_cell(info: &GitInfo, options: &GitInfoOptions, flavor: Flavor) -> String {
+    let mut contributors = info
+        .other_contributors
+        .iter()
+        .map(|author| person_markdown(author, info, flavor))
+        .collect::<Vec<_>>();
+    if info.truncated {
+        contributors.push(escape_markdown(&options.incomplete_suffix));
+    }
+    contributors.join(flavor.cell_separator())
+}
+
+/// Renders [`GitInfoOptions::activity_graph`] on a single line, if enabled and supported by the renderer.
+fn activity_graph(info: &GitInfo, options: &GitInfoOptions, flavor: Flavor) -> Option<String> {
+    let months = &info.activity;
+    match (options.activity_graph, flavor) {
+        (ActivityGraph::None, _) | (ActivityGraph::Heatmap, Flavor::Markdown) => None,
+        _ if months.is_empty() => Some("n/a".to_string()),
+        (ActivityGraph::Sparkline, Flavor::Markdown) => {
+            const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+            Some(
+                activity::levels(months, BLOCKS.len())
+                    .into_iter()
+                    .map(|level| BLOCKS[level])
+                    .collect(),
+            )
+        }
+        (ActivityGraph::Sparkline, _) => Some(sparkline_svg(months, options)),
+        (ActivityGraph::Heatmap, _) => Some(heatmap_svg(months, options)),
+    }
+}
+
+/// Opens an SVG element of the configured size, with its accessible title.
+fn svg_start(class: &str, title: &str, width: u32, height: u32) -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" class=\"{}\" width=\"{}\" height=\"{}\" \
+         viewBox=\"0 0 {} {}\" role=\"img\"><title>{}</title>",
+        class,
+        width,
+        height,
+        width,
+        height,
+        escape_html(title)
+    )
+}
+
+/// Summarizes the activity for screen readers, like "12 commits from Jan 2022 to Dec 2024".
+fn activity_title(months: &[MonthlyActivity], options: &GitInfoOptions) -> String {
+    let commits = months.iter().map(|month| month.commits).sum::<usize>();
+    format!(
+        "{} commit{} from {} to {}",
+        commits,
+        if commits == 1 { "" } else { "s" },
+        months
+            .first()
+            .map(|month| month.label(options.locale.as_deref()))
+            .unwrap_or_default(),
+        months
+            .last()
+            .map(|month| month.label(options.locale.as_deref()))
+            .unwrap_or_default()
+    )
+}
+
+/// Draws the commits by month as a line, the busiest month at the top.
+fn sparkline_svg(months: &[MonthlyActivity], options: &GitInfoOptions) -> String {
+    let (width, height) = (options.activity_graph_width, options.activity_graph_height);
+    let max = months
+        .iter()
+        .map(|month| month.commits)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    // Keep a pixel of margin, so that the stroke is not clipped
+    let step = f64::from(width.saturating_sub(2)) / (months.len().max(2) - 1) as f64;
+    let range = f64::from(height.saturating_sub(2));
+    let points = months
+        .iter()
+        .enumerate()
+        .map(|(i, month)| {
+            let x = 1.0 + step * i as f64;
+            let y = 1.0 + range * (1.0 - month.commits as f64 / max as f64);
+            format!("{},{}", coordinate(x), coordinate(y))
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    format!(
+        "{}<polyline fill=\"none\" stroke=\"currentColor\" stroke-width=\"1\" points=\"{}\"/></svg>",
+        svg_start(
+            "git-info-sparkline",
+            &activity_title(months, options),
+            width,
+            height
+        ),
+        points
+    )
+}
+
+/// Formats an SVG coordinate to a tenth of a pixel, without trailing `.0`.
+fn coordinate(value: f64) -> String {
+    let formatted = format!("{:.1}", value);
+    match formatted.strip_suffix(".0") {
+        Some(integer) => integer.to_string(),
+        None => formatted,
+    }
+}
+
+/// Draws the commits by month as a grid with a row per year, each cell of class
+/// `git-info-heat-0` (no commits) to `git-info-heat-4` (the busiest months).
+fn heatmap_svg(months: &[MonthlyActivity], options: &GitInfoOptions) -> String {
+    const LEVELS: usize = 5;
+    let (width, height) = (options.activity_graph_width, options.activity_graph_height);
+    let first_year = months.first().map_or(0, |month| month.year);
+    let years = months.last().map_or(1, |month| month.year - first_year + 1) as u32;
+
+    // Square cells, as large as both dimensions allow
+    let size = (width / 12).min(height / years).max(1);
+    let cells = months
+        .iter()
+        .zip(activity::levels(months, LEVELS))
+        .map(|(month, level)| {
+            format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" class=\"git-info-heat-{}\" \
+                 fill=\"currentColor\" fill-opacity=\"{:.2}\"><title>{}: {} commit{}</title></rect>",
+                (month.month - 1) * size,
+                (month.year - first_year) as u32 * size,
+                size,
+                size,
+                level,
+                0.1 + 0.9 * level as f64 / (LEVELS - 1) as f64,
+                month.label(options.locale.as_deref()),
+                month.commits,
+                if month.commits == 1 { "" } else { "s" }
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        "{}{}</svg>",
+        svg_start(
+            "git-info-heatmap",
+            &activity_title(months, options),
+            width,
+            height
+        ),
+        cells
+    )
+}
+
+/// Describes the commits not published yet, like "3 changes pending publication", if any.
+fn unpublished_changes(info: &GitInfo) -> String {
+    match info.unpublished_changes {
+        0 => String::new(),
+        1 => "1 change pending publication".to_string(),
+        n => format!("{} changes pending publication", thousands(n as u64)),
+    }
+}
+
+/// Names the primary author, like "Jane Doe (72%)".
+fn primary_author(info: &GitInfo, options: &GitInfoOptions) -> String {
+    match &info.primary_author {
+        Some(author) if options.primary_author_percentage => {
+            format!("{} ({}%)", author.name, author.percentage())
+        }
+        Some(author) => author.name.clone(),
+        None => "n/a".to_string(),
+    }
+}
+
+/// Describes the lines changed in the history, like "1,450 lines added / 600 removed across 27 commits",
+/// approximated (`≈`) for truncated histories.
+fn churn(info: &GitInfo) -> String {
+    format!(
+        "{}{} line{} added / {} removed across {} commit{}",
+        if info.truncated { "≈" } else { "" },
+        thousands(info.lines_added),
+        if info.lines_added == 1 { "" } else { "s" },
+        thousands(info.lines_removed),
+        thousands(info.commit_count as u64),
+        if info.commit_count == 1 { "" } else { "s" }
+    )
+}
+
+/// Formats a number with comma thousands separators.
+fn thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut formatted = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+/// The label and the CSS class of the signature of the last edit, if signed.
+fn signature<'a>(info: &GitInfo, options: &'a GitInfoOptions) -> Option<(&'a str, &'static str)> {
+    let labels = &options.signature_labels;
+    match info.signature.as_ref()?.status {
+        SignatureStatus::Good => Some((&labels.verified, "git-info-signature-verified")),
+        SignatureStatus::Bad | SignatureStatus::Expired | SignatureStatus::Revoked => {
+            Some((&labels.unverified, "git-info-signature-unverified"))
+        }
+        SignatureStatus::UnknownValidity | SignatureStatus::MissingKey => {
+            Some((&labels.unknown, "git-info-signature-unknown"))
+        }
+        SignatureStatus::Unsigned => None,
+    }
+}
+
+/// The first release containing the last edit, or [`GitInfoOptions::unreleased_text`].
+pub fn version<'a>(info: &'a GitInfo, options: &'a GitInfoOptions) -> &'a str {
+    info.version.as_deref().unwrap_or(&options.unreleased_text)
+}
+
+/// Describes the number of contributors, like "1 contributor" or "5+ contributors"
+/// for truncated histories.
+fn contributor_count(info: &GitInfo, options: &GitInfoOptions) -> String {
+    let count = info.contributor_count(options.identity);
+    format!(
+        "{}{} {}",
+        count,
+        if info.truncated { "+" } else { "" },
+        if count == 1 && !info.truncated {
+            "contributor"
+        } else {
+            "contributors"
+        }
+    )
+}
+
+fn names_cell(names: &[String], flavor: Flavor) -> String {
+    names
+        .iter()
+        .map(|name| escape_markdown(name))
+        .collect::<Vec<_>>()
+        .join(flavor.cell_separator())
+}
+
+fn date_cell(entry: Option<&GitHistoryEntry>, options: &GitInfoOptions) -> String {
+    entry.map_or_else(
+        || "n/a".to_string(),
+        |e| escape_markdown(&options.format_date(&e.timestamp)),
+    )
+}
+
+fn author_cell(
+    entry: Option<&GitHistoryEntry>,
+    info: &GitInfo,
+    options: &GitInfoOptions,
+    flavor: Flavor,
+) -> String {
+    entry.map_or_else(
+        || "n/a".to_string(),
+        |e| match both_identities(e, options) {
+            Some((author, committer)) => format!(
+                "authored by {}, committed by {}",
+                person_markdown(author, info, flavor),
+                person_markdown(committer, info, flavor)
+            ),
+            None => person_markdown(e.name(options.identity), info, flavor),
+        },
+    )
+}
+
+/// A name in markdown, linked to the GitHub profile of the person if known.
+fn person_markdown(name: &str, info: &GitInfo, flavor: Flavor) -> String {
+    match info.profiles.get(name) {
+        None => escape_markdown(name),
+        Some(profile) => format!(
+            "{}[{}]({})",
+            avatar(profile, flavor),
+            escape_markdown(name),
+            profile.url()
+        ),
+    }
+}
+
+/// A name in HTML, linked to the GitHub profile of the person if known.
+fn person_html(name: &str, info: &GitInfo, flavor: Flavor) -> String {
+    match info.profiles.get(name) {
+        None => escape_html(name),
+        Some(profile) => format!(
+            "<a class=\"git-info-profile\" href=\"{}\">{}{}</a>",
+            escape_html(&profile.url()),
+            avatar(profile, flavor),
+            escape_html(name)
+        ),
+    }
+}
+
+/// The avatar shown before a name, only where raw HTML is allowed.
+fn avatar(profile: &GitHubProfile, flavor: Flavor) -> String {
+    let end = match flavor {
+        Flavor::Html => ">",
+        Flavor::Xhtml => " />",
+        Flavor::Markdown => return String::new(),
+    };
+    // Twice as large as shown, for high density screens
+    format!(
+        "<img class=\"git-info-avatar\" src=\"{}\" alt=\"\" width=\"{size}\" height=\"{size}\"{} ",
+        escape_html(&profile.avatar(AVATAR_SIZE * 2)),
+        end,
+        size = AVATAR_SIZE
+    )
+}
+
+/// The only commit of a chapter, if [`GitInfoOptions::collapse_single_edit`] is enabled
+/// and the chapter was never edited after the commit which created it.
+fn single_edit<'a>(info: &'a GitInfo, options: &GitInfoOptions) -> Option<&'a GitHistoryEntry> {
+    match (&info.created, &info.last_edit) {
+        (Some(created), Some(last_edit))
+            if options.collapse_single_edit
+                && !info.truncated
+                && created.hash == last_edit.hash =>
+        {
+            Some(created)
+        }
+        _ => None,
+    }
+}
+
+/// The author and the committer of a commit, if [`GitInfoOptions::show_both`] is enabled
+/// and they differ.
+fn both_identities<'a>(
+    entry: &'a GitHistoryEntry,
+    options: &GitInfoOptions,
+) -> Option<(&'a str, &'a str)> {
+    Some((entry.author.as_str(), entry.committer.as_str())).filter(|(author, committer)| {
+        options.show_both && !committer.is_empty() && author != committer
+    })
+}
+
+/// Renders the generated chapter listing the contributors of the whole book.
+///
+/// Links to the chapters are relative to `chapter_path`, the path of the generated chapter.
+pub fn contributors_chapter(
+    title: &str,
+    chapter_path: &Path,
+    contributors: &[Contributor],
+) -> String {
+    let rows = contributors
+        .iter()
+        .map(|contributor| {
+            let chapters = contributor
+                .chapters
+                .iter()
+                .map(|(name, path)| chapter_link(name, chapter_path, path))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(
+                "| {} | {} | {} |\n",
+                escape_markdown(&contributor.name),
+                contributor.commit_count,
+                chapters
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        "# {}\n\n| Contributor | Commits | Chapters |\n| --- | :---: | --- |\n{}",
+        escape_markdown(title),
+        rows
+    )
+}
+
+/// Renders the generated chapter listing the most recently edited chapters.
+///
+/// `chapters` holds the name, path and info of each listed chapter, in display order.
+pub fn recently_updated_chapter(
+    settings: &RecentlyUpdated,
+    chapters: &[(&str, &Path, &GitInfo)],
+    options: &GitInfoOptions,
+) -> String {
+    let (subject_header, subject_align) = if settings.show_subject {
+        (" Change |", " --- |")
+    } else {
+        ("", "")
+    };
+    let rows = chapters
+        .iter()
+        .map(|(name, path, info)| {
+            let subject = match (&info.last_edit, settings.show_subject) {
+                (Some(last_edit), true) => format!(" {} |", escape_markdown(&last_edit.subject)),
+                _ => String::new(),
+            };
+            format!(
+                "| {} | {} | {} |{}\n",
+                chapter_link(name, &settings.path, path),
+                date_cell(info.last_edit.as_ref(), options),
+                author_cell(info.last_edit.as_ref(), info, options, Flavor::Markdown),
+                subject
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        "# {}\n\n| Chapter | Updated on | Updated by |{}\n| --- | :---: | :---: |{}\n{}",
+        escape_markdown(&settings.title),
+        subject_header,
+        subject_align,
+        rows
+    )
+}
+
+/// Renders the generated changelog chapter, grouping the commits by month.
+///
+/// `commits` holds each listed commit, newest first, along with the names and paths
+/// of the chapters it touched.
+pub fn changelog_chapter(
+    settings: &Changelog,
+    commits: &[(&GitHistoryEntry, Vec<(&str, &Path)>)],
+    options: &GitInfoOptions,
+) -> String {
+    let mut content = format!("# {}\n", escape_markdown(&settings.title));
+    let mut month = None;
+    for (entry, chapters) in commits {
+        let entry_month = locale::format(&entry.timestamp, "%B %Y", options.locale.as_deref());
+        if month.as_ref() != Some(&entry_month) {
+            content.push_str(&format!("\n## {}\n\n", entry_month));
+            month = Some(entry_month);
+        }
+
+        content.push_str(&format!(
+            "- **{}** {}: {}",
+            date_cell(Some(entry), options),
+            escape_markdown(entry.name(options.identity)),
+            escape_markdown(&entry.subject)
+        ));
+        if !chapters.is_empty() {
+            let links = chapters
+                .iter()
+                .map(|(name, path)| chapter_link(name, &settings.path, path))
+                .collect::<Vec<_>>();
+            content.push_str(&format!(" ({})", links.join(", ")));
+        }
+        if let Some(url) = &settings.commit_url {
+            content.push_str(&format!(
+                " [`{}`]({})",
+                entry.short_hash,
+                url.replace("{hash}", &entry.hash)
+            ));
+        }
+        content.push('\n');
+    }
+    content
+}
+
+/// Renders a markdown link to the chapter at `target` from the chapter at `from`.
+///
+/// Links point at the `.md` sources, relative to the linking chapter, which mdbook rewrites
+/// to the rendered pages exactly like the links written by hand in the book.
+fn chapter_link(name: &str, from: &Path, target: &Path) -> String {
+    let up = "../".repeat(from.components().count().saturating_sub(1));
+    format!("[{}]({}{})", escape_markdown(name), up, url_path(target))
+}
+
+/// Renders a schema.org `TechArticle` describing the chapter as a JSON-LD script.
+pub fn json_ld(info: &GitInfo, url: Option<&str>, identity: Identity) -> String {
+    let person = |name: &str| json!({ "@type": "Person", "name": name });
+
+    let mut article = json!({
+        "@context": "https://schema.org",
+        "@type": "TechArticle",
+    });
+    if let Some(created) = &info.created {
+        article["dateCreated"] = json!(created.timestamp.to_rfc3339());
+        article["author"] = person(created.name(identity));
+    }
+    if let Some(last_edit) = &info.last_edit {
+        article["dateModified"] = json!(last_edit.timestamp.to_rfc3339());
+    }
+    let contributors = info
+        .last_edit
+        .iter()
+        .map(|last_edit| last_edit.name(identity))
+        .filter(|name| {
+            info.created
+                .as_ref()
+                .is_none_or(|c| c.name(identity) != *name)
+        })
+        .chain(info.other_contributors.iter().map(String::as_str))
+        .map(person)
+        .collect::<Vec<_>>();
+    if !contributors.is_empty() {
+        article["contributor"] = json!(contributors);
+    }
+    if let Some(url) = url {
+        article["url"] = json!(url);
+    }
+
+    format!(
+        "\n<script type=\"application/ld+json\">{}</script>\n",
+        escape_script_json(&article.to_string())
+    )
+}
+
+/// Renders the info of the chapter as a hidden JSON script for themes, with the same fields
+/// as the metadata output.
+pub fn data_script(info: &GitInfo) -> String {
+    format!(
+        "\n<script type=\"application/json\" class=\"git-info-data\">{}</script>\n",
+        escape_script_json(&json!(info).to_string())
+    )
+}
+
+/// Makes serialized JSON safe to embed in a `<script>` element.
+///
+/// `<`, `>` and `&` can only appear inside JSON strings, where their unicode escapes are
+/// equivalent, so this prevents `</script>` breakouts without altering the data.
+pub fn escape_script_json(json: &str) -> String {
+    json.replace('<', "\\u003c")
+        .replace('>', "\\u003e")
+        .replace('&', "\\u0026")
+}
+
+/// Builds the URL of the rendered page of a chapter, the same way mdbook maps
+/// `foo/bar.md` to `foo/bar.html`.
+pub fn page_url(site_url: &str, chapter_path: &Path) -> String {
+    file_url(site_url, &chapter_path.with_extension("html"))
+}
+
+/// Builds the URL of a file of the built book, given its path relative to the book's root.
+pub fn file_url(site_url: &str, path: &Path) -> String {
+    format!("{}/{}", site_url.trim_end_matches('/'), url_path(path))
+}
+
+/// Joins the components of a relative path with `/`, percent-encoding every byte outside of
+/// the unreserved characters of RFC 3986.
+fn url_path(path: &Path) -> String {
+    let path = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(char::from(byte))
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Escapes the characters that have a special meaning in raw HTML.
+pub fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Escapes a string so that it renders literally inside a markdown table cell.
+///
+/// HTML special characters are turned into entities (markdown passes raw HTML through),
+/// and markdown punctuation, including the `|` cell separator, is backslash-escaped.
+pub fn escape_markdown(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in escape_html(s).chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '|' | '~') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{GitInfoOptionsBuilder, OutputFormat};
+
+    /// Renders the footer of a chapter created by `creator` and last edited by `editor`,
+    /// with `contributor` in between.
+    fn render(creator: &str, contributor: &str, editor: &str, options: &GitInfoOptions) -> String {
+        let history = [
+            GitHistoryEntry::test("c", editor, "2024-03-01T12:00:00Z"),
+            GitHistoryEntry::test("b", contributor, "2024-02-01T12:00:00Z"),
+            GitHistoryEntry::test("a", creator, "2024-01-01T12:00:00Z"),
+        ];
+        footer(
+            &GitInfo::from_history(&history, options),
+            options,
+            Flavor::Html,
+        )
+    }
+
+    /// The cells of the last row of the markdown table in `footer`, split on unescaped pipes.
+    fn last_row(footer: &str) -> Vec<String> {
+        let row = footer.lines().rfind(|line| line.starts_with('|')).unwrap();
+        let mut cells = vec![String::new()];
+        let mut escaped = false;
+        for c in row.chars() {
+            match c {
+                '|' if !escaped => cells.push(String::new()),
+                c => cells.last_mut().unwrap().push(c),
+            }
+            escaped = c == '\\' && !escaped;
+        }
+        cells[1..cells.len() - 1]
+            .iter()
+            .map(|cell| cell.trim().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn pipes_stay_in_their_cell() {
+        let footer = render("A|B", "C", "D||E", &GitInfoOptions::default());
+        assert_eq!(
+            last_row(&footer),
+            [
+                "**01 Jan 2024**",
+                "**A\\|B**",
+                "**01 Mar 2024**",
+                "**D\\|\\|E**",
+                "C"
+            ]
+        );
+    }
+
+    #[test]
+    fn html_is_not_injected_in_the_markdown_table() {
+        let footer = render(
+            "<script>alert(1)</script>",
+            "<b>&</b>",
+            "Eve",
+            &GitInfoOptions::default(),
+        );
+        assert!(!footer.contains("<script>"));
+        assert!(!footer.contains("<b>"));
+        assert_eq!(
+            last_row(&footer)[1],
+            "**&lt;script&gt;alert(1)&lt;/script&gt;**"
+        );
+        assert_eq!(last_row(&footer)[4], "&lt;b&gt;&amp;&lt;/b&gt;");
+    }
+
+    #[test]
+    fn backticks_and_underscores_are_literal_in_the_markdown_table() {
+        let footer = render(
+            "`rm -rf`",
+            "snake_case_name",
+            "__init__",
+            &GitInfoOptions::default(),
+        );
+        let row = last_row(&footer);
+        assert_eq!(row[1], "**\\`rm -rf\\`**");
+        assert_eq!(row[3], "**\\_\\_init\\_\\_**");
+        assert_eq!(row[4], "snake\\_case\\_name");
+    }
+
+    #[test]
+    fn html_output_is_escaped_exactly_once() {
+        let options = GitInfoOptions::builder().output(OutputFormat::Html).build();
+        let footer = render("<script>", "A & B", "snake_case|`x`", &options);
+        assert!(!footer.contains("<script>"));
+        assert!(footer.contains("<span class=\"git-info-author\">&lt;script&gt;</span>"));
+        assert!(footer.contains("<li>A &amp; B</li>"));
+        // No markdown escapes inside HTML, where they would show up literally
+        assert!(footer.contains("<span class=\"git-info-author\">snake_case|`x`</span>"));
+    }
+
+    #[test]
+    fn escape_markdown_escapes_html_and_markdown_punctuation() {
+        assert_eq!(escape_markdown("a|b"), "a\\|b");
+        assert_eq!(escape_markdown("<i>"), "&lt;i&gt;");
+        assert_eq!(escape_markdown("`code`"), "\\`code\\`");
+        assert_eq!(escape_markdown("_a_*b*"), "\\_a\\_\\*b\\*");
+        assert_eq!(escape_markdown("[x](y) ~z~ \\"), "\\[x\\](y) \\~z\\~ \\\\");
+        assert_eq!(escape_markdown("Zoë Ünal"), "Zoë Ünal");
+    }
+
+    #[test]
+    fn escape_html_escapes_special_characters() {
+        assert_eq!(
+            escape_html("<a href=\"x\">'&'</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;"
+        );
+        assert_eq!(escape_html("a|b_`c`"), "a|b_`c`");
+    }
+
+    #[test]
+    fn only_html_footers_are_collapsible() {
+        let history = [GitHistoryEntry::test("a", "Alice", "2024-01-01T12:00:00Z")];
+        let options = GitInfoOptions::builder()
+            .collapsible(true)
+            .open_by_default(true)
+            .build();
+        let info = GitInfo::from_history(&history, &options);
+
+        let html = footer(&info, &options, Flavor::Html);
+        assert!(html.contains("<details class=\"git-info\" open>\n<summary>Page history</summary>"));
+        assert!(html.trim_end().ends_with("</details>"));
+
+        for flavor in [Flavor::Xhtml, Flavor::Markdown] {
+            let collapsed = footer(&info, &options, flavor);
+            assert!(!collapsed.contains("<details"), "{:?}", flavor);
+            assert_eq!(collapsed, footer(&info, &GitInfoOptions::default(), flavor));
+        }
+    }
+
+    #[test]
+    fn urls_percent_encode_reserved_characters() {
+        assert_eq!(
+            file_url(
+                "https://example.com/book/",
+                Path::new("guide/a b#c?d%e/Ünï_-~.html")
+            ),
+            "https://example.com/book/guide/a%20b%23c%3Fd%25e/%C3%9Cn%C3%AF_-~.html"
+        );
+        assert_eq!(
+            file_url("https://example.com", Path::new("")),
+            "https://example.com/"
+        );
+        assert_eq!(
+            chapter_link(
+                "Q&A",
+                Path::new("guide/intro.md"),
+                Path::new("faq/q&a (draft).md")
+            ),
+            "[Q&amp;A](../faq/q%26a%20%28draft%29.md)"
+        );
+    }
+
+    /// The header and the cells of the markdown table of the footer of `history`.
+    fn table(history: &[GitHistoryEntry], options: &GitInfoOptions) -> (Vec<String>, Vec<String>) {
+        let footer = footer(
+            &GitInfo::from_history(history, options),
+            options,
+            Flavor::Html,
+        );
+        let header = footer.lines().find(|line| line.starts_with('|')).unwrap();
+        (last_row(header), last_row(&footer))
+    }
+
+    /// Options selecting every column which may be empty, besides the creation and last edit.
+    fn selected_columns() -> GitInfoOptionsBuilder {
+        GitInfoOptions::builder()
+            .notes_ref("reviews")
+            .primary_author(true)
+            .show_version(true)
+            .show_contributor_count(true)
+    }
+
+    #[test]
+    fn single_commits_are_written_once() {
+        let history = [GitHistoryEntry::test("a", "Alice", "2024-01-01T12:00:00Z")];
+
+        let options = selected_columns()
+            .hide_empty_columns(true)
+            .collapse_single_edit(true)
+            .build();
+        assert_eq!(
+            table(&history, &options),
+            (
+                vec![
+                    "Written on".to_string(),
+                    "Written by".to_string(),
+                    "Contributors".to_string()
+                ],
+                vec![
+                    "**01 Jan 2024**".to_string(),
+                    "**Alice**".to_string(),
+                    "1 contributor".to_string()
+                ]
+            )
+        );
+
+        // Each option works on its own
+        let (header, _) = table(
+            &history,
+            &selected_columns().hide_empty_columns(true).build(),
+        );
+        assert_eq!(
+            header,
+            [
+                "Created on",
+                "Created by",
+                "Last edit on",
+                "Last edit by",
+                "Contributors"
+            ]
+        );
+        let (header, row) = table(
+            &history,
+            &selected_columns().collapse_single_edit(true).build(),
+        );
+        assert_eq!(
+            header[..3],
+            ["Written on", "Written by", "Other contributors"]
+        );
+        assert_eq!(
+            row[2..],
+            ["", "n/a", "n/a", "n/a", "unreleased", "1 contributor"]
+        );
+    }
+
+    #[test]
+    fn single_authors_have_no_other_contributors() {
+        let history = [
+            GitHistoryEntry::test("c", "Alice", "2024-03-01T12:00:00Z"),
+            GitHistoryEntry::test("b", "Alice", "2024-02-01T12:00:00Z"),
+            GitHistoryEntry::test("a", "Alice", "2024-01-01T12:00:00Z"),
+        ];
+        let options = GitInfoOptions::builder()
+            .hide_empty_columns(true)
+            .collapse_single_edit(true)
+            .build();
+
+        // Several commits are never collapsed, even by a single author
+        assert_eq!(
+            table(&history, &options),
+            (
+                vec![
+                    "Created on".to_string(),
+                    "Created by".to_string(),
+                    "Last edit on".to_string(),
+                    "Last edit by".to_string()
+                ],
+                vec![
+                    "**01 Jan 2024**".to_string(),
+                    "**Alice**".to_string(),
+                    "**01 Mar 2024**".to_string(),
+                    "**Alice**".to_string()
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn many_authors_keep_every_column_with_content() {
+        let history = [
+            GitHistoryEntry::test("c", "Carol", "2024-03-01T12:00:00Z"),
+            GitHistoryEntry::test("b", "Bob", "2024-02-01T12:00:00Z"),
+            GitHistoryEntry::test("a", "Alice", "2024-01-01T12:00:00Z"),
+        ];
+        let options = selected_columns()
+            .hide_empty_columns(true)
+            .collapse_single_edit(true)
+            .build();
+
+        let (header, row) = table(&history, &options);
+        assert_eq!(
+            header,
+            [
+                "Created on",
+                "Created by",
+                "Last edit on",
+                "Last edit by",
+                "Other contributors",
+                "Contributors"
+            ]
+        );
+        assert_eq!(row[4..], ["Bob", "3 contributors"]);
+    }
+
+    /// A chapter with several other contributors, separated by line breaks in their cell.
+    fn contributed_info(options: &GitInfoOptions) -> GitInfo {
+        let history = [
+            GitHistoryEntry::test("d", "Dave & Co", "2024-04-01T12:00:00Z"),
+            GitHistoryEntry::test("c", "Carol", "2024-03-01T12:00:00Z"),
+            GitHistoryEntry::test("b", "Bob", "2024-02-01T12:00:00Z"),
+            GitHistoryEntry::test("a", "Alice", "2024-01-01T12:00:00Z"),
+        ];
+        GitInfo::from_history(&history, options)
+    }
+
+    /// Converts markdown to HTML the way mdbook does.
+    fn to_html(markdown: &str) -> String {
+        let mut html = String::new();
+        pulldown_cmark::html::push_html(
+            &mut html,
+            Parser::new_ext(markdown, pulldown_cmark::Options::ENABLE_TABLES),
+        );
+        html
+    }
+
+    /// Converts markdown to HTML like mdbook, and parses it as XML.
+    fn parse_xhtml(markdown: &str) -> Result<(), String> {
+        let document = format!(
+            "<body xmlns=\"http://www.w3.org/1999/xhtml\">{}</body>",
+            to_html(markdown)
+        );
+        roxmltree::Document::parse(&document)
+            .map(|_| ())
+            .map_err(|e| format!("{}\n{}", e, document))
+    }
+
+    #[test]
+    fn epub_footers_are_well_formed_xml() {
+        let options = GitInfoOptions::default();
+        let info = contributed_info(&options);
+
+        let xhtml = footer(&info, &options, Flavor::Xhtml);
+        assert!(xhtml.contains("Bob<br />Carol"), "{}", xhtml);
+        if let Err(e) = parse_xhtml(&xhtml) {
+            panic!("{}", e);
+        }
+
+        // The same footer for HTML has its line breaks left open
+        assert!(parse_xhtml(&footer(&info, &options, Flavor::Html)).is_err());
+    }
+
+    #[test]
+    fn footers_hide_in_print_exactly_when_configured() {
+        let history = [GitHistoryEntry::test("a", "Alice", "2024-01-01T12:00:00Z")];
+        let rule = "@media print { .git-info-footer { display: none; } }";
+
+        for hide_in_print in [false, true] {
+            let options = GitInfoOptions::builder()
+                .hide_in_print(hide_in_print)
+                .build();
+            let info = GitInfo::from_history(&history, &options);
+
+            let html = footer(&info, &options, Flavor::Html);
+            assert_eq!(html.contains(rule), hide_in_print, "{}", html);
+            assert_eq!(html.matches("<style").count(), hide_in_print as usize);
+
+            // The wrapper is always there, and leaves the table to markdown
+            let rendered = to_html(&html);
+            assert!(
+                rendered.contains("<div class=\"git-info-footer\">\n<br>\n<hr />"),
+                "{}",
+                rendered
+            );
+            assert!(rendered.contains("<table>"), "{}", rendered);
+            assert!(
+                rendered.trim_end().ends_with("</table>\n</div>"),
+                "{}",
+                rendered
+            );
+
+            // Readers of EPUBs and plain markdown have no print.html
+            for flavor in [Flavor::Xhtml, Flavor::Markdown] {
+                assert!(
+                    !footer(&info, &options, flavor).contains(rule),
+                    "{:?}",
+                    flavor
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn semantic_html_snapshot() {
+        let options = GitInfoOptions::builder().output(OutputFormat::Html).build();
+        let info = contributed_info(&options);
+
+        let html = footer(&info, &options, Flavor::Html);
+        assert_eq!(
+            html,
+            "\n\n\
+             <footer class=\"git-info git-info-footer\">\n\
+             <dl>\n\
+             <dt class=\"git-info-created\">Created</dt>\n\
+             <dd class=\"git-info-created\"><time datetime=\"2024-01-01T12:00:00+00:00\">01 Jan 2024</time> \
+             by <span class=\"git-info-author\">Alice</span></dd>\n\
+             <dt class=\"git-info-last-edit\">Last edit</dt>\n\
+             <dd class=\"git-info-last-edit\"><time datetime=\"2024-04-01T12:00:00+00:00\">01 Apr 2024</time> \
+             by <span class=\"git-info-author\">Dave &amp; Co</span></dd>\n\
+             <dt class=\"git-info-contributors\">Other contributors</dt>\n\
+             <dd class=\"git-info-contributors\"><ul><li>Bob</li><li>Carol</li></ul></dd>\n\
+             </dl>\n\
+             </footer>\n"
+        );
+        assert_eq!(footer(&info, &options, Flavor::Xhtml), html);
+        assert!(parse_xhtml(&html).is_ok());
+
+        // Plain markdown has no HTML to style
+        assert_eq!(
+            footer(&info, &options, Flavor::Markdown),
+            footer(&info, &GitInfoOptions::default(), Flavor::Markdown)
+        );
+    }
+
+    /// Parses back the JSON inside the single `<script>` element of `html`.
+    fn script_json(html: &str) -> serde_json::Value {
+        assert_eq!(html.matches("</script>").count(), 1, "{}", html);
+        let start = html.find("<script").unwrap();
+        let body = &html[start..];
+        let body = &body[body.find('>').unwrap() + 1..body.find("</script>").unwrap()];
+        serde_json::from_str(body).unwrap()
+    }
+
+    #[test]
+    fn json_ld_is_parsed_back() {
+        let history = [
+            GitHistoryEntry::test("c", "Carol", "2024-03-01T12:00:00Z"),
+            GitHistoryEntry::test(
+                "b",
+                "</script><script>alert(1)</script>",
+                "2024-02-01T12:00:00Z",
+            ),
+            GitHistoryEntry::test("a", "Alice", "2024-01-01T12:00:00Z"),
+        ];
+        let info = GitInfo::from_history(&history, &GitInfoOptions::default());
+
+        let script = json_ld(
+            &info,
+            Some("https://example.com/intro.html"),
+            Identity::Author,
+        );
+        assert!(script.contains("<script type=\"application/ld+json\">"));
+        assert_eq!(
+            script_json(&script),
+            json!({
+                "@context": "https://schema.org",
+                "@type": "TechArticle",
+                "dateCreated": "2024-01-01T12:00:00+00:00",
+                "dateModified": "2024-03-01T12:00:00+00:00",
+                "author": { "@type": "Person", "name": "Alice" },
+                "contributor": [
+                    { "@type": "Person", "name": "Carol" },
+                    { "@type": "Person", "name": "</script><script>alert(1)</script>" },
+                ],
+                "url": "https://example.com/intro.html",
+            })
+        );
+
+        // The creator is not a contributor of their own chapter, and there is no URL without a site
+        let info = GitInfo::from_history(&history[2..], &GitInfoOptions::default());
+        let article = script_json(&json_ld(&info, None, Identity::Author));
+        assert_eq!(article["author"]["name"], "Alice");
+        assert!(article.get("contributor").is_none());
+        assert!(article.get("url").is_none());
+    }
+
+    #[test]
+    fn data_scripts_are_parsed_back() {
+        let history = [
+            GitHistoryEntry::test("b", "</script><!--", "2024-02-01T12:00:00Z"),
+            GitHistoryEntry::test("a", "Alice & Bob", "2024-01-01T12:00:00Z"),
+        ];
+        let info = GitInfo::from_history(&history, &GitInfoOptions::default());
+
+        let script = data_script(&info);
+        assert!(script.contains("<script type=\"application/json\" class=\"git-info-data\">"));
+        assert!(!script.contains("<!--"), "{}", script);
+        let data = script_json(&script);
+        assert_eq!(data, serde_json::to_value(&info).unwrap());
+        assert_eq!(data["last_edit"]["author"], "</script><!--");
+
+        // Themes get back the same info
+        let parsed: GitInfo = serde_json::from_value(data).unwrap();
+        assert_eq!(parsed.created.unwrap().author, "Alice & Bob");
+        assert_eq!(parsed.commit_count, 2);
+    }
+
+    #[test]
+    fn sections_end_at_the_next_heading_of_the_same_level() {
+        let content = "# Intro\n\n## About `this` page\n\nText.\n\n### Details\n\n# Next\n";
+        let end = content.find("# Next").unwrap();
+
+        assert_eq!(section_end(content, "about this page", false), Some(end));
+        assert_eq!(section_end(content, "about this page", true), None);
+        assert_eq!(section_end(content, " About this page ", true), Some(end));
+        assert_eq!(section_end(content, "Details", false), Some(end));
+        assert_eq!(section_end(content, "Next", false), Some(content.len()));
+        assert_eq!(section_end(content, "Text.", false), None);
+    }
+
+    fn activity(months: &[(i32, u32, usize)]) -> GitInfo {
+        GitInfo {
+            activity: months
+                .iter()
+                .map(|&(year, month, commits)| MonthlyActivity {
+                    year,
+                    month,
+                    commits,
+                })
+                .collect(),
+            ..GitInfo::default()
+        }
+    }
+
+    #[test]
+    fn sparklines_draw_the_busiest_month_at_the_top() {
+        let options = GitInfoOptions::builder()
+            .activity_graph(ActivityGraph::Sparkline)
+            .activity_graph_size(22, 12)
+            .build();
+        let info = activity(&[(2024, 1, 1), (2024, 2, 0), (2024, 3, 3)]);
+
+        let svg = activity_graph(&info, &options, Flavor::Html).unwrap();
+        let document = roxmltree::Document::parse(&svg).unwrap();
+        let root = document.root_element();
+        assert_eq!(root.attribute("class"), Some("git-info-sparkline"));
+        assert_eq!(root.attribute("viewBox"), Some("0 0 22 12"));
+        let title = root.first_element_child().unwrap();
+        assert_eq!(title.text(), Some("4 commits from Jan 2024 to Mar 2024"));
+        let line = title.next_sibling_element().unwrap();
+        assert_eq!(line.attribute("points"), Some("1,7.7 11,11 21,1"));
+
+        // Without HTML, a line of blocks
+        assert_eq!(
+            activity_graph(&info, &options, Flavor::Markdown).as_deref(),
+            Some("▄▁█")
+        );
+    }
+
+    #[test]
+    fn heatmaps_have_a_row_per_year() {
+        let options = GitInfoOptions::builder()
+            .activity_graph(ActivityGraph::Heatmap)
+            .activity_graph_size(120, 20)
+            .build();
+        let info = activity(&[(2023, 11, 1), (2023, 12, 0), (2024, 1, 4)]);
+
+        let svg = activity_graph(&info, &options, Flavor::Xhtml).unwrap();
+        let document = roxmltree::Document::parse(&svg).unwrap();
+        let cells = document
+            .descendants()
+            .filter(|node| node.has_tag_name("rect"))
+            .map(|rect| {
+                (
+                    rect.attribute("x").unwrap(),
+                    rect.attribute("y").unwrap(),
+                    rect.attribute("width").unwrap(),
+                    rect.attribute("class").unwrap(),
+                    rect.first_element_child().unwrap().text().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            cells,
+            [
+                ("100", "0", "10", "git-info-heat-1", "Nov 2023: 1 commit"),
+                ("110", "0", "10", "git-info-heat-0", "Dec 2023: 0 commits"),
+                ("0", "10", "10", "git-info-heat-4", "Jan 2024: 4 commits"),
+            ]
+        );
+
+        // Nothing to draw without HTML, and nothing to count without history
+        assert_eq!(activity_graph(&info, &options, Flavor::Markdown), None);
+        assert_eq!(
+            activity_graph(&GitInfo::default(), &options, Flavor::Html).as_deref(),
+            Some("n/a")
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use crate::feed::XmlWriter;
+use crate::metadata;
+use crate::render;
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SITEMAP_NAMESPACE: &str = "http://www.sitemaps.org/schemas/sitemap/0.9";
+
+/// Path of the page built from a chapter, relative to the root of the built book.
+///
+/// As in mdbook, `foo/bar.md` becomes `foo/bar.html`, and a `README.md` becomes the
+/// `index.html` of its directory.
+pub fn page_path(chapter_path: &Path) -> PathBuf {
+    let is_readme = chapter_path
+        .file_stem()
+        .is_some_and(|stem| stem.to_string_lossy().eq_ignore_ascii_case("readme"));
+    if is_readme {
+        chapter_path.with_file_name("index.html")
+    } else {
+        chapter_path.with_extension("html")
+    }
+}
+
+/// Renders a sitemap listing the page of each given chapter, as `(path, last edit)`, in order.
+///
+/// Pages without history have no `lastmod`, and pages listed twice are kept once.
+pub fn render(site_url: &str, chapters: &[(&Path, Option<DateTime<Utc>>)]) -> String {
+    let mut seen = Vec::new();
+    let mut xml = XmlWriter::new();
+    xml.open("urlset", &[("xmlns", SITEMAP_NAMESPACE)]);
+    for (path, last_edit) in chapters {
+        let page = page_path(path);
+        if seen.contains(&page) {
+            continue;
+        }
+        xml.open("url", &[]);
+        xml.leaf("loc", &[], Some(&render::file_url(site_url, &page)));
+        if let Some(last_edit) = last_edit {
+            // W3C datetime, as required by the sitemap protocol
+            let lastmod = last_edit.to_rfc3339_opts(SecondsFormat::Secs, true);
+            xml.leaf("lastmod", &[], Some(&lastmod));
+        }
+        xml.close("url");
+        seen.push(page);
+    }
+    xml.close("urlset");
+    xml.finish()
+}
+
+/// Writes the sitemap to `path`, atomically, unless it is already up to date.
+///
+/// Rewriting an unchanged sitemap in the source directory would retrigger `mdbook serve`.
+pub fn write(
+    path: &Path,
+    site_url: &str,
+    chapters: &[(&Path, Option<DateTime<Utc>>)],
+) -> Result<()> {
+    let xml = render(site_url, chapters);
+    if fs::read(path).ok().as_deref() != Some(xml.as_bytes()) {
+        metadata::write_atomically(path, xml.as_bytes())
+            .with_context(|| format!("Cannot write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readmes_are_the_index_of_their_directory() {
+        assert_eq!(page_path(Path::new("README.md")), Path::new("index.html"));
+        assert_eq!(
+            page_path(Path::new("guide/readme.md")),
+            Path::new("guide/index.html")
+        );
+        assert_eq!(
+            page_path(Path::new("guide/intro.md")),
+            Path::new("guide/intro.html")
+        );
+        assert_eq!(
+            page_path(Path::new("guide/README-old.md")),
+            Path::new("guide/README-old.html")
+        );
+    }
+
+    #[test]
+    fn pages_are_listed_once() {
+        let date = "2024-03-05T12:00:00Z".parse().unwrap();
+        let xml = render(
+            "https://example.com/book",
+            &[
+                (Path::new("README.md"), Some(date)),
+                (Path::new("my guide.md"), None),
+                (Path::new("README.md"), None),
+            ],
+        );
+        assert_eq!(xml.matches("<url>").count(), 2);
+        assert!(xml.contains("<loc>https://example.com/book/index.html</loc>"));
+        assert_eq!(
+            xml.matches("<lastmod>2024-03-05T12:00:00Z</lastmod>")
+                .count(),
+            1
+        );
+        assert!(xml.contains("<loc>https://example.com/book/my%20guide.html</loc>"));
+    }
+}
@@ -0,0 +1,103 @@
+use anyhow::{Context, Result};
+use log::warn;
+use std::fs;
+use std::path::Path;
+
+/// Version of the bundled stylesheet, bumped whenever its contents change.
+pub const VERSION: u32 = 3;
+
+/// File of the stylesheet linked by the chapters with
+/// [`GitInfoOptions::inline_css`](crate::config::GitInfoOptions::inline_css), relative to the
+/// source directory so that mdbook copies it to the built book.
+pub const LINKED_FILE: &str = "git-info.css";
+
+const HEADER_PREFIX: &str = "/* mdbook-git-info stylesheet v";
+
+const CSS: &str = include_str!("../assets/git-info.css");
+
+/// Returns the bundled stylesheet, including its version header.
+pub fn contents() -> String {
+    format!("{}{} */\n{}", HEADER_PREFIX, VERSION, CSS)
+}
+
+/// Writes the bundled stylesheet to `path`, unless it is already up to date.
+///
+/// A file without the version header is assumed to be user-provided and is never overwritten.
+pub fn write(path: &Path) -> Result<()> {
+    match fs::read_to_string(path) {
+        Ok(existing) => match existing.strip_prefix(HEADER_PREFIX) {
+            Some(rest) if rest.split(' ').next() == Some(&VERSION.to_string()) => return Ok(()),
+            Some(_) => {}
+            None => {
+                warn!(
+                    "Not overwriting {}, which was not written by mdbook-git-info",
+                    path.display()
+                );
+                return Ok(());
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e).with_context(|| format!("Cannot read {}", path.display())),
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Cannot create {}", parent.display()))?;
+    }
+    fs::write(path, contents()).with_context(|| format!("Cannot write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn missing_stylesheets_are_written() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("theme/git-info.css");
+        write(&path)?;
+        let written = fs::read_to_string(&path)?;
+        assert!(written.starts_with(&format!("{}{} */\n", HEADER_PREFIX, VERSION)));
+        assert_eq!(written, contents());
+        Ok(())
+    }
+
+    #[test]
+    fn current_stylesheets_are_not_rewritten() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("git-info.css");
+        // Same version, so the tweaked rules are kept
+        let tweaked = format!(
+            "{}{} */\n.git-info {{ color: red; }}\n",
+            HEADER_PREFIX, VERSION
+        );
+        fs::write(&path, &tweaked)?;
+        write(&path)?;
+        assert_eq!(fs::read_to_string(&path)?, tweaked);
+        Ok(())
+    }
+
+    #[test]
+    fn older_stylesheets_are_upgraded() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("git-info.css");
+        fs::write(
+            &path,
+            format!("{}{} */\n.old {{}}\n", HEADER_PREFIX, VERSION - 1),
+        )?;
+        write(&path)?;
+        assert_eq!(fs::read_to_string(&path)?, contents());
+        Ok(())
+    }
+
+    #[test]
+    fn user_stylesheets_are_never_overwritten() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("git-info.css");
+        fs::write(&path, ".mine {}\n")?;
+        write(&path)?;
+        assert_eq!(fs::read_to_string(&path)?, ".mine {}\n");
+        Ok(())
+    }
+}
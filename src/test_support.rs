@@ -0,0 +1,290 @@
+//! Builders of throwaway git repositories containing a book, for the tests of code built on top
+//! of the preprocessor.
+//!
+//! ```no_run
+//! use mdbook::preprocess::Preprocessor;
+//! use mdbook_git_info::test_support::FixtureRepo;
+//! use mdbook_git_info::GitInfoPreprocessor;
+//!
+//! let mut repo = FixtureRepo::new()?;
+//! repo.file("src/SUMMARY.md", "# Summary\n\n- [Intro](intro.md)\n")?
+//!     .file("src/intro.md", "# Intro\n")?;
+//! repo.commit("Add the intro", "Alice", "alice@example.com", "2024-01-01T12:00:00Z".parse()?)?;
+//! repo.tag("v1.0")?;
+//!
+//! let (ctx, book) = repo.book("html")?;
+//! let book = GitInfoPreprocessor::new().run(&ctx, book)?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use mdbook::book::Book;
+use mdbook::preprocess::PreprocessorContext;
+use mdbook::MDBook;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Variables of the environment which would make git operate on another repository.
+const SANITIZED_ENV: [&str; 4] = ["GIT_DIR", "GIT_WORK_TREE", "GIT_INDEX_FILE", "GIT_PAGER"];
+
+/// Branch checked out by new repositories, whatever the configuration of the user.
+const DEFAULT_BRANCH: &str = "main";
+
+/// Directories created by this process, so that concurrent tests never share one.
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary directory, removed with its content when dropped, also while unwinding a panic.
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Creates a new empty directory in the temporary directory of the system.
+    pub fn new() -> Result<Self> {
+        let parent = std::env::temp_dir();
+        loop {
+            let name = format!(
+                "mdbook-git-info-{}-{}",
+                process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            );
+            let path = parent.join(name);
+            match fs::create_dir(&path) {
+                Ok(()) => {
+                    // Removed even if it cannot be resolved
+                    let mut dir = TempDir { path };
+
+                    // The preprocessor compares canonical paths, and the temporary directory
+                    // may be behind a symlink
+                    dir.path = fs::canonicalize(&dir.path).with_context(|| {
+                        format!("Cannot resolve the directory {}", dir.path.display())
+                    })?;
+                    return Ok(dir);
+                }
+                // Left behind by a process which was killed
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("Cannot create a directory in {}", parent.display())
+                    })
+                }
+            }
+        }
+    }
+
+    /// Path of the directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        // Nothing sensible can be done about a failure while dropping
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// A git repository in a temporary directory, built step by step.
+///
+/// Commits are created with fixed author and committer identities and dates, so that their
+/// hashes are the same on every run. The repository is removed when the fixture is dropped.
+#[derive(Debug)]
+pub struct FixtureRepo {
+    dir: TempDir,
+}
+
+impl FixtureRepo {
+    /// Creates an empty repository on the `main` branch.
+    pub fn new() -> Result<Self> {
+        let repo = FixtureRepo {
+            dir: TempDir::new()?,
+        };
+        repo.git(&["init", "--quiet"])?;
+        repo.git(&[
+            "symbolic-ref",
+            "HEAD",
+            &format!("refs/heads/{}", DEFAULT_BRANCH),
+        ])?;
+        Ok(repo)
+    }
+
+    /// Root of the working tree.
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Writes a file of the working tree, relative to its root, creating its parent directories.
+    ///
+    /// The file is only committed by the next call to [`FixtureRepo::commit`].
+    pub fn file(
+        &mut self,
+        path: impl AsRef<Path>,
+        contents: impl AsRef<[u8]>,
+    ) -> Result<&mut Self> {
+        let path = self.path().join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Cannot create the directory {}", parent.display()))?;
+        }
+        fs::write(&path, contents).with_context(|| format!("Cannot write {}", path.display()))?;
+        Ok(self)
+    }
+
+    /// Deletes a file of the working tree, relative to its root.
+    pub fn remove(&mut self, path: impl AsRef<Path>) -> Result<&mut Self> {
+        let path = self.path().join(path);
+        fs::remove_file(&path).with_context(|| format!("Cannot delete {}", path.display()))?;
+        Ok(self)
+    }
+
+    /// Commits every change of the working tree, returning the hash of the new commit.
+    ///
+    /// The author is also the committer, and both dates are `date`.
+    pub fn commit(
+        &mut self,
+        message: &str,
+        author: &str,
+        email: &str,
+        date: DateTime<Utc>,
+    ) -> Result<String> {
+        self.commit_by(message, author, email, author, email, date)
+    }
+
+    /// Commits every change of the working tree like [`FixtureRepo::commit`], with a committer
+    /// other than the author, like when applying a patch.
+    pub fn commit_by(
+        &mut self,
+        message: &str,
+        author: &str,
+        author_email: &str,
+        committer: &str,
+        committer_email: &str,
+        date: DateTime<Utc>,
+    ) -> Result<String> {
+        self.git(&["add", "--all"])?;
+
+        // Git's own date format, which no configuration can reinterpret
+        let date = format!("{} +0000", date.timestamp());
+        let mut command = self.command();
+        command
+            .args(["commit", "--quiet", "--allow-empty", "--no-verify"])
+            .arg("--message")
+            .arg(message)
+            .env("GIT_AUTHOR_NAME", author)
+            .env("GIT_AUTHOR_EMAIL", author_email)
+            .env("GIT_AUTHOR_DATE", &date)
+            .env("GIT_COMMITTER_NAME", committer)
+            .env("GIT_COMMITTER_EMAIL", committer_email)
+            .env("GIT_COMMITTER_DATE", &date);
+        run(command, "commit")?;
+
+        self.git(&["rev-parse", "HEAD"])
+    }
+
+    /// Merges `branch` into the current branch with a merge commit, even if it could be
+    /// fast-forwarded, returning the hash of the merge commit.
+    ///
+    /// Identities and dates are set like by [`FixtureRepo::commit`]. Conflicts are errors.
+    pub fn merge(
+        &mut self,
+        branch: &str,
+        message: &str,
+        author: &str,
+        email: &str,
+        date: DateTime<Utc>,
+    ) -> Result<String> {
+        let date = format!("{} +0000", date.timestamp());
+        let mut command = self.command();
+        command
+            .args(["merge", "--quiet", "--no-ff", "--no-edit", "--no-verify"])
+            .arg("--message")
+            .arg(message)
+            .arg(branch)
+            .env("GIT_AUTHOR_NAME", author)
+            .env("GIT_AUTHOR_EMAIL", email)
+            .env("GIT_AUTHOR_DATE", &date)
+            .env("GIT_COMMITTER_NAME", author)
+            .env("GIT_COMMITTER_EMAIL", email)
+            .env("GIT_COMMITTER_DATE", &date);
+        run(command, &format!("merge {}", branch))?;
+
+        self.git(&["rev-parse", "HEAD"])
+    }
+
+    /// Creates a lightweight tag on the current commit.
+    pub fn tag(&mut self, name: &str) -> Result<&mut Self> {
+        self.git(&["tag", name])?;
+        Ok(self)
+    }
+
+    /// Creates a branch on the current commit and checks it out.
+    pub fn branch(&mut self, name: &str) -> Result<&mut Self> {
+        self.git(&["checkout", "--quiet", "-b", name])?;
+        Ok(self)
+    }
+
+    /// Checks out an existing branch, tag or commit.
+    pub fn checkout(&mut self, name: &str) -> Result<&mut Self> {
+        self.git(&["checkout", "--quiet", name])?;
+        Ok(self)
+    }
+
+    /// Loads the book at the root of the working tree, returning the input a preprocessor would
+    /// receive from mdbook when building it with `renderer`.
+    ///
+    /// The book is configured by the `book.toml` of the working tree, if any.
+    pub fn book(&self, renderer: &str) -> Result<(PreprocessorContext, Book)> {
+        let book = MDBook::load(self.path())
+            .with_context(|| format!("Cannot load the book in {}", self.path().display()))?;
+        let ctx = serde_json::from_value::<PreprocessorContext>(serde_json::json!({
+            "root": book.root,
+            "config": book.config,
+            "renderer": renderer,
+            "mdbook_version": mdbook::MDBOOK_VERSION,
+        }))
+        .context("Cannot create the preprocessor context")?;
+        Ok((ctx, book.book))
+    }
+
+    /// Runs git in the working tree, returning its trimmed output.
+    pub fn git(&self, args: &[&str]) -> Result<String> {
+        let mut command = self.command();
+        command.args(args);
+        run(command, &args.join(" "))
+    }
+
+    /// Creates a git command unaffected by the configuration of the user.
+    fn command(&self) -> Command {
+        let mut command = Command::new("git");
+        for var in SANITIZED_ENV {
+            command.env_remove(var);
+        }
+        command
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("HOME", self.path())
+            .env("XDG_CONFIG_HOME", self.path())
+            .current_dir(self.path())
+            .args(["-c", "commit.gpgSign=false", "-c", "tag.gpgSign=false"])
+            .args(["-c", "core.autocrlf=false"]);
+        command
+    }
+}
+
+fn run(mut command: Command, description: &str) -> Result<String> {
+    let output = command
+        .output()
+        .with_context(|| format!("Cannot run git {}", description))?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            description,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
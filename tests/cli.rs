@@ -0,0 +1,239 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use mdbook::renderer::RenderContext;
+use mdbook::MDBook;
+use mdbook_git_info::test_support::FixtureRepo;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const SUMMARY: &str = "# Summary\n\n- [Intro](intro.md)\n- [Guide](guide.md)\n";
+
+fn date(text: &str) -> DateTime<Utc> {
+    text.parse().unwrap()
+}
+
+/// A book whose intro is written by Alice and edited by Bob, and whose guide is written by Alice.
+fn fixture() -> Result<FixtureRepo> {
+    let mut repo = FixtureRepo::new()?;
+    repo.file("src/SUMMARY.md", SUMMARY)?
+        .file("src/intro.md", "# Intro\n")?
+        .file("src/guide.md", "# Guide\n")?;
+    repo.commit(
+        "Add the intro",
+        "Alice",
+        "alice@example.com",
+        date("2024-01-10T12:00:00Z"),
+    )?;
+    repo.file("src/intro.md", "# Intro\n\nMore.\n")?;
+    repo.commit(
+        "Expand the intro",
+        "Bob",
+        "bob@example.com",
+        date("2024-03-05T12:00:00Z"),
+    )?;
+    Ok(repo)
+}
+
+/// Runs the binary in the root of `repo`.
+fn run(repo: &FixtureRepo, args: &[&str]) -> Result<Output> {
+    Ok(Command::new(env!("CARGO_BIN_EXE_mdbook-git-info"))
+        .args(args)
+        .current_dir(repo.path())
+        .output()?)
+}
+
+fn json(output: &Output) -> serde_json::Value {
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn inspect_prints_the_history_of_a_file() -> Result<()> {
+    let repo = fixture()?;
+
+    let output = run(&repo, &["inspect", "src/intro.md", "--json"])?;
+
+    assert!(output.status.success());
+    let inspected = json(&output);
+    let authors = inspected["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["author"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(authors, ["Bob", "Alice"]);
+    assert_eq!(inspected["info"]["created"]["author"], "Alice");
+    assert_eq!(inspected["info"]["commit_count"], 2);
+    Ok(())
+}
+
+#[test]
+fn stats_are_sorted_and_filtered() -> Result<()> {
+    let repo = fixture()?;
+
+    let output = run(&repo, &["stats", "--json", "--sort", "last-edit"])?;
+    assert!(output.status.success());
+    let titles = json(&output)
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|chapter| chapter["title"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(titles, ["Guide", "Intro"]);
+
+    // Both were edited in 2024, long before any reasonable threshold
+    let output = run(&repo, &["stats", "--json", "--stale-days", "1"])?;
+    assert_eq!(json(&output).as_array().unwrap().len(), 2);
+    let output = run(&repo, &["stats", "--stale-days", "many"])?;
+    assert_eq!(output.status.code(), Some(1));
+    Ok(())
+}
+
+#[test]
+fn preview_prints_what_is_added() -> Result<()> {
+    let repo = fixture()?;
+
+    let output = run(&repo, &["preview", "--diff", "--chapter", "intro.md"])?;
+
+    assert!(output.status.success());
+    let diff = String::from_utf8(output.stdout)?;
+    assert!(diff.starts_with("--- a/intro.md\n+++ b/intro.md\n@@ -1,3 +1,"));
+    assert!(diff.contains("\n+| **10 Jan 2024** | **Alice** | **05 Mar 2024** | **Bob** |"));
+    assert!(!diff.contains("guide.md"));
+    Ok(())
+}
+
+#[test]
+fn broken_lint_rules_exit_with_code_2() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file(
+        "book.toml",
+        "[preprocessor.git-info.lint]\nmin_contributors = 2\nmin_contributors_level = \"warn\"\n",
+    )?;
+
+    // Warnings alone pass
+    let output = run(&repo, &["lint"])?;
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8(output.stdout)?.contains("Checked 2 chapters: 0 error(s), 1 warning(s)")
+    );
+
+    let output = run(&repo, &["lint", "--max-age-days", "30", "--format", "json"])?;
+    assert_eq!(output.status.code(), Some(2));
+    let rules = json(&output)
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|violation| {
+            (
+                violation["title"].as_str().unwrap().to_string(),
+                violation["rule"].as_str().unwrap().to_string(),
+                violation["level"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        rules,
+        [
+            ("Intro".into(), "max-age-days".into(), "error".into()),
+            ("Guide".into(), "max-age-days".into(), "error".into()),
+            ("Guide".into(), "min-contributors".into(), "warn".into()),
+        ]
+    );
+    // Only the report is printed, which keeps the JSON parsable
+    assert!(output.stderr.is_empty());
+
+    // Errors of the command itself are told apart
+    let output = run(&repo, &["lint", "--max-age-days", "soon"])?;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr)?.contains("--max-age-days must be a number of days"));
+    Ok(())
+}
+
+#[test]
+fn renderers_write_a_report() -> Result<()> {
+    let repo = fixture()?;
+    let book = MDBook::load(repo.path())?;
+    let destination = repo.path().join("book/git-info");
+    let ctx = RenderContext::new(repo.path(), book.book, book.config, &destination);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mdbook-git-info"))
+        .current_dir(repo.path())
+        .stdin(Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(&serde_json::to_vec(&ctx)?)?;
+    assert!(child.wait()?.success());
+
+    let report =
+        serde_json::from_slice::<serde_json::Value>(&fs::read(destination.join("git-info.json"))?)?;
+    assert_eq!(report[0]["title"], "Intro");
+    assert_eq!(report[0]["created_by"], "Alice");
+    assert_eq!(report[0]["last_edit_by"], "Bob");
+    assert_eq!(
+        report[0]["contributors"],
+        serde_json::json!(["Alice", "Bob"])
+    );
+    assert_eq!(report[1]["commit_count"], 1);
+    let html = fs::read_to_string(destination.join("index.html"))?;
+    assert!(html.contains("<td>Intro</td><td>intro.md</td><td>10 Jan 2024</td><td>Alice</td>"));
+    // Nothing is added to the sources
+    assert_eq!(
+        fs::read_to_string(repo.path().join("src/intro.md"))?,
+        "# Intro\n\nMore.\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn runs_end_with_a_summary() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file(
+        "src/SUMMARY.md",
+        format!(
+            "{}- [Draft]()\n- [Gone](gone.md)\n- [New](new.md)\n",
+            SUMMARY
+        ),
+    )?
+    .file("src/gone.md", "# Gone\n")?
+    .file("src/new.md", "# New\n")?
+    .file(
+        "book.toml",
+        "[preprocessor.git-info]\nexclude = [\"guide.md\"]\n",
+    )?;
+    let (ctx, book) = repo.book("html")?;
+    // Removed after loading the book, like a chapter deleted by another preprocessor
+    fs::remove_file(repo.path().join("src/gone.md"))?;
+    let input = serde_json::to_vec(&(&ctx, &book))?;
+
+    let preprocess = |quiet: bool| -> Result<String> {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_mdbook-git-info"))
+            .args(["--set", &format!("quiet={}", quiet)])
+            .env("RUST_LOG", "info")
+            .current_dir(repo.path())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        child.stdin.take().unwrap().write_all(&input)?;
+        let output = child.wait_with_output()?;
+        assert!(output.status.success());
+        Ok(String::from_utf8(output.stderr)?)
+    };
+
+    // The chapter without history is still enriched, with a warning
+    let logs = preprocess(false)?;
+    assert!(
+        logs.contains(
+            "git-info: enriched 2 chapters, skipped 3 (1 excluded, 1 drafts, 1 missing file), 0 errors, "
+        ),
+        "{}",
+        logs
+    );
+    assert!(logs.contains("Chapter New has no git history"), "{}", logs);
+    assert!(!preprocess(true)?.contains("git-info: enriched"));
+    Ok(())
+}
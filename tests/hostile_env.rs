@@ -0,0 +1,75 @@
+//! Runs in its own process, since it changes the environment of the whole process.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use mdbook::book::Book;
+use mdbook::preprocess::Preprocessor;
+use mdbook::BookItem;
+use mdbook_git_info::test_support::FixtureRepo;
+use mdbook_git_info::GitInfoPreprocessor;
+use std::env;
+
+fn date(text: &str) -> DateTime<Utc> {
+    text.parse().unwrap()
+}
+
+fn content<'a>(book: &'a Book, name: &str) -> &'a str {
+    book.iter()
+        .find_map(|item| match item {
+            BookItem::Chapter(chapter) if chapter.name == name => Some(chapter.content.as_str()),
+            _ => None,
+        })
+        .unwrap()
+}
+
+#[test]
+fn the_environment_and_configuration_of_the_user_are_ignored() -> Result<()> {
+    let mut repo = FixtureRepo::new()?;
+    repo.file("src/SUMMARY.md", "# Summary\n\n- [Intro](intro.md)\n")?
+        .file("src/intro.md", "# Intro\n")?;
+    repo.commit(
+        "Add the intro",
+        "Alice",
+        "alice@example.com",
+        date("2024-01-10T12:00:00Z"),
+    )?;
+
+    // Another repository, which git would read instead if told to
+    let mut decoy = FixtureRepo::new()?;
+    decoy.file("src/intro.md", "# Decoy\n")?;
+    decoy.commit(
+        "Add the decoy",
+        "Mallory",
+        "mallory@example.com",
+        date("2020-01-01T12:00:00Z"),
+    )?;
+
+    // Configuration changing the output of git log
+    for (key, value) in [
+        ("color.ui", "always"),
+        ("log.showSignature", "true"),
+        ("log.decorate", "full"),
+        ("core.quotePath", "true"),
+        ("core.pager", "false"),
+    ] {
+        repo.git(&["config", key, value])?;
+    }
+    env::set_var("GIT_DIR", decoy.path().join(".git"));
+    env::set_var("GIT_WORK_TREE", decoy.path());
+    env::set_var("GIT_INDEX_FILE", decoy.path().join("missing-index"));
+    env::set_var("GIT_PAGER", "false");
+    env::set_var("PAGER", "false");
+
+    let (ctx, book) = repo.book("html")?;
+    let book = GitInfoPreprocessor::new().run(&ctx, book)?;
+
+    let intro = content(&book, "Intro");
+    assert!(
+        intro.contains("| **10 Jan 2024** | **Alice** | **10 Jan 2024** | **Alice** |"),
+        "{}",
+        intro
+    );
+    assert!(!intro.contains("Mallory"));
+    assert!(!intro.contains('\u{1b}'));
+    Ok(())
+}
@@ -0,0 +1,1111 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use mdbook::book::{Book, Chapter};
+use mdbook::preprocess::{Preprocessor, PreprocessorContext};
+use mdbook::BookItem;
+use mdbook_git_info::config::{ExtractionMode, Identity, IgnoredCommitEffect};
+use mdbook_git_info::git_history::CommitSignature;
+use mdbook_git_info::history_file::HistoryFile;
+use mdbook_git_info::provider::{CliProvider, HistoryProvider};
+use mdbook_git_info::test_support::FixtureRepo;
+use mdbook_git_info::{GitHistoryEntry, GitInfoOptions, GitInfoPreprocessor};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const SUMMARY: &str = "# Summary\n\n- [Intro](intro.md)\n- [Guide](guide.md)\n";
+
+fn date(text: &str) -> DateTime<Utc> {
+    text.parse().unwrap()
+}
+
+/// A book whose intro is written by Alice and edited by Bob, and whose guide is written by Alice.
+fn fixture() -> Result<FixtureRepo> {
+    let mut repo = FixtureRepo::new()?;
+    repo.file("src/SUMMARY.md", SUMMARY)?
+        .file("src/intro.md", "# Intro\n")?
+        .file("src/guide.md", "# Guide\n")?;
+    repo.commit(
+        "Add the intro",
+        "Alice",
+        "alice@example.com",
+        date("2024-01-10T12:00:00Z"),
+    )?;
+    repo.file("src/intro.md", "# Intro\n\nMore.\n")?;
+    repo.commit(
+        "Expand the intro",
+        "Bob",
+        "bob@example.com",
+        date("2024-03-05T12:00:00Z"),
+    )?;
+    Ok(repo)
+}
+
+/// Provider running git like [`CliProvider`], counting the extractions of each file and of
+/// whole directories.
+#[derive(Debug, Default, Clone)]
+struct CountingProvider {
+    extractions: Arc<Mutex<BTreeMap<PathBuf, usize>>>,
+}
+
+impl CountingProvider {
+    fn count(&self, path: &Path) {
+        *self
+            .extractions
+            .lock()
+            .unwrap()
+            .entry(path.to_path_buf())
+            .or_default() += 1;
+    }
+
+    fn extractions(&self) -> BTreeMap<PathBuf, usize> {
+        self.extractions.lock().unwrap().clone()
+    }
+}
+
+impl HistoryProvider for CountingProvider {
+    fn file_history(&self, path: &Path, options: &GitInfoOptions) -> Result<Vec<GitHistoryEntry>> {
+        self.count(path);
+        CliProvider.file_history(path, options)
+    }
+
+    fn file_endpoints(
+        &self,
+        path: &Path,
+        options: &GitInfoOptions,
+    ) -> Result<Vec<GitHistoryEntry>> {
+        self.count(path);
+        CliProvider.file_endpoints(path, options)
+    }
+
+    fn all_histories(
+        &self,
+        dir: &Path,
+        options: &GitInfoOptions,
+    ) -> Result<HashMap<PathBuf, Vec<GitHistoryEntry>>> {
+        self.count(dir);
+        CliProvider.all_histories(dir, options)
+    }
+
+    fn recent_commits(
+        &self,
+        dir: &Path,
+        options: &GitInfoOptions,
+        max_commits: usize,
+        include_merges: bool,
+    ) -> Result<Vec<(GitHistoryEntry, Vec<PathBuf>)>> {
+        CliProvider.recent_commits(dir, options, max_commits, include_merges)
+    }
+
+    fn head(&self, dir: &Path, options: &GitInfoOptions) -> Result<String> {
+        CliProvider.head(dir, options)
+    }
+
+    fn head_ref(&self, dir: &Path, options: &GitInfoOptions) -> Result<Option<String>> {
+        CliProvider.head_ref(dir, options)
+    }
+
+    fn dirty_files(&self, dir: &Path, options: &GitInfoOptions) -> Result<HashSet<PathBuf>> {
+        CliProvider.dirty_files(dir, options)
+    }
+
+    fn is_shallow(&self, dir: &Path, options: &GitInfoOptions) -> Result<bool> {
+        CliProvider.is_shallow(dir, options)
+    }
+
+    fn root(&self, dir: &Path, options: &GitInfoOptions) -> Result<PathBuf> {
+        CliProvider.root(dir, options)
+    }
+
+    fn remote_url(
+        &self,
+        dir: &Path,
+        name: &str,
+        options: &GitInfoOptions,
+    ) -> Result<Option<String>> {
+        CliProvider.remote_url(dir, name, options)
+    }
+
+    fn blame(&self, path: &Path, options: &GitInfoOptions) -> Result<Vec<(String, usize)>> {
+        CliProvider.blame(path, options)
+    }
+
+    fn unpublished_commits(
+        &self,
+        dir: &Path,
+        published_ref: &str,
+        options: &GitInfoOptions,
+    ) -> Result<Option<HashSet<String>>> {
+        CliProvider.unpublished_commits(dir, published_ref, options)
+    }
+
+    fn first_tag_containing(
+        &self,
+        dir: &Path,
+        hash: &str,
+        pattern: &str,
+        options: &GitInfoOptions,
+    ) -> Result<Option<String>> {
+        CliProvider.first_tag_containing(dir, hash, pattern, options)
+    }
+
+    fn signature(
+        &self,
+        dir: &Path,
+        hash: &str,
+        options: &GitInfoOptions,
+    ) -> Result<CommitSignature> {
+        CliProvider.signature(dir, hash, options)
+    }
+
+    fn notes(
+        &self,
+        dir: &Path,
+        notes_ref: &str,
+        options: &GitInfoOptions,
+    ) -> Result<HashMap<String, String>> {
+        CliProvider.notes(dir, notes_ref, options)
+    }
+}
+
+fn content<'a>(book: &'a Book, name: &str) -> &'a str {
+    book.iter()
+        .find_map(|item| match item {
+            BookItem::Chapter(Chapter {
+                name: chapter,
+                content,
+                ..
+            }) if chapter == name => Some(content.as_str()),
+            _ => None,
+        })
+        .unwrap_or_else(|| panic!("No chapter {}", name))
+}
+
+#[test]
+fn footer_credits_the_creator_and_the_last_editor() -> Result<()> {
+    let repo = fixture()?;
+    let (ctx, book) = repo.book("html")?;
+
+    let book = GitInfoPreprocessor::new().run(&ctx, book)?;
+
+    let intro = content(&book, "Intro");
+    assert!(intro.starts_with("# Intro\n\nMore.\n"));
+    assert!(intro.contains("| **10 Jan 2024** | **Alice** | **05 Mar 2024** | **Bob** |"));
+    Ok(())
+}
+
+#[test]
+fn uncommitted_chapters_are_left_untouched() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file(
+        "src/SUMMARY.md",
+        format!("{}- [Notes](notes.md)\n", SUMMARY),
+    )?
+    .file("src/notes.md", "# Notes\n")?;
+    let (ctx, book) = repo.book("html")?;
+
+    let book = GitInfoPreprocessor::new().run(&ctx, book)?;
+
+    assert_eq!(content(&book, "Notes"), "# Notes\n");
+    Ok(())
+}
+
+#[test]
+fn excluded_chapters_are_left_untouched() -> Result<()> {
+    let repo = fixture()?;
+    let (ctx, book) = repo.book("html")?;
+    let options = GitInfoOptions::builder().exclude(["intro.md"]).build();
+
+    let book = GitInfoPreprocessor::with_options(options).run(&ctx, book)?;
+
+    assert_eq!(content(&book, "Intro"), "# Intro\n\nMore.\n");
+    Ok(())
+}
+
+#[test]
+fn book_toml_overrides_programmatic_options() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file(
+        "book.toml",
+        "[preprocessor.git-info]\ndate_format = \"%Y-%m-%d\"\n",
+    )?;
+    let (ctx, book) = repo.book("html")?;
+    let options = GitInfoOptions::builder().date_format("%d/%m/%Y").build();
+
+    let book = GitInfoPreprocessor::with_options(options).run(&ctx, book)?;
+
+    assert!(content(&book, "Intro").contains("**2024-01-10**"));
+    Ok(())
+}
+
+#[test]
+fn markdown_renderer_gets_no_html() -> Result<()> {
+    let repo = fixture()?;
+    let (ctx, book) = repo.book("markdown")?;
+
+    let book = GitInfoPreprocessor::new().run(&ctx, book)?;
+
+    let intro = content(&book, "Intro");
+    assert!(intro.contains("**Bob**"));
+    assert!(!intro.contains('<'));
+    Ok(())
+}
+
+#[test]
+fn version_names_the_first_tag_containing_the_last_edit() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.tag("v1.0")?;
+    repo.file("src/guide.md", "# Guide\n\nNew.\n")?;
+    repo.commit(
+        "Expand the guide",
+        "Carol",
+        "carol@example.com",
+        date("2024-04-01T12:00:00Z"),
+    )?;
+    let (ctx, book) = repo.book("html")?;
+    let options = GitInfoOptions::builder().show_version(true).build();
+
+    let book = GitInfoPreprocessor::with_options(options).run(&ctx, book)?;
+
+    assert!(content(&book, "Intro").contains("| v1.0 |"));
+    assert!(content(&book, "Guide").contains("| unreleased |"));
+    Ok(())
+}
+
+#[test]
+fn pending_changes_leave_out_ignored_commits() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.tag("v1.0")?;
+    repo.file("src/intro.md", "# Intro\n\nMore.\n\n")?;
+    repo.commit(
+        "chore: trim the intro",
+        "Carol",
+        "carol@example.com",
+        date("2024-04-01T12:00:00Z"),
+    )?;
+    repo.file("src/intro.md", "# Intro\n\nEven more.\n")?;
+    repo.commit(
+        "Clarify the intro",
+        "Carol",
+        "carol@example.com",
+        date("2024-04-02T12:00:00Z"),
+    )?;
+    let (ctx, book) = repo.book("html")?;
+    let options = GitInfoOptions::builder()
+        .published_ref("v1.0")
+        .ignore_commit_types(["chore"])
+        .ignore_commit_types_affects([IgnoredCommitEffect::Totals])
+        .build();
+
+    let book = GitInfoPreprocessor::with_options(options).run(&ctx, book)?;
+
+    assert!(content(&book, "Intro").contains("| 1 change pending publication |"));
+    assert!(!content(&book, "Guide").contains("pending publication"));
+    Ok(())
+}
+
+#[test]
+fn included_files_count_as_edits_of_the_chapter() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file("src/shared/warning.md", "Careful.\n")?.file(
+        "src/guide.md",
+        "# Guide\n\n{{#include shared/warning.md}}\n",
+    )?;
+    repo.commit(
+        "Share the warning",
+        "Alice",
+        "alice@example.com",
+        date("2024-01-20T12:00:00Z"),
+    )?;
+    repo.file("src/shared/warning.md", "Very careful.\n")?;
+    repo.commit(
+        "Reword the warning",
+        "Carol",
+        "carol@example.com",
+        date("2024-04-01T12:00:00Z"),
+    )?;
+    let (ctx, book) = repo.book("html")?;
+
+    let options = GitInfoOptions::builder().include_includes(true).build();
+    let book = GitInfoPreprocessor::with_options(options).run(&ctx, book)?;
+    assert!(content(&book, "Guide")
+        .contains("| **10 Jan 2024** | **Alice** | **01 Apr 2024** | **Carol** |"));
+
+    // Only the chapter itself counts by default
+    let (ctx, book) = repo.book("html")?;
+    let book = GitInfoPreprocessor::new().run(&ctx, book)?;
+    assert!(content(&book, "Guide")
+        .contains("| **10 Jan 2024** | **Alice** | **20 Jan 2024** | **Alice** |"));
+    Ok(())
+}
+
+#[test]
+fn exported_histories_build_the_book_without_git() -> Result<()> {
+    let repo = fixture()?;
+    let (ctx, book) = repo.book("html")?;
+    let with_git = GitInfoPreprocessor::new().run(&ctx, book)?;
+
+    // The guide is left out of the export
+    HistoryFile::extract(
+        &repo.path().join("src"),
+        [Path::new("intro.md")],
+        &GitInfoOptions::default(),
+    )?
+    .save(&repo.path().join("history.json"))?;
+    fs::remove_dir_all(repo.path().join(".git"))?;
+
+    // No git binary, like on a machine without git, but without changing the PATH of the
+    // other tests running in parallel
+    let options = GitInfoOptions::builder()
+        .history_file("history.json")
+        .git_binary(repo.path().join("missing-git"))
+        .require_git(true)
+        .build();
+    let (ctx, book) = repo.book("html")?;
+    let without_git = GitInfoPreprocessor::with_options(options).run(&ctx, book)?;
+
+    assert!(content(&without_git, "Intro").contains("**Bob**"));
+    assert_eq!(content(&without_git, "Intro"), content(&with_git, "Intro"));
+    assert_eq!(content(&without_git, "Guide"), "# Guide\n");
+    Ok(())
+}
+
+#[test]
+fn overrides_apply_to_the_matching_chapters() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file(
+        "book.toml",
+        "[preprocessor.git-info]\n\
+         date_format = \"%B %Y\"\n\
+         [preprocessor.git-info.overrides.\"intro.md\"]\n\
+         date_format = \"%Y-%m-%d\"\n\
+         [preprocessor.git-info.overrides.\"g*.md\"]\n\
+         date_format = \"%d/%m/%Y\"\n",
+    )?;
+    let (ctx, book) = repo.book("html")?;
+
+    let book = GitInfoPreprocessor::new().run(&ctx, book)?;
+
+    assert!(content(&book, "Intro")
+        .contains("| **2024-01-10** | **Alice** | **2024-03-05** | **Bob** |"));
+    assert!(content(&book, "Guide").contains("| **10/01/2024** | **Alice** |"));
+    Ok(())
+}
+
+#[test]
+fn footers_are_the_same_without_the_full_history() -> Result<()> {
+    let mut repo = fixture()?;
+    for (i, author) in ["Carol", "Dave"].iter().enumerate() {
+        repo.file("src/intro.md", format!("# Intro\n\nEdit {}.\n", i))?;
+        repo.commit(
+            &format!("Edit the intro again ({})", i),
+            author,
+            &format!("{}@example.com", author.to_lowercase()),
+            date(&format!("2024-0{}-01T12:00:00Z", i + 4)),
+        )?;
+    }
+
+    for max_history in [None, Some(1)] {
+        // The other contributors are only known from the full history
+        let mut builder = GitInfoOptions::builder()
+            .extraction(ExtractionMode::PerFile)
+            .show_other_contributors(false);
+        if let Some(max_history) = max_history {
+            builder = builder.max_history(max_history);
+        }
+        let endpoints = builder.clone().build();
+        // Only needed by the metadata file, which leaves the footers alone
+        let full = builder.metadata_output("metadata.json").build();
+        assert!(!endpoints.needs_full_history());
+        assert!(full.needs_full_history());
+
+        let (ctx, book) = repo.book("html")?;
+        let from_endpoints = GitInfoPreprocessor::with_options(endpoints).run(&ctx, book)?;
+        let (ctx, book) = repo.book("html")?;
+        let from_full = GitInfoPreprocessor::with_options(full).run(&ctx, book)?;
+
+        assert!(content(&from_endpoints, "Intro")
+            .contains("| **10 Jan 2024** | **Alice** | **01 May 2024** | **Dave** |"));
+        for chapter in ["Intro", "Guide"] {
+            assert_eq!(
+                content(&from_endpoints, chapter),
+                content(&from_full, chapter)
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn ignored_commit_types_are_not_the_last_edit() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file("src/intro.md", "# Intro\n\nMore.\n\n")?;
+    repo.commit(
+        "chore(format): trim the intro",
+        "Carol",
+        "carol@example.com",
+        date("2024-04-01T12:00:00Z"),
+    )?;
+    let options = GitInfoOptions::builder()
+        .ignore_commit_types(["chore"])
+        .build();
+
+    let (ctx, book) = repo.book("html")?;
+    let book = GitInfoPreprocessor::with_options(options).run(&ctx, book)?;
+    assert!(content(&book, "Intro")
+        .contains("| **10 Jan 2024** | **Alice** | **05 Mar 2024** | **Bob** | Carol |"));
+
+    let (ctx, book) = repo.book("html")?;
+    let book = GitInfoPreprocessor::new().run(&ctx, book)?;
+    assert!(content(&book, "Intro").contains("| **01 Apr 2024** | **Carol** |"));
+    Ok(())
+}
+
+#[test]
+fn owners_come_from_the_codeowners_file() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file(
+        ".github/CODEOWNERS",
+        "# Reviewers of the book\n\
+         src/ @example/writers\n\
+         src/guide.md @alice bob@example.com\n",
+    )?;
+    let (ctx, book) = repo.book("html")?;
+    let options = GitInfoOptions::builder().codeowners(true).build();
+
+    let book = GitInfoPreprocessor::with_options(options).run(&ctx, book)?;
+
+    assert!(content(&book, "Intro").contains("| Owners |"));
+    assert!(content(&book, "Intro").contains("| @example/writers |"));
+    assert!(content(&book, "Guide").contains("| @alice<br>bob@example.com |"));
+    Ok(())
+}
+
+#[test]
+fn front_matter_overrides_or_skips_chapters() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file(
+        "src/intro.md",
+        "---\ncreated: 2018-05-01\nauthors: [Ada]\n---\n# Intro\n\nMore.\n",
+    )?
+    .file("src/guide.md", "---\ngit-info: skip\n---\n# Guide\n")?;
+    let (ctx, book) = repo.book("html")?;
+
+    let book = GitInfoPreprocessor::new().run(&ctx, book)?;
+
+    assert!(content(&book, "Intro")
+        .contains("| **01 May 2018** | **Ada** | **05 Mar 2024** | **Bob** |"));
+    assert_eq!(
+        content(&book, "Guide"),
+        "---\ngit-info: skip\n---\n# Guide\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn malformed_front_matter_falls_back_to_git() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file(
+        "src/intro.md",
+        "---\ncreated: yesterday\n---\n# Intro\n\nMore.\n",
+    )?;
+    let (ctx, book) = repo.book("html")?;
+
+    let book = GitInfoPreprocessor::new().run(&ctx, book)?;
+
+    assert!(content(&book, "Intro")
+        .contains("| **10 Jan 2024** | **Alice** | **05 Mar 2024** | **Bob** |"));
+    Ok(())
+}
+
+#[test]
+fn feeds_list_the_most_recent_edits_first() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file("src/guide.md", "# Guide\n\nMore.\n")?;
+    repo.commit(
+        "Expand the guide",
+        "Carol",
+        "carol@example.com",
+        date("2024-04-01T12:00:00Z"),
+    )?;
+    repo.file(
+        "book.toml",
+        "[book]\n\
+         title = \"Fish & Chips\"\n\
+         [preprocessor.git-info.feed]\n\
+         site_url = \"https://example.com\"\n",
+    )?;
+    let (ctx, book) = repo.book("html")?;
+
+    GitInfoPreprocessor::new().run(&ctx, book)?;
+
+    let feed = fs::read_to_string(repo.path().join("src/feed.xml"))?;
+    assert!(feed.contains("<title>Fish &amp; Chips</title>"));
+    assert!(feed.contains("<updated>2024-04-01T12:00:00+00:00</updated>"));
+    let intro = feed.find("<title>Intro</title>").unwrap();
+    let guide = feed.find("<title>Guide</title>").unwrap();
+    assert!(guide < intro, "{}", feed);
+    Ok(())
+}
+
+#[test]
+fn inlined_css_is_linked_once_per_chapter() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file(
+        "src/SUMMARY.md",
+        "# Summary\n\n- [Intro](intro.md)\n- [Guide](guide/index.md)\n",
+    )?
+    .file("src/guide/index.md", "# Guide\n")?;
+    repo.commit(
+        "Move the guide",
+        "Alice",
+        "alice@example.com",
+        date("2024-04-01T12:00:00Z"),
+    )?;
+    repo.file(
+        "book.toml",
+        "[preprocessor.git-info]\ninline_css = true\nhide_in_print = true\n",
+    )?;
+
+    let (ctx, book) = repo.book("html")?;
+    let book = GitInfoPreprocessor::new().run(&ctx, book)?;
+
+    let intro = content(&book, "Intro");
+    assert!(intro.contains(
+        "\n\n<link rel=\"stylesheet\" class=\"git-info-style\" href=\"git-info.css\">\n\n<style"
+    ));
+    assert!(content(&book, "Guide").contains("href=\"../git-info.css\""));
+    // The rules themselves are only in the linked file
+    let stylesheet = fs::read_to_string(repo.path().join("src/git-info.css"))?;
+    assert!(stylesheet.contains("margin-top: 3em"));
+    assert!(!intro.contains("margin-top: 3em"));
+
+    // Other renderers get neither the link nor the file
+    fs::remove_file(repo.path().join("src/git-info.css"))?;
+    let (ctx, book) = repo.book("markdown")?;
+    let book = GitInfoPreprocessor::new().run(&ctx, book)?;
+    assert!(!content(&book, "Intro").contains("git-info.css"));
+    assert!(!repo.path().join("src/git-info.css").exists());
+    Ok(())
+}
+
+#[test]
+fn strict_books_with_complete_histories_pass() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file("book.toml", "[preprocessor.git-info]\nstrict = true\n")?;
+    let (ctx, book) = repo.book("html")?;
+
+    let book = GitInfoPreprocessor::new().run(&ctx, book)?;
+
+    assert!(content(&book, "Intro").contains("| **10 Jan 2024** | **Alice** |"));
+    assert!(content(&book, "Guide").contains("| **10 Jan 2024** | **Alice** |"));
+    Ok(())
+}
+
+#[test]
+fn strict_books_fail_on_chapters_without_history() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file("book.toml", "[preprocessor.git-info]\nstrict = true\n")?
+        .file(
+            "src/SUMMARY.md",
+            format!("{}- [New](new.md)\n- [Later](later.md)\n", SUMMARY),
+        )?
+        .file("src/new.md", "# New\n")?
+        .file("src/later.md", "# Later\n")?;
+    let (ctx, book) = repo.book("html")?;
+
+    let error = GitInfoPreprocessor::new()
+        .run(&ctx, book)
+        .unwrap_err()
+        .to_string();
+
+    // Every failing chapter is listed at once, with its resolved path
+    assert!(
+        error.starts_with("2 chapters without usable git history:\n"),
+        "{}",
+        error
+    );
+    let new = repo.path().join("src/new.md");
+    assert!(error.contains(&format!("  - New ({}): no git history\n", new.display())));
+    assert!(error.contains("  - Later ("));
+    assert!(!error.contains("Intro"));
+    assert!(error.contains("Usual causes:"));
+
+    // Without strict mode, the same book builds
+    repo.file("book.toml", "")?;
+    let (ctx, book) = repo.book("html")?;
+    GitInfoPreprocessor::new().run(&ctx, book)?;
+    Ok(())
+}
+
+/// Runs the preprocessor with the provenance stamp at the end of the first chapter, and returns
+/// the stamp along with the short hash of `HEAD`.
+fn provenance_stamp(repo: &FixtureRepo) -> Result<(String, String)> {
+    let (ctx, book) = repo.book("html")?;
+    let book = GitInfoPreprocessor::new().run(&ctx, book)?;
+    let head = repo.git(&["rev-parse", "--short=7", "HEAD"])?;
+    let intro = content(&book, "Intro");
+    let stamp = intro
+        .lines()
+        .find(|line| line.starts_with("<p class=\"git-info-provenance\">"))
+        .unwrap_or_else(|| panic!("No provenance in {}", intro))
+        .to_string();
+    Ok((stamp, head.trim().to_string()))
+}
+
+#[test]
+fn provenance_names_the_checked_out_branch() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file("book.toml", "[preprocessor.git-info.provenance]\n")?;
+    repo.commit(
+        "Show the provenance",
+        "Alice",
+        "alice@example.com",
+        date("2024-03-06T12:00:00Z"),
+    )?;
+
+    let (stamp, head) = provenance_stamp(&repo)?;
+
+    assert!(
+        stamp.ends_with(&format!("from commit <code>{}</code> (main).</p>", head)),
+        "{}",
+        stamp
+    );
+    // Only in the first chapter
+    let (ctx, book) = repo.book("html")?;
+    let book = GitInfoPreprocessor::new().run(&ctx, book)?;
+    assert!(!content(&book, "Guide").contains("git-info-provenance"));
+    Ok(())
+}
+
+#[test]
+fn provenance_flags_uncommitted_changes() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file("book.toml", "[preprocessor.git-info.provenance]\n")?;
+    repo.commit(
+        "Show the provenance",
+        "Alice",
+        "alice@example.com",
+        date("2024-03-06T12:00:00Z"),
+    )?;
+    repo.file("src/guide.md", "# Guide\n\nNot committed yet.\n")?;
+
+    let (stamp, head) = provenance_stamp(&repo)?;
+
+    assert!(
+        stamp.ends_with(&format!(
+            "from commit <code>{}</code> (main), \
+             <span class=\"git-info-dirty\">with uncommitted changes</span>.</p>",
+            head
+        )),
+        "{}",
+        stamp
+    );
+    Ok(())
+}
+
+#[test]
+fn provenance_of_detached_heads() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file("book.toml", "[preprocessor.git-info.provenance]\n")?;
+    let hash = repo.commit(
+        "Show the provenance",
+        "Alice",
+        "alice@example.com",
+        date("2024-03-06T12:00:00Z"),
+    )?;
+
+    // Without a tag, only the commit is known
+    repo.checkout(&hash)?;
+    let (stamp, head) = provenance_stamp(&repo)?;
+    assert!(
+        stamp.ends_with(&format!("from commit <code>{}</code>.</p>", head)),
+        "{}",
+        stamp
+    );
+
+    repo.tag("v1.0")?;
+    let (stamp, head) = provenance_stamp(&repo)?;
+    assert!(
+        stamp.ends_with(&format!("from commit <code>{}</code> (v1.0).</p>", head)),
+        "{}",
+        stamp
+    );
+    Ok(())
+}
+
+#[test]
+fn disabled_books_are_left_untouched() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file(
+        "book.toml",
+        "[preprocessor.git-info]\nenabled = false\nmetadata_output = \"git-info.json\"\n",
+    )?;
+    let (ctx, book) = repo.book("html")?;
+
+    let book = GitInfoPreprocessor::new().run(&ctx, book)?;
+
+    assert_eq!(content(&book, "Intro"), "# Intro\n\nMore.\n");
+    assert!(!repo.path().join("git-info.json").exists());
+    Ok(())
+}
+
+#[test]
+fn books_build_without_git_unless_required() -> Result<()> {
+    let repo = fixture()?;
+    let (ctx, book) = repo.book("html")?;
+    let options = GitInfoOptions::builder()
+        .git_binary(repo.path().join("missing-git"))
+        .metadata_output("git-info.json");
+
+    let untouched = GitInfoPreprocessor::with_options(options.clone().require_git(false).build())
+        .run(&ctx, book.clone())?;
+    assert_eq!(content(&untouched, "Intro"), "# Intro\n\nMore.\n");
+    assert!(!repo.path().join("git-info.json").exists());
+
+    let error = GitInfoPreprocessor::with_options(options.require_git(true).build())
+        .run(&ctx, book)
+        .unwrap_err();
+    let error = format!("{:#}", error);
+    assert!(error.contains("Cannot run git using"), "{}", error);
+    assert!(error.contains("missing-git"), "{}", error);
+    Ok(())
+}
+
+fn chapter<'a>(book: &'a Book, name: &str) -> &'a Chapter {
+    book.iter()
+        .find_map(|item| match item {
+            BookItem::Chapter(chapter) if chapter.name == name => Some(chapter),
+            _ => None,
+        })
+        .unwrap_or_else(|| panic!("No chapter {}", name))
+}
+
+#[test]
+fn contributors_are_credited_in_a_generated_chapter() -> Result<()> {
+    let repo = fixture()?;
+    let (ctx, book) = repo.book("html")?;
+    let options = GitInfoOptions::builder()
+        .contributors_chapter(Default::default())
+        .build();
+
+    let book = GitInfoPreprocessor::with_options(options).run(&ctx, book)?;
+
+    // Appended after the rest of the book, without a file of its own
+    assert!(matches!(
+        &book.sections[book.sections.len() - 2..],
+        [BookItem::Separator, BookItem::Chapter(_)]
+    ));
+    let contributors = chapter(&book, "Contributors");
+    assert_eq!(
+        contributors.content,
+        "# Contributors\n\n\
+         | Contributor | Commits | Chapters |\n\
+         | --- | :---: | --- |\n\
+         | Alice | 1 | [Intro](intro.md), [Guide](guide.md) |\n\
+         | Bob | 1 | [Intro](intro.md) |\n"
+    );
+    assert_eq!(
+        contributors.path.as_deref(),
+        Some(Path::new("contributors.md"))
+    );
+    assert_eq!(contributors.source_path, None);
+    Ok(())
+}
+
+#[test]
+fn generated_chapters_listed_in_the_summary_are_filled_in() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file(
+        "src/SUMMARY.md",
+        "# Summary\n\n- [Thanks](contributors.md)\n- [Intro](intro.md)\n- [Guide](guide.md)\n",
+    )?
+    .file("src/contributors.md", "")?;
+    repo.commit(
+        "List the contributors",
+        "Carol",
+        "carol@example.com",
+        date("2024-04-01T12:00:00Z"),
+    )?;
+    let (ctx, book) = repo.book("html")?;
+    let options = GitInfoOptions::builder()
+        .contributors_chapter(Default::default())
+        .build();
+
+    let book = GitInfoPreprocessor::with_options(options).run(&ctx, book)?;
+
+    assert_eq!(book.sections.len(), 3);
+    let thanks = chapter(&book, "Thanks");
+    assert!(thanks.content.starts_with("# Contributors\n"));
+    assert!(
+        !thanks.content.contains("git-info-footer"),
+        "{}",
+        thanks.content
+    );
+    assert_eq!(
+        thanks.source_path.as_deref(),
+        Some(Path::new("contributors.md"))
+    );
+    Ok(())
+}
+
+#[test]
+fn recently_updated_chapters_are_listed_in_a_generated_chapter() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file(
+        "book.toml",
+        "[preprocessor.git-info.recently_updated]\n\
+         position = \"front\"\n\
+         max_entries = 1\n\
+         show_subject = true\n",
+    )?;
+    let (ctx, book) = repo.book("html")?;
+
+    let book = GitInfoPreprocessor::new().run(&ctx, book)?;
+
+    // Only the most recent edit, before every other chapter
+    assert!(
+        matches!(&book.sections[0], BookItem::Chapter(chapter) if chapter.name == "Recently updated")
+    );
+    let recent = chapter(&book, "Recently updated");
+    assert_eq!(
+        recent.content,
+        "# Recently updated\n\n\
+         | Chapter | Updated on | Updated by | Change |\n\
+         | --- | :---: | :---: | --- |\n\
+         | [Intro](intro.md) | 05 Mar 2024 | Bob | Expand the intro |\n"
+    );
+    assert_eq!(recent.source_path, None);
+    Ok(())
+}
+
+#[test]
+fn changelogs_group_the_commits_by_month() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file(
+        "src/SUMMARY.md",
+        format!("{}- [Changelog](changelog.md)\n", SUMMARY),
+    )?
+    .file("src/changelog.md", "")?
+    .file(
+        "book.toml",
+        "[preprocessor.git-info.changelog]\n\
+         commit_url = \"https://example.com/commit/{hash}\"\n",
+    )?;
+    repo.commit(
+        "Add a changelog",
+        "Carol",
+        "carol@example.com",
+        date("2024-03-20T12:00:00Z"),
+    )?;
+    let (ctx, book) = repo.book("html")?;
+
+    let book = GitInfoPreprocessor::new().run(&ctx, book)?;
+
+    // Filled in where the summary lists it, without a footer of its own
+    let changelog = chapter(&book, "Changelog");
+    let link = |repo: &FixtureRepo, subject: &str| {
+        let hash = repo
+            .git(&["log", "-1", "--format=%H", "--grep", subject])
+            .unwrap();
+        let hash = hash.trim();
+        format!("[`{}`](https://example.com/commit/{})", &hash[..7], hash)
+    };
+    assert_eq!(
+        changelog.content,
+        format!(
+            "# Changelog\n\
+             \n## March 2024\n\n\
+             - **20 Mar 2024** Carol: Add a changelog {}\n\
+             - **05 Mar 2024** Bob: Expand the intro ([Intro](intro.md)) {}\n\
+             \n## January 2024\n\n\
+             - **10 Jan 2024** Alice: Add the intro ([Intro](intro.md), [Guide](guide.md)) {}\n",
+            link(&repo, "Add a changelog"),
+            link(&repo, "Expand the intro"),
+            link(&repo, "Add the intro"),
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn authors_and_committers_are_told_apart() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file("src/guide.md", "# Guide\n\nPatched.\n")?;
+    repo.commit_by(
+        "Apply a patch",
+        "Dave",
+        "dave@example.com",
+        "Carol",
+        "carol@example.com",
+        date("2024-04-01T12:00:00Z"),
+    )?;
+    repo.file("src/guide.md", "# Guide\n\nPatched again.\n")?;
+    repo.commit_by(
+        "Apply another patch",
+        "Erin",
+        "erin@example.com",
+        "Carol",
+        "carol@example.com",
+        date("2024-05-01T12:00:00Z"),
+    )?;
+    let (ctx, book) = repo.book("html")?;
+    let footer = |options: GitInfoOptions| -> Result<String> {
+        let book = GitInfoPreprocessor::with_options(options).run(&ctx, book.clone())?;
+        let guide = content(&book, "Guide");
+        Ok(guide
+            .lines()
+            .rfind(|line| line.starts_with('|'))
+            .unwrap()
+            .to_string())
+    };
+
+    // The creator is the same person either way
+    assert_eq!(
+        footer(GitInfoOptions::default())?,
+        "| **10 Jan 2024** | **Alice** | **01 May 2024** | **Erin** | Dave |"
+    );
+    assert_eq!(
+        footer(
+            GitInfoOptions::builder()
+                .identity(Identity::Committer)
+                .build()
+        )?,
+        "| **10 Jan 2024** | **Alice** | **01 May 2024** | **Carol** |  |"
+    );
+    assert_eq!(
+        footer(GitInfoOptions::builder().show_both(true).build())?,
+        "| **10 Jan 2024** | **Alice** | **01 May 2024** | \
+         **authored by Erin, committed by Carol** | Dave |"
+    );
+    Ok(())
+}
+
+#[test]
+fn every_chapter_shows_the_last_update_of_the_book() -> Result<()> {
+    let mut repo = fixture()?;
+    repo.file("src/SUMMARY.md", format!("{}- [FAQ](faq.md)\n", SUMMARY))?
+        .file(
+            "src/faq.md",
+            "# FAQ\n\nUpdated {{#git_book_last_edit_date}}.\n",
+        )?;
+    repo.commit(
+        "Add the FAQ",
+        "Carol",
+        "carol@example.com",
+        date("2024-06-01T12:00:00Z"),
+    )?;
+    repo.file("src/guide.md", "# Guide\n\n")?;
+    repo.commit(
+        "chore: trim the guide",
+        "Dave",
+        "dave@example.com",
+        date("2024-07-01T12:00:00Z"),
+    )?;
+    let (ctx, book) = repo.book("html")?;
+    let options = GitInfoOptions::builder()
+        .show_book_last_update(true)
+        .ignore_commit_types(["chore"])
+        .build();
+
+    let book = GitInfoPreprocessor::with_options(options).run(&ctx, book)?;
+
+    // The newest edit of any chapter, leaving out the ignored commit
+    for name in ["Intro", "Guide", "FAQ"] {
+        assert!(
+            content(&book, name).contains("\nBook last updated: 01 Jun 2024\n"),
+            "{}",
+            name
+        );
+    }
+    assert!(content(&book, "Intro").contains("| **05 Mar 2024** | **Bob** |"));
+    assert!(content(&book, "FAQ").starts_with("# FAQ\n\nUpdated 01 Jun 2024.\n"));
+    Ok(())
+}
+
+#[test]
+fn only_the_listed_renderers_get_footers() -> Result<()> {
+    let mut repo = fixture()?;
+    let footer = "| **10 Jan 2024** | **Alice** | **05 Mar 2024** | **Bob** |";
+    let preprocessor = GitInfoPreprocessor::new();
+
+    // `supports` cannot know the configuration yet
+    assert!(preprocessor.supports_renderer("linkcheck"));
+
+    let (ctx, book) = repo.book("html")?;
+    assert!(content(&preprocessor.run(&ctx, book)?, "Intro").contains(footer));
+    let (ctx, book) = repo.book("linkcheck")?;
+    assert_eq!(
+        content(&preprocessor.run(&ctx, book)?, "Intro"),
+        "# Intro\n\nMore.\n"
+    );
+
+    repo.file(
+        "book.toml",
+        "[preprocessor.git-info]\nrenderers = [\"custom\"]\n",
+    )?;
+    let (ctx, book) = repo.book("custom")?;
+    assert!(content(&preprocessor.run(&ctx, book)?, "Intro").contains(footer));
+    let (ctx, book) = repo.book("html")?;
+    assert_eq!(
+        content(&preprocessor.run(&ctx, book)?, "Intro"),
+        "# Intro\n\nMore.\n"
+    );
+    Ok(())
+}
+
+/// The book of [`fixture`], with the intro listed twice, which recent versions of mdbook only
+/// allow in books built programmatically.
+fn book_with_duplicate(repo: &FixtureRepo) -> Result<(PreprocessorContext, Book)> {
+    let (ctx, mut book) = repo.book("html")?;
+    let mut again = book
+        .iter()
+        .find_map(|item| match item {
+            BookItem::Chapter(chapter) if chapter.name == "Intro" => Some(chapter.clone()),
+            _ => None,
+        })
+        .unwrap();
+    again.name = "Intro again".to_string();
+    book.push_item(again);
+    Ok((ctx, book))
+}
+
+#[test]
+fn each_file_is_extracted_once() -> Result<()> {
+    let repo = fixture()?;
+    let src = repo.path().join("src");
+
+    // Per file, the intro is listed twice but extracted once
+    let provider = CountingProvider::default();
+    let options = GitInfoOptions::builder()
+        .extraction(ExtractionMode::PerFile)
+        .build();
+    let (ctx, book) = book_with_duplicate(&repo)?;
+    let per_file = GitInfoPreprocessor::with_options(options)
+        .with_provider(provider.clone())
+        .run(&ctx, book)?;
+    assert_eq!(
+        provider.extractions(),
+        [(src.join("guide.md"), 1), (src.join("intro.md"), 1)].into()
+    );
+    assert!(content(&per_file, "Intro").contains("**Bob**"));
+    assert_eq!(
+        content(&per_file, "Intro"),
+        content(&per_file, "Intro again")
+    );
+
+    // In batch mode, the whole source directory is extracted once
+    let provider = CountingProvider::default();
+    let (ctx, book) = book_with_duplicate(&repo)?;
+    let batched = GitInfoPreprocessor::new()
+        .with_provider(provider.clone())
+        .run(&ctx, book)?;
+    assert_eq!(provider.extractions(), [(src, 1)].into());
+    assert_eq!(content(&batched, "Intro"), content(&per_file, "Intro"));
+    assert_eq!(
+        content(&batched, "Intro again"),
+        content(&per_file, "Intro")
+    );
+    Ok(())
+}
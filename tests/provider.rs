@@ -0,0 +1,248 @@
+//! Suite run against every backend, which must all return the same data for the same repository.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use mdbook_git_info::provider::{CliProvider, HistoryProvider};
+use mdbook_git_info::test_support::FixtureRepo;
+use mdbook_git_info::GitInfoOptions;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn date(text: &str) -> DateTime<Utc> {
+    text.parse().unwrap()
+}
+
+/// Hashes of the commits of [`fixture`], oldest first.
+struct Commits {
+    created: String,
+    reviewed: String,
+    side: String,
+    merge: String,
+}
+
+/// A book whose intro is created by Alice, then edited by Bob with a reviewer on a side branch
+/// while Carol adds a guide on the main branch, and the two are merged by Carol.
+fn fixture() -> Result<(FixtureRepo, Commits)> {
+    let mut repo = FixtureRepo::new()?;
+    repo.file("src/intro.md", "# Intro\n\nFirst.\n")?;
+    let created = repo.commit(
+        "Add the intro",
+        "Alice",
+        "alice@example.com",
+        date("2024-01-10T12:00:00Z"),
+    )?;
+    repo.branch("side")?;
+    repo.file("src/intro.md", "# Intro\n\nFirst.\nSecond.\n")?;
+    let reviewed = repo.commit(
+        "Expand the intro\n\nMore details.\n\nReviewed-by: Dave <dave@example.com>",
+        "Bob",
+        "bob@example.com",
+        date("2024-02-01T12:00:00Z"),
+    )?;
+    repo.checkout("main")?;
+    repo.file("src/guide.md", "# Guide\n")?;
+    let side = repo.commit(
+        "Add the guide",
+        "Carol",
+        "carol@example.com",
+        date("2024-02-15T12:00:00Z"),
+    )?;
+    let merge = repo.merge(
+        "side",
+        "Merge the side branch",
+        "Carol",
+        "carol@example.com",
+        date("2024-03-01T12:00:00Z"),
+    )?;
+    Ok((
+        repo,
+        Commits {
+            created,
+            reviewed,
+            side,
+            merge,
+        },
+    ))
+}
+
+fn options() -> GitInfoOptions {
+    // Not supported by every backend
+    GitInfoOptions::builder().follow_renames(false).build()
+}
+
+fn hashes<'a>(
+    entries: impl IntoIterator<Item = &'a mdbook_git_info::GitHistoryEntry>,
+) -> Vec<&'a str> {
+    entries
+        .into_iter()
+        .map(|entry| entry.hash.as_str())
+        .collect()
+}
+
+fn suite(provider: &dyn HistoryProvider) -> Result<()> {
+    let (mut repo, commits) = fixture()?;
+    let src = repo.path().join("src");
+    let options = options();
+
+    // History of a single file, through the merge
+    let history = provider.file_history(&src.join("intro.md"), &options)?;
+    assert_eq!(hashes(&history), [&commits.reviewed, &commits.created]);
+    let edit = &history[0];
+    assert_eq!(edit.short_hash, commits.reviewed[..7]);
+    assert_eq!(edit.author, "Bob");
+    assert_eq!(edit.email, "bob@example.com");
+    assert_eq!(edit.committer, "Bob");
+    assert_eq!(edit.committer_email, "bob@example.com");
+    assert_eq!(edit.timestamp, date("2024-02-01T12:00:00Z"));
+    assert_eq!(edit.commit_timestamp, date("2024-02-01T12:00:00Z"));
+    assert_eq!(edit.subject, "Expand the intro");
+    assert_eq!(
+        edit.trailers,
+        [(
+            "Reviewed-by".to_string(),
+            "Dave <dave@example.com>".to_string()
+        )]
+    );
+    assert!(history[1].trailers.is_empty());
+
+    // Truncated histories keep the creation
+    let truncated = provider.file_history(
+        &src.join("intro.md"),
+        &GitInfoOptions::builder()
+            .follow_renames(false)
+            .max_history(1)
+            .build(),
+    )?;
+    assert_eq!(hashes(&truncated), [&commits.reviewed, &commits.created]);
+    let endpoints = provider.file_endpoints(&src.join("intro.md"), &options)?;
+    assert_eq!(hashes(&endpoints), [&commits.reviewed, &commits.created]);
+
+    // Every history at once, relative to the directory, without the merge
+    let all = provider.all_histories(&src, &options)?;
+    let mut paths = all.keys().cloned().collect::<Vec<_>>();
+    paths.sort();
+    assert_eq!(
+        paths,
+        [PathBuf::from("guide.md"), PathBuf::from("intro.md")]
+    );
+    assert_eq!(hashes(&all[Path::new("intro.md")]), hashes(&history));
+    assert_eq!(hashes(&all[Path::new("guide.md")]), [&commits.side]);
+
+    // Line counts
+    let churn = GitInfoOptions::builder()
+        .follow_renames(false)
+        .churn(true)
+        .build();
+    let history = provider.file_history(&src.join("intro.md"), &churn)?;
+    assert_eq!(
+        history
+            .iter()
+            .map(|entry| (entry.lines_added, entry.lines_removed))
+            .collect::<Vec<_>>(),
+        [(1, 0), (3, 0)]
+    );
+    let all = provider.all_histories(&src, &churn)?;
+    assert_eq!(all[Path::new("intro.md")][0].lines_added, 1);
+
+    // Recent commits, with and without merges
+    let recent = provider.recent_commits(&src, &options, 10, false)?;
+    assert_eq!(
+        recent
+            .iter()
+            .map(|(entry, paths)| (entry.hash.as_str(), paths.clone()))
+            .collect::<Vec<_>>(),
+        [
+            (commits.side.as_str(), vec![PathBuf::from("guide.md")]),
+            (commits.reviewed.as_str(), vec![PathBuf::from("intro.md")]),
+            (commits.created.as_str(), vec![PathBuf::from("intro.md")]),
+        ]
+    );
+    let recent = provider.recent_commits(&src, &options, 2, true)?;
+    assert_eq!(
+        hashes(recent.iter().map(|(entry, _)| entry)),
+        [&commits.merge, &commits.side]
+    );
+    assert!(recent[0].1.is_empty());
+
+    // Repository
+    assert_eq!(provider.head(&src, &options)?, commits.merge);
+    assert_eq!(provider.head_ref(&src, &options)?.as_deref(), Some("main"));
+    assert_eq!(provider.root(&src, &options)?, repo.path());
+    assert!(!provider.is_shallow(&src, &options)?);
+    assert_eq!(provider.remote_url(&src, "origin", &options)?, None);
+    repo.git(&["remote", "add", "origin", "https://example.com/book.git"])?;
+    assert_eq!(
+        provider.remote_url(&src, "origin", &options)?.as_deref(),
+        Some("https://example.com/book.git")
+    );
+
+    // Blame, without uncommitted lines
+    repo.file("src/intro.md", "# Intro\n\nFirst.\nSecond.\nThird.\n")?;
+    assert_eq!(
+        provider.blame(&src.join("intro.md"), &options)?,
+        [("Alice".to_string(), 3), ("Bob".to_string(), 1)]
+    );
+    assert_eq!(
+        provider.dirty_files(&src, &options)?,
+        [fs::canonicalize(src.join("intro.md"))?].into()
+    );
+
+    // Releases and publication
+    repo.git(&["checkout", "--quiet", "--", "src/intro.md"])?;
+    repo.checkout(&commits.side)?;
+    repo.tag("v1.0")?;
+    repo.checkout("main")?;
+    assert_eq!(
+        provider
+            .first_tag_containing(&src, &commits.created, "v*", &options)?
+            .as_deref(),
+        Some("v1.0")
+    );
+    assert_eq!(
+        provider.first_tag_containing(&src, &commits.reviewed, "v*", &options)?,
+        None
+    );
+    assert_eq!(
+        provider.unpublished_commits(&src, "missing", &options)?,
+        None
+    );
+    // Merges are left out, like in the histories
+    assert_eq!(
+        provider.unpublished_commits(&src, "v1.0", &options)?,
+        Some([commits.reviewed.clone()].into())
+    );
+
+    // Notes
+    repo.git(&[
+        "-c",
+        "user.name=Dave",
+        "-c",
+        "user.email=dave@example.com",
+        "notes",
+        "--ref=reviews",
+        "add",
+        "--message",
+        "Reviewed: 2024-03-02",
+        &commits.reviewed,
+    ])?;
+    let notes = provider.notes(&src, "reviews", &options)?;
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[&commits.reviewed], "Reviewed: 2024-03-02");
+    assert!(provider.notes(&src, "missing", &options)?.is_empty());
+
+    // Detached at a tag
+    repo.checkout("v1.0")?;
+    assert_eq!(provider.head_ref(&src, &options)?.as_deref(), Some("v1.0"));
+    Ok(())
+}
+
+#[test]
+fn cli() -> Result<()> {
+    suite(&CliProvider)
+}
+
+#[cfg(feature = "libgit2")]
+#[test]
+fn libgit2() -> Result<()> {
+    suite(&mdbook_git_info::provider::Libgit2Provider)
+}